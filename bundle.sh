@@ -19,5 +19,8 @@ cp "target/release/${BINARY_NAME}" "$BUNDLE_DIR/Contents/MacOS/${BINARY_NAME}"
 # Copy Info.plist
 cp Info.plist "$BUNDLE_DIR/Contents/Info.plist"
 
+# Copy the AppleScript dictionary
+cp Zeditor.sdef "$BUNDLE_DIR/Contents/Resources/Zeditor.sdef"
+
 echo "Built ${BUNDLE_DIR}"
 echo "Run with: open ${BUNDLE_DIR}"
@@ -0,0 +1,145 @@
+//! Benchmarks for the editing and layout hot paths, driven through the test
+//! harness. GPUI's test platform lays text out with a stand-in text system,
+//! so these time the editor's own work (edits, offset math, line caching,
+//! wrapping and hit-testing bookkeeping) rather than font shaping.
+//!
+//! `cargo bench -p zeditor-core --features bench`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use gpui::*;
+use zeditor_core::editor::Paste;
+use zeditor_core::test_support::EditorTestContext;
+
+mod fixtures;
+
+fn editor() -> EditorTestContext {
+    EditorTestContext::new(&mut TestAppContext::single())
+}
+
+/// Byte offset where line `line` of `text` starts.
+fn line_start(text: &str, line: usize) -> usize {
+    text.split('\n').take(line).map(|l| l.len() + 1).sum()
+}
+
+fn paste_into_cursors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("paste_into_cursors");
+    for cursors in [10, 100, 1_000] {
+        let mut cx = editor();
+        cx.update(|_, cx| cx.write_to_clipboard(ClipboardItem::new_string("pasted text".into())));
+        let state = (0..cursors)
+            .map(|i| format!("line {}ˇ", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        group.bench_with_input(BenchmarkId::from_parameter(cursors), &state, |b, state| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    cx.set_state(state);
+                    let start = Instant::now();
+                    cx.dispatch_action(Paste);
+                    cx.run_until_parked();
+                    total += start.elapsed();
+                }
+                total
+            })
+        });
+    }
+    group.finish();
+}
+
+fn flat_offsets(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flat_offsets");
+    for (name, text) in [
+        ("100k_lines", fixtures::prose(100_000)),
+        ("giant_line", fixtures::giant_line(1 << 20)),
+        ("emoji", fixtures::emoji_heavy(10_000)),
+    ] {
+        let mut cx = editor();
+        cx.set_state(&format!("ˇ{}", text));
+        let editor = cx.editor.clone();
+        let offset = text.len() - text.len() / 3;
+        group.bench_function(BenchmarkId::new("position_from_flat", name), |b| {
+            b.iter(|| editor.read_with(&cx.cx, |e, _| e.position_from_flat(black_box(offset))))
+        });
+        let position = editor.read_with(&cx.cx, |e, _| e.position_from_flat(offset));
+        group.bench_function(BenchmarkId::new("flat_offset", name), |b| {
+            b.iter(|| editor.read_with(&cx.cx, |e, _| e.flat_offset(black_box(&position))))
+        });
+    }
+    group.finish();
+}
+
+fn wrap(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wrap");
+    group.sample_size(10);
+    for (name, text) in [
+        ("100k_lines", fixtures::prose(100_000)),
+        ("giant_line", fixtures::giant_line(1 << 20)),
+        ("emoji", fixtures::emoji_heavy(10_000)),
+    ] {
+        let mut cx = editor();
+        cx.set_word_wrap(true);
+        cx.set_state(&format!("ˇ{}", text));
+        let editor = cx.editor.clone();
+
+        // A new font size throws away every line's layout
+        let mut font_size = 14.;
+        group.bench_function(BenchmarkId::new("full", name), |b| {
+            b.iter(|| {
+                font_size = if font_size == 14. { 15. } else { 14. };
+                editor.update(&mut cx.cx, |e, cx| e.set_font_size(font_size, cx));
+                cx.run_until_parked();
+            })
+        });
+
+        // One step of a resize drag
+        let mut width = px(800.);
+        group.bench_function(BenchmarkId::new("resize_step", name), |b| {
+            b.iter(|| {
+                width = if width == px(800.) { px(790.) } else { px(800.) };
+                cx.set_window_size(size(width, px(600.)));
+            })
+        });
+    }
+    group.finish();
+}
+
+fn scroll_and_hit_test(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scroll_and_hit_test");
+    for (name, text) in [
+        ("100k_lines", fixtures::prose(100_000)),
+        ("giant_line", fixtures::giant_line(1 << 20)),
+        ("emoji", fixtures::emoji_heavy(10_000)),
+    ] {
+        let mut cx = editor();
+        cx.set_state(&format!("ˇ{}", text));
+        let target = (line_start(&text, 5) + 40).min(text.len());
+        let position = cx.position_for_offset(target);
+
+        group.bench_function(BenchmarkId::new("click", name), |b| {
+            b.iter(|| cx.click_at(position, Modifiers::default(), 1))
+        });
+
+        let mut down = true;
+        group.bench_function(BenchmarkId::new("scroll", name), |b| {
+            b.iter(|| {
+                let delta = if down { px(-120.) } else { px(120.) };
+                down = !down;
+                cx.simulate_event(ScrollWheelEvent {
+                    position,
+                    delta: ScrollDelta::Pixels(point(px(0.), delta)),
+                    modifiers: Modifiers::default(),
+                    touch_phase: TouchPhase::Moved,
+                });
+                cx.run_until_parked();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, paste_into_cursors, flat_offsets, wrap, scroll_and_hit_test);
+criterion_main!(benches);
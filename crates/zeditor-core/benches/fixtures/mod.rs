@@ -0,0 +1,39 @@
+//! Generated documents for the benchmarks, including the pathological
+//! shapes that have been slow before: one giant line, and text dense with
+//! multi-codepoint graphemes.
+
+/// Ordinary prose, `lines` lines of varying length.
+pub fn prose(lines: usize) -> String {
+    const WORDS: [&str; 12] = [
+        "the", "quick", "brown", "fox", "jumps", "over", "a", "lazy", "dog", "while", "editors", "wrap",
+    ];
+    (0..lines)
+        .map(|i| {
+            let len = 4 + (i * 7) % 24;
+            (0..len).map(|w| WORDS[(i + w * 5) % WORDS.len()]).collect::<Vec<_>>().join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A single line of `bytes` bytes of prose, as in a minified file or a
+/// pasted log dump.
+pub fn giant_line(bytes: usize) -> String {
+    let mut line = prose(bytes / 40 + 1).replace('\n', " ");
+    line.truncate(bytes);
+    line
+}
+
+/// `lines` lines mixing text with emoji that take several code points
+/// each: ZWJ families, skin tones, flags, keycaps and combining marks.
+pub fn emoji_heavy(lines: usize) -> String {
+    const PIECES: [&str; 10] = [
+        "👨‍👩‍👧‍👦", "👍🏽", "🇭🇷", "🇯🇵", "1️⃣", "e\u{301}", "🏳️‍🌈", "漢字", "wide", "🧑🏿‍💻",
+    ];
+    (0..lines)
+        .map(|i| {
+            (0..16).map(|p| PIECES[(i * 3 + p) % PIECES.len()]).collect::<Vec<_>>().join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,145 @@
+//! Small arithmetic evaluator used by `EvaluateSelection`.
+//!
+//! Supports `+ - * / % ^`, parentheses, unary minus and decimal numbers.
+//! `^` is right-associative and binds tighter than unary minus, so `-2^2`
+//! is `-4`.
+
+use anyhow::{Result, anyhow, bail};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Evaluate an arithmetic expression.
+pub fn evaluate(expr: &str) -> Result<f64> {
+    let mut parser = Parser {
+        chars: expr.chars().peekable(),
+    };
+    let value = parser.expr()?;
+    if let Some(c) = parser.peek() {
+        bail!("unexpected '{}'", c);
+    }
+    if !value.is_finite() {
+        bail!("result is not a finite number");
+    }
+    Ok(value)
+}
+
+/// Format a result without float noise: integers print without a decimal
+/// point, everything else with at most 10 fractional digits.
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        return format!("{}", value as i64);
+    }
+    let formatted = format!("{:.10}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    value += self.term()?;
+                }
+                Some('-') => {
+                    self.chars.next();
+                    value -= self.term()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+        loop {
+            match self.peek() {
+                Some('*' | '×') => {
+                    self.chars.next();
+                    value *= self.unary()?;
+                }
+                Some('/' | '÷') => {
+                    self.chars.next();
+                    let divisor = self.unary()?;
+                    if divisor == 0.0 {
+                        bail!("division by zero");
+                    }
+                    value /= divisor;
+                }
+                Some('%') => {
+                    self.chars.next();
+                    let divisor = self.unary()?;
+                    if divisor == 0.0 {
+                        bail!("division by zero");
+                    }
+                    value %= divisor;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(-self.unary()?)
+            }
+            Some('+') => {
+                self.chars.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<f64> {
+        let base = self.atom()?;
+        if self.peek() == Some('^') {
+            self.chars.next();
+            let exponent = self.unary()?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.expr()?;
+                if self.peek() != Some(')') {
+                    bail!("missing ')'");
+                }
+                self.chars.next();
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.' || *c == '_') {
+                    if c != '_' {
+                        number.push(c);
+                    }
+                }
+                number
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("invalid number '{}'", number))
+            }
+            Some(c) => Err(anyhow!("unexpected '{}'", c)),
+            None => Err(anyhow!("unexpected end of expression")),
+        }
+    }
+}
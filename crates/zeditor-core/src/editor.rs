@@ -0,0 +1,4027 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use gpui::*;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::*;
+
+use crate::calc;
+use crate::elastic_tabs::{self, TabLayout};
+use crate::host;
+use crate::markdown;
+use crate::settings::{CursorShape, EditorConfig, EditorSettings, LineHeight, WordMotion};
+use crate::snapshot::{EditorSnapshot, SNAPSHOT_VERSION, SavedCursor};
+use crate::syntax;
+use crate::text_objects;
+use crate::theme::{EditorTheme, SyntaxColors, translucent, with_alpha};
+
+const CURSOR_BLINK_INTERVAL: Duration = Duration::from_millis(600);
+const CURSOR_FADE_DURATION: Duration = Duration::from_millis(400);
+const CURSOR_ANIMATION_STEP: Duration = Duration::from_millis(16);
+const CURSOR_MOVE_DURATION: Duration = Duration::from_millis(60);
+const SCROLL_ANIMATION_DURATION: Duration = Duration::from_millis(120);
+const DEFAULT_FONT_SIZE: f32 = 14.;
+const MIN_FONT_SIZE: f32 = 8.;
+const MAX_FONT_SIZE: f32 = 48.;
+const SCROLLBAR_THICKNESS: Pixels = px(8.);
+const SCROLLBAR_MIN_THUMB: Pixels = px(24.);
+/// While the wrap width keeps changing, lines below the viewport keep their
+/// old wrapping until it has held still this long
+const REWRAP_DELAY: Duration = Duration::from_millis(150);
+
+/// The editor font with the ligature and OpenType feature preferences applied.
+fn editor_font(base: Font, config: &EditorConfig) -> Font {
+    let mut features: Vec<(String, u32)> = Vec::new();
+    if !config.ligatures {
+        features.push(("calt".to_string(), 0));
+        features.push(("liga".to_string(), 0));
+    }
+    features.extend(
+        config
+            .font_features
+            .iter()
+            .map(|(tag, value)| (tag.clone(), *value)),
+    );
+    Font {
+        features: FontFeatures(Arc::new(features)),
+        ..base
+    }
+}
+
+/// Line height for the editor text style, clamped to a usable range. Fixed
+/// heights scale with the zoom factor so zooming keeps the same spacing.
+fn editor_line_height(line_height: LineHeight, zoom: f32) -> DefiniteLength {
+    match line_height {
+        LineHeight::Pixels(height) => px(height.clamp(8., 96.) * zoom).into(),
+        LineHeight::Relative(factor) => relative(factor.clamp(0.8, 4.)),
+    }
+}
+
+/// Map a buffer column to the displayed (tab-expanded) text of a line.
+/// Identity when elastic tabstops are off and `layouts` is empty.
+fn display_col(layouts: &[TabLayout], line: usize, col: Column) -> usize {
+    layouts.get(line).map_or(col.offset(), |layout| layout.to_display(col.offset()))
+}
+
+/// Map a column in the displayed text of a line back to the buffer.
+fn buffer_col(layouts: &[TabLayout], line: usize, col: usize) -> usize {
+    layouts.get(line).map_or(col, |layout| layout.to_buffer(col))
+}
+
+/// Snap `index`, as gpui hit-tested it in the displayed `text` of a line,
+/// to the grapheme boundary nearest `x`. gpui works in glyphs, and one
+/// grapheme can be several ("é" as e plus a combining accent, "❤️" with its
+/// variation selector); it also sends clicks anywhere on a line's last glyph
+/// to the end, which misses by most of a full-width character.
+/// `x_for_index` gives a boundary's x, or None when it's on another visual
+/// line.
+fn snap_to_grapheme(text: &str, index: usize, x: Pixels, x_for_index: impl Fn(usize) -> Option<Pixels>) -> usize {
+    let floor = Column::floor(text, index);
+    [floor.prev(text), floor, floor.next(text)]
+        .into_iter()
+        .filter_map(|col| Some((col.offset(), (x_for_index(col.offset())? - x).abs())))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map_or(floor.offset(), |(index, _)| index)
+}
+
+/// Index in the displayed `text` of a line nearest `x` in its unwrapped
+/// `layout`, on a grapheme boundary.
+fn closest_grapheme_for_x(layout: &LineLayout, text: &str, x: Pixels) -> usize {
+    snap_to_grapheme(text, layout.closest_index_for_x(x), x, |index| Some(layout.x_for_index(index)))
+}
+
+/// Text runs for one shaped line: a single run in `color`, or split by
+/// token when `highlight` gives syntax colors.
+fn line_runs(text: &str, font: &Font, color: Hsla, highlight: Option<&SyntaxColors>) -> Vec<TextRun> {
+    let run = |len: usize, color: Hsla| TextRun {
+        len,
+        font: font.clone(),
+        color,
+        background_color: None,
+        underline: None,
+        strikethrough: None,
+    };
+    let Some(syntax_colors) = highlight else {
+        return vec![run(text.len(), color)];
+    };
+    let mut runs = Vec::new();
+    let mut offset = 0;
+    for (range, kind) in syntax::highlight_line(text) {
+        if range.start > offset {
+            runs.push(run(range.start - offset, color));
+        }
+        runs.push(run(range.len(), syntax_colors.color(kind).into()));
+        offset = range.end;
+    }
+    if offset < text.len() || runs.is_empty() {
+        runs.push(run(text.len() - offset, color));
+    }
+    runs
+}
+
+/// The text shown for a line: tab-expanded with elastic tabstops, else as is.
+fn display_text<'a>(lines: &'a [String], layouts: &'a [TabLayout], line: usize) -> &'a str {
+    layouts
+        .get(line)
+        .map_or(lines[line].as_str(), |layout| layout.display.as_str())
+}
+
+/// Text encoded as Base64, standard or URL-safe, padded or not, and
+/// wrapped across lines or not. None unless it decodes to UTF-8.
+fn decode_base64(text: &str) -> Option<String> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let unpadded = compact.trim_end_matches('=');
+    let engine = if unpadded.contains(['-', '_']) { URL_SAFE_NO_PAD } else { STANDARD_NO_PAD };
+    let bytes = engine.decode(unpadded).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Whether animations should be skipped, per the preference or the system.
+fn motion_reduced(cx: &App) -> bool {
+    cx.global::<EditorSettings>()
+        .reduce_motion
+        .unwrap_or_else(|| host::current().reduce_motion())
+}
+
+/// Whether a Unicode word segment is a word rather than spaces or
+/// punctuation.
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// The Unicode word segment (a word, a run of spaces or a punctuation mark)
+/// holding byte `offset` of `line`; at the end of the line, the last one.
+fn word_segment_at(line: &str, offset: usize) -> Range<usize> {
+    line.split_word_bound_indices()
+        .map(|(start, segment)| start..start + segment.len())
+        .find(|segment| segment.contains(&offset) || segment.end == line.len())
+        .unwrap_or(offset..offset)
+}
+
+fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+actions!(
+    multi_line_editor,
+    [
+        Backspace,
+        Delete,
+        Left,
+        Right,
+        Up,
+        Down,
+        SelectLeft,
+        SelectRight,
+        SelectUp,
+        SelectDown,
+        SelectAll,
+        Home,
+        End,
+        DocumentStart,
+        DocumentEnd,
+        ShowCharacterPalette,
+        Paste,
+        Cut,
+        Copy,
+        WordLeft,
+        WordRight,
+        SelectWordLeft,
+        SelectWordRight,
+        DeleteToStart,
+        DeleteWordBackward,
+        Enter,
+        MoveLineUp,
+        MoveLineDown,
+        AddCursorUp,
+        AddCursorDown,
+        SubmitAndPaste,
+        SelectHome,
+        SelectEnd,
+        SelectDocumentStart,
+        SelectDocumentEnd,
+        ToggleWordWrap,
+        ZoomIn,
+        ZoomOut,
+        ZoomReset,
+        EvaluateSelection,
+        InsertTimestamp,
+        InsertTab,
+        ToggleReadOnly,
+        ExpandSelection,
+        ShrinkSelection,
+        SelectInsideBrackets,
+        SelectAroundBrackets,
+        CopyAsRichText,
+        PasteAsMarkdown,
+        Base64Encode,
+        Base64Decode,
+    ]
+);
+
+/// The editor's default key bindings, in the `MultiLineEditor` context
+/// (plus `single_line` for the one-row layout).
+pub fn key_bindings() -> Vec<KeyBinding> {
+    vec![
+        KeyBinding::new("backspace", Backspace, Some("MultiLineEditor")),
+        KeyBinding::new("delete", Delete, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-backspace", DeleteToStart, Some("MultiLineEditor")),
+        KeyBinding::new("alt-backspace", DeleteWordBackward, Some("MultiLineEditor")),
+        KeyBinding::new("left", Left, Some("MultiLineEditor")),
+        KeyBinding::new("right", Right, Some("MultiLineEditor")),
+        KeyBinding::new("up", Up, Some("MultiLineEditor")),
+        KeyBinding::new("down", Down, Some("MultiLineEditor")),
+        KeyBinding::new("shift-left", SelectLeft, Some("MultiLineEditor")),
+        KeyBinding::new("shift-right", SelectRight, Some("MultiLineEditor")),
+        KeyBinding::new("shift-up", SelectUp, Some("MultiLineEditor")),
+        KeyBinding::new("shift-down", SelectDown, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-a", SelectAll, Some("MultiLineEditor")),
+        KeyBinding::new("home", Home, Some("MultiLineEditor")),
+        KeyBinding::new("end", End, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-left", Home, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-right", End, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-up", DocumentStart, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-down", DocumentEnd, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-left", SelectHome, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-right", SelectEnd, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-up", SelectDocumentStart, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-down", SelectDocumentEnd, Some("MultiLineEditor")),
+        KeyBinding::new("alt-left", WordLeft, Some("MultiLineEditor")),
+        KeyBinding::new("alt-right", WordRight, Some("MultiLineEditor")),
+        KeyBinding::new("alt-shift-left", SelectWordLeft, Some("MultiLineEditor")),
+        KeyBinding::new("alt-shift-right", SelectWordRight, Some("MultiLineEditor")),
+        KeyBinding::new("enter", Enter, Some("MultiLineEditor")),
+        KeyBinding::new("alt-up", MoveLineUp, Some("MultiLineEditor")),
+        KeyBinding::new("alt-down", MoveLineDown, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-alt-up", AddCursorUp, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-alt-down", AddCursorDown, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-cmd-space", ShowCharacterPalette, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-v", Paste, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-v", PasteAsMarkdown, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-c", Copy, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-shift-c", CopyAsRichText, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-x", Cut, Some("MultiLineEditor")),
+        KeyBinding::new("alt-z", ToggleWordWrap, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-=", ZoomIn, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-+", ZoomIn, Some("MultiLineEditor")),
+        KeyBinding::new("cmd--", ZoomOut, Some("MultiLineEditor")),
+        KeyBinding::new("cmd-0", ZoomReset, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-=", EvaluateSelection, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-;", InsertTimestamp, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-b", Base64Encode, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-shift-b", Base64Decode, Some("MultiLineEditor")),
+        KeyBinding::new("tab", InsertTab, Some("MultiLineEditor && !single_line")),
+        KeyBinding::new("cmd-alt-r", ToggleReadOnly, Some("MultiLineEditor")),
+        // alt-up/down already move lines
+        KeyBinding::new("ctrl-shift-up", ExpandSelection, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-shift-down", ShrinkSelection, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-shift-i", SelectInsideBrackets, Some("MultiLineEditor")),
+        KeyBinding::new("ctrl-shift-a", SelectAroundBrackets, Some("MultiLineEditor")),
+    ]
+}
+
+/// A place in a line: a byte offset that always falls on a grapheme
+/// cluster boundary, so slicing or inserting there can't split a character.
+/// Columns are made against the text of the line they index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Column(usize);
+
+impl Column {
+    pub const START: Column = Column(0);
+
+    /// The last boundary of `line` at or before byte `offset`.
+    pub fn floor(line: &str, offset: usize) -> Self {
+        if offset >= line.len() {
+            return Self::end(line);
+        }
+        let boundary = line
+            .grapheme_indices(true)
+            .map(|(idx, _)| idx)
+            .take_while(|&idx| idx <= offset)
+            .last()
+            .unwrap_or(0);
+        Column(boundary)
+    }
+
+    /// The first boundary of `line` at or after byte `offset`.
+    pub fn ceil(line: &str, offset: usize) -> Self {
+        let boundary = line
+            .grapheme_indices(true)
+            .map(|(idx, _)| idx)
+            .find(|&idx| idx >= offset)
+            .unwrap_or(line.len());
+        Column(boundary)
+    }
+
+    pub fn end(line: &str) -> Self {
+        Column(line.len())
+    }
+
+    /// The boundary before this one in `line`, or the start.
+    pub fn prev(self, line: &str) -> Self {
+        let boundary = line
+            .grapheme_indices(true)
+            .rev()
+            .find_map(|(idx, _)| if idx < self.0 { Some(idx) } else { None })
+            .unwrap_or(0);
+        Column(boundary)
+    }
+
+    /// The boundary after this one in `line`, or the end.
+    pub fn next(self, line: &str) -> Self {
+        let boundary = line
+            .grapheme_indices(true)
+            .find_map(|(idx, _)| if idx > self.0 { Some(idx) } else { None })
+            .unwrap_or(line.len());
+        Column(boundary)
+    }
+
+    /// Byte offset into the line.
+    pub fn offset(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CursorPosition {
+    pub line: usize,
+    pub col: Column,
+}
+
+impl CursorPosition {
+    pub(crate) fn new(line: usize, col: Column) -> Self {
+        Self { line, col }
+    }
+
+    fn start() -> Self {
+        Self::new(0, Column::START)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub position: CursorPosition,
+    pub anchor: Option<CursorPosition>,
+}
+
+impl Cursor {
+    fn new(line: usize, col: Column) -> Self {
+        Self {
+            position: CursorPosition::new(line, col),
+            anchor: None,
+        }
+    }
+
+    fn selection_range(&self) -> Option<(CursorPosition, CursorPosition)> {
+        let anchor = self.anchor.as_ref()?;
+        if *anchor < self.position {
+            Some((anchor.clone(), self.position.clone()))
+        } else if *anchor > self.position {
+            Some((self.position.clone(), anchor.clone()))
+        } else {
+            None
+        }
+    }
+
+    fn has_selection(&self) -> bool {
+        self.selection_range().is_some()
+    }
+
+    fn selection_start(&self) -> CursorPosition {
+        match &self.anchor {
+            Some(a) if *a < self.position => a.clone(),
+            _ => self.position.clone(),
+        }
+    }
+
+    fn selection_end(&self) -> CursorPosition {
+        match &self.anchor {
+            Some(a) if *a > self.position => a.clone(),
+            _ => self.position.clone(),
+        }
+    }
+}
+
+/// Line separator of the text the editor was filled with. Lines are always
+/// held without `\r`; submitted text gets the separator back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    /// Convert `\n`-separated text to this line ending.
+    pub fn apply(self, text: String) -> String {
+        match self {
+            LineEnding::Lf => text,
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// What changed in a `MultiLineEditor`, for whoever holds one to subscribe
+/// to.
+pub enum EditorEvent {
+    /// The buffer text changed; `edits` counts the edits so far
+    ContentChanged { edits: usize },
+    /// A cursor moved or a selection changed
+    SelectionChanged,
+    /// Cursors were added or removed
+    CursorCountChanged { count: usize },
+    /// The text went out through one of the submit actions
+    Submitted,
+}
+
+pub struct MultiLineEditor {
+    pub focus_handle: FocusHandle,
+    pub lines: Vec<String>,
+    pub cursors: Vec<Cursor>,
+    pub scroll_offset: Point<Pixels>,
+    pub preferred_col_x: Option<Pixels>,
+    pub marked_range: Option<Range<usize>>,
+    pub is_selecting: bool,
+    pub word_wrap: bool,
+    /// Blocks every edit (typing, IME, paste, line moves); navigation,
+    /// selection and copy still work
+    pub read_only: bool,
+    /// Compact quick-entry row: no gutter, and the "single_line" key context
+    /// lets Enter submit
+    pub single_line: bool,
+    /// Named scratchpad this buffer is saved to; None for the default one
+    pub scratchpad: Option<String>,
+    /// File this buffer was opened from and saves back to, instead of a
+    /// scratchpad
+    pub file: Option<PathBuf>,
+    /// Lines as last loaded from or saved to `file`
+    pub saved_lines: Vec<String>,
+    /// Temp file the buffer is being edited through in another app, read
+    /// back whenever that app saves it
+    pub external_file: Option<PathBuf>,
+    pub line_ending: LineEnding,
+    /// Lines the editor was last pre-filled with, for the diff view
+    pub original_lines: Option<Vec<String>>,
+    /// Per-cursor pieces of the last multi-cursor copy, pasted back one per
+    /// cursor when the clipboard still holds their joined text
+    pub clipboard_pieces: Option<Vec<String>>,
+    /// (cursors before, cursors after) for each `ExpandSelection` step, so
+    /// `ShrinkSelection` can walk back while the selection is untouched
+    pub selection_history: Vec<(Vec<Cursor>, Vec<Cursor>)>,
+    /// Selections a command couldn't convert, drawn in the error color
+    /// while they stay selected
+    pub error_ranges: Vec<(CursorPosition, CursorPosition)>,
+    /// First cursor as last reported to screen readers; None to report it
+    /// on the next render
+    pub reported_cursor: Option<Cursor>,
+    /// Number of cursors last announced to screen readers
+    pub reported_cursor_count: usize,
+    /// Bumped on every edit, so a selection going away by typing over it
+    /// isn't announced as cleared
+    pub edits: usize,
+    /// `edits` when the first cursor was last reported
+    pub reported_edits: usize,
+    /// Cursors as of the last `SelectionChanged`
+    emitted_cursors: Vec<Cursor>,
+    /// Editor font size; starts at the preference, changed by zooming,
+    /// kept for the session
+    pub font_size: f32,
+    // Layout cache for IME/mouse
+    pub last_shaped_lines: Vec<ShapedLine>,
+    pub last_wrapped_lines: Vec<WrappedLine>,
+    pub last_bounds: Option<Bounds<Pixels>>,
+    pub last_line_height: Pixels,
+    pub last_max_line_width: Pixels,
+    /// Number of visual lines per logical line (1 when not wrapped)
+    pub last_visual_line_counts: Vec<usize>,
+    /// Tab expansion per line when the buffer has tabs (empty otherwise)
+    pub last_tab_layouts: Vec<TabLayout>,
+    /// What the last paint was built from, so the next one only reshapes
+    /// changed lines and keeps the carets and selections if they're as
+    /// they were
+    paint_cache: PaintCache,
+    /// Bumped to cancel a pending re-wrap of lines left wrapped at an old
+    /// width
+    rewrap_epoch: usize,
+    /// Set when cursor moves; cleared after paint applies scroll_to_cursor
+    pub needs_scroll_to_cursor: bool,
+    /// Width of the line number gutter (set during paint)
+    pub last_gutter_width: Pixels,
+    /// Horizontal scrollbar (track, thumb) from the last paint, when shown
+    pub last_hscrollbar: Option<(Bounds<Pixels>, Bounds<Pixels>)>,
+    /// Offset from the thumb's left edge to the mouse while dragging it
+    pub hscrollbar_drag: Option<Pixels>,
+    // Cursor blink state
+    pub cursor_opacity: f32,
+    pub cursor_fading_in: bool,
+    pub blink_epoch: usize,
+    pub fade_start: Option<Instant>,
+    // Animated caret movement (single cursor only), in document coordinates
+    pub last_caret_target: Option<Point<Pixels>>,
+    pub caret_anim_from: Option<Point<Pixels>>,
+    pub caret_anim_start: Option<Instant>,
+    pub caret_anim_epoch: usize,
+    // Smooth scrolling
+    pub scroll_anim_from: Point<Pixels>,
+    pub scroll_anim_target: Option<Point<Pixels>>,
+    pub scroll_anim_start: Option<Instant>,
+    pub scroll_anim_epoch: usize,
+}
+
+impl MultiLineEditor {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let focus_handle = cx.focus_handle();
+        let settings = cx.global::<EditorSettings>();
+        let (word_wrap, font_size) = (settings.config.word_wrap, settings.config.font_size * settings.text_scale);
+        let mut editor = Self {
+            focus_handle,
+            lines: vec![String::new()],
+            cursors: vec![Cursor::new(0, Column::START)],
+            scroll_offset: point(px(0.), px(0.)),
+            preferred_col_x: None,
+            marked_range: None,
+            is_selecting: false,
+            word_wrap,
+            read_only: false,
+            single_line: false,
+            scratchpad: None,
+            file: None,
+            saved_lines: Vec::new(),
+            external_file: None,
+            line_ending: LineEnding::default(),
+            original_lines: None,
+            clipboard_pieces: None,
+            selection_history: Vec::new(),
+            error_ranges: Vec::new(),
+            reported_cursor: None,
+            reported_cursor_count: 1,
+            edits: 0,
+            reported_edits: 0,
+            emitted_cursors: vec![Cursor::new(0, Column::START)],
+            font_size: font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE),
+            last_shaped_lines: Vec::new(),
+            last_wrapped_lines: Vec::new(),
+            last_bounds: None,
+            last_line_height: px(24.),
+            last_max_line_width: px(0.),
+            last_visual_line_counts: Vec::new(),
+            last_tab_layouts: Vec::new(),
+            paint_cache: PaintCache::default(),
+            rewrap_epoch: 0,
+            needs_scroll_to_cursor: false,
+            last_gutter_width: px(0.),
+            last_hscrollbar: None,
+            hscrollbar_drag: None,
+            cursor_opacity: 1.0,
+            cursor_fading_in: true,
+            blink_epoch: 0,
+            fade_start: None,
+            last_caret_target: None,
+            caret_anim_from: None,
+            caret_anim_start: None,
+            caret_anim_epoch: 0,
+            scroll_anim_from: point(px(0.), px(0.)),
+            scroll_anim_target: None,
+            scroll_anim_start: None,
+            scroll_anim_epoch: 0,
+        };
+        editor.reset_cursor_blink(cx);
+        editor
+    }
+
+    /// Reset editor contents with the given text, or empty if None.
+    pub fn reset_with_text(&mut self, text: Option<String>, cx: &mut Context<Self>) {
+        if let Some(text) = text {
+            self.line_ending = if text.contains("\r\n") {
+                LineEnding::Crlf
+            } else {
+                LineEnding::Lf
+            };
+            let new_lines: Vec<String> = text
+                .split('\n')
+                .map(|s| s.strip_suffix('\r').unwrap_or(s).to_string())
+                .collect();
+            let last_line = new_lines.len() - 1;
+            let last_col = Column::end(&new_lines[last_line]);
+            self.original_lines = Some(new_lines.clone());
+            self.lines = new_lines;
+            self.cursors = vec![Cursor {
+                position: CursorPosition::new(last_line, last_col),
+                anchor: Some(CursorPosition::start()),
+            }];
+        } else {
+            self.lines = vec![String::new()];
+            self.cursors = vec![Cursor::new(0, Column::START)];
+            self.original_lines = None;
+            self.line_ending = LineEnding::Lf;
+        }
+
+        self.stop_scroll_animation();
+        self.scroll_offset = point(px(0.), px(0.));
+        self.preferred_col_x = None;
+        self.marked_range = None;
+        self.error_ranges.clear();
+        self.external_file = None;
+        self.reset_cursor_blink(cx);
+        self.edited(cx);
+        cx.notify();
+    }
+
+    /// Swap in text changed outside the popup, keeping the cursors where
+    /// they were as far as the new text allows.
+    pub fn replace_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        self.lines = text
+            .split('\n')
+            .map(|s| s.strip_suffix('\r').unwrap_or(s).to_string())
+            .collect();
+        let cursors = std::mem::take(&mut self.cursors);
+        self.cursors = cursors
+            .iter()
+            .map(|c| {
+                let pos = self.clamp_position(&c.position);
+                Cursor::new(pos.line, pos.col)
+            })
+            .collect();
+        self.merge_overlapping_cursors();
+        self.marked_range = None;
+        self.error_ranges.clear();
+        self.needs_scroll_to_cursor = true;
+        self.edited(cx);
+        cx.notify();
+    }
+
+    /// Put back text saved from an earlier session, caret at the end.
+    pub fn restore_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        self.lines = text.split('\n').map(|s| s.to_string()).collect();
+        let last_line = self.lines.len() - 1;
+        self.cursors = vec![Cursor::new(last_line, Column::end(&self.lines[last_line]))];
+        self.marked_range = None;
+        self.error_ranges.clear();
+        self.needs_scroll_to_cursor = true;
+        self.edited(cx);
+        cx.notify();
+    }
+
+    /// Load a file for editing, caret at the start. Saving writes back to
+    /// `path`.
+    pub fn open_file(&mut self, path: PathBuf, text: String, cx: &mut Context<Self>) {
+        self.reset_with_text(Some(text), cx);
+        self.cursors = vec![Cursor::new(0, Column::START)];
+        self.saved_lines = self.lines.clone();
+        self.file = Some(path);
+        self.emit_cursor_changes(cx);
+    }
+
+    /// Write the buffer back to its file, in the line ending it was read
+    /// with.
+    pub fn save_file(&mut self, cx: &mut Context<Self>) -> std::io::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+        std::fs::write(path, self.line_ending.apply(self.lines.join("\n")))?;
+        self.saved_lines = self.lines.clone();
+        cx.notify();
+        Ok(())
+    }
+
+    /// Whether the buffer's file has changes that aren't saved.
+    pub fn is_dirty(&self) -> bool {
+        self.file.is_some() && self.lines != self.saved_lines
+    }
+
+    /// Name of the buffer's file, without its directory.
+    pub fn file_name(&self) -> Option<String> {
+        let name = self.file.as_deref().and_then(Path::file_name)?;
+        Some(name.to_string_lossy().into_owned())
+    }
+
+    /// The text, cursors, scroll position and modes, for saving and
+    /// restoring later.
+    pub fn snapshot(&self) -> EditorSnapshot {
+        EditorSnapshot {
+            version: SNAPSHOT_VERSION,
+            text: Some(self.lines.join("\n")),
+            line_ending: self.line_ending,
+            cursors: self
+                .cursors
+                .iter()
+                .map(|c| SavedCursor {
+                    position: (c.position.line, c.position.col.offset()),
+                    anchor: c.anchor.as_ref().map(|a| (a.line, a.col.offset())),
+                })
+                .collect(),
+            scroll_x: self.scroll_offset.x.into(),
+            scroll_y: self.scroll_offset.y.into(),
+            word_wrap: self.word_wrap,
+            read_only: self.read_only,
+        }
+    }
+
+    /// Bring back a snapshot. Without text in it, the rest applies to the
+    /// current text, with positions clamped in case it changed since.
+    pub fn restore_snapshot(&mut self, snapshot: &EditorSnapshot, cx: &mut Context<Self>) {
+        if let Some(text) = &snapshot.text {
+            self.restore_text(text, cx);
+            self.line_ending = snapshot.line_ending;
+        }
+        let clamp = |(line, col): (usize, usize)| {
+            let line = line.min(self.lines.len() - 1);
+            CursorPosition::new(line, Column::floor(&self.lines[line], col))
+        };
+        let cursors: Vec<Cursor> = snapshot
+            .cursors
+            .iter()
+            .map(|c| Cursor {
+                position: clamp(c.position),
+                anchor: c.anchor.map(clamp),
+            })
+            .collect();
+        if !cursors.is_empty() {
+            self.cursors = cursors;
+            self.merge_overlapping_cursors();
+        }
+        self.word_wrap = snapshot.word_wrap;
+        self.read_only = snapshot.read_only;
+        self.stop_scroll_animation();
+        self.scroll_offset = point(px(snapshot.scroll_x), px(snapshot.scroll_y));
+        // Keep the saved scroll position rather than jumping to the caret
+        self.needs_scroll_to_cursor = false;
+        self.emit_cursor_changes(cx);
+        cx.notify();
+    }
+
+    // --- Flat offset ↔ CursorPosition conversions (for IME) ---
+
+    fn flat_text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Byte offset of `pos` in the text joined with `\n`.
+    pub fn flat_offset(&self, pos: &CursorPosition) -> usize {
+        let mut offset = 0;
+        for i in 0..pos.line.min(self.lines.len()) {
+            offset += self.lines[i].len() + 1; // +1 for newline
+        }
+        if pos.line < self.lines.len() {
+            offset += pos.col.offset().min(self.lines[pos.line].len());
+        }
+        offset
+    }
+
+    /// The position at byte `offset` of the text joined with `\n`,
+    /// clamped to the text and floored to a grapheme boundary.
+    pub fn position_from_flat(&self, offset: usize) -> CursorPosition {
+        let mut remaining = offset;
+        for (i, line) in self.lines.iter().enumerate() {
+            if remaining <= line.len() {
+                return CursorPosition::new(i, Column::floor(line, remaining));
+            }
+            remaining -= line.len() + 1; // +1 for newline
+        }
+        let last = self.lines.len().saturating_sub(1);
+        CursorPosition::new(last, Column::end(&self.lines[last]))
+    }
+
+    fn flat_selected_range(&self) -> Range<usize> {
+        let c = &self.cursors[0];
+        let start = self.flat_offset(&c.selection_start());
+        let end = self.flat_offset(&c.selection_end());
+        start..end
+    }
+
+    // --- Public query methods ---
+
+    pub fn status_text(&self) -> String {
+        let c = &self.cursors[0];
+        let line = c.position.line + 1;
+        let col = self.lines[c.position.line][..c.position.col.offset()].graphemes(true).count() + 1;
+        let total_lines = self.lines.len();
+        let total_chars: usize = self.lines.iter().map(|l| l.len()).sum::<usize>() + self.lines.len().saturating_sub(1); // chars + newlines
+
+        // Check if there's a selection
+        let has_selection = self.cursors.iter().any(|c| c.has_selection());
+        if has_selection {
+            // Count selected characters across all cursors
+            let mut selected_chars = 0usize;
+            let mut selected_lines = std::collections::BTreeSet::new();
+            for c in &self.cursors {
+                if let Some((start, end)) = c.selection_range() {
+                    for l in start.line..=end.line {
+                        selected_lines.insert(l);
+                    }
+                    selected_chars += self.text_in_range(&start, &end).len();
+                }
+            }
+            let sel_lines = selected_lines.len();
+            format!(
+                "{}:{} ({}, {})",
+                line,
+                col,
+                host::text_count("editor.lines", sel_lines, "{count} line", "{count} lines"),
+                host::text_count("editor.chars", selected_chars, "{count} char", "{count} chars"),
+            )
+        } else {
+            format!(
+                "{}:{} ({}, {})",
+                line,
+                col,
+                host::text_count("editor.lines", total_lines, "{count} line", "{count} lines"),
+                host::text_count("editor.chars", total_chars, "{count} char", "{count} chars"),
+            )
+        }
+    }
+
+    /// Short label for the buffer's tab: the file or scratchpad name, else
+    /// its first non-blank line.
+    pub fn title(&self) -> String {
+        if let Some(name) = self.file_name() {
+            return name;
+        }
+        if let Some(name) = &self.scratchpad {
+            return name.clone();
+        }
+        let first = self.lines.iter().map(|l| l.trim()).find(|l| !l.is_empty());
+        match first {
+            Some(line) if line.chars().count() > 16 => {
+                format!("{}…", line.chars().take(16).collect::<String>())
+            }
+            Some(line) => line.to_string(),
+            None => host::text("editor.untitled", "Untitled", &[]),
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.cursors.iter().any(|c| c.has_selection())
+    }
+
+    /// Narrow the cursors down to the first selection, or select the whole
+    /// buffer when nothing is selected, and return the selected text, so
+    /// what's typed next replaces it.
+    pub fn select_for_replacement(&mut self, cx: &mut Context<Self>) -> String {
+        let mut selected: Vec<Cursor> =
+            self.cursors.iter().filter(|c| c.has_selection()).cloned().collect();
+        selected.sort_by(|a, b| a.position.cmp(&b.position));
+        self.cursors = match selected.into_iter().next() {
+            Some(cursor) => vec![cursor],
+            None => {
+                let last_line = self.lines.len() - 1;
+                vec![Cursor {
+                    position: CursorPosition::new(last_line, Column::end(&self.lines[last_line])),
+                    anchor: Some(CursorPosition::start()),
+                }]
+            }
+        };
+        self.emit_cursor_changes(cx);
+        cx.notify();
+        match self.cursors[0].selection_range() {
+            Some((start, end)) => self.text_in_range(&start, &end),
+            None => String::new(),
+        }
+    }
+
+    pub fn has_multiple_cursors(&self) -> bool {
+        self.cursors.len() > 1
+    }
+
+    pub fn collapse_to_primary_cursor(&mut self, cx: &mut Context<Self>) {
+        self.cursors.truncate(1);
+        self.cursors[0].anchor = None;
+        self.emit_cursor_changes(cx);
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    // --- Cursor manipulation ---
+
+    fn clamp_position(&self, pos: &CursorPosition) -> CursorPosition {
+        let line = pos.line.min(self.lines.len().saturating_sub(1));
+        CursorPosition::new(line, Column::floor(&self.lines[line], pos.col.offset()))
+    }
+
+    fn move_cursors_to(&mut self, pos: CursorPosition, cx: &mut Context<Self>) {
+        let pos = self.clamp_position(&pos);
+        self.cursors = vec![Cursor::new(pos.line, pos.col)];
+        self.error_ranges.clear();
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn select_primary_to(&mut self, pos: CursorPosition, cx: &mut Context<Self>) {
+        let pos = self.clamp_position(&pos);
+        let c = &mut self.cursors[0];
+        if c.anchor.is_none() {
+            c.anchor = Some(c.position.clone());
+        }
+        c.position = pos;
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    fn move_each_cursor<F>(&mut self, f: F, cx: &mut Context<Self>)
+    where
+        F: Fn(&CursorPosition, &[String]) -> CursorPosition,
+    {
+        for c in &mut self.cursors {
+            c.position = f(&c.position, &self.lines);
+            c.anchor = None;
+        }
+        self.merge_overlapping_cursors();
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn select_each_cursor<F>(&mut self, f: F, cx: &mut Context<Self>)
+    where
+        F: Fn(&CursorPosition, &[String]) -> CursorPosition,
+    {
+        for c in &mut self.cursors {
+            if c.anchor.is_none() {
+                c.anchor = Some(c.position.clone());
+            }
+            c.position = f(&c.position, &self.lines);
+        }
+        self.merge_overlapping_cursors();
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    fn merge_overlapping_cursors(&mut self) {
+        if self.cursors.len() <= 1 {
+            return;
+        }
+        self.cursors
+            .sort_by(|a, b| a.position.cmp(&b.position));
+        self.cursors.dedup_by(|a, b| {
+            // If two cursors are at the same position, merge them
+            if a.position == b.position {
+                // Keep the wider selection
+                if a.anchor.is_some() && b.anchor.is_none() {
+                    b.anchor = a.anchor.clone();
+                }
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    // --- Navigation helpers ---
+
+    /// Start of the word before `col`, or of the word it's in.
+    fn prev_word_boundary(line: &str, col: Column, words: WordMotion) -> Column {
+        if words == WordMotion::Programmer {
+            return Self::prev_programmer_word_boundary(line, col);
+        }
+        line.split_word_bound_indices()
+            .rev()
+            .find(|&(idx, segment)| idx < col.offset() && is_word(segment))
+            .map_or(Column::START, |(idx, _)| Column(idx))
+    }
+
+    /// End of the word after `col`, or of the word it's in.
+    fn next_word_boundary(line: &str, col: Column, words: WordMotion) -> Column {
+        if words == WordMotion::Programmer {
+            return Self::next_programmer_word_boundary(line, col);
+        }
+        line.split_word_bound_indices()
+            .map(|(idx, segment)| (idx + segment.len(), segment))
+            .find(|&(end, segment)| end > col.offset() && is_word(segment))
+            .map_or(Column::end(line), |(end, _)| Column(end))
+    }
+
+    fn prev_programmer_word_boundary(line: &str, col: Column) -> Column {
+        let col = col.offset();
+        let mut prev_offset = col;
+        let mut found_word = false;
+        for (idx, grapheme) in line.grapheme_indices(true).rev() {
+            if idx >= col {
+                continue;
+            }
+            let is_word = grapheme
+                .chars()
+                .next()
+                .map(|c| c.is_alphanumeric() || c == '_')
+                .unwrap_or(false);
+            if is_word {
+                found_word = true;
+                prev_offset = idx;
+            } else if found_word {
+                break;
+            } else {
+                prev_offset = idx;
+            }
+        }
+        if found_word {
+            Column(prev_offset)
+        } else {
+            Column::START
+        }
+    }
+
+    fn next_programmer_word_boundary(line: &str, col: Column) -> Column {
+        let col = col.offset();
+        let mut in_word = false;
+        for (idx, grapheme) in line.grapheme_indices(true) {
+            if idx <= col {
+                continue;
+            }
+            let is_word = grapheme
+                .chars()
+                .next()
+                .map(|c| c.is_alphanumeric() || c == '_')
+                .unwrap_or(false);
+            if is_word {
+                in_word = true;
+            } else if in_word {
+                return Column(idx);
+            }
+        }
+        Column::end(line)
+    }
+
+    fn position_left(pos: &CursorPosition, lines: &[String]) -> CursorPosition {
+        if pos.col > Column::START {
+            CursorPosition::new(pos.line, pos.col.prev(&lines[pos.line]))
+        } else if pos.line > 0 {
+            CursorPosition::new(pos.line - 1, Column::end(&lines[pos.line - 1]))
+        } else {
+            pos.clone()
+        }
+    }
+
+    fn position_right(pos: &CursorPosition, lines: &[String]) -> CursorPosition {
+        if pos.col < Column::end(&lines[pos.line]) {
+            CursorPosition::new(pos.line, pos.col.next(&lines[pos.line]))
+        } else if pos.line + 1 < lines.len() {
+            CursorPosition::new(pos.line + 1, Column::START)
+        } else {
+            pos.clone()
+        }
+    }
+
+    fn position_word_left(pos: &CursorPosition, lines: &[String], words: WordMotion) -> CursorPosition {
+        if pos.col > Column::START {
+            CursorPosition::new(pos.line, Self::prev_word_boundary(&lines[pos.line], pos.col, words))
+        } else if pos.line > 0 {
+            CursorPosition::new(pos.line - 1, Column::end(&lines[pos.line - 1]))
+        } else {
+            pos.clone()
+        }
+    }
+
+    fn position_word_right(pos: &CursorPosition, lines: &[String], words: WordMotion) -> CursorPosition {
+        if pos.col < Column::end(&lines[pos.line]) {
+            CursorPosition::new(pos.line, Self::next_word_boundary(&lines[pos.line], pos.col, words))
+        } else if pos.line + 1 < lines.len() {
+            CursorPosition::new(pos.line + 1, Column::START)
+        } else {
+            pos.clone()
+        }
+    }
+
+    // --- Actions ---
+
+    fn left(&mut self, _: &Left, _: &mut Window, cx: &mut Context<Self>) {
+        let has_selection = self.cursors.iter().any(|c| c.has_selection());
+        if has_selection {
+            // Collapse to selection start
+            for c in &mut self.cursors {
+                let start = c.selection_start();
+                c.position = start;
+                c.anchor = None;
+            }
+            self.merge_overlapping_cursors();
+            self.preferred_col_x = None;
+            self.needs_scroll_to_cursor = true;
+            self.reset_cursor_blink(cx);
+            cx.notify();
+        } else {
+            self.preferred_col_x = None;
+            self.move_each_cursor(Self::position_left, cx);
+        }
+    }
+
+    fn right(&mut self, _: &Right, _: &mut Window, cx: &mut Context<Self>) {
+        let has_selection = self.cursors.iter().any(|c| c.has_selection());
+        if has_selection {
+            for c in &mut self.cursors {
+                let end = c.selection_end();
+                c.position = end;
+                c.anchor = None;
+            }
+            self.merge_overlapping_cursors();
+            self.preferred_col_x = None;
+            self.needs_scroll_to_cursor = true;
+            self.reset_cursor_blink(cx);
+            cx.notify();
+        } else {
+            self.preferred_col_x = None;
+            self.move_each_cursor(Self::position_right, cx);
+        }
+    }
+
+    fn up(&mut self, _: &Up, _: &mut Window, cx: &mut Context<Self>) {
+        self.move_vertically(-1, false, cx);
+    }
+
+    fn down(&mut self, _: &Down, _: &mut Window, cx: &mut Context<Self>) {
+        self.move_vertically(1, false, cx);
+    }
+
+    fn select_left(&mut self, _: &SelectLeft, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.select_each_cursor(Self::position_left, cx);
+    }
+
+    fn select_right(&mut self, _: &SelectRight, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.select_each_cursor(Self::position_right, cx);
+    }
+
+    fn select_up(&mut self, _: &SelectUp, _: &mut Window, cx: &mut Context<Self>) {
+        self.move_vertically(-1, true, cx);
+    }
+
+    fn select_down(&mut self, _: &SelectDown, _: &mut Window, cx: &mut Context<Self>) {
+        self.move_vertically(1, true, cx);
+    }
+
+    fn select_all(&mut self, _: &SelectAll, _: &mut Window, cx: &mut Context<Self>) {
+        let last_line = self.lines.len() - 1;
+        let last_col = Column::end(&self.lines[last_line]);
+        self.cursors = vec![Cursor {
+            position: CursorPosition::new(last_line, last_col),
+            anchor: Some(CursorPosition::start()),
+        }];
+        cx.notify();
+    }
+
+    fn home(&mut self, _: &Home, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.move_each_cursor(
+            |pos, _lines| CursorPosition::new(pos.line, Column::START),
+            cx,
+        );
+    }
+
+    fn end(&mut self, _: &End, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.move_each_cursor(
+            |pos, lines| CursorPosition::new(pos.line, Column::end(&lines[pos.line])),
+            cx,
+        );
+    }
+
+    fn document_start(&mut self, _: &DocumentStart, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.move_cursors_to(CursorPosition::start(), cx);
+    }
+
+    fn document_end(&mut self, _: &DocumentEnd, _: &mut Window, cx: &mut Context<Self>) {
+        let last = self.lines.len() - 1;
+        self.preferred_col_x = None;
+        self.move_cursors_to(CursorPosition::new(last, Column::end(&self.lines[last])), cx);
+    }
+
+    fn select_home(&mut self, _: &SelectHome, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.select_each_cursor(
+            |pos, _lines| CursorPosition::new(pos.line, Column::START),
+            cx,
+        );
+    }
+
+    fn select_end(&mut self, _: &SelectEnd, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        self.select_each_cursor(
+            |pos, lines| CursorPosition::new(pos.line, Column::end(&lines[pos.line])),
+            cx,
+        );
+    }
+
+    fn select_document_start(&mut self, _: &SelectDocumentStart, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        let pos = CursorPosition::start();
+        for c in &mut self.cursors {
+            if c.anchor.is_none() {
+                c.anchor = Some(c.position.clone());
+            }
+            c.position = pos.clone();
+        }
+        self.merge_overlapping_cursors();
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    fn select_document_end(&mut self, _: &SelectDocumentEnd, _: &mut Window, cx: &mut Context<Self>) {
+        let last = self.lines.len() - 1;
+        let last_col = Column::end(&self.lines[last]);
+        self.preferred_col_x = None;
+        let pos = CursorPosition::new(last, last_col);
+        for c in &mut self.cursors {
+            if c.anchor.is_none() {
+                c.anchor = Some(c.position.clone());
+            }
+            c.position = pos.clone();
+        }
+        self.merge_overlapping_cursors();
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    /// Grow every selection to the next enclosing word, line, paragraph,
+    /// bracketed block or the whole document.
+    fn expand_selection(&mut self, _: &ExpandSelection, _: &mut Window, cx: &mut Context<Self>) {
+        let text = self.flat_text();
+        let before = self.cursors.clone();
+        for i in 0..self.cursors.len() {
+            let c = &self.cursors[i];
+            let range = self.flat_offset(&c.selection_start())..self.flat_offset(&c.selection_end());
+            if let Some(larger) = text_objects::expand(&text, range) {
+                self.cursors[i] = Cursor {
+                    position: self.position_from_flat(larger.end),
+                    anchor: Some(self.position_from_flat(larger.start)),
+                };
+            }
+        }
+        if self.cursors == before {
+            return;
+        }
+        self.merge_overlapping_cursors();
+        if self.selection_history.last().is_some_and(|(_, after)| *after != before) {
+            self.selection_history.clear();
+        }
+        self.selection_history.push((before, self.cursors.clone()));
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    /// Undo the last `ExpandSelection` step, if the selection hasn't been
+    /// changed some other way since.
+    fn shrink_selection(&mut self, _: &ShrinkSelection, _: &mut Window, cx: &mut Context<Self>) {
+        match self.selection_history.pop() {
+            Some((before, after)) if after == self.cursors => {
+                self.cursors = before;
+                self.preferred_col_x = None;
+                self.needs_scroll_to_cursor = true;
+                cx.notify();
+            }
+            _ => self.selection_history.clear(),
+        }
+    }
+
+    fn select_inside_brackets(&mut self, _: &SelectInsideBrackets, _: &mut Window, cx: &mut Context<Self>) {
+        self.select_enclosing_pair(false, cx);
+    }
+
+    fn select_around_brackets(&mut self, _: &SelectAroundBrackets, _: &mut Window, cx: &mut Context<Self>) {
+        self.select_enclosing_pair(true, cx);
+    }
+
+    /// Select the contents of the nearest bracket or quote pair around each
+    /// cursor, optionally including the delimiters. Repeating the action
+    /// moves out to the next pair. Cursors outside any pair stay put.
+    fn select_enclosing_pair(&mut self, include_delimiters: bool, cx: &mut Context<Self>) {
+        let text = self.flat_text();
+        for i in 0..self.cursors.len() {
+            let c = &self.cursors[i];
+            let mut range = self.flat_offset(&c.selection_start())..self.flat_offset(&c.selection_end());
+            let mut selected = None;
+            while let Some((open, close)) = text_objects::enclosing_pair(&text, &range) {
+                let pair = if include_delimiters { open..close + 1 } else { open + 1..close };
+                if pair != range {
+                    selected = Some(pair);
+                    break;
+                }
+                range = open..close + 1;
+            }
+            if let Some(pair) = selected {
+                self.cursors[i] = Cursor {
+                    position: self.position_from_flat(pair.end),
+                    anchor: Some(self.position_from_flat(pair.start)),
+                };
+            }
+        }
+        self.merge_overlapping_cursors();
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    fn word_left(&mut self, _: &WordLeft, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        let words = cx.global::<EditorSettings>().config.word_motion;
+        self.move_each_cursor(|pos, lines| Self::position_word_left(pos, lines, words), cx);
+    }
+
+    fn word_right(&mut self, _: &WordRight, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        let words = cx.global::<EditorSettings>().config.word_motion;
+        self.move_each_cursor(|pos, lines| Self::position_word_right(pos, lines, words), cx);
+    }
+
+    fn select_word_left(&mut self, _: &SelectWordLeft, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        let words = cx.global::<EditorSettings>().config.word_motion;
+        self.select_each_cursor(|pos, lines| Self::position_word_left(pos, lines, words), cx);
+    }
+
+    fn select_word_right(&mut self, _: &SelectWordRight, _: &mut Window, cx: &mut Context<Self>) {
+        self.preferred_col_x = None;
+        let words = cx.global::<EditorSettings>().config.word_motion;
+        self.select_each_cursor(|pos, lines| Self::position_word_right(pos, lines, words), cx);
+    }
+
+    fn backspace(&mut self, _: &Backspace, window: &mut Window, cx: &mut Context<Self>) {
+        self.edit_with_cursors(
+            |pos, lines| {
+                // If at start of line, select back to end of previous line
+                if pos.col == Column::START {
+                    if pos.line > 0 {
+                        Some((
+                            CursorPosition::new(pos.line - 1, Column::end(&lines[pos.line - 1])),
+                            pos.clone(),
+                        ))
+                    } else {
+                        None
+                    }
+                } else {
+                    let prev = pos.col.prev(&lines[pos.line]);
+                    Some((CursorPosition::new(pos.line, prev), pos.clone()))
+                }
+            },
+            "",
+            window,
+            cx,
+        );
+    }
+
+    fn delete(&mut self, _: &Delete, window: &mut Window, cx: &mut Context<Self>) {
+        self.edit_with_cursors(
+            |pos, lines| {
+                if pos.col >= Column::end(&lines[pos.line]) {
+                    if pos.line + 1 < lines.len() {
+                        Some((pos.clone(), CursorPosition::new(pos.line + 1, Column::START)))
+                    } else {
+                        None
+                    }
+                } else {
+                    let next = pos.col.next(&lines[pos.line]);
+                    Some((pos.clone(), CursorPosition::new(pos.line, next)))
+                }
+            },
+            "",
+            window,
+            cx,
+        );
+    }
+
+    fn delete_to_start(&mut self, _: &DeleteToStart, window: &mut Window, cx: &mut Context<Self>) {
+        self.edit_with_cursors(
+            |pos, _lines| {
+                if pos.col > Column::START {
+                    Some((CursorPosition::new(pos.line, Column::START), pos.clone()))
+                } else {
+                    None
+                }
+            },
+            "",
+            window,
+            cx,
+        );
+    }
+
+    fn delete_word_backward(
+        &mut self,
+        _: &DeleteWordBackward,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let words = cx.global::<EditorSettings>().config.word_motion;
+        self.edit_with_cursors(
+            move |pos, lines| {
+                if pos.col > Column::START {
+                    let prev = Self::prev_word_boundary(&lines[pos.line], pos.col, words);
+                    Some((CursorPosition::new(pos.line, prev), pos.clone()))
+                } else if pos.line > 0 {
+                    Some((
+                        CursorPosition::new(pos.line - 1, Column::end(&lines[pos.line - 1])),
+                        pos.clone(),
+                    ))
+                } else {
+                    None
+                }
+            },
+            "",
+            window,
+            cx,
+        );
+    }
+
+    fn enter(&mut self, _: &Enter, window: &mut Window, cx: &mut Context<Self>) {
+        // Insert newline at each cursor
+        self.insert_text_at_cursors("\n", window, cx);
+    }
+
+    fn move_line_up(&mut self, _: &MoveLineUp, _: &mut Window, cx: &mut Context<Self>) {
+        if self.read_only {
+            return;
+        }
+        // Collect affected line ranges for each cursor
+        let mut moved = false;
+        for c in &mut self.cursors {
+            let start_line = c.selection_start().line;
+            if start_line > 0 {
+                moved = true;
+            }
+        }
+        if !moved {
+            return;
+        }
+
+        // For simplicity with single/primary cursor
+        let start_line = self.cursors[0].selection_start().line;
+        let end_line = self.cursors[0].selection_end().line;
+
+        if start_line == 0 {
+            return;
+        }
+
+        let removed = self.lines.remove(start_line - 1);
+        let insert_at = (end_line).min(self.lines.len());
+        self.lines.insert(insert_at, removed);
+
+        for c in &mut self.cursors {
+            if c.position.line >= start_line && c.position.line <= end_line {
+                c.position.line -= 1;
+            }
+            if let Some(ref mut a) = c.anchor
+                && a.line >= start_line
+                && a.line <= end_line
+            {
+                a.line -= 1;
+            }
+        }
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        self.edited(cx);
+        cx.notify();
+    }
+
+    fn move_line_down(&mut self, _: &MoveLineDown, _: &mut Window, cx: &mut Context<Self>) {
+        if self.read_only {
+            return;
+        }
+        let start_line = self.cursors[0].selection_start().line;
+        let end_line = self.cursors[0].selection_end().line;
+
+        if end_line + 1 >= self.lines.len() {
+            return;
+        }
+
+        let removed = self.lines.remove(end_line + 1);
+        self.lines.insert(start_line, removed);
+
+        for c in &mut self.cursors {
+            if c.position.line >= start_line && c.position.line <= end_line {
+                c.position.line += 1;
+            }
+            if let Some(ref mut a) = c.anchor
+                && a.line >= start_line
+                && a.line <= end_line
+            {
+                a.line += 1;
+            }
+        }
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        self.edited(cx);
+        cx.notify();
+    }
+
+    fn add_cursor_up(&mut self, _: &AddCursorUp, _: &mut Window, cx: &mut Context<Self>) {
+        let first = self
+            .cursors
+            .iter()
+            .min_by_key(|c| c.position.line)
+            .unwrap();
+        if first.position.line == 0 {
+            return;
+        }
+        let new_line = first.position.line - 1;
+        let col = self.col_for_preferred_x(new_line, cx);
+        self.cursors.push(Cursor::new(new_line, col));
+        self.merge_overlapping_cursors();
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn add_cursor_down(&mut self, _: &AddCursorDown, _: &mut Window, cx: &mut Context<Self>) {
+        let last = self
+            .cursors
+            .iter()
+            .max_by_key(|c| c.position.line)
+            .unwrap();
+        if last.position.line + 1 >= self.lines.len() {
+            return;
+        }
+        let new_line = last.position.line + 1;
+        let col = self.col_for_preferred_x(new_line, cx);
+        self.cursors.push(Cursor::new(new_line, col));
+        self.merge_overlapping_cursors();
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn show_character_palette(
+        &mut self,
+        _: &ShowCharacterPalette,
+        window: &mut Window,
+        _: &mut Context<Self>,
+    ) {
+        window.show_character_palette();
+    }
+
+    fn paste(&mut self, _: &Paste, window: &mut Window, cx: &mut Context<Self>) {
+        let convert_html = cx.global::<EditorSettings>().config.paste_html_as_markdown;
+        if convert_html && self.paste_html_as_markdown(window, cx) {
+            return;
+        }
+        let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) else {
+            return;
+        };
+        // Distribute a multi-cursor copy back over the same number of cursors
+        if let Some(pieces) = &self.clipboard_pieces
+            && pieces.len() == self.cursors.len()
+            && pieces.join("\n") == text
+        {
+            let pieces = pieces.clone();
+            let texts: Vec<&str> = pieces.iter().map(String::as_str).collect();
+            self.insert_texts_at_cursors(&texts, window, cx);
+        } else {
+            self.insert_text_at_cursors(&text, window, cx);
+        }
+    }
+
+    /// Paste the clipboard's HTML converted to Markdown, falling back to a
+    /// plain paste when there's no HTML.
+    fn paste_as_markdown(&mut self, _: &PasteAsMarkdown, window: &mut Window, cx: &mut Context<Self>) {
+        if !self.paste_html_as_markdown(window, cx) {
+            self.paste(&Paste, window, cx);
+        }
+    }
+
+    /// Insert the clipboard's HTML as Markdown at every cursor. Returns
+    /// false when the clipboard holds no HTML.
+    fn paste_html_as_markdown(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(html) = host::current().read_html() else {
+            return false;
+        };
+        let text = markdown::from_html(&html);
+        if text.is_empty() {
+            return false;
+        }
+        self.insert_text_at_cursors(&text, window, cx);
+        true
+    }
+
+    /// Selected text of every cursor, in document order.
+    fn selected_pieces(&self) -> Vec<String> {
+        let mut selections: Vec<(CursorPosition, CursorPosition)> = self
+            .cursors
+            .iter()
+            .filter_map(|c| c.selection_range())
+            .collect();
+        selections.sort_by(|a, b| a.0.cmp(&b.0));
+        selections
+            .iter()
+            .map(|(start, end)| self.text_in_range(start, end))
+            .collect()
+    }
+
+    /// Write the selections to the clipboard, one line per cursor, and
+    /// remember the pieces for a matching multi-cursor paste.
+    /// Returns false when nothing is selected.
+    fn copy_selections(&mut self, cx: &mut Context<Self>) -> bool {
+        let pieces = self.selected_pieces();
+        if pieces.is_empty() {
+            return false;
+        }
+        cx.write_to_clipboard(ClipboardItem::new_string(pieces.join("\n")));
+        self.clipboard_pieces = if pieces.len() > 1 { Some(pieces) } else { None };
+        true
+    }
+
+    fn copy(&mut self, _: &Copy, _: &mut Window, cx: &mut Context<Self>) {
+        self.copy_selections(cx);
+    }
+
+    /// Copy the selections, or the whole buffer when nothing is selected,
+    /// as Markdown rendered to rich text, with the Markdown as plain text.
+    fn copy_as_rich_text(&mut self, _: &CopyAsRichText, _: &mut Window, cx: &mut Context<Self>) {
+        let pieces = self.selected_pieces();
+        let plain = if pieces.is_empty() {
+            self.lines.join("\n")
+        } else {
+            pieces.join("\n\n")
+        };
+        host::current().write_rich_text(&plain, &markdown::to_html(&plain), cx);
+        self.clipboard_pieces = None;
+    }
+
+    fn cut(&mut self, _: &Cut, window: &mut Window, cx: &mut Context<Self>) {
+        if self.copy_selections(cx) {
+            self.insert_text_at_cursors("", window, cx);
+        }
+    }
+
+    /// Evaluate each selection as arithmetic. A selection ending in `=` gets
+    /// the result appended (`12*(3+4)=` → `12*(3+4)=84`), otherwise it is
+    /// replaced by the result. Selections that don't parse are left as is.
+    fn evaluate_selection(&mut self, _: &EvaluateSelection, window: &mut Window, cx: &mut Context<Self>) {
+        let mut sorted = self.cursors.clone();
+        sorted.sort_by(|a, b| a.position.cmp(&b.position));
+
+        let mut evaluated_any = false;
+        let replacements: Vec<String> = sorted
+            .iter()
+            .map(|c| {
+                let Some((start, end)) = c.selection_range() else {
+                    return String::new();
+                };
+                let selected = self.text_in_range(&start, &end);
+                let (expr, append) = match selected.trim_end().strip_suffix('=') {
+                    Some(expr) => (expr, true),
+                    None => (selected.as_str(), false),
+                };
+                match calc::evaluate(expr) {
+                    Ok(value) => {
+                        evaluated_any = true;
+                        let result = calc::format_number(value);
+                        if append {
+                            format!("{}{}", selected, result)
+                        } else {
+                            result
+                        }
+                    }
+                    Err(_) => selected,
+                }
+            })
+            .collect();
+
+        if evaluated_any {
+            let texts: Vec<&str> = replacements.iter().map(String::as_str).collect();
+            self.insert_texts_at_cursors(&texts, window, cx);
+        }
+    }
+
+    fn base64_encode(&mut self, _: &Base64Encode, window: &mut Window, cx: &mut Context<Self>) {
+        self.convert_selections(|text| Some(STANDARD.encode(text)), window, cx);
+    }
+
+    fn base64_decode(&mut self, _: &Base64Decode, window: &mut Window, cx: &mut Context<Self>) {
+        self.convert_selections(decode_base64, window, cx);
+    }
+
+    /// The text of each selection, as `selection_texts`, selecting the
+    /// whole buffer first when nothing is selected. For converting
+    /// somewhere else and handing back to `replace_selections` with the
+    /// cursors as they are now.
+    pub fn selection_texts_or_buffer(&mut self, cx: &mut Context<Self>) -> Vec<Option<String>> {
+        if !self.has_selection() {
+            self.select_for_replacement(cx);
+        }
+        self.selection_texts()
+    }
+
+    /// Text of each cursor's selection in document order, None for cursors
+    /// without one.
+    fn selection_texts(&self) -> Vec<Option<String>> {
+        let mut sorted = self.cursors.clone();
+        sorted.sort_by(|a, b| a.position.cmp(&b.position));
+        sorted
+            .iter()
+            .map(|c| {
+                let (start, end) = c.selection_range()?;
+                Some(self.text_in_range(&start, &end))
+            })
+            .collect()
+    }
+
+    /// Replace each selection with `convert` of its text. Selections it
+    /// returns None for are left as they were, still selected, and marked
+    /// in `error_ranges`.
+    fn convert_selections(
+        &mut self,
+        mut convert: impl FnMut(&str) -> Option<String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.read_only || !self.has_selection() {
+            return;
+        }
+        let converted = self
+            .selection_texts()
+            .into_iter()
+            .map(|text| convert(&text?))
+            .collect();
+        self.replace_selections(converted, window, cx);
+    }
+
+    /// Replace each selection with its entry in `converted`, in document
+    /// order. Selections with None are left as they were, still selected,
+    /// and marked in `error_ranges`.
+    pub fn replace_selections(
+        &mut self,
+        converted: Vec<Option<String>>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.read_only || !self.has_selection() || converted.len() != self.cursors.len() {
+            return;
+        }
+        let mut sorted = self.cursors.clone();
+        sorted.sort_by(|a, b| a.position.cmp(&b.position));
+
+        let mut failed = Vec::new();
+        let replacements: Vec<String> = sorted
+            .iter()
+            .zip(converted)
+            .enumerate()
+            .map(|(i, (c, converted))| {
+                let Some((start, end)) = c.selection_range() else {
+                    return String::new();
+                };
+                converted.unwrap_or_else(|| {
+                    failed.push(i);
+                    self.text_in_range(&start, &end)
+                })
+            })
+            .collect();
+
+        let texts: Vec<&str> = replacements.iter().map(String::as_str).collect();
+        self.insert_texts_at_cursors(&texts, window, cx);
+        if failed.is_empty() || self.cursors.len() != replacements.len() {
+            return;
+        }
+
+        // Select the unconverted text again: each cursor now sits at the
+        // end of what was inserted for it
+        let mut order: Vec<usize> = (0..self.cursors.len()).collect();
+        order.sort_by(|&a, &b| self.cursors[a].position.cmp(&self.cursors[b].position));
+        for i in failed {
+            let cursor = order[i];
+            let end = self.cursors[cursor].position.clone();
+            let text = &replacements[i];
+            let start = match text.rfind('\n') {
+                None => {
+                    let col = Column::floor(&self.lines[end.line], end.col.offset() - text.len());
+                    CursorPosition::new(end.line, col)
+                }
+                Some(_) => {
+                    let line = end.line - text.matches('\n').count();
+                    let first_len = text.find('\n').unwrap_or(0);
+                    let col = Column::floor(&self.lines[line], self.lines[line].len() - first_len);
+                    CursorPosition::new(line, col)
+                }
+            };
+            self.cursors[cursor].anchor = Some(start.clone());
+            self.error_ranges.push((start, end));
+        }
+        self.emit_cursor_changes(cx);
+        cx.notify();
+    }
+
+    fn insert_timestamp(&mut self, _: &InsertTimestamp, window: &mut Window, cx: &mut Context<Self>) {
+        use std::fmt::Write;
+
+        let format = &cx.global::<EditorSettings>().config.timestamp_format;
+        let now = chrono::Local::now();
+        let mut timestamp = String::new();
+        // An invalid format string makes Display fail; fall back to ISO 8601
+        if write!(timestamp, "{}", now.format(format)).is_err() {
+            timestamp = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+        }
+        self.insert_text_at_cursors(&timestamp, window, cx);
+    }
+
+    /// Indent at each cursor: a tab character, or spaces up to the next tab
+    /// stop.
+    fn insert_tab(&mut self, _: &InsertTab, window: &mut Window, cx: &mut Context<Self>) {
+        let prefs = &cx.global::<EditorSettings>().config;
+        if prefs.hard_tabs {
+            self.insert_text_at_cursors("\t", window, cx);
+            return;
+        }
+        let tab_size = prefs.tab_size.max(1);
+        let mut starts: Vec<CursorPosition> = self
+            .cursors
+            .iter()
+            .map(|c| c.selection_range().map_or(c.position.clone(), |(start, _)| start))
+            .collect();
+        starts.sort();
+        let indents: Vec<String> = starts
+            .iter()
+            .map(|start| {
+                let line = &self.lines[start.line];
+                let width = elastic_tabs::display_width(&line[..start.col.offset().min(line.len())], tab_size);
+                " ".repeat(tab_size - width % tab_size)
+            })
+            .collect();
+        let indents: Vec<&str> = indents.iter().map(String::as_str).collect();
+        self.insert_texts_at_cursors(&indents, window, cx);
+    }
+
+    /// Get the text to submit/paste.
+    /// - If any cursor has a selection, join all selected texts
+    ///   (same line = space separator, different lines = newline separator)
+    /// - If no selections, return all editor text
+    pub fn get_submit_text(&self) -> String {
+        // Check if any cursor has a selection
+        let has_any_selection = self.cursors.iter().any(|c| c.has_selection());
+
+        if !has_any_selection {
+            // No selections - return entire editor content
+            return self.lines.join("\n");
+        }
+
+        // Collect all selections sorted by position
+        let mut selections: Vec<(CursorPosition, CursorPosition)> = self
+            .cursors
+            .iter()
+            .filter_map(|c| c.selection_range())
+            .collect();
+        selections.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Join selections: same line = space, different lines = newline
+        let mut result = String::new();
+        let mut last_line: Option<usize> = None;
+
+        for (start, end) in selections {
+            let text = self.text_in_range(&start, &end);
+
+            if let Some(prev_line) = last_line {
+                if start.line == prev_line {
+                    // Same line as previous selection - join with space
+                    result.push(' ');
+                } else {
+                    // Different line - join with newline
+                    result.push('\n');
+                }
+            }
+
+            result.push_str(&text);
+            last_line = Some(end.line);
+        }
+
+        result
+    }
+
+    // --- Layout helpers (abstract over wrapped/unwrapped) ---
+
+    fn x_for_index_in_line(&self, line: usize, col: Column) -> Pixels {
+        let col = display_col(&self.last_tab_layouts, line, col);
+        if self.word_wrap {
+            self.last_wrapped_lines.get(line)
+                .map(|wl| wl.unwrapped_layout.x_for_index(col))
+                .unwrap_or(px(0.))
+        } else {
+            self.last_shaped_lines.get(line)
+                .map(|l| l.x_for_index(col))
+                .unwrap_or(px(0.))
+        }
+    }
+
+    /// Top left of the caret at `pos` in the laid-out document, relative to
+    /// the content area before scrolling. Wrapped lines place it on the
+    /// visual row holding it.
+    pub(crate) fn caret_origin(&self, pos: &CursorPosition) -> Point<Pixels> {
+        let col = display_col(&self.last_tab_layouts, pos.line, pos.col);
+        if self.word_wrap {
+            let rows: usize = self.last_visual_line_counts.iter().take(pos.line).sum();
+            let within = self.last_wrapped_lines.get(pos.line)
+                .and_then(|wl| wl.position_for_index(col, self.last_line_height))
+                .unwrap_or(point(px(0.), px(0.)));
+            point(within.x, self.last_line_height * rows + within.y)
+        } else {
+            let x = self.last_shaped_lines.get(pos.line)
+                .map(|l| l.x_for_index(col))
+                .unwrap_or(px(0.));
+            point(x, self.last_line_height * pos.line)
+        }
+    }
+
+    /// Bounds of the selection highlights as last painted, in window
+    /// coordinates.
+    #[cfg(feature = "test-support")]
+    pub(crate) fn painted_selections(&self) -> Vec<Bounds<Pixels>> {
+        self.paint_cache
+            .overlay
+            .as_ref()
+            .map_or_else(Vec::new, |overlay| overlay.selections.iter().map(|quad| quad.bounds).collect())
+    }
+
+    fn closest_index_for_x_in_line(&self, line: usize, x: Pixels) -> Column {
+        let text = display_text(&self.lines, &self.last_tab_layouts, line);
+        let col = if self.word_wrap {
+            self.last_wrapped_lines.get(line)
+                .map(|wl| closest_grapheme_for_x(&wl.unwrapped_layout, text, x))
+                .unwrap_or(0)
+        } else {
+            self.last_shaped_lines.get(line)
+                .map(|l| closest_grapheme_for_x(l, text, x))
+                .unwrap_or(0)
+        };
+        Column::floor(&self.lines[line], buffer_col(&self.last_tab_layouts, line, col))
+    }
+
+    // --- Vertical movement ---
+
+    fn move_vertically(&mut self, direction: i32, selecting: bool, cx: &mut Context<Self>) {
+        // Ensure preferred_col_x is set from current position
+        if self.preferred_col_x.is_none() {
+            self.preferred_col_x = Some(self.x_for_index_in_line(
+                self.cursors[0].position.line,
+                self.cursors[0].position.col,
+            ));
+        }
+
+        for c in &mut self.cursors {
+            let new_line = if direction < 0 {
+                if c.position.line == 0 {
+                    if !selecting {
+                        c.position = CursorPosition::start();
+                        c.anchor = None;
+                    } else {
+                        if c.anchor.is_none() {
+                            c.anchor = Some(c.position.clone());
+                        }
+                        c.position = CursorPosition::start();
+                    }
+                    continue;
+                }
+                c.position.line - 1
+            } else {
+                if c.position.line + 1 >= self.lines.len() {
+                    let end_col = Column::end(&self.lines[c.position.line]);
+                    if !selecting {
+                        c.position = CursorPosition::new(c.position.line, end_col);
+                        c.anchor = None;
+                    } else {
+                        if c.anchor.is_none() {
+                            c.anchor = Some(c.position.clone());
+                        }
+                        c.position = CursorPosition::new(c.position.line, end_col);
+                    }
+                    continue;
+                }
+                c.position.line + 1
+            };
+
+            // Find col from preferred_col_x
+            let col = if let Some(px_x) = self.preferred_col_x {
+                let text = display_text(&self.lines, &self.last_tab_layouts, new_line);
+                if self.word_wrap {
+                    self.last_wrapped_lines.get(new_line)
+                        .map(|wl| closest_grapheme_for_x(&wl.unwrapped_layout, text, px_x))
+                        .map(|col| buffer_col(&self.last_tab_layouts, new_line, col))
+                        .unwrap_or(0)
+                } else {
+                    self.last_shaped_lines.get(new_line)
+                        .map(|l| closest_grapheme_for_x(l, text, px_x))
+                        .map(|col| buffer_col(&self.last_tab_layouts, new_line, col))
+                        .unwrap_or(c.position.col.offset())
+                }
+            } else {
+                c.position.col.offset()
+            };
+            let col = Column::floor(&self.lines[new_line], col);
+
+            if selecting {
+                if c.anchor.is_none() {
+                    c.anchor = Some(c.position.clone());
+                }
+            } else {
+                c.anchor = None;
+            }
+            c.position = CursorPosition::new(new_line, col);
+        }
+
+        self.merge_overlapping_cursors();
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn col_for_preferred_x(&self, line: usize, _cx: &mut Context<Self>) -> Column {
+        if let Some(px_x) = self.preferred_col_x {
+            return self.closest_index_for_x_in_line(line, px_x);
+        }
+        // Fallback: use primary cursor col clamped to line length
+        Column::floor(&self.lines[line], self.cursors[0].position.col.offset())
+    }
+
+    // --- Text extraction ---
+
+    fn text_in_range(&self, start: &CursorPosition, end: &CursorPosition) -> String {
+        if start.line == end.line {
+            return self.lines[start.line][start.col.offset()..end.col.offset()].to_string();
+        }
+        let mut result = String::new();
+        // First line
+        result.push_str(&self.lines[start.line][start.col.offset()..]);
+        // Middle lines
+        for i in (start.line + 1)..end.line {
+            result.push('\n');
+            result.push_str(&self.lines[i]);
+        }
+        // Last line
+        result.push('\n');
+        result.push_str(&self.lines[end.line][..end.col.offset()]);
+        result
+    }
+
+    // --- Multi-cursor edit ---
+
+    pub fn insert_text_at_cursors(
+        &mut self,
+        text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let texts = vec![text; self.cursors.len()];
+        self.insert_texts_at_cursors(&texts, window, cx);
+    }
+
+    /// Insert `texts[i]` at the i-th cursor in document order, replacing its
+    /// selection. `texts` must have one entry per cursor.
+    fn insert_texts_at_cursors(
+        &mut self,
+        texts: &[&str],
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.read_only {
+            return;
+        }
+        self.error_ranges.clear();
+        // Sort cursors in reverse document order (bottom-first)
+        let mut indexed: Vec<(usize, Cursor)> =
+            self.cursors.iter().cloned().enumerate().collect();
+        indexed.sort_by(|a, b| b.1.position.cmp(&a.1.position));
+
+        let mut new_positions: Vec<(usize, CursorPosition)> = Vec::new();
+
+        for (rank, (orig_idx, c)) in indexed.iter().enumerate() {
+            let text = texts[indexed.len() - 1 - rank];
+            let (del_start, del_end) = if let Some((s, e)) = c.selection_range() {
+                (s, e)
+            } else {
+                (c.position.clone(), c.position.clone())
+            };
+
+            self.delete_range(&del_start, &del_end);
+            let inserted_pos = self.insert_at(&del_start, text);
+
+            // Cursors already placed sit after this edit: move them with the
+            // text that followed it
+            let newlines = text.matches('\n').count();
+            let end_line = del_start.line + newlines;
+            let end_col = if newlines == 0 {
+                del_start.col.offset() + text.len()
+            } else {
+                text.len() - text.rfind('\n').unwrap() - 1
+            };
+            for (_, pos) in &mut new_positions {
+                let col = if pos.line == del_end.line {
+                    end_col + pos.col.offset().saturating_sub(del_end.col.offset())
+                } else {
+                    pos.col.offset()
+                };
+                pos.line = pos.line - del_end.line + end_line;
+                pos.col = Column::floor(&self.lines[pos.line], col);
+            }
+            new_positions.push((*orig_idx, inserted_pos));
+        }
+
+        // Rebuild cursors in original order
+        new_positions.sort_by_key(|(idx, _)| *idx);
+        self.cursors = new_positions
+            .into_iter()
+            .map(|(_, pos)| Cursor::new(pos.line, pos.col))
+            .collect();
+
+        self.merge_overlapping_cursors();
+        self.marked_range = None;
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        self.edited(cx);
+        cx.notify();
+    }
+
+    fn edit_with_cursors<F>(
+        &mut self,
+        expand_fn: F,
+        replacement: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) where
+        F: Fn(&CursorPosition, &[String]) -> Option<(CursorPosition, CursorPosition)>,
+    {
+        if self.read_only {
+            return;
+        }
+        // For cursors without selection, expand using expand_fn
+        for c in &mut self.cursors {
+            if !c.has_selection()
+                && let Some((start, end)) = expand_fn(&c.position, &self.lines)
+            {
+                c.anchor = Some(start);
+                c.position = end;
+                // Normalize so anchor < position
+                let s = c.selection_start();
+                let e = c.selection_end();
+                c.anchor = Some(s);
+                c.position = e;
+            }
+        }
+        self.insert_text_at_cursors(replacement, window, cx);
+    }
+
+    // --- Low-level text mutation ---
+
+    /// Delete a range and return the deleted text
+    fn delete_range(&mut self, start: &CursorPosition, end: &CursorPosition) -> String {
+        if start == end {
+            return String::new();
+        }
+        let deleted = self.text_in_range(start, end);
+
+        if start.line == end.line {
+            self.lines[start.line] = format!(
+                "{}{}",
+                &self.lines[start.line][..start.col.offset()],
+                &self.lines[start.line][end.col.offset()..]
+            );
+        } else {
+            let new_line = format!(
+                "{}{}",
+                &self.lines[start.line][..start.col.offset()],
+                &self.lines[end.line][end.col.offset()..]
+            );
+            // Remove lines from start.line+1 to end.line (inclusive)
+            for _ in start.line + 1..=end.line {
+                self.lines.remove(start.line + 1);
+            }
+            self.lines[start.line] = new_line;
+        }
+
+        deleted
+    }
+
+    /// Insert text at position, return new cursor position after insert
+    fn insert_at(&mut self, pos: &CursorPosition, text: &str) -> CursorPosition {
+        if text.is_empty() {
+            return pos.clone();
+        }
+
+        let insert_lines: Vec<&str> = text.split('\n').collect();
+
+        if insert_lines.len() == 1 {
+            // Single-line insert
+            self.lines[pos.line].insert_str(pos.col.offset(), text);
+            // Past all of the text, even when its end joins the grapheme
+            // that follows
+            let col = Column::ceil(&self.lines[pos.line], pos.col.offset() + text.len());
+            return CursorPosition::new(pos.line, col);
+        }
+
+        // Multi-line insert
+        let after_cursor = self.lines[pos.line][pos.col.offset()..].to_string();
+        self.lines[pos.line] =
+            format!("{}{}", &self.lines[pos.line][..pos.col.offset()], insert_lines[0]);
+
+        for (i, segment) in insert_lines[1..].iter().enumerate() {
+            if i == insert_lines.len() - 2 {
+                // Last segment — append the text that was after the cursor
+                self.lines
+                    .insert(pos.line + 1 + i, format!("{}{}", segment, after_cursor));
+            } else {
+                self.lines
+                    .insert(pos.line + 1 + i, segment.to_string());
+            }
+        }
+
+        let new_line = pos.line + insert_lines.len() - 1;
+        let new_col = Column::ceil(&self.lines[new_line], insert_lines.last().unwrap().len());
+        CursorPosition::new(new_line, new_col)
+    }
+
+    // --- Mouse ---
+
+    fn on_mouse_down(
+        &mut self,
+        event: &MouseDownEvent,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some((track, thumb)) = self.last_hscrollbar
+            && track.contains(&event.position)
+        {
+            // Grab the thumb where clicked, or center it under the mouse
+            let grab = if thumb.contains(&event.position) {
+                event.position.x - thumb.left()
+            } else {
+                thumb.size.width / 2.
+            };
+            self.hscrollbar_drag = Some(grab);
+            self.drag_hscrollbar_to(event.position.x, cx);
+            return;
+        }
+
+        let pos = self.position_for_mouse(event.position);
+        if event.click_count == 2 && !event.modifiers.shift {
+            self.is_selecting = false;
+            self.select_word_at(pos, cx);
+            return;
+        }
+        self.is_selecting = true;
+        if event.modifiers.shift {
+            self.select_primary_to(pos, cx);
+        } else {
+            self.move_cursors_to(pos, cx);
+        }
+    }
+
+    /// Select the word under a double-click, as the system's tokenizer for
+    /// the user's locale splits it, so runs of Japanese, Chinese or Thai
+    /// break into words; elsewhere by Unicode word boundaries.
+    fn select_word_at(&mut self, pos: CursorPosition, cx: &mut Context<Self>) {
+        let line = &self.lines[pos.line];
+        let offset = pos.col.offset();
+        let range = host::current()
+            .word_at(line, offset)
+            .unwrap_or_else(|| word_segment_at(line, offset));
+        let start = CursorPosition::new(pos.line, Column::floor(line, range.start));
+        let end = CursorPosition::new(pos.line, Column::floor(line, range.end));
+        self.cursors = vec![Cursor {
+            position: end,
+            anchor: Some(start),
+        }];
+        self.error_ranges.clear();
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        cx.notify();
+    }
+
+    fn on_mouse_up(&mut self, _: &MouseUpEvent, _: &mut Window, _: &mut Context<Self>) {
+        self.is_selecting = false;
+        self.hscrollbar_drag = None;
+    }
+
+    fn on_mouse_move(&mut self, event: &MouseMoveEvent, _: &mut Window, cx: &mut Context<Self>) {
+        if self.hscrollbar_drag.is_some() {
+            self.drag_hscrollbar_to(event.position.x, cx);
+        } else if self.is_selecting {
+            let pos = self.position_for_mouse(event.position);
+            self.select_primary_to(pos, cx);
+        }
+    }
+
+    fn zoom_in(&mut self, _: &ZoomIn, _: &mut Window, cx: &mut Context<Self>) {
+        self.set_font_size(self.font_size + 1., cx);
+    }
+
+    fn zoom_out(&mut self, _: &ZoomOut, _: &mut Window, cx: &mut Context<Self>) {
+        self.set_font_size(self.font_size - 1., cx);
+    }
+
+    fn zoom_reset(&mut self, _: &ZoomReset, _: &mut Window, cx: &mut Context<Self>) {
+        let settings = cx.global::<EditorSettings>();
+        let font_size = settings.config.font_size * settings.text_scale;
+        self.set_font_size(font_size, cx);
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32, cx: &mut Context<Self>) {
+        let font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        if font_size == self.font_size {
+            return;
+        }
+        self.font_size = font_size;
+        // Cached layouts were shaped at the old size; re-shape on next paint
+        // and keep the primary cursor in view once they are fresh.
+        self.last_shaped_lines.clear();
+        self.last_wrapped_lines.clear();
+        self.last_visual_line_counts.clear();
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        cx.notify();
+    }
+
+    /// Scroll horizontally so the dragged thumb follows the mouse.
+    fn drag_hscrollbar_to(&mut self, mouse_x: Pixels, cx: &mut Context<Self>) {
+        let (Some((track, thumb)), Some(grab), Some(bounds)) =
+            (self.last_hscrollbar, self.hscrollbar_drag, self.last_bounds)
+        else {
+            return;
+        };
+        let travel = track.size.width - thumb.size.width;
+        let max_x = self.last_max_line_width - (bounds.size.width - self.last_gutter_width);
+        if travel <= px(0.) || max_x <= px(0.) {
+            return;
+        }
+        let fraction = ((mouse_x - grab - track.left()) / travel).clamp(0.0, 1.0);
+        self.stop_scroll_animation();
+        self.scroll_offset.x = max_x * fraction;
+        cx.notify();
+    }
+
+    pub fn toggle_read_only(&mut self, _: &ToggleReadOnly, _: &mut Window, cx: &mut Context<Self>) {
+        self.read_only = !self.read_only;
+        self.marked_range = None;
+        let message = if self.read_only {
+            host::text("editor.read_only_on", "Read-only on", &[])
+        } else {
+            host::text("editor.read_only_off", "Read-only off", &[])
+        };
+        host::current().announce(&message);
+        cx.notify();
+    }
+
+    pub fn toggle_word_wrap(&mut self, _: &ToggleWordWrap, _: &mut Window, cx: &mut Context<Self>) {
+        self.word_wrap = !self.word_wrap;
+        self.scroll_offset.x = px(0.);
+        cx.notify();
+    }
+
+    fn on_scroll(
+        &mut self,
+        event: &ScrollWheelEvent,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match event.delta {
+            // Trackpad: the OS already delivers momentum as a stream of pixel
+            // deltas, so apply them directly and let a new touch cancel any
+            // running animation.
+            ScrollDelta::Pixels(d) => {
+                if event.touch_phase == TouchPhase::Started || self.scroll_anim_target.is_some() {
+                    self.stop_scroll_animation();
+                }
+                self.scroll_offset.y -= d.y;
+                if !self.word_wrap {
+                    self.scroll_offset.x -= d.x;
+                }
+                self.clamp_scroll(cx);
+                cx.notify();
+            }
+            // Mouse wheel: step by lines, animated from wherever the previous
+            // step was heading.
+            ScrollDelta::Lines(d) => {
+                let mut target = self.scroll_anim_target.unwrap_or(self.scroll_offset);
+                target.y -= d.y * self.last_line_height;
+                if !self.word_wrap {
+                    target.x -= d.x * self.last_line_height;
+                }
+                let target = self.clamped_scroll(target, cx);
+                self.animate_scroll_to(target, cx);
+            }
+        }
+    }
+
+    fn position_for_mouse(&self, point: Point<Pixels>) -> CursorPosition {
+        let bounds = match &self.last_bounds {
+            Some(b) => b,
+            None => return CursorPosition::start(),
+        };
+
+        let y = point.y - bounds.top() + self.scroll_offset.y;
+
+        if self.word_wrap {
+            // Find which logical line this visual Y falls into
+            let mut visual_y = px(0.);
+            for (line_idx, &count) in self.last_visual_line_counts.iter().enumerate() {
+                let line_visual_height = self.last_line_height * count;
+                if y < visual_y + line_visual_height {
+                    // Mouse is within this logical line's visual area
+                    let local_y = y - visual_y;
+                    let local_pos = Point::new(point.x - bounds.left() - self.last_gutter_width, local_y);
+                    if let Some(wl) = self.last_wrapped_lines.get(line_idx) {
+                        let text = display_text(&self.lines, &self.last_tab_layouts, line_idx);
+                        let col = match wl.closest_index_for_position(local_pos, self.last_line_height) {
+                            Ok(idx) => {
+                                // Only boundaries on the clicked row compete
+                                let row_y = self.last_line_height * ((local_y / self.last_line_height) as usize);
+                                snap_to_grapheme(text, idx, local_pos.x, |index| {
+                                    wl.position_for_index(index, self.last_line_height)
+                                        .filter(|p| p.y == row_y)
+                                        .map(|p| p.x)
+                                })
+                            }
+                            // Left of or past the row's text
+                            Err(idx) => idx,
+                        };
+                        let col = buffer_col(&self.last_tab_layouts, line_idx, col);
+                        return CursorPosition::new(line_idx, Column::floor(&self.lines[line_idx], col));
+                    }
+                    return CursorPosition::new(line_idx, Column::START);
+                }
+                visual_y += line_visual_height;
+            }
+            // Past the end
+            let last = self.lines.len().saturating_sub(1);
+            CursorPosition::new(last, Column::end(&self.lines[last]))
+        } else {
+            let line = if y < px(0.) {
+                0
+            } else {
+                let l = (y / self.last_line_height) as usize;
+                l.min(self.lines.len().saturating_sub(1))
+            };
+
+            let col = if let Some(shaped) = self.last_shaped_lines.get(line) {
+                let text = display_text(&self.lines, &self.last_tab_layouts, line);
+                let x = point.x - bounds.left() - self.last_gutter_width + self.scroll_offset.x;
+                buffer_col(&self.last_tab_layouts, line, closest_grapheme_for_x(shaped, text, x))
+            } else {
+                0
+            };
+
+            CursorPosition::new(line, Column::floor(&self.lines[line], col))
+        }
+    }
+
+    fn clamped_scroll(&mut self, offset: Point<Pixels>, cx: &App) -> Point<Pixels> {
+        let current = std::mem::replace(&mut self.scroll_offset, offset);
+        self.clamp_scroll(cx);
+        std::mem::replace(&mut self.scroll_offset, current)
+    }
+
+    fn clamp_scroll(&mut self, cx: &App) {
+        if self.scroll_offset.y < px(0.) {
+            self.scroll_offset.y = px(0.);
+        }
+        if self.scroll_offset.x < px(0.) {
+            self.scroll_offset.x = px(0.);
+        }
+        if let Some(bounds) = &self.last_bounds {
+            // Vertical: total visual lines * line_height
+            let total_visual_lines: usize = if self.word_wrap {
+                self.last_visual_line_counts.iter().sum()
+            } else {
+                self.lines.len()
+            };
+            let total_y = self.last_line_height * total_visual_lines;
+            let scroll_past_end = cx.global::<EditorSettings>().config.scroll_past_end.clamp(0.0, 1.0);
+            let overscroll = (bounds.size.height - self.last_line_height).max(px(0.)) * scroll_past_end;
+            let max_y = (total_y - bounds.size.height + overscroll).max(px(0.));
+            if self.scroll_offset.y > max_y {
+                self.scroll_offset.y = max_y;
+            }
+
+            // Horizontal: only when not wrapping
+            if self.word_wrap {
+                self.scroll_offset.x = px(0.);
+            } else {
+                let content_width = bounds.size.width - self.last_gutter_width;
+                let max_x = (self.last_max_line_width - content_width).max(px(0.));
+                if self.scroll_offset.x > max_x {
+                    self.scroll_offset.x = max_x;
+                }
+            }
+        }
+    }
+
+    fn scroll_to_cursor(&mut self, cx: &mut Context<Self>) {
+        let bounds = match &self.last_bounds {
+            Some(b) => *b,
+            None => return,
+        };
+        // Work from where a running animation is heading, then animate there
+        let start = self.scroll_offset;
+        if let Some(target) = self.scroll_anim_target {
+            self.scroll_offset = target;
+        }
+        let cursor_line = self.cursors[0].position.line;
+        let cursor_col = display_col(&self.last_tab_layouts, cursor_line, self.cursors[0].position.col);
+
+        if self.word_wrap {
+            // Compute visual Y by summing visual line counts for lines before cursor,
+            // then add the wrapped sub-line offset for the cursor's line
+            let visual_y_lines: usize = self.last_visual_line_counts.iter().take(cursor_line).sum();
+            // Find which visual sub-line within this wrapped line the cursor is on
+            let sub_line = if let Some(wrapped) = self.last_wrapped_lines.get(cursor_line) {
+                if let Some(pos) = wrapped.position_for_index(cursor_col, self.last_line_height) {
+                    (pos.y / self.last_line_height) as usize
+                } else {
+                    0
+                }
+            } else {
+                0
+            };
+            let cursor_y = self.last_line_height * (visual_y_lines + sub_line);
+            let visible_top = self.scroll_offset.y;
+            let visible_bottom = visible_top + bounds.size.height - self.last_line_height;
+            if cursor_y < visible_top {
+                self.scroll_offset.y = cursor_y;
+            } else if cursor_y > visible_bottom {
+                self.scroll_offset.y = cursor_y - bounds.size.height + self.last_line_height;
+            }
+        } else {
+            // Non-wrapped: simple line-based Y
+            let cursor_y = self.last_line_height * cursor_line;
+            let visible_top = self.scroll_offset.y;
+            let visible_bottom = visible_top + bounds.size.height - self.last_line_height;
+            if cursor_y < visible_top {
+                self.scroll_offset.y = cursor_y;
+            } else if cursor_y > visible_bottom {
+                self.scroll_offset.y = cursor_y - bounds.size.height + self.last_line_height;
+            }
+
+            // Horizontal scroll to cursor (content area excludes gutter)
+            let cursor_x = self.last_shaped_lines
+                .get(cursor_line)
+                .map(|l| l.x_for_index(cursor_col))
+                .unwrap_or(px(0.));
+            let content_width = bounds.size.width - self.last_gutter_width;
+            let visible_left = self.scroll_offset.x;
+            let visible_right = visible_left + content_width - px(16.); // padding
+            if cursor_x < visible_left {
+                self.scroll_offset.x = cursor_x;
+            } else if cursor_x > visible_right {
+                self.scroll_offset.x = cursor_x - content_width + px(16.);
+            }
+        }
+        self.clamp_scroll(cx);
+
+        let target = std::mem::replace(&mut self.scroll_offset, start);
+        if Some(target) == self.scroll_anim_target {
+            // Already heading there
+        } else if target == start {
+            self.stop_scroll_animation();
+        } else {
+            self.animate_scroll_to(target, cx);
+        }
+    }
+
+    // --- Smooth scrolling ---
+
+    fn stop_scroll_animation(&mut self) {
+        self.scroll_anim_epoch += 1;
+        self.scroll_anim_target = None;
+        self.scroll_anim_start = None;
+    }
+
+    fn animate_scroll_to(&mut self, target: Point<Pixels>, cx: &mut Context<Self>) {
+        if !cx.global::<EditorSettings>().config.smooth_scroll || motion_reduced(cx) {
+            self.stop_scroll_animation();
+            self.scroll_offset = target;
+            cx.notify();
+            return;
+        }
+
+        self.scroll_anim_from = self.scroll_offset;
+        self.scroll_anim_target = Some(target);
+        self.scroll_anim_start = Some(Instant::now());
+        self.scroll_anim_epoch += 1;
+        let epoch = self.scroll_anim_epoch;
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            loop {
+                cx.background_executor()
+                    .timer(CURSOR_ANIMATION_STEP)
+                    .await;
+                let should_continue = this
+                    .update(cx, |this, cx| {
+                        if this.scroll_anim_epoch != epoch {
+                            return false;
+                        }
+                        let (Some(target), Some(start)) =
+                            (this.scroll_anim_target, this.scroll_anim_start)
+                        else {
+                            return false;
+                        };
+                        let progress = (start.elapsed().as_secs_f32()
+                            / SCROLL_ANIMATION_DURATION.as_secs_f32())
+                        .min(1.0);
+                        let eased = ease_in_out_cubic(progress);
+                        let from = this.scroll_anim_from;
+                        this.scroll_offset = point(
+                            from.x + (target.x - from.x) * eased,
+                            from.y + (target.y - from.y) * eased,
+                        );
+                        if progress >= 1.0 {
+                            this.scroll_anim_target = None;
+                            this.scroll_anim_start = None;
+                        }
+                        cx.notify();
+                        progress < 1.0
+                    })
+                    .unwrap_or(false);
+                if !should_continue {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Paint again once the wrap width has held still, so lines left
+    /// wrapped at an earlier width during a resize get re-wrapped.
+    fn schedule_rewrap(&mut self, cx: &mut Context<Self>) {
+        self.rewrap_epoch += 1;
+        let epoch = self.rewrap_epoch;
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            cx.background_executor().timer(REWRAP_DELAY).await;
+            this.update(cx, |this, cx| {
+                if this.rewrap_epoch == epoch {
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    // --- Cursor blink ---
+
+    fn reset_cursor_blink(&mut self, cx: &mut Context<Self>) {
+        self.cursor_opacity = 1.0;
+        self.cursor_fading_in = true;
+        self.fade_start = None;
+        self.blink_epoch += 1;
+        let epoch = self.blink_epoch;
+        // With reduced motion the caret blinks without fading
+        let fade = if motion_reduced(cx) {
+            Duration::ZERO
+        } else {
+            CURSOR_FADE_DURATION
+        };
+        let fade_steps = (fade.as_millis() / CURSOR_ANIMATION_STEP.as_millis()) as usize;
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            cx.background_executor()
+                .timer(CURSOR_BLINK_INTERVAL)
+                .await;
+
+            loop {
+                let fading_in = this
+                    .update(cx, |this, cx| {
+                        if this.blink_epoch != epoch {
+                            return None;
+                        }
+                        this.cursor_fading_in = !this.cursor_fading_in;
+                        this.fade_start = Some(Instant::now());
+                        cx.notify();
+                        Some(this.cursor_fading_in)
+                    })
+                    .ok()
+                    .flatten();
+
+                let Some(fading_in) = fading_in else {
+                    break;
+                };
+
+                for _ in 0..fade_steps {
+                    cx.background_executor()
+                        .timer(CURSOR_ANIMATION_STEP)
+                        .await;
+                    let should_continue = this
+                        .update(cx, |this, cx| {
+                            if this.blink_epoch != epoch {
+                                return false;
+                            }
+                            if let Some(start) = this.fade_start {
+                                let elapsed = start.elapsed().as_secs_f32();
+                                let progress =
+                                    (elapsed / CURSOR_FADE_DURATION.as_secs_f32()).min(1.0);
+                                let eased = ease_in_out_cubic(progress);
+                                this.cursor_opacity =
+                                    if fading_in { eased } else { 1.0 - eased };
+                                cx.notify();
+                            }
+                            true
+                        })
+                        .unwrap_or(false);
+                    if !should_continue {
+                        return;
+                    }
+                }
+
+                let should_continue = this
+                    .update(cx, |this, cx| {
+                        if this.blink_epoch != epoch {
+                            return false;
+                        }
+                        this.cursor_opacity = if fading_in { 1.0 } else { 0.0 };
+                        this.fade_start = None;
+                        cx.notify();
+                        true
+                    })
+                    .unwrap_or(false);
+                if !should_continue {
+                    break;
+                }
+
+                let remaining = CURSOR_BLINK_INTERVAL.saturating_sub(fade);
+                if !remaining.is_zero() {
+                    cx.background_executor().timer(remaining).await;
+                }
+            }
+        })
+        .detach();
+    }
+
+    // --- Animated caret movement ---
+
+    /// Where the primary caret is drawn right now for a given target, taking
+    /// an in-flight glide into account.
+    fn caret_position_for(&self, target: Point<Pixels>) -> Point<Pixels> {
+        match (self.caret_anim_from, self.caret_anim_start) {
+            (Some(from), Some(start)) => {
+                let progress = (start.elapsed().as_secs_f32()
+                    / CURSOR_MOVE_DURATION.as_secs_f32())
+                .min(1.0);
+                let eased = ease_in_out_cubic(progress);
+                point(
+                    from.x + (target.x - from.x) * eased,
+                    from.y + (target.y - from.y) * eased,
+                )
+            }
+            _ => target,
+        }
+    }
+
+    /// Record the primary caret's laid-out position and start a glide from the
+    /// previously drawn position when it moved.
+    fn track_caret_target(&mut self, target: Option<Point<Pixels>>, cx: &mut Context<Self>) {
+        let enabled = cx.global::<EditorSettings>().config.animated_cursor && !motion_reduced(cx);
+        let previous = self.last_caret_target;
+        self.last_caret_target = target;
+
+        let (Some(previous), Some(target)) = (previous, target) else {
+            self.caret_anim_from = None;
+            return;
+        };
+        if !enabled || self.has_multiple_cursors() {
+            self.caret_anim_from = None;
+            return;
+        }
+        if previous == target {
+            return;
+        }
+
+        self.caret_anim_from = Some(self.caret_position_for(previous));
+        self.caret_anim_start = Some(Instant::now());
+        self.caret_anim_epoch += 1;
+        let epoch = self.caret_anim_epoch;
+        cx.notify();
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            loop {
+                cx.background_executor()
+                    .timer(CURSOR_ANIMATION_STEP)
+                    .await;
+                let should_continue = this
+                    .update(cx, |this, cx| {
+                        if this.caret_anim_epoch != epoch {
+                            return false;
+                        }
+                        let done = this
+                            .caret_anim_start
+                            .map(|start| start.elapsed() >= CURSOR_MOVE_DURATION)
+                            .unwrap_or(true);
+                        if done {
+                            this.caret_anim_from = None;
+                            this.caret_anim_start = None;
+                        }
+                        cx.notify();
+                        !done
+                    })
+                    .unwrap_or(false);
+                if !should_continue {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Tell listeners the text changed, and about the cursors the change
+    /// moved.
+    fn edited(&mut self, cx: &mut Context<Self>) {
+        self.edits += 1;
+        cx.emit(EditorEvent::ContentChanged { edits: self.edits });
+        self.emit_cursor_changes(cx);
+    }
+
+    /// Tell listeners the text was submitted.
+    pub fn submitted(&mut self, cx: &mut Context<Self>) {
+        cx.emit(EditorEvent::Submitted);
+    }
+
+    /// Tell listeners about cursors that moved, appeared or went away since
+    /// this was last called: after each command and mouse event (see
+    /// `cursor_listener`), each edit, and each host call that sets cursors.
+    fn emit_cursor_changes(&mut self, cx: &mut Context<Self>) {
+        if self.cursors == self.emitted_cursors {
+            return;
+        }
+        if self.cursors.len() != self.emitted_cursors.len() {
+            cx.emit(EditorEvent::CursorCountChanged {
+                count: self.cursors.len(),
+            });
+        }
+        cx.emit(EditorEvent::SelectionChanged);
+        self.emitted_cursors = self.cursors.clone();
+    }
+
+    /// `cx.listener` for a command or mouse handler, reporting the cursors
+    /// it moved once it's done.
+    fn cursor_listener<E: ?Sized>(
+        cx: &Context<Self>,
+        handler: impl Fn(&mut Self, &E, &mut Window, &mut Context<Self>) + 'static,
+    ) -> impl Fn(&E, &mut Window, &mut App) + 'static {
+        cx.listener(move |this, event, window, cx| {
+            handler(this, event, window, cx);
+            this.emit_cursor_changes(cx);
+        })
+    }
+
+    /// Keep screen readers up to date with the caret or selection, and
+    /// announce a change in the number of cursors or a selection cleared
+    /// without an edit.
+    fn report_to_screen_reader(&mut self) {
+        let host = host::current();
+        let count = self.cursors.len();
+        let count_changed = count != self.reported_cursor_count;
+        if count_changed {
+            self.reported_cursor_count = count;
+            host.announce(&host::text_count("editor.cursors", count, "{count} cursor", "{count} cursors"));
+        }
+
+        if self.reported_cursor.as_ref() == Some(&self.cursors[0]) {
+            return;
+        }
+        let had_selection = self.reported_cursor.as_ref().is_some_and(Cursor::has_selection);
+        let cleared = had_selection && !self.cursors[0].has_selection();
+        if cleared && !count_changed && self.edits == self.reported_edits {
+            host.announce(&host::text("editor.selection_cleared", "Selection cleared", &[]));
+        }
+        self.reported_cursor = Some(self.cursors[0].clone());
+        self.reported_edits = self.edits;
+        let flat = self.flat_text();
+        let range = Self::range_to_utf16(&flat, &self.flat_selected_range());
+        host.set_draft_selection(range);
+    }
+
+    // --- UTF-16 conversions for IME ---
+
+    fn offset_to_utf16(text: &str, offset: usize) -> usize {
+        let mut utf16_offset = 0;
+        let mut utf8_count = 0;
+        for ch in text.chars() {
+            if utf8_count >= offset {
+                break;
+            }
+            utf8_count += ch.len_utf8();
+            utf16_offset += ch.len_utf16();
+        }
+        utf16_offset
+    }
+
+    fn offset_from_utf16(text: &str, offset: usize) -> usize {
+        let mut utf8_offset = 0;
+        let mut utf16_count = 0;
+        for ch in text.chars() {
+            if utf16_count >= offset {
+                break;
+            }
+            utf16_count += ch.len_utf16();
+            utf8_offset += ch.len_utf8();
+        }
+        utf8_offset
+    }
+
+    fn range_to_utf16(text: &str, range: &Range<usize>) -> Range<usize> {
+        Self::offset_to_utf16(text, range.start)..Self::offset_to_utf16(text, range.end)
+    }
+
+    fn range_from_utf16(text: &str, range: &Range<usize>) -> Range<usize> {
+        Self::offset_from_utf16(text, range.start)..Self::offset_from_utf16(text, range.end)
+    }
+}
+
+// --- EntityInputHandler for IME ---
+
+impl EntityInputHandler for MultiLineEditor {
+    fn text_for_range(
+        &mut self,
+        range_utf16: Range<usize>,
+        actual_range: &mut Option<Range<usize>>,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) -> Option<String> {
+        let flat = self.flat_text();
+        let range = Self::range_from_utf16(&flat, &range_utf16);
+        actual_range.replace(Self::range_to_utf16(&flat, &range));
+        Some(flat[range].to_string())
+    }
+
+    fn selected_text_range(
+        &mut self,
+        _ignore_disabled_input: bool,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) -> Option<UTF16Selection> {
+        let flat = self.flat_text();
+        let range = self.flat_selected_range();
+        let c = &self.cursors[0];
+        let reversed = c
+            .anchor
+            .as_ref()
+            .map(|a| *a > c.position)
+            .unwrap_or(false);
+        Some(UTF16Selection {
+            range: Self::range_to_utf16(&flat, &range),
+            reversed,
+        })
+    }
+
+    fn marked_text_range(
+        &self,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) -> Option<Range<usize>> {
+        let flat = self.flat_text();
+        self.marked_range
+            .as_ref()
+            .map(|range| Self::range_to_utf16(&flat, range))
+    }
+
+    fn unmark_text(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+        self.marked_range = None;
+    }
+
+    fn replace_text_in_range(
+        &mut self,
+        range_utf16: Option<Range<usize>>,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.read_only {
+            return;
+        }
+        // Plain typing goes in at every cursor
+        if range_utf16.is_none() && self.marked_range.is_none() && self.cursors.len() > 1 {
+            self.insert_text_at_cursors(new_text, window, cx);
+            return;
+        }
+        let flat = self.flat_text();
+        let range = range_utf16
+            .as_ref()
+            .map(|r| Self::range_from_utf16(&flat, r))
+            .or(self.marked_range.clone())
+            .unwrap_or_else(|| self.flat_selected_range());
+
+        let start_pos = self.position_from_flat(range.start);
+        let end_pos = self.position_from_flat(range.end);
+
+        self.delete_range(&start_pos, &end_pos);
+        let new_pos = self.insert_at(&start_pos, new_text);
+
+        self.cursors = vec![Cursor::new(new_pos.line, new_pos.col)];
+        self.marked_range = None;
+        self.preferred_col_x = None;
+        self.needs_scroll_to_cursor = true;
+        self.reset_cursor_blink(cx);
+        self.edited(cx);
+        cx.notify();
+    }
+
+    fn replace_and_mark_text_in_range(
+        &mut self,
+        range_utf16: Option<Range<usize>>,
+        new_text: &str,
+        new_selected_range_utf16: Option<Range<usize>>,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.read_only {
+            return;
+        }
+        let flat = self.flat_text();
+        let range = range_utf16
+            .as_ref()
+            .map(|r| Self::range_from_utf16(&flat, r))
+            .or(self.marked_range.clone())
+            .unwrap_or_else(|| self.flat_selected_range());
+
+        let start_pos = self.position_from_flat(range.start);
+        let end_pos = self.position_from_flat(range.end);
+
+        self.delete_range(&start_pos, &end_pos);
+        let new_end = self.insert_at(&start_pos, new_text);
+
+        let mark_start = self.flat_offset(&start_pos);
+        let mark_end = self.flat_offset(&new_end);
+        self.marked_range = Some(mark_start..mark_end);
+
+        if let Some(sel_utf16) = new_selected_range_utf16 {
+            // Relative to the composed text, not the whole buffer
+            let sel = Self::range_from_utf16(new_text, &sel_utf16);
+            let sel_start = self.position_from_flat(sel.start + mark_start);
+            let sel_end = self.position_from_flat(sel.end + mark_start);
+            if sel_start == sel_end {
+                self.cursors = vec![Cursor::new(sel_start.line, sel_start.col)];
+            } else {
+                self.cursors = vec![Cursor {
+                    position: CursorPosition::new(sel_end.line, sel_end.col),
+                    anchor: Some(CursorPosition::new(sel_start.line, sel_start.col)),
+                }];
+            }
+        } else {
+            self.cursors = vec![Cursor::new(new_end.line, new_end.col)];
+        }
+
+        self.needs_scroll_to_cursor = true;
+        self.edited(cx);
+        cx.notify();
+    }
+
+    fn bounds_for_range(
+        &mut self,
+        range_utf16: Range<usize>,
+        bounds: Bounds<Pixels>,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) -> Option<Bounds<Pixels>> {
+        let flat = self.flat_text();
+        let range = Self::range_from_utf16(&flat, &range_utf16);
+        let start_pos = self.position_from_flat(range.start);
+        let end_pos = self.position_from_flat(range.end);
+
+        let origin = point(bounds.left() + self.last_gutter_width, bounds.top()) - self.scroll_offset;
+        let start = self.caret_origin(&start_pos);
+        let end = self.caret_origin(&end_pos);
+        Some(Bounds::from_corners(
+            origin + start,
+            origin + point(end.x, end.y + self.last_line_height),
+        ))
+    }
+
+    fn character_index_for_point(
+        &mut self,
+        point: Point<Pixels>,
+        _window: &mut Window,
+        _cx: &mut Context<Self>,
+    ) -> Option<usize> {
+        self.last_bounds.as_ref()?;
+        let pos = self.position_for_mouse(point);
+        let flat = self.flat_text();
+        let offset = self.flat_offset(&pos);
+        Some(Self::offset_to_utf16(&flat, offset))
+    }
+}
+
+// --- Render ---
+
+impl Render for MultiLineEditor {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.report_to_screen_reader();
+        let theme = cx.global::<EditorTheme>();
+        let zoom = self.font_size / DEFAULT_FONT_SIZE;
+        let line_height = editor_line_height(cx.global::<EditorSettings>().config.line_height, zoom);
+        let opacity = cx.global::<EditorSettings>().background_opacity;
+        div()
+            .flex()
+            .key_context(if self.single_line {
+                "MultiLineEditor single_line"
+            } else {
+                "MultiLineEditor"
+            })
+            .track_focus(&self.focus_handle)
+            .cursor(CursorStyle::IBeam)
+            .on_action(Self::cursor_listener(cx, Self::backspace))
+            .on_action(Self::cursor_listener(cx, Self::delete))
+            .on_action(Self::cursor_listener(cx, Self::delete_to_start))
+            .on_action(Self::cursor_listener(cx, Self::delete_word_backward))
+            .on_action(Self::cursor_listener(cx, Self::left))
+            .on_action(Self::cursor_listener(cx, Self::right))
+            .on_action(Self::cursor_listener(cx, Self::up))
+            .on_action(Self::cursor_listener(cx, Self::down))
+            .on_action(Self::cursor_listener(cx, Self::select_left))
+            .on_action(Self::cursor_listener(cx, Self::select_right))
+            .on_action(Self::cursor_listener(cx, Self::select_up))
+            .on_action(Self::cursor_listener(cx, Self::select_down))
+            .on_action(Self::cursor_listener(cx, Self::select_all))
+            .on_action(Self::cursor_listener(cx, Self::home))
+            .on_action(Self::cursor_listener(cx, Self::end))
+            .on_action(Self::cursor_listener(cx, Self::document_start))
+            .on_action(Self::cursor_listener(cx, Self::document_end))
+            .on_action(Self::cursor_listener(cx, Self::select_home))
+            .on_action(Self::cursor_listener(cx, Self::select_end))
+            .on_action(Self::cursor_listener(cx, Self::select_document_start))
+            .on_action(Self::cursor_listener(cx, Self::select_document_end))
+            .on_action(Self::cursor_listener(cx, Self::word_left))
+            .on_action(Self::cursor_listener(cx, Self::word_right))
+            .on_action(Self::cursor_listener(cx, Self::select_word_left))
+            .on_action(Self::cursor_listener(cx, Self::select_word_right))
+            .on_action(Self::cursor_listener(cx, Self::enter))
+            .on_action(Self::cursor_listener(cx, Self::move_line_up))
+            .on_action(Self::cursor_listener(cx, Self::move_line_down))
+            .on_action(Self::cursor_listener(cx, Self::add_cursor_up))
+            .on_action(Self::cursor_listener(cx, Self::add_cursor_down))
+            .on_action(Self::cursor_listener(cx, Self::show_character_palette))
+            .on_action(Self::cursor_listener(cx, Self::paste))
+            .on_action(Self::cursor_listener(cx, Self::cut))
+            .on_action(Self::cursor_listener(cx, Self::copy))
+            .on_action(Self::cursor_listener(cx, Self::copy_as_rich_text))
+            .on_action(Self::cursor_listener(cx, Self::paste_as_markdown))
+            .on_action(Self::cursor_listener(cx, Self::base64_encode))
+            .on_action(Self::cursor_listener(cx, Self::base64_decode))
+            .on_action(Self::cursor_listener(cx, Self::toggle_word_wrap))
+            .on_action(Self::cursor_listener(cx, Self::zoom_in))
+            .on_action(Self::cursor_listener(cx, Self::zoom_out))
+            .on_action(Self::cursor_listener(cx, Self::zoom_reset))
+            .on_action(Self::cursor_listener(cx, Self::evaluate_selection))
+            .on_action(Self::cursor_listener(cx, Self::insert_timestamp))
+            .on_action(Self::cursor_listener(cx, Self::insert_tab))
+            .on_action(Self::cursor_listener(cx, Self::toggle_read_only))
+            .on_action(Self::cursor_listener(cx, Self::expand_selection))
+            .on_action(Self::cursor_listener(cx, Self::shrink_selection))
+            .on_action(Self::cursor_listener(cx, Self::select_inside_brackets))
+            .on_action(Self::cursor_listener(cx, Self::select_around_brackets))
+            .on_mouse_down(MouseButton::Left, Self::cursor_listener(cx, Self::on_mouse_down))
+            .on_mouse_up(MouseButton::Left, Self::cursor_listener(cx, Self::on_mouse_up))
+            .on_mouse_up_out(MouseButton::Left, Self::cursor_listener(cx, Self::on_mouse_up))
+            .on_mouse_move(Self::cursor_listener(cx, Self::on_mouse_move))
+            .on_scroll_wheel(cx.listener(Self::on_scroll))
+            .bg(translucent(theme.surface0, opacity))
+            .size_full()
+            .overflow_hidden()
+            .font_family(cx.global::<EditorSettings>().config.font_family.clone())
+            .line_height(line_height)
+            .text_size(px(self.font_size))
+            .child(
+                div()
+                    .w_full()
+                    .flex_1()
+                    .overflow_hidden()
+                    .p(px(8.))
+                    .child(MultiLineTextElement {
+                        input: cx.entity().clone(),
+                    }),
+            )
+    }
+}
+
+impl EventEmitter<EditorEvent> for MultiLineEditor {}
+
+impl Focusable for MultiLineEditor {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+// --- Element ---
+
+struct MultiLineTextElement {
+    input: Entity<MultiLineEditor>,
+}
+
+struct MultiLinePrepaintState {
+    shaped_lines: Vec<ShapedLine>,
+    wrapped_lines: Vec<WrappedLine>,
+    word_wrap: bool,
+    visual_line_counts: Vec<usize>,
+    max_line_width: Pixels,
+    cursors: Vec<CursorPaint>,
+    cursor_opacity: f32,
+    selections: Vec<PaintQuad>,
+    scroll_offset: Point<Pixels>,
+    line_height: Pixels,
+    gutter_width: Pixels,
+    gutter_line_numbers: Vec<(ShapedLine, Pixels)>, // (shaped number, y position)
+    /// Primary caret origin in document coordinates (single cursor only)
+    caret_target: Option<Point<Pixels>>,
+    /// Horizontal scrollbar (track, thumb) when lines overflow in no-wrap mode
+    hscrollbar: Option<(Bounds<Pixels>, Bounds<Pixels>)>,
+    tab_layouts: Vec<TabLayout>,
+    /// For the next frame
+    cache: PaintCache,
+}
+
+/// Prepaint results kept from one frame to the next. A blinking caret
+/// changes nothing here, so its frames reuse all of it.
+#[derive(Default)]
+struct PaintCache {
+    /// What `line_texts` were shaped with; lines are reused only while it
+    /// stays the same
+    layout_key: Option<LineLayoutKey>,
+    /// Display text of each line in `last_shaped_lines` or
+    /// `last_wrapped_lines`
+    line_texts: Vec<SharedString>,
+    /// Width each of `last_wrapped_lines` was wrapped at
+    line_wrap_widths: Vec<Pixels>,
+    /// Content width lines were last wrapped to, and when it last changed
+    wrap_width: Pixels,
+    wrap_width_changed: Option<Instant>,
+    /// Some lines are still wrapped at an earlier width
+    stale_wraps: bool,
+    overlay: Option<OverlayCache>,
+}
+
+/// Everything shaping a line depends on besides its text.
+#[derive(PartialEq)]
+struct LineLayoutKey {
+    font: Font,
+    font_size: Pixels,
+    color: Hsla,
+    highlight: Option<SyntaxColors>,
+    word_wrap: bool,
+}
+
+/// Carets, selections and line numbers as last built, before the caret
+/// glide is applied. Rebuilt when any line was reshaped or `key` changes.
+struct OverlayCache {
+    key: OverlayKey,
+    cursors: Vec<CursorPaint>,
+    selections: Vec<PaintQuad>,
+    gutter_line_numbers: Vec<(ShapedLine, Pixels)>,
+}
+
+#[derive(PartialEq)]
+struct OverlayKey {
+    cursors: Vec<Cursor>,
+    error_ranges: Vec<(CursorPosition, CursorPosition)>,
+    bounds: Bounds<Pixels>,
+    scroll_offset: Point<Pixels>,
+    line_height: Pixels,
+    focused: bool,
+    show_line_numbers: bool,
+    caret: (CursorShape, Pixels, bool),
+    /// Selection, error, caret, secondary caret, glyph under a block caret,
+    /// line numbers
+    colors: [Rgba; 6],
+}
+
+/// Finds a line shaped for the last frame by its text: at the same index
+/// when lines haven't moved, else anywhere.
+struct CachedLines<'a> {
+    texts: &'a [SharedString],
+    by_text: Option<HashMap<&'a str, usize>>,
+}
+
+impl<'a> CachedLines<'a> {
+    fn new(texts: &'a [SharedString]) -> Self {
+        Self { texts, by_text: None }
+    }
+
+    fn find(&mut self, index: usize, text: &str) -> Option<usize> {
+        if self.texts.get(index).is_some_and(|t| t.as_ref() == text) {
+            return Some(index);
+        }
+        let texts = self.texts;
+        self.by_text
+            .get_or_insert_with(|| texts.iter().enumerate().map(|(i, t)| (t.as_ref(), i)).collect())
+            .get(text)
+            .copied()
+    }
+}
+
+/// A caret ready to paint. Block carets carry the glyph underneath so it can
+/// be redrawn in the background color on top of the caret.
+#[derive(Clone)]
+struct CursorPaint {
+    bounds: Bounds<Pixels>,
+    color: Rgba,
+    glyph: Option<(ShapedLine, Point<Pixels>)>,
+}
+
+/// Everything needed to turn a caret origin into a `CursorPaint`.
+struct CaretLayout {
+    shape: CursorShape,
+    width: Pixels,
+    font: Font,
+    font_size: Pixels,
+    line_height: Pixels,
+    glyph_color: Rgba,
+}
+
+impl CaretLayout {
+    fn layout(
+        &self,
+        origin: Point<Pixels>,
+        line_text: &str,
+        col: usize,
+        color: Rgba,
+        window: &mut Window,
+    ) -> CursorPaint {
+        if self.shape == CursorShape::Bar {
+            return CursorPaint {
+                bounds: Bounds::new(origin, size(self.width, self.line_height)),
+                color,
+                glyph: None,
+            };
+        }
+
+        // Block and underline carets span the grapheme under the cursor
+        // (a space at end of line)
+        let grapheme = line_text
+            .get(col..)
+            .and_then(|rest| rest.graphemes(true).next())
+            .filter(|g| *g != "\t")
+            .unwrap_or(" ");
+        let text: SharedString = grapheme.to_string().into();
+        let run = TextRun {
+            len: text.len(),
+            font: self.font.clone(),
+            color: self.glyph_color.into(),
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        };
+        let shaped = window
+            .text_system()
+            .shape_line(text, self.font_size, &[run], None);
+        let cell_width = shaped.width;
+
+        if self.shape == CursorShape::Underline {
+            CursorPaint {
+                bounds: Bounds::new(
+                    point(origin.x, origin.y + self.line_height - self.width),
+                    size(cell_width, self.width),
+                ),
+                color,
+                glyph: None,
+            }
+        } else {
+            CursorPaint {
+                bounds: Bounds::new(origin, size(cell_width, self.line_height)),
+                color,
+                glyph: Some((shaped, origin)),
+            }
+        }
+    }
+}
+
+impl IntoElement for MultiLineTextElement {
+    type Element = Self;
+    fn into_element(self) -> Self::Element {
+        self
+    }
+}
+
+impl Element for MultiLineTextElement {
+    type RequestLayoutState = ();
+    type PrepaintState = MultiLinePrepaintState;
+
+    fn id(&self) -> Option<ElementId> {
+        None
+    }
+
+    fn request_layout(
+        &mut self,
+        _id: Option<&GlobalElementId>,
+        _inspector_id: Option<&InspectorElementId>,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> (LayoutId, Self::RequestLayoutState) {
+        let mut style = Style::default();
+        style.size.width = relative(1.).into();
+        style.size.height = relative(1.).into();
+        (window.request_layout(style, [], cx), ())
+    }
+
+    fn prepaint(
+        &mut self,
+        _id: Option<&GlobalElementId>,
+        _inspector_id: Option<&InspectorElementId>,
+        bounds: Bounds<Pixels>,
+        _request_layout: &mut Self::RequestLayoutState,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> Self::PrepaintState {
+        let input = self.input.read(cx);
+        let theme = cx.global::<EditorTheme>();
+        let style = window.text_style();
+        let editor_prefs = &cx.global::<EditorSettings>().config;
+        let font = editor_font(style.font(), editor_prefs);
+        let font_size = style.font_size.to_pixels(window.rem_size());
+        let line_height = window.line_height();
+        let scroll_offset = input.scroll_offset;
+        let cursor_opacity = input.cursor_opacity;
+        let word_wrap = input.word_wrap;
+        let highlight = editor_prefs.syntax_highlighting.then_some(&theme.syntax);
+
+        // Expand tabs into aligned columns, or to fixed stops, before
+        // shaping; every column below goes through this mapping
+        let tab_layouts = if editor_prefs.elastic_tabstops {
+            elastic_tabs::layout_lines(&input.lines)
+        } else if input.lines.iter().any(|line| line.contains('\t')) {
+            elastic_tabs::fixed_layout(&input.lines, editor_prefs.tab_size)
+        } else {
+            Vec::new()
+        };
+
+        // Calculate gutter width based on number of digits in max line number
+        let line_count = input.lines.len();
+        let digit_count = if line_count == 0 { 1 } else { (line_count as f64).log10().floor() as usize + 1 };
+        let sample_text: SharedString = "8".repeat(digit_count).into();
+        let gutter_run = TextRun {
+            len: sample_text.len(),
+            font: font.clone(),
+            color: theme.overlay0.into(),
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        };
+        let sample_shaped = window.text_system().shape_line(sample_text, font_size, &[gutter_run], None);
+        let gutter_padding = px(16.); // padding after line numbers
+        let show_line_numbers = editor_prefs.line_numbers && !input.single_line;
+        let gutter_width = if !show_line_numbers {
+            px(0.)
+        } else {
+            sample_shaped.width + gutter_padding
+        };
+
+        let content_left = bounds.left() + gutter_width;
+        let content_width = bounds.size.width - gutter_width;
+
+        let mut shaped_lines = Vec::new();
+        let mut wrapped_lines = Vec::new();
+        let mut visual_line_counts = Vec::with_capacity(input.lines.len());
+        let mut max_line_width = px(0.);
+        let mut line_texts = Vec::with_capacity(input.lines.len());
+
+        // Lines whose text is as it was last frame keep their shaping (and
+        // highlighting)
+        let layout_key = LineLayoutKey {
+            font: font.clone(),
+            font_size,
+            color: style.color,
+            highlight: highlight.copied(),
+            word_wrap,
+        };
+        let cache = &input.paint_cache;
+        let cache_valid = cache.layout_key.as_ref() == Some(&layout_key);
+        let mut cached = CachedLines::new(if cache_valid { &cache.line_texts } else { &[] });
+        let mut layout_changed = !cache_valid || input.lines.len() != cache.line_texts.len();
+        let mut line_wrap_widths = Vec::new();
+
+        // Re-wrapping a whole document on every step of a resize drag is
+        // too slow for big buffers. Lines that fit at both widths keep
+        // their (lack of) breaks, and while the width is changing, lines
+        // below the viewport keep their old breaks, to be re-wrapped once
+        // it holds still.
+        let wrap_width_changed = if cache.wrap_width != content_width {
+            layout_changed = true;
+            Some(Instant::now())
+        } else {
+            cache.wrap_width_changed
+        };
+        let resizing = wrap_width_changed.is_some_and(|at| at.elapsed() < REWRAP_DELAY);
+        let mut stale_wraps = false;
+        let mut rows_above = 0;
+
+        for line_idx in 0..input.lines.len() {
+            let line_text = display_text(&input.lines, &tab_layouts, line_idx);
+            let display_text: SharedString = if line_text.is_empty() {
+                " ".into()
+            } else {
+                line_text.to_string().into()
+            };
+            let reused = cached.find(line_idx, &display_text);
+            layout_changed |= reused != Some(line_idx);
+            line_texts.push(display_text.clone());
+
+            if word_wrap {
+                // Shape with wrapping — wrap within content area
+                let previous = reused.and_then(|i| {
+                    Some((input.last_wrapped_lines.get(i)?, *cache.line_wrap_widths.get(i)?))
+                });
+                let below_viewport = line_height * rows_above - scroll_offset.y > bounds.size.height;
+                let (wl, wrapped_at) = match previous {
+                    Some((wl, width)) if width == content_width => (wl.clone(), width),
+                    Some((wl, _))
+                        if wl.wrap_boundaries.is_empty() && wl.unwrapped_layout.width <= content_width =>
+                    {
+                        (wl.clone(), content_width)
+                    }
+                    Some((wl, width)) if resizing && below_viewport => {
+                        stale_wraps = true;
+                        (wl.clone(), width)
+                    }
+                    _ => {
+                        layout_changed = true;
+                        let runs = line_runs(&display_text, &font, style.color, highlight);
+                        let wl = window
+                            .text_system()
+                            .shape_text(display_text, font_size, &runs, Some(content_width), None)
+                            .ok()
+                            .and_then(|mut lines| lines.pop())
+                            .unwrap_or_default();
+                        (wl, content_width)
+                    }
+                };
+                rows_above += wl.wrap_boundaries.len() + 1;
+                visual_line_counts.push(wl.wrap_boundaries.len() + 1);
+                line_wrap_widths.push(wrapped_at);
+                wrapped_lines.push(wl);
+            } else {
+                // Shape without wrapping
+                let shaped = match reused.and_then(|i| input.last_shaped_lines.get(i)) {
+                    Some(shaped) => shaped.clone(),
+                    None => {
+                        let runs = line_runs(&display_text, &font, style.color, highlight);
+                        window
+                            .text_system()
+                            .shape_line(display_text, font_size, &runs, None)
+                    }
+                };
+                if shaped.width > max_line_width {
+                    max_line_width = shaped.width;
+                }
+                shaped_lines.push(shaped);
+                visual_line_counts.push(1);
+            }
+        }
+
+        // Carets, selections and line numbers only move when the lines,
+        // cursors or view do
+        let is_focused = input.focus_handle.is_focused(window);
+        let selection_color = if is_focused { theme.selection } else { theme.selection_inactive };
+        let error_color = with_alpha(theme.error, 0.35);
+        let overlay_key = OverlayKey {
+            cursors: input.cursors.clone(),
+            error_ranges: input.error_ranges.clone(),
+            bounds,
+            scroll_offset,
+            line_height,
+            focused: is_focused,
+            show_line_numbers,
+            caret: (
+                editor_prefs.cursor_shape,
+                px(editor_prefs.cursor_width.max(theme.cursor_min_width)),
+                editor_prefs.tint_secondary_cursors,
+            ),
+            colors: [selection_color, error_color, theme.cursor, theme.cursor_secondary, theme.surface0, theme.overlay0],
+        };
+        let cached_overlay = cache
+            .overlay
+            .as_ref()
+            .filter(|overlay| !layout_changed && overlay.key == overlay_key);
+        let (mut cursor_rects, selections, gutter_line_numbers) = if let Some(overlay) = cached_overlay {
+            (overlay.cursors.clone(), overlay.selections.clone(), overlay.gutter_line_numbers.clone())
+        } else {
+            // Shape line numbers
+            let gutter_color = theme.overlay0;
+            let mut gutter_line_numbers = Vec::with_capacity(line_count);
+            let mut visual_y = px(0.);
+            for (i, &vcount) in visual_line_counts.iter().enumerate() {
+                let y = visual_y - scroll_offset.y;
+                // Only shape if potentially visible
+                let visual_height = line_height * vcount;
+                if show_line_numbers && y + visual_height >= px(0.) && y <= bounds.size.height {
+                    let num_str: SharedString = format!("{}", i + 1).into();
+                    let num_run = TextRun {
+                        len: num_str.len(),
+                        font: font.clone(),
+                        color: gutter_color.into(),
+                        background_color: None,
+                        underline: None,
+                        strikethrough: None,
+                    };
+                    let shaped_num = window.text_system().shape_line(num_str, font_size, &[num_run], None);
+                    gutter_line_numbers.push((shaped_num, y));
+                }
+                visual_y += visual_height;
+            }
+
+            // Build cursor rects and selection rects
+            let mut cursor_rects = Vec::new();
+            let mut selections = Vec::new();
+            // The first cursor is the one Escape collapses to
+            let caret_color = |i: usize| {
+                if i > 0 && editor_prefs.tint_secondary_cursors {
+                    theme.cursor_secondary
+                } else {
+                    theme.cursor
+                }
+            };
+            let caret = CaretLayout {
+                shape: editor_prefs.cursor_shape,
+                width: px(editor_prefs.cursor_width.max(theme.cursor_min_width)),
+                font: font.clone(),
+                font_size,
+                line_height,
+                glyph_color: theme.surface0,
+            };
+
+            // Helper: compute the visual Y offset for a logical line
+            let visual_y_for_line = |line: usize| -> Pixels {
+                let visual_lines_before: usize = visual_line_counts.iter().take(line).sum();
+                line_height * visual_lines_before
+            };
+
+            if word_wrap {
+                // Wrapped mode: use WrappedLineLayout position_for_index
+                for (i, c) in input.cursors.iter().enumerate() {
+                    let base_y = visual_y_for_line(c.position.line);
+                    let cursor_col = display_col(&tab_layouts, c.position.line, c.position.col);
+                    let cursor_text = display_text(&input.lines, &tab_layouts, c.position.line);
+                    let (cx_offset, cy_offset) = if let Some(wl) = wrapped_lines.get(c.position.line) {
+                        if let Some(pos) = wl.position_for_index(cursor_col, line_height) {
+                            (pos.x, pos.y)
+                        } else {
+                            (px(0.), px(0.))
+                        }
+                    } else {
+                        (px(0.), px(0.))
+                    };
+
+                    let cursor_screen = point(
+                        content_left + cx_offset,
+                        bounds.top() + base_y + cy_offset - scroll_offset.y,
+                    );
+
+                    if !c.has_selection() && is_focused {
+                        cursor_rects.push(caret.layout(
+                            cursor_screen,
+                            cursor_text,
+                            cursor_col,
+                            caret_color(i),
+                            window,
+                        ));
+                    }
+
+                    if let Some((start, end)) = c.selection_range() {
+                        let selection_color = if input.error_ranges.contains(&(start.clone(), end.clone())) {
+                            error_color
+                        } else {
+                            selection_color
+                        };
+                        // For wrapped selections, paint per-visual-line segments
+                        for line_idx in start.line..=end.line {
+                            let col_start = if line_idx == start.line { start.col } else { Column::START };
+                            let col_end = if line_idx == end.line { end.col } else { Column::end(&input.lines[line_idx]) };
+                            let col_start = display_col(&tab_layouts, line_idx, col_start);
+                            let col_end = display_col(&tab_layouts, line_idx, col_end);
+                            let base = visual_y_for_line(line_idx);
+
+                            if let Some(wl) = wrapped_lines.get(line_idx) {
+                                let start_pos = wl.position_for_index(col_start, line_height).unwrap_or(point(px(0.), px(0.)));
+                                let end_pos = wl.position_for_index(col_end, line_height).unwrap_or(point(px(0.), px(0.)));
+
+                                if start_pos.y == end_pos.y {
+                                    // Same visual line
+                                    selections.push(fill(
+                                        Bounds::from_corners(
+                                            point(content_left + start_pos.x, bounds.top() + base + start_pos.y - scroll_offset.y),
+                                            point(content_left + end_pos.x, bounds.top() + base + end_pos.y + line_height - scroll_offset.y),
+                                        ),
+                                        selection_color,
+                                    ));
+                                } else {
+                                    // Spans multiple visual lines
+                                    // First visual line
+                                    selections.push(fill(
+                                        Bounds::from_corners(
+                                            point(content_left + start_pos.x, bounds.top() + base + start_pos.y - scroll_offset.y),
+                                            point(content_left + content_width, bounds.top() + base + start_pos.y + line_height - scroll_offset.y),
+                                        ),
+                                        selection_color,
+                                    ));
+                                    // Middle visual lines
+                                    let start_vline = (start_pos.y / line_height) as usize;
+                                    let end_vline = (end_pos.y / line_height) as usize;
+                                    for vl in (start_vline + 1)..end_vline {
+                                        let vy = line_height * vl;
+                                        selections.push(fill(
+                                            Bounds::from_corners(
+                                                point(content_left, bounds.top() + base + vy - scroll_offset.y),
+                                                point(content_left + content_width, bounds.top() + base + vy + line_height - scroll_offset.y),
+                                            ),
+                                            selection_color,
+                                        ));
+                                    }
+                                    // Last visual line
+                                    selections.push(fill(
+                                        Bounds::from_corners(
+                                            point(content_left, bounds.top() + base + end_pos.y - scroll_offset.y),
+                                            point(content_left + end_pos.x, bounds.top() + base + end_pos.y + line_height - scroll_offset.y),
+                                        ),
+                                        selection_color,
+                                    ));
+                                }
+                            }
+                        }
+
+                        // Cursor at selection edge
+                        if is_focused {
+                            cursor_rects.push(caret.layout(
+                                cursor_screen,
+                                cursor_text,
+                                cursor_col,
+                                caret_color(i),
+                                window,
+                            ));
+                        }
+                    }
+                }
+            } else {
+                // Non-wrapped mode: use ShapedLine x_for_index
+                if is_focused {
+                    for (i, c) in input.cursors.iter().enumerate() {
+                        if !c.has_selection() {
+                            let cursor_col = display_col(&tab_layouts, c.position.line, c.position.col);
+                            let x = shaped_lines
+                                .get(c.position.line)
+                                .map(|l| l.x_for_index(cursor_col))
+                                .unwrap_or(px(0.));
+                            let y = line_height * c.position.line;
+                            cursor_rects.push(caret.layout(
+                                point(
+                                    content_left + x - scroll_offset.x,
+                                    bounds.top() + y - scroll_offset.y,
+                                ),
+                                display_text(&input.lines, &tab_layouts, c.position.line),
+                                cursor_col,
+                                caret_color(i),
+                                window,
+                            ));
+                        }
+                    }
+                }
+
+                for (i, c) in input.cursors.iter().enumerate() {
+                    if let Some((start, end)) = c.selection_range() {
+                        let selection_color = if input.error_ranges.contains(&(start.clone(), end.clone())) {
+                            error_color
+                        } else {
+                            selection_color
+                        };
+                        for line_idx in start.line..=end.line {
+                            let col_start = if line_idx == start.line { start.col } else { Column::START };
+                            let col_end = if line_idx == end.line { end.col } else { Column::end(&input.lines[line_idx]) };
+                            let col_start = display_col(&tab_layouts, line_idx, col_start);
+                            let col_end = display_col(&tab_layouts, line_idx, col_end);
+
+                            let x_start = shaped_lines.get(line_idx).map(|l| l.x_for_index(col_start)).unwrap_or(px(0.));
+                            let x_end = shaped_lines.get(line_idx).map(|l| l.x_for_index(col_end)).unwrap_or(px(0.));
+                            let y = line_height * line_idx;
+
+                            selections.push(fill(
+                                Bounds::from_corners(
+                                    point(content_left + x_start - scroll_offset.x, bounds.top() + y - scroll_offset.y),
+                                    point(content_left + x_end - scroll_offset.x, bounds.top() + y + line_height - scroll_offset.y),
+                                ),
+                                selection_color,
+                            ));
+                        }
+
+                        if is_focused {
+                            let cursor_col = display_col(&tab_layouts, c.position.line, c.position.col);
+                            let x = shaped_lines.get(c.position.line).map(|l| l.x_for_index(cursor_col)).unwrap_or(px(0.));
+                            let y = line_height * c.position.line;
+                            cursor_rects.push(caret.layout(
+                                point(content_left + x - scroll_offset.x, bounds.top() + y - scroll_offset.y),
+                                display_text(&input.lines, &tab_layouts, c.position.line),
+                                cursor_col,
+                                caret_color(i),
+                                window,
+                            ));
+                        }
+                    }
+                }
+            }
+            (cursor_rects, selections, gutter_line_numbers)
+        };
+        let overlay = OverlayCache {
+            key: overlay_key,
+            cursors: cursor_rects.clone(),
+            selections: selections.clone(),
+            gutter_line_numbers: gutter_line_numbers.clone(),
+        };
+
+        // Offset the single caret along its glide path, if one is in flight
+        let mut caret_target = None;
+        if input.cursors.len() == 1
+            && let Some(cursor) = cursor_rects.first_mut()
+        {
+            let document_origin = point(content_left - scroll_offset.x, bounds.top() - scroll_offset.y);
+            let target = cursor.bounds.origin - document_origin;
+            let shift = input.caret_position_for(target) - target;
+            cursor.bounds.origin = cursor.bounds.origin + shift;
+            if let Some((_, origin)) = &mut cursor.glyph {
+                *origin = *origin + shift;
+            }
+            caret_target = Some(target);
+        }
+
+        // Horizontal scrollbar when unwrapped lines overflow the content area
+        let hscrollbar = if !word_wrap && max_line_width > content_width {
+            let track = Bounds::new(
+                point(content_left, bounds.bottom() - SCROLLBAR_THICKNESS),
+                size(content_width, SCROLLBAR_THICKNESS),
+            );
+            let thumb_width = (content_width * (content_width / max_line_width))
+                .max(SCROLLBAR_MIN_THUMB)
+                .min(content_width);
+            let max_scroll = max_line_width - content_width;
+            let fraction = (scroll_offset.x / max_scroll).clamp(0.0, 1.0);
+            let thumb = Bounds::new(
+                point(track.left() + (content_width - thumb_width) * fraction, track.top()),
+                size(thumb_width, SCROLLBAR_THICKNESS),
+            );
+            Some((track, thumb))
+        } else {
+            None
+        };
+
+        MultiLinePrepaintState {
+            shaped_lines,
+            wrapped_lines,
+            word_wrap,
+            visual_line_counts,
+            max_line_width,
+            cursors: cursor_rects,
+            cursor_opacity,
+            selections,
+            scroll_offset,
+            line_height,
+            gutter_width,
+            gutter_line_numbers,
+            caret_target,
+            hscrollbar,
+            tab_layouts,
+            cache: PaintCache {
+                layout_key: Some(layout_key),
+                line_texts,
+                line_wrap_widths,
+                wrap_width: content_width,
+                wrap_width_changed,
+                stale_wraps,
+                overlay: Some(overlay),
+            },
+        }
+    }
+
+    fn paint(
+        &mut self,
+        _id: Option<&GlobalElementId>,
+        _inspector_id: Option<&InspectorElementId>,
+        bounds: Bounds<Pixels>,
+        _request_layout: &mut Self::RequestLayoutState,
+        prepaint: &mut Self::PrepaintState,
+        window: &mut Window,
+        cx: &mut App,
+    ) {
+        let focus_handle = self.input.read(cx).focus_handle.clone();
+        window.handle_input(
+            &focus_handle,
+            ElementInputHandler::new(bounds, self.input.clone()),
+            cx,
+        );
+
+        // Paint selections
+        for sel in prepaint.selections.drain(..) {
+            window.paint_quad(sel);
+        }
+
+        let line_height = prepaint.line_height;
+        let scroll_offset = prepaint.scroll_offset;
+        let gutter_width = prepaint.gutter_width;
+        let content_left = bounds.left() + gutter_width;
+
+        // Paint line numbers in the gutter (right-aligned)
+        for (shaped_num, y) in &prepaint.gutter_line_numbers {
+            let num_x = bounds.left() + gutter_width - px(16.) - shaped_num.width;
+            let origin = point(num_x, bounds.top() + *y);
+            shaped_num
+                .paint(origin, line_height, TextAlign::Left, None, window, cx)
+                .ok();
+        }
+
+        if prepaint.word_wrap {
+            // Paint wrapped lines
+            let mut visual_y = px(0.);
+            for (i, wrapped) in prepaint.wrapped_lines.iter().enumerate() {
+                let visual_height = line_height * prepaint.visual_line_counts[i];
+                let y = bounds.top() + visual_y - scroll_offset.y;
+                // Skip lines outside visible bounds
+                if y + visual_height >= bounds.top() && y <= bounds.bottom() {
+                    let origin = point(content_left, y);
+                    wrapped
+                        .paint(origin, line_height, TextAlign::Left, None, window, cx)
+                        .ok();
+                }
+                visual_y += visual_height;
+            }
+        } else {
+            // Paint unwrapped lines
+            for (i, shaped) in prepaint.shaped_lines.iter().enumerate() {
+                let y = bounds.top() + line_height * i - scroll_offset.y;
+                if y + line_height < bounds.top() || y > bounds.bottom() {
+                    continue;
+                }
+                let origin = point(content_left - scroll_offset.x, y);
+                shaped
+                    .paint(origin, line_height, TextAlign::Left, None, window, cx)
+                    .ok();
+            }
+        }
+
+        // Paint cursors
+        let opacity = prepaint.cursor_opacity;
+        if opacity > 0.0 && focus_handle.is_focused(window) {
+            for cursor in &prepaint.cursors {
+                let hsla: Hsla = cursor.color.into();
+                let color_with_opacity = Hsla {
+                    h: hsla.h,
+                    s: hsla.s,
+                    l: hsla.l,
+                    a: opacity,
+                };
+                window.paint_quad(fill(cursor.bounds, color_with_opacity));
+                // Invert the glyph under a block caret while it is mostly visible
+                if opacity >= 0.5
+                    && let Some((glyph, origin)) = &cursor.glyph
+                {
+                    glyph
+                        .paint(*origin, line_height, TextAlign::Left, None, window, cx)
+                        .ok();
+                }
+            }
+        }
+
+        // Paint the horizontal scrollbar over the last visible line
+        if let Some((track, thumb)) = prepaint.hscrollbar {
+            let theme = cx.global::<EditorTheme>();
+            window.paint_quad(fill(track, theme.mantle).corner_radii(px(4.)));
+            window.paint_quad(fill(thumb, theme.surface2).corner_radii(px(4.)));
+        }
+
+        // Update cached layout info
+        let shaped_lines: Vec<ShapedLine> = prepaint.shaped_lines.drain(..).collect();
+        let wrapped_lines: Vec<WrappedLine> = prepaint.wrapped_lines.drain(..).collect();
+        let visual_line_counts = prepaint.visual_line_counts.clone();
+        let max_line_width = prepaint.max_line_width;
+        let caret_target = prepaint.caret_target;
+        let hscrollbar = prepaint.hscrollbar;
+        let tab_layouts = std::mem::take(&mut prepaint.tab_layouts);
+        let cache = std::mem::take(&mut prepaint.cache);
+        self.input.update(cx, |input, cx| {
+            input.last_shaped_lines = shaped_lines;
+            input.last_wrapped_lines = wrapped_lines;
+            input.last_visual_line_counts = visual_line_counts;
+            input.last_max_line_width = max_line_width;
+            input.last_bounds = Some(bounds);
+            input.last_line_height = line_height;
+            input.last_gutter_width = gutter_width;
+            input.last_hscrollbar = hscrollbar;
+            input.last_tab_layouts = tab_layouts;
+            input.paint_cache = cache;
+            if input.paint_cache.stale_wraps {
+                input.schedule_rewrap(cx);
+            }
+            input.track_caret_target(caret_target, cx);
+            // Apply scroll_to_cursor with fresh layout data when cursor moved
+            if input.needs_scroll_to_cursor {
+                input.needs_scroll_to_cursor = false;
+                let old_scroll = input.scroll_offset;
+                input.scroll_to_cursor(cx);
+                if input.scroll_offset != old_scroll {
+                    cx.notify();
+                }
+            }
+        });
+    }
+
+    fn source_location(&self) -> Option<&'static core::panic::Location<'static>> {
+        None
+    }
+}
@@ -0,0 +1,163 @@
+//! Elastic tabstops: tab-terminated cells on adjacent lines are padded so
+//! their columns line up, like a table. Tabs are expanded to spaces in the
+//! display text, and `TabLayout` maps offsets between buffer and display.
+//! Without elastic tabstops, tabs expand to the next fixed tab stop.
+
+use unicode_segmentation::*;
+
+/// Minimum gap between the end of a cell and the next column
+const CELL_PADDING: usize = 2;
+
+/// How one buffer line is displayed with its tabs expanded to spaces.
+#[derive(Clone, Debug, Default)]
+pub struct TabLayout {
+    /// Line text with every tab replaced by padding spaces
+    pub display: String,
+    /// (byte offset of the tab in the buffer line, spaces it expands to)
+    tabs: Vec<(usize, usize)>,
+}
+
+impl TabLayout {
+    /// Map a byte offset in the buffer line to one in `display`.
+    pub fn to_display(&self, col: usize) -> usize {
+        let mut display_col = col;
+        for &(tab, spaces) in &self.tabs {
+            if tab >= col {
+                break;
+            }
+            display_col += spaces - 1;
+        }
+        display_col
+    }
+
+    /// Map a byte offset in `display` back to the buffer line. Offsets inside
+    /// an expanded tab snap to its nearer edge.
+    pub fn to_buffer(&self, display_col: usize) -> usize {
+        let mut shift = 0;
+        for &(tab, spaces) in &self.tabs {
+            let start = tab + shift;
+            if display_col <= start {
+                break;
+            }
+            if display_col < start + spaces {
+                return if display_col - start <= spaces / 2 {
+                    tab
+                } else {
+                    tab + 1
+                };
+            }
+            shift += spaces - 1;
+        }
+        display_col - shift
+    }
+}
+
+/// Columns `text` takes up with its tabs expanded to stops every
+/// `tab_size` columns, counted in graphemes like `layout_lines`.
+pub fn display_width(text: &str, tab_size: usize) -> usize {
+    let tab_size = tab_size.max(1);
+    text.graphemes(true).fold(0, |width, g| {
+        if g == "\t" {
+            width + tab_size - width % tab_size
+        } else {
+            width + 1
+        }
+    })
+}
+
+/// Lay out all lines with each tab padded to the next stop every
+/// `tab_size` columns.
+pub fn fixed_layout(lines: &[String], tab_size: usize) -> Vec<TabLayout> {
+    let tab_size = tab_size.max(1);
+    lines
+        .iter()
+        .map(|line| {
+            if !line.contains('\t') {
+                return TabLayout {
+                    display: line.clone(),
+                    tabs: Vec::new(),
+                };
+            }
+            let mut display = String::with_capacity(line.len());
+            let mut tabs = Vec::new();
+            let mut width = 0;
+            for (offset, g) in line.grapheme_indices(true) {
+                if g == "\t" {
+                    let spaces = tab_size - width % tab_size;
+                    tabs.push((offset, spaces));
+                    display.extend(std::iter::repeat_n(' ', spaces));
+                    width += spaces;
+                } else {
+                    display.push_str(g);
+                    width += 1;
+                }
+            }
+            TabLayout { display, tabs }
+        })
+        .collect()
+}
+
+/// Lay out all lines, aligning each tab-terminated cell with the matching
+/// cells on adjacent lines. Widths are measured in graphemes, which matches
+/// the monospace editor font.
+pub fn layout_lines(lines: &[String]) -> Vec<TabLayout> {
+    // Width of each tab-terminated cell per line (the trailing cell is free)
+    let cells: Vec<Vec<usize>> = lines
+        .iter()
+        .map(|line| {
+            let mut parts: Vec<&str> = line.split('\t').collect();
+            parts.pop();
+            parts.iter().map(|cell| cell.graphemes(true).count()).collect()
+        })
+        .collect();
+
+    // For each column, a run of adjacent lines that all have a cell in that
+    // column forms a block sharing the widest cell's width
+    let mut widths: Vec<Vec<usize>> = cells.iter().map(|c| vec![0; c.len()]).collect();
+    let column_count = cells.iter().map(Vec::len).max().unwrap_or(0);
+    for column in 0..column_count {
+        let mut line = 0;
+        while line < lines.len() {
+            if cells[line].len() <= column {
+                line += 1;
+                continue;
+            }
+            let block_start = line;
+            let mut width = 0;
+            while line < lines.len() && cells[line].len() > column {
+                width = width.max(cells[line][column]);
+                line += 1;
+            }
+            for line_widths in &mut widths[block_start..line] {
+                line_widths[column] = width + CELL_PADDING;
+            }
+        }
+    }
+
+    lines
+        .iter()
+        .zip(cells.iter().zip(widths))
+        .map(|(line, (cells, widths))| {
+            if cells.is_empty() {
+                return TabLayout {
+                    display: line.clone(),
+                    tabs: Vec::new(),
+                };
+            }
+            let mut display = String::with_capacity(line.len());
+            let mut tabs = Vec::with_capacity(cells.len());
+            let mut offset = 0;
+            for (i, part) in line.split('\t').enumerate() {
+                display.push_str(part);
+                offset += part.len();
+                if i < cells.len() {
+                    let spaces = widths[i] - cells[i];
+                    tabs.push((offset, spaces));
+                    display.extend(std::iter::repeat_n(' ', spaces));
+                    offset += 1;
+                }
+            }
+            TabLayout { display, tabs }
+        })
+        .collect()
+}
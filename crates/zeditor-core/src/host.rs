@@ -0,0 +1,84 @@
+//! What the editor asks of the system around it: clipboard flavors beyond
+//! plain text, the locale's word tokenizer, screen readers and its text in
+//! the user's language. The app installs its implementation once at
+//! launch; without one the editor sticks to what GPUI provides, in
+//! English.
+
+use std::fmt::Display;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use gpui::{App, ClipboardItem};
+
+pub trait EditorHost: Send + Sync {
+    /// Whether the system asks for less motion (macOS's Reduce Motion).
+    fn reduce_motion(&self) -> bool {
+        false
+    }
+
+    /// Put `html` on the clipboard alongside `plain`, plus RTF where the
+    /// OS can convert to it, so apps take whichever they render best.
+    /// Only `plain` goes there where rich flavors aren't supported.
+    fn write_rich_text(&self, plain: &str, _html: &str, cx: &mut App) {
+        cx.write_to_clipboard(ClipboardItem::new_string(plain.to_string()));
+    }
+
+    /// Byte range of the word around byte `offset` of `text`, as the
+    /// system's tokenizer for the user's locale splits it. None where there
+    /// is no such tokenizer, or no word there.
+    fn word_at(&self, _text: &str, _offset: usize) -> Option<Range<usize>> {
+        None
+    }
+
+    /// The HTML on the clipboard, if whatever copied it put some there.
+    fn read_html(&self) -> Option<String> {
+        None
+    }
+
+    /// Have the screen reader speak `message`, e.g. "3 cursors".
+    fn announce(&self, _message: &str) {}
+
+    /// Caret or selection in the active buffer, as a UTF-16 range into its
+    /// text, for screen readers.
+    fn set_draft_selection(&self, _selection: Range<usize>) {}
+
+    /// The user's language's text for `key` (an `editor.*` key), or None
+    /// to keep the editor's English.
+    fn text(&self, _key: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Plain GPUI, for when no host was installed.
+struct NoHost;
+
+impl EditorHost for NoHost {}
+
+static HOST: OnceLock<&'static dyn EditorHost> = OnceLock::new();
+
+/// Install the app's host. Only the first call counts.
+pub fn set_host(host: &'static dyn EditorHost) {
+    HOST.set(host).ok();
+}
+
+/// The installed host.
+pub fn current() -> &'static dyn EditorHost {
+    HOST.get().copied().unwrap_or(&NoHost)
+}
+
+/// The host's text for `key`, or `english`, with each `{name}` replaced by
+/// its value in `args`.
+pub fn text(key: &str, english: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = current().text(key).unwrap_or_else(|| english.to_string());
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+/// `text` for `count`, from `key.one` or `key.other`, with `{count}`
+/// filled in.
+pub fn text_count(key: &str, count: usize, one: &str, other: &str) -> String {
+    let (suffix, english) = if count == 1 { ("one", one) } else { ("other", other) };
+    text(&format!("{}.{}", key, suffix), english, &[("count", &count)])
+}
@@ -0,0 +1,23 @@
+//! The multi-cursor text editor behind Zeditor, as a GPUI view other apps
+//! can embed. The app provides the settings and colors as globals
+//! (`EditorSettings`, `EditorTheme`), binds the default keys, and can hook
+//! the editor up to the system through `EditorHost`.
+
+pub mod calc;
+pub mod editor;
+pub mod elastic_tabs;
+pub mod host;
+pub mod markdown;
+pub mod settings;
+pub mod snapshot;
+pub mod syntax;
+pub mod text_objects;
+pub mod theme;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+pub use editor::{EditorEvent, MultiLineEditor, key_bindings};
+pub use host::{EditorHost, set_host};
+pub use settings::EditorSettings;
+pub use snapshot::EditorSnapshot;
+pub use theme::EditorTheme;
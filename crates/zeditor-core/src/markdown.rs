@@ -0,0 +1,524 @@
+//! Markdown to HTML for copying drafts as rich text, and HTML back to
+//! Markdown for pasting from web pages. Covers what chat and mail drafts
+//! use: headings, paragraphs, lists, quotes, fenced code, rules, and inline
+//! emphasis, code, strikethrough and links. Single newlines are kept as
+//! line breaks, as they were typed.
+
+/// A block-level element being built up line by line.
+enum Block {
+    Paragraph(Vec<String>),
+    List { ordered: bool, items: Vec<String> },
+    Quote(Vec<String>),
+    Code(Vec<String>),
+}
+
+/// `markdown` as an HTML fragment.
+pub fn to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut block: Option<Block> = None;
+
+    for line in markdown.lines() {
+        if let Some(Block::Code(lines)) = &mut block {
+            if line.trim_start().starts_with("```") {
+                flush(&mut html, block.take());
+            } else {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush(&mut html, block.take());
+            block = Some(Block::Code(Vec::new()));
+        } else if trimmed.is_empty() {
+            flush(&mut html, block.take());
+        } else if let Some((level, text)) = heading(trimmed) {
+            flush(&mut html, block.take());
+            html.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline(text)));
+        } else if is_rule(trimmed) {
+            flush(&mut html, block.take());
+            html.push_str("<hr>\n");
+        } else if let Some(text) = trimmed.strip_prefix('>') {
+            let text = text.strip_prefix(' ').unwrap_or(text).to_string();
+            match &mut block {
+                Some(Block::Quote(lines)) => lines.push(text),
+                _ => {
+                    flush(&mut html, block.take());
+                    block = Some(Block::Quote(vec![text]));
+                }
+            }
+        } else if let Some((ordered, text)) = list_item(trimmed) {
+            match &mut block {
+                Some(Block::List { ordered: o, items }) if *o == ordered => items.push(text.to_string()),
+                _ => {
+                    flush(&mut html, block.take());
+                    block = Some(Block::List {
+                        ordered,
+                        items: vec![text.to_string()],
+                    });
+                }
+            }
+        } else {
+            match &mut block {
+                Some(Block::Paragraph(lines)) | Some(Block::Quote(lines)) => lines.push(trimmed.to_string()),
+                // A line under a list item continues it
+                Some(Block::List { items, .. }) => {
+                    if let Some(last) = items.last_mut() {
+                        last.push('\n');
+                        last.push_str(trimmed);
+                    }
+                }
+                _ => {
+                    flush(&mut html, block.take());
+                    block = Some(Block::Paragraph(vec![trimmed.to_string()]));
+                }
+            }
+        }
+    }
+    flush(&mut html, block);
+    html
+}
+
+fn flush(html: &mut String, block: Option<Block>) {
+    match block {
+        None => {}
+        Some(Block::Paragraph(lines)) => {
+            html.push_str(&format!("<p>{}</p>\n", inline_lines(&lines)));
+        }
+        Some(Block::Quote(lines)) => {
+            html.push_str(&format!("<blockquote><p>{}</p></blockquote>\n", inline_lines(&lines)));
+        }
+        Some(Block::List { ordered, items }) => {
+            let tag = if ordered { "ol" } else { "ul" };
+            html.push_str(&format!("<{}>\n", tag));
+            for item in items {
+                let lines: Vec<String> = item.lines().map(str::to_string).collect();
+                html.push_str(&format!("<li>{}</li>\n", inline_lines(&lines)));
+            }
+            html.push_str(&format!("</{}>\n", tag));
+        }
+        Some(Block::Code(lines)) => {
+            html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&lines.join("\n"))));
+        }
+    }
+}
+
+fn inline_lines(lines: &[String]) -> String {
+    lines.iter().map(|line| inline(line)).collect::<Vec<_>>().join("<br>\n")
+}
+
+/// `# Title` as (1, "Title"), up to six levels.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then_some((level, text.trim()))
+}
+
+/// `---`, `***` or `___`, optionally spaced out.
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|&c| c == marks[0])
+}
+
+/// `- item`, `* item`, `+ item` or `1. item`, as (ordered, text).
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = line.strip_prefix(bullet) {
+            return Some((false, text));
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = &line[digits..];
+    let text = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") "))?;
+    Some((true, text))
+}
+
+/// Inline code, links, bold, italics and strikethrough in `text`.
+fn inline(text: &str) -> String {
+    let mut html = String::new();
+    let mut rest = text;
+    let mut prev: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        // Underscores inside words (snake_case) aren't emphasis
+        let in_word = prev.is_some_and(char::is_alphanumeric);
+        prev = Some(c);
+        if c == '\\'
+            && let Some(escaped) = rest[1..].chars().next()
+            && escaped.is_ascii_punctuation()
+        {
+            html.push_str(&escape(&escaped.to_string()));
+            rest = &rest[1 + escaped.len_utf8()..];
+            continue;
+        }
+        if c == '`'
+            && let Some(end) = rest[1..].find('`')
+        {
+            html.push_str(&format!("<code>{}</code>", escape(&rest[1..1 + end])));
+            rest = &rest[end + 2..];
+            continue;
+        }
+        if c == '['
+            && let Some(close) = rest.find("](")
+            && let Some(end) = rest[close..].find(')')
+        {
+            let label = &rest[1..close];
+            let url = &rest[close + 2..close + end];
+            html.push_str(&format!("<a href=\"{}\">{}</a>", escape(url), inline(label)));
+            rest = &rest[close + end + 1..];
+            continue;
+        }
+        let mut matched = false;
+        for (marker, tag) in [("**", "strong"), ("__", "strong"), ("~~", "del"), ("*", "em"), ("_", "em")] {
+            if let Some(after) = rest.strip_prefix(marker)
+                && !(in_word && marker.starts_with('_'))
+                && !after.starts_with(char::is_whitespace)
+                && let Some(end) = after.find(marker)
+                && end > 0
+            {
+                html.push_str(&format!("<{0}>{1}</{0}>", tag, inline(&after[..end])));
+                rest = &after[end + marker.len()..];
+                matched = true;
+                break;
+            }
+        }
+        if !matched {
+            html.push_str(&escape(&c.to_string()));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `html` as Markdown. Tags without a Markdown equivalent keep only their
+/// text; scripts, styles and the document head are dropped.
+pub fn from_html(html: &str) -> String {
+    let mut writer = MarkdownWriter::default();
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+        } else if rest.starts_with('<')
+            && let Some(end) = rest.find('>')
+        {
+            writer.tag(&rest[1..end]);
+            rest = &rest[end + 1..];
+        } else {
+            let end = rest
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| c == '<')
+                .map_or(rest.len(), |(i, _)| i);
+            writer.text(&decode_entities(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    writer.out.trim_end().to_string()
+}
+
+/// Markdown built up from HTML tags and text in document order.
+#[derive(Default)]
+struct MarkdownWriter {
+    out: String,
+    /// Newlines owed before the next text, for block boundaries
+    pending_breaks: usize,
+    /// Quote depth the blank lines among those newlines belong to
+    break_quote_depth: usize,
+    pending_space: bool,
+    /// Opening emphasis and link markers, held back until text follows so
+    /// spaces go outside them and empty elements leave nothing
+    pending_markup: String,
+    at_line_start: bool,
+    /// Marker of a list item whose first line hasn't started yet
+    pending_marker: Option<String>,
+    quote_depth: usize,
+    /// Open lists, innermost last, with the next number for ordered ones
+    lists: Vec<Option<usize>>,
+    /// Targets of open links; None for links that aren't kept
+    links: Vec<Option<String>>,
+    in_pre: bool,
+    /// Depth inside elements whose content is dropped
+    skip_depth: usize,
+}
+
+impl MarkdownWriter {
+    fn tag(&mut self, tag: &str) {
+        let closing = tag.starts_with('/');
+        let tag = tag.trim_start_matches('/');
+        let name_len = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+        let name = tag[..name_len].to_ascii_lowercase();
+        if matches!(name.as_str(), "script" | "style" | "head" | "title" | "template") {
+            if closing {
+                self.skip_depth = self.skip_depth.saturating_sub(1);
+            } else if !tag.ends_with('/') {
+                self.skip_depth += 1;
+            }
+            return;
+        }
+        if self.skip_depth > 0 {
+            return;
+        }
+
+        match (name.as_str(), closing) {
+            ("p" | "table", _) => self.block_break(2),
+            ("div" | "section" | "article" | "header" | "footer" | "tr" | "dt" | "dd", _) => {
+                self.block_break(1)
+            }
+            ("br", _) => self.newline(),
+            ("td" | "th", false) => self.pending_space = true,
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                self.block_break(2);
+                let level = name[1..].parse().unwrap_or(1);
+                self.write(&format!("{} ", "#".repeat(level)));
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => self.block_break(2),
+            ("strong" | "b", false) => self.pending_markup.push_str("**"),
+            ("strong" | "b", true) => self.close_markup("**", "**"),
+            ("em" | "i", false) => self.pending_markup.push('*'),
+            ("em" | "i", true) => self.close_markup("*", "*"),
+            ("del" | "s" | "strike", false) => self.pending_markup.push_str("~~"),
+            ("del" | "s" | "strike", true) => self.close_markup("~~", "~~"),
+            ("code" | "kbd" | "samp", false) if !self.in_pre => self.pending_markup.push('`'),
+            ("code" | "kbd" | "samp", true) if !self.in_pre => self.close_markup("`", "`"),
+            ("a", false) => {
+                let href = attribute(tag, "href")
+                    .filter(|href| !href.is_empty() && !href.starts_with("javascript:"));
+                if href.is_some() {
+                    self.pending_markup.push('[');
+                }
+                self.links.push(href);
+            }
+            ("a", true) => {
+                if let Some(Some(href)) = self.links.pop() {
+                    self.close_markup("[", &format!("]({})", href));
+                }
+            }
+            ("img", false) => {
+                if let Some(src) = attribute(tag, "src") {
+                    let alt = attribute(tag, "alt").unwrap_or_default();
+                    self.write(&format!("![{}]({})", alt, src));
+                }
+            }
+            ("ul" | "ol", false) => {
+                self.block_break(if self.lists.is_empty() { 2 } else { 1 });
+                self.lists.push((name == "ol").then_some(1));
+            }
+            ("ul" | "ol", true) => {
+                self.lists.pop();
+                self.block_break(if self.lists.is_empty() { 2 } else { 1 });
+            }
+            ("li", false) => {
+                self.block_break(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.pending_marker = Some(marker);
+            }
+            ("li", true) => self.block_break(1),
+            ("blockquote", false) => {
+                self.block_break(2);
+                self.quote_depth += 1;
+            }
+            ("blockquote", true) => {
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.block_break(2);
+            }
+            ("pre", false) => {
+                self.block_break(2);
+                self.write("```");
+                self.newline();
+                self.in_pre = true;
+            }
+            ("pre", true) => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+                self.write("```");
+                self.in_pre = false;
+                self.block_break(2);
+            }
+            ("hr", false) => {
+                self.block_break(2);
+                self.write("---");
+                self.block_break(2);
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip_depth > 0 {
+            return;
+        }
+        if self.in_pre {
+            for (i, line) in text.split('\n').enumerate() {
+                if i > 0 {
+                    self.newline();
+                }
+                if !line.is_empty() {
+                    self.write(line);
+                }
+            }
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.pending_space = true;
+            } else {
+                let mut buf = [0; 4];
+                self.write(c.encode_utf8(&mut buf));
+            }
+        }
+    }
+
+    /// Ask for `count` newlines before whatever comes next.
+    fn block_break(&mut self, count: usize) {
+        if self.pending_breaks == 0 {
+            self.break_quote_depth = self.quote_depth;
+        }
+        self.break_quote_depth = self.break_quote_depth.min(self.quote_depth);
+        self.pending_breaks = self.pending_breaks.max(count);
+        self.pending_space = false;
+    }
+
+    /// Close an inline element opened with `open`, dropping it if nothing
+    /// was written inside. A space before the close goes after it.
+    fn close_markup(&mut self, open: &str, close: &str) {
+        if let Some(kept) = self.pending_markup.strip_suffix(open) {
+            self.pending_markup.truncate(kept.len());
+            return;
+        }
+        let space = std::mem::take(&mut self.pending_space);
+        self.write(close);
+        self.pending_space = space;
+    }
+
+    /// End the current line, keeping quote markers on the next.
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+        self.pending_space = false;
+    }
+
+    /// Append `text` after any owed breaks, space and line prefix.
+    fn write(&mut self, text: &str) {
+        if self.pending_breaks > 0 && !self.out.is_empty() {
+            if !self.at_line_start {
+                self.out.push('\n');
+            }
+            for _ in 1..self.pending_breaks {
+                self.out.push_str(&">".repeat(self.break_quote_depth));
+                self.out.push('\n');
+            }
+            self.at_line_start = true;
+        }
+        self.pending_breaks = 0;
+        if self.out.is_empty() {
+            self.at_line_start = true;
+        }
+        if self.at_line_start {
+            if self.quote_depth > 0 {
+                self.out.push_str(&"> ".repeat(self.quote_depth));
+            }
+            let depth = self.lists.len();
+            match self.pending_marker.take() {
+                Some(marker) => {
+                    self.out.push_str(&"   ".repeat(depth.saturating_sub(1)));
+                    self.out.push_str(&marker);
+                }
+                None => self.out.push_str(&"   ".repeat(depth)),
+            }
+            self.at_line_start = false;
+        } else if self.pending_space {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+        self.out.push_str(&std::mem::take(&mut self.pending_markup));
+        self.out.push_str(text);
+    }
+}
+
+/// Value of attribute `name` in the inside of a start tag, quoted or not.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(found) = lower[from..].find(name) {
+        let start = from + found;
+        from = start + name.len();
+        let preceded = lower[..start].ends_with(char::is_whitespace);
+        let after = lower[from..].trim_start();
+        if !preceded || !after.starts_with('=') {
+            continue;
+        }
+        let value = tag[tag.len() - after.len() + 1..].trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or(""),
+            _ => value.split(char::is_whitespace).next().unwrap_or("").trim_end_matches('/'),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+/// Replace the character references browsers commonly emit.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some((end, _)) = rest.char_indices().take(12).find(|&(_, c)| c == ';') else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
@@ -0,0 +1,147 @@
+//! Editor settings. `EditorConfig` is the serializable part an app can
+//! keep in its own preferences file; `EditorSettings` is the global the
+//! editor reads it from.
+
+use std::collections::BTreeMap;
+
+use gpui::Global;
+use serde::{Deserialize, Serialize};
+
+/// Shape of the text caret.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorShape {
+    #[default]
+    Bar,
+    /// Covers the whole character cell; the glyph underneath is inverted
+    Block,
+    Underline,
+}
+
+/// What word motions (alt-arrows, alt-backspace) treat as a word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordMotion {
+    /// Unicode word boundaries: each CJK character is a word, and
+    /// contractions like "don't" are one
+    #[default]
+    Unicode,
+    /// Runs of letters, digits and underscores, so `snake_case_names` are
+    /// one word
+    Programmer,
+}
+
+/// Editor line height, either fixed or relative to the font size.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineHeight {
+    Pixels(f32),
+    Relative(f32),
+}
+
+impl Default for LineHeight {
+    fn default() -> Self {
+        LineHeight::Pixels(24.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Wrap long lines in new buffers; Alt+Z still toggles it per buffer
+    pub word_wrap: bool,
+    /// Columns between tab stops, and how many spaces Tab indents by
+    pub tab_size: usize,
+    /// Tab inserts a tab character rather than spaces
+    pub hard_tabs: bool,
+    pub font_family: String,
+    /// Font size buffers open at and zooming resets to
+    pub font_size: f32,
+    /// Line number gutter (never shown in the compact row)
+    pub line_numbers: bool,
+    pub cursor_shape: CursorShape,
+    /// Draw every caret but the first in the theme's secondary cursor color
+    pub tint_secondary_cursors: bool,
+    /// Thickness in pixels of bar and underline carets
+    pub cursor_width: f32,
+    pub word_motion: WordMotion,
+    /// Glide the caret to its new position instead of jumping
+    pub animated_cursor: bool,
+    /// Animate scroll-to-cursor and mouse wheel scrolling
+    pub smooth_scroll: bool,
+    /// How far past the last line the view can scroll, as a fraction of the
+    /// viewport (0 = stop at the last line, 1 = last line can reach the top)
+    pub scroll_past_end: f32,
+    /// Programming ligatures (OpenType `calt`/`liga`) in the editor font
+    pub ligatures: bool,
+    /// Extra OpenType features, e.g. `{"zero": 1, "ss02": 1}`
+    pub font_features: BTreeMap<String, u32>,
+    pub line_height: LineHeight,
+    /// Align tab-separated columns across adjacent lines
+    pub elastic_tabstops: bool,
+    /// Color keywords, strings, comments, numbers and function calls
+    pub syntax_highlighting: bool,
+    /// Paste HTML from the clipboard (copied from a web page) as Markdown
+    /// rather than its plain text; `PasteAsMarkdown` does it either way
+    pub paste_html_as_markdown: bool,
+    /// strftime-style format used by `InsertTimestamp`
+    pub timestamp_format: String,
+    /// Command `OpenInExternalEditor` runs with the file, e.g. `zed` or
+    /// `code`; empty uses `$VISUAL`/`$EDITOR`, then the system default
+    pub external_editor: String,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            word_wrap: false,
+            tab_size: 4,
+            hard_tabs: false,
+            font_family: "JetBrains Mono".to_string(),
+            font_size: 14.0,
+            line_numbers: true,
+            cursor_shape: CursorShape::Bar,
+            tint_secondary_cursors: false,
+            cursor_width: 2.0,
+            word_motion: WordMotion::default(),
+            animated_cursor: false,
+            smooth_scroll: true,
+            scroll_past_end: 0.0,
+            ligatures: true,
+            font_features: BTreeMap::new(),
+            line_height: LineHeight::default(),
+            elastic_tabstops: false,
+            syntax_highlighting: false,
+            paste_html_as_markdown: false,
+            timestamp_format: "%Y-%m-%dT%H:%M:%S%:z".to_string(),
+            external_editor: String::new(),
+        }
+    }
+}
+
+/// What the editor reads while it runs. The app sets it at launch and
+/// again whenever its preferences or the system's text size change.
+#[derive(Clone, Debug)]
+pub struct EditorSettings {
+    pub config: EditorConfig,
+    /// Skip animations (smooth scrolling, the caret glide and blink fade);
+    /// None follows the system via `EditorHost::reduce_motion`
+    pub reduce_motion: Option<bool>,
+    /// Opacity of the editor background, for translucent windows
+    pub background_opacity: f32,
+    /// Multiplier on the font size from the system's text size setting
+    pub text_scale: f32,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            config: EditorConfig::default(),
+            reduce_motion: None,
+            background_opacity: 1.0,
+            text_scale: 1.0,
+        }
+    }
+}
+
+impl Global for EditorSettings {}
@@ -0,0 +1,50 @@
+//! Editor state an app can save and bring back: the text, cursors, scroll
+//! position and modes of one editor, for restoring a session, keeping a
+//! draft's place, or recovering after a crash.
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::LineEnding;
+
+/// Version written into new snapshots. Bumped whenever fields are added;
+/// fields are never removed or change meaning, so any build reads any
+/// snapshot, skipping fields it doesn't know and defaulting ones it lacks.
+///
+/// 0: cursors, scroll position and word wrap (no `version` field)
+/// 1: text, line ending, read-only
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A cursor as (line, col), with the selection anchor if any.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedCursor {
+    pub position: (usize, usize),
+    pub anchor: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSnapshot {
+    /// `SNAPSHOT_VERSION` of the build that wrote it
+    pub version: u32,
+    /// The buffer with `\n` line breaks; None to keep the editor's text
+    /// and only restore the rest
+    pub text: Option<String>,
+    pub line_ending: LineEnding,
+    /// Cursors in order, the first being the primary one
+    pub cursors: Vec<SavedCursor>,
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    pub word_wrap: bool,
+    pub read_only: bool,
+}
+
+impl EditorSnapshot {
+    /// The same state without the text, for keeping next to text that's
+    /// stored anyway.
+    pub fn without_text(&self) -> Self {
+        Self {
+            text: None,
+            ..self.clone()
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Rough, language-agnostic highlighting for code pasted into the popup:
+//! line comments, quoted strings, numbers, common keywords and called
+//! functions. Each line is read on its own, so block comments and strings
+//! spanning lines aren't recognized.
+
+use std::ops::Range;
+
+/// What a highlighted span of a line is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+    Function,
+}
+
+/// Keywords shared by the languages most often pasted: Rust, Python,
+/// JavaScript/TypeScript, Go, C and shell
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "def", "default",
+    "defer", "do", "elif", "else", "enum", "export", "extends", "false", "fi", "fn", "for", "from",
+    "func", "function", "go", "if", "impl", "import", "in", "interface", "let", "loop", "match", "mod",
+    "mut", "new", "nil", "None", "null", "package", "pub", "return", "self", "static", "struct",
+    "switch", "then", "this", "throw", "trait", "True", "true", "False", "try", "type", "typedef",
+    "undefined", "use", "var", "void", "where", "while", "with", "yield",
+];
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Highlighted spans of `line`, as byte ranges in order. Text between
+/// them is plain.
+pub fn highlight_line(line: &str) -> Vec<(Range<usize>, TokenKind)> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let rest = &line[start..];
+        // `#` and `--` only start a comment at the start of the line, so
+        // `a#b` and `x--` stay plain; `#[` is a Rust attribute
+        let at_line_start = line[..start].trim().is_empty();
+        let hash_comment = c == '#' && !rest.starts_with("#[");
+        if rest.starts_with("//") || (at_line_start && (hash_comment || rest.starts_with("--"))) {
+            tokens.push((start..line.len(), TokenKind::Comment));
+            break;
+        }
+        if c == '"' || c == '\'' || c == '`' {
+            match quoted_len(rest, c) {
+                Some(len) => {
+                    let end = start + len;
+                    tokens.push((start..end, TokenKind::String));
+                    while chars.peek().is_some_and(|&(i, _)| i < end) {
+                        chars.next();
+                    }
+                }
+                // A lone apostrophe (Rust lifetimes, prose) isn't a string
+                None if c == '\'' => {}
+                // An unclosed string runs to the end of the line
+                None => {
+                    tokens.push((start..line.len(), TokenKind::String));
+                    break;
+                }
+            }
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let mut end = start + 1;
+            while let Some(&(i, next)) = chars.peek() {
+                if !(is_ident_char(next) || next == '.') {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            tokens.push((start..end, TokenKind::Number));
+            continue;
+        }
+        if is_ident_char(c) {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, next)) = chars.peek() {
+                if !is_ident_char(next) {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            let word = &line[start..end];
+            if KEYWORDS.contains(&word) {
+                tokens.push((start..end, TokenKind::Keyword));
+            } else if line[end..].trim_start().starts_with('(') {
+                tokens.push((start..end, TokenKind::Function));
+            }
+        }
+    }
+    tokens
+}
+
+/// Byte length of the string that opens `text` with `quote`, closing
+/// quote included, or None if it isn't closed on this line.
+fn quoted_len(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
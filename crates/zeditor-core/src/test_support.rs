@@ -0,0 +1,336 @@
+//! Driving the editor in tests, in a window of GPUI's test platform: set
+//! up text and cursors, feed it keystrokes, actions, clicks and IME calls,
+//! then check the text, cursors and scroll position it ends up with.
+//!
+//! Cursors are written into the text with markers: `ˇ` is a caret and
+//! `«…»` a selection, with its caret at the end unless a `ˇ` right after
+//! `«` puts it at the start.
+//!
+//! ```ignore
+//! #[gpui::test]
+//! fn add_cursor_down(cx: &mut TestAppContext) {
+//!     let mut cx = EditorTestContext::new(cx);
+//!     cx.set_state("onˇe\ntwo");
+//!     cx.dispatch_action(AddCursorDown);
+//!     cx.assert_state("onˇe\ntwˇo");
+//! }
+//! ```
+
+use std::ops::{Deref, DerefMut, Range};
+
+use gpui::*;
+
+use crate::editor::{Cursor, MultiLineEditor, key_bindings};
+use crate::settings::EditorSettings;
+use crate::theme::{EditorTheme, SyntaxColors};
+
+pub struct EditorTestContext {
+    pub cx: VisualTestContext,
+    pub editor: Entity<MultiLineEditor>,
+}
+
+impl EditorTestContext {
+    /// An empty editor focused in a new test window, with the default
+    /// settings (motion reduced, so scrolling lands at once), a plain
+    /// palette and the default key bindings.
+    pub fn new(cx: &mut TestAppContext) -> Self {
+        cx.update(|cx| {
+            cx.set_global(EditorSettings {
+                reduce_motion: Some(true),
+                ..EditorSettings::default()
+            });
+            cx.set_global(plain_theme());
+            cx.bind_keys(key_bindings());
+        });
+        let window = cx.add_window(|_, cx| MultiLineEditor::new(cx));
+        let editor = window.root(cx).unwrap();
+        let mut cx = VisualTestContext::from_window(window.into(), cx);
+        cx.update(|window, cx| {
+            let focus = editor.read(cx).focus_handle.clone();
+            window.focus(&focus, cx);
+        });
+        cx.run_until_parked();
+        Self { cx, editor }
+    }
+
+    /// Replace the text and cursors with `marked`.
+    pub fn set_state(&mut self, marked: &str) {
+        let (text, offsets) = parse_marked(marked);
+        self.editor.update(&mut self.cx, |editor, cx| {
+            editor.reset_with_text(Some(text), cx);
+            editor.cursors = offsets
+                .into_iter()
+                .map(|(anchor, head)| Cursor {
+                    position: editor.position_from_flat(head),
+                    anchor: (anchor != head).then(|| editor.position_from_flat(anchor)),
+                })
+                .collect();
+            editor.needs_scroll_to_cursor = true;
+            cx.notify();
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// The text with the cursors marked in it.
+    pub fn state(&mut self) -> String {
+        self.editor.read_with(&self.cx, |editor, _| {
+            let offsets = editor
+                .cursors
+                .iter()
+                .map(|cursor| {
+                    let head = editor.flat_offset(&cursor.position);
+                    let anchor = cursor.anchor.as_ref().map_or(head, |a| editor.flat_offset(a));
+                    (anchor, head)
+                })
+                .collect::<Vec<_>>();
+            marked_text(&editor.lines.join("\n"), &offsets)
+        })
+    }
+
+    #[track_caller]
+    pub fn assert_state(&mut self, marked: &str) {
+        let state = self.state();
+        assert_eq!(state, marked, "\nexpected:\n{}\nactual:\n{}", marked, state);
+    }
+
+    pub fn lines(&mut self) -> Vec<String> {
+        self.editor.read_with(&self.cx, |editor, _| editor.lines.clone())
+    }
+
+    pub fn cursors(&mut self) -> Vec<Cursor> {
+        self.editor.read_with(&self.cx, |editor, _| editor.cursors.clone())
+    }
+
+    pub fn scroll_offset(&mut self) -> Point<Pixels> {
+        self.editor.read_with(&self.cx, |editor, _| editor.scroll_offset)
+    }
+
+    pub fn set_word_wrap(&mut self, word_wrap: bool) {
+        self.editor.update(&mut self.cx, |editor, cx| {
+            editor.word_wrap = word_wrap;
+            cx.notify();
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// Resize the window, for wrapping and scrolling.
+    pub fn set_window_size(&mut self, size: Size<Pixels>) {
+        self.cx.simulate_resize(size);
+        self.cx.run_until_parked();
+    }
+
+    /// Where the caret at flat byte `offset` of the text would be drawn, in
+    /// window coordinates: the middle of its left edge, as of the last
+    /// layout. Clicking there puts the caret at `offset`.
+    pub fn position_for_offset(&mut self, offset: usize) -> Point<Pixels> {
+        self.editor.read_with(&self.cx, |editor, _| {
+            let bounds = editor.last_bounds.expect("the editor hasn't been laid out");
+            let caret = editor.caret_origin(&editor.position_from_flat(offset));
+            point(
+                bounds.left() + editor.last_gutter_width + caret.x - editor.scroll_offset.x,
+                bounds.top() + caret.y - editor.scroll_offset.y + editor.last_line_height / 2.,
+            )
+        })
+    }
+
+    /// The selection highlights of the last frame, in window coordinates:
+    /// one rect per visual row each selection covers.
+    pub fn selection_rects(&mut self) -> Vec<Bounds<Pixels>> {
+        self.editor.read_with(&self.cx, |editor, _| editor.painted_selections())
+    }
+
+    /// Press and release the left button at `position`; `click_count` 2 is
+    /// a double click.
+    pub fn click_at(&mut self, position: Point<Pixels>, modifiers: Modifiers, click_count: usize) {
+        self.cx.simulate_event(MouseDownEvent {
+            button: MouseButton::Left,
+            position,
+            modifiers,
+            click_count,
+            ..Default::default()
+        });
+        self.cx.simulate_event(MouseUpEvent {
+            button: MouseButton::Left,
+            position,
+            modifiers,
+            click_count,
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// Click the caret position at flat byte `offset`.
+    pub fn click(&mut self, offset: usize, modifiers: Modifiers) {
+        let position = self.position_for_offset(offset);
+        self.click_at(position, modifiers, 1);
+    }
+
+    /// Press the left button at `from`, move to `to` and let go there.
+    pub fn drag(&mut self, from: Point<Pixels>, to: Point<Pixels>, modifiers: Modifiers) {
+        self.cx.simulate_event(MouseDownEvent {
+            button: MouseButton::Left,
+            position: from,
+            modifiers,
+            click_count: 1,
+            ..Default::default()
+        });
+        self.cx.simulate_event(MouseMoveEvent {
+            position: to,
+            pressed_button: Some(MouseButton::Left),
+            modifiers,
+        });
+        self.cx.simulate_event(MouseUpEvent {
+            button: MouseButton::Left,
+            position: to,
+            modifiers,
+            click_count: 1,
+        });
+        self.cx.run_until_parked();
+    }
+
+    // --- IME, through the editor's input handler ---
+
+    /// Compose `text` in place of the marked text (or the selection), with
+    /// `selected` as the composition's own selection.
+    pub fn ime_compose(&mut self, text: &str, selected: Option<Range<usize>>) {
+        let editor = self.editor.clone();
+        self.cx.update(|window, cx| {
+            editor.update(cx, |editor, cx| {
+                editor.replace_and_mark_text_in_range(None, text, selected, window, cx);
+            });
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// Commit `text` in place of the marked text (or the selection).
+    pub fn ime_commit(&mut self, text: &str) {
+        self.replace_text_in_range(None, text);
+    }
+
+    /// Replace the flat UTF-16 `range` of the text, as an IME or the
+    /// accessibility layer would.
+    pub fn replace_text_in_range(&mut self, range: Option<Range<usize>>, text: &str) {
+        let editor = self.editor.clone();
+        self.cx.update(|window, cx| {
+            editor.update(cx, |editor, cx| {
+                editor.replace_text_in_range(range, text, window, cx);
+            });
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// Keep the composed text as it is and end the composition.
+    pub fn ime_unmark(&mut self) {
+        let editor = self.editor.clone();
+        self.cx.update(|window, cx| {
+            editor.update(cx, |editor, cx| editor.unmark_text(window, cx));
+        });
+        self.cx.run_until_parked();
+    }
+
+    /// The flat UTF-16 range being composed, if any.
+    pub fn marked_text_range(&mut self) -> Option<Range<usize>> {
+        let editor = self.editor.clone();
+        self.cx.update(|window, cx| {
+            editor.update(cx, |editor, cx| editor.marked_text_range(window, cx))
+        })
+    }
+}
+
+impl Deref for EditorTestContext {
+    type Target = VisualTestContext;
+
+    fn deref(&self) -> &VisualTestContext {
+        &self.cx
+    }
+}
+
+impl DerefMut for EditorTestContext {
+    fn deref_mut(&mut self) -> &mut VisualTestContext {
+        &mut self.cx
+    }
+}
+
+/// Black on white; tests don't look at the colors.
+fn plain_theme() -> EditorTheme {
+    EditorTheme {
+        surface0: rgb(0xffffff),
+        overlay0: rgb(0x808080),
+        mantle: rgb(0xf0f0f0),
+        surface2: rgb(0xc0c0c0),
+        cursor: rgb(0x000000),
+        cursor_secondary: rgb(0x404040),
+        cursor_min_width: 1.,
+        selection: rgb(0xb4d5fe),
+        selection_inactive: rgb(0xdcdcdc),
+        error: rgb(0xffc0c0),
+        syntax: SyntaxColors {
+            keyword: rgb(0x0000c0),
+            string: rgb(0x008000),
+            comment: rgb(0x707070),
+            number: rgb(0x800080),
+            function: rgb(0x806000),
+        },
+    }
+}
+
+/// The text of `marked` without its markers, and (anchor, head) flat
+/// offsets of the cursors in it, in the order they're written.
+pub fn parse_marked(marked: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::new();
+    let mut cursors = Vec::new();
+    // Start of the open selection, and where a caret in it was marked
+    let mut open: Option<(usize, Option<usize>)> = None;
+    for ch in marked.chars() {
+        match ch {
+            'ˇ' => match &mut open {
+                Some((_, caret)) => *caret = Some(text.len()),
+                None => cursors.push((text.len(), text.len())),
+            },
+            '«' => {
+                assert!(open.is_none(), "nested « in {:?}", marked);
+                open = Some((text.len(), None));
+            }
+            '»' => {
+                let (start, caret) = open.take().unwrap_or_else(|| panic!("» without « in {:?}", marked));
+                let end = text.len();
+                cursors.push(match caret {
+                    Some(caret) if caret == start => (end, start),
+                    None => (start, end),
+                    Some(caret) if caret == end => (start, end),
+                    Some(_) => panic!("a ˇ inside «…» has to be at one end, in {:?}", marked),
+                });
+            }
+            _ => text.push(ch),
+        }
+    }
+    assert!(open.is_none(), "« without » in {:?}", marked);
+    (text, cursors)
+}
+
+/// `text` with markers for cursors given as (anchor, head) flat offsets.
+pub fn marked_text(text: &str, cursors: &[(usize, usize)]) -> String {
+    // (offset, order at the same offset, marker)
+    let mut markers = Vec::new();
+    for &(anchor, head) in cursors {
+        if anchor == head {
+            markers.push((head, 2, "ˇ"));
+        } else {
+            let (start, end) = (anchor.min(head), anchor.max(head));
+            markers.push((start, 1, "«"));
+            markers.push((end, 0, "»"));
+            if head == start {
+                markers.push((start, 2, "ˇ"));
+            }
+        }
+    }
+    markers.sort();
+    let mut marked = String::new();
+    let mut last = 0;
+    for (offset, _, marker) in markers {
+        marked.push_str(&text[last..offset]);
+        marked.push_str(marker);
+        last = offset;
+    }
+    marked.push_str(&text[last..]);
+    marked
+}
@@ -0,0 +1,159 @@
+//! Ranges of structure around a selection — words, lines, paragraphs and
+//! bracketed blocks — used by `ExpandSelection`. All offsets are byte
+//! offsets into the flat (newline-joined) buffer text.
+
+use std::ops::Range;
+
+use unicode_segmentation::*;
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// The smallest structural range strictly containing `range`, or None when
+/// `range` already covers the whole text.
+pub fn expand(text: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let mut candidates = Vec::new();
+    candidates.extend(word_at(text, &range));
+    let line = line_at(text, &range);
+    candidates.push(paragraph_at(text, &line));
+    candidates.push(line);
+    let mut inner = range.clone();
+    while let Some((open, close)) = enclosing_brackets(text, &inner) {
+        candidates.push(open + 1..close);
+        inner = open..close + 1;
+        candidates.push(inner.clone());
+    }
+    candidates.push(0..text.len());
+
+    candidates
+        .into_iter()
+        .filter(|c| c.start <= range.start && c.end >= range.end && *c != range)
+        .min_by_key(|c| c.len())
+}
+
+/// The word containing `range`, when the range lies within a single word.
+/// A caret directly after a word counts as inside it.
+fn word_at(text: &str, range: &Range<usize>) -> Option<Range<usize>> {
+    text.split_word_bound_indices()
+        .map(|(start, word)| (start..start + word.len(), word))
+        .filter(|(_, word)| word.chars().any(|c| c.is_alphanumeric() || c == '_'))
+        .map(|(word_range, _)| word_range)
+        .find(|w| w.start <= range.start && range.end <= w.end)
+}
+
+/// Full lines (without the trailing newline) touched by `range`.
+fn line_at(text: &str, range: &Range<usize>) -> Range<usize> {
+    let start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| range.end + i);
+    start..end
+}
+
+/// Extend a line range over adjacent non-blank lines.
+fn paragraph_at(text: &str, lines: &Range<usize>) -> Range<usize> {
+    if text[lines.clone()].trim().is_empty() {
+        return lines.clone();
+    }
+    let mut start = lines.start;
+    while start > 0 {
+        let prev_start = text[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+        if text[prev_start..start - 1].trim().is_empty() {
+            break;
+        }
+        start = prev_start;
+    }
+    let mut end = lines.end;
+    while end < text.len() {
+        let next_end = text[end + 1..]
+            .find('\n')
+            .map_or(text.len(), |i| end + 1 + i);
+        if text[end + 1..next_end].trim().is_empty() {
+            break;
+        }
+        end = next_end;
+    }
+    start..end
+}
+
+/// Byte offsets of the innermost `()`, `[]` or `{}` pair with the opener
+/// before `range` and the closer after it.
+pub fn enclosing_brackets(text: &str, range: &Range<usize>) -> Option<(usize, usize)> {
+    // Walk back to the nearest opener that isn't closed before `range`
+    let mut closers = Vec::new();
+    let (open, kind) = text[..range.start].char_indices().rev().find_map(|(i, c)| {
+        if BRACKETS.iter().any(|&(_, close)| close == c) {
+            closers.push(c);
+            None
+        } else if let Some(&pair) = BRACKETS.iter().find(|&&(open, _)| open == c) {
+            if closers.last() == Some(&pair.1) {
+                closers.pop();
+                None
+            } else if closers.is_empty() {
+                Some((i, pair))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    })?;
+
+    // Then forward to its matching closer
+    let mut depth = 0usize;
+    for (i, c) in text[range.end..].char_indices() {
+        if c == kind.0 {
+            depth += 1;
+        } else if c == kind.1 {
+            if depth == 0 {
+                return Some((open, range.end + i));
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Byte offsets of the innermost bracket pair or same-line quote pair
+/// (`"`, `'` or `` ` ``) around `range`.
+pub fn enclosing_pair(text: &str, range: &Range<usize>) -> Option<(usize, usize)> {
+    let quotes = enclosing_quotes(text, range);
+    let brackets = enclosing_brackets(text, range);
+    match (quotes, brackets) {
+        (Some(q), Some(b)) => Some(if q.0 > b.0 { q } else { b }),
+        (q, b) => q.or(b),
+    }
+}
+
+/// Quotes pair up left to right within a line; backslash-escaped quotes are
+/// skipped.
+fn enclosing_quotes(text: &str, range: &Range<usize>) -> Option<(usize, usize)> {
+    let line = line_at(text, range);
+    if text[line.clone()].contains('\n') {
+        return None;
+    }
+    let mut best: Option<(usize, usize)> = None;
+    for quote in ['"', '\'', '`'] {
+        let mut open = None;
+        let mut escaped = false;
+        for (i, c) in text[line.clone()].char_indices() {
+            let i = line.start + i;
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                match open.take() {
+                    None => open = Some(i),
+                    Some(start) => {
+                        if start < range.start && i >= range.end && best.is_none_or(|b| start > b.0) {
+                            best = Some((start, i));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    best
+}
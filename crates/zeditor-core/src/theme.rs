@@ -0,0 +1,69 @@
+//! Colors the editor paints with, set by the app from its own theme.
+
+use gpui::{Global, Rgba};
+
+use crate::syntax::TokenKind;
+
+/// The editor's palette. Names follow the Catppuccin roles the app's
+/// themes are built from.
+#[derive(Clone, Debug)]
+pub struct EditorTheme {
+    /// Editor background, and the glyph redrawn over a block caret
+    pub surface0: Rgba,
+    /// Line numbers
+    pub overlay0: Rgba,
+    /// Horizontal scrollbar track
+    pub mantle: Rgba,
+    /// Horizontal scrollbar thumb
+    pub surface2: Rgba,
+    /// The caret, and the first of several
+    pub cursor: Rgba,
+    /// The other carets, when tinting them is on
+    pub cursor_secondary: Rgba,
+    /// Carets are drawn at least this wide, whatever the setting says
+    pub cursor_min_width: f32,
+    /// Behind selected text while the editor has focus
+    pub selection: Rgba,
+    /// Behind selected text while it doesn't
+    pub selection_inactive: Rgba,
+    /// Behind selections a command couldn't convert
+    pub error: Rgba,
+    pub syntax: SyntaxColors,
+}
+
+impl Global for EditorTheme {}
+
+/// Colors for the kinds of token the highlighter picks out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SyntaxColors {
+    pub keyword: Rgba,
+    pub string: Rgba,
+    pub comment: Rgba,
+    pub number: Rgba,
+    pub function: Rgba,
+}
+
+impl SyntaxColors {
+    pub fn color(&self, kind: TokenKind) -> Rgba {
+        match kind {
+            TokenKind::Keyword => self.keyword,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Number => self.number,
+            TokenKind::Function => self.function,
+        }
+    }
+}
+
+/// `color` at a fixed alpha, for tinted backgrounds.
+pub fn with_alpha(color: Rgba, alpha: f32) -> Rgba {
+    Rgba { a: alpha, ..color }
+}
+
+/// `color` with its alpha scaled by the window opacity preference.
+pub fn translucent(color: Rgba, opacity: f32) -> Rgba {
+    Rgba {
+        a: color.a * opacity.clamp(0.2, 1.0),
+        ..color
+    }
+}
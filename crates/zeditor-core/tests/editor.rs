@@ -0,0 +1,208 @@
+use gpui::{ClipboardItem, TestAppContext, px, size};
+use zeditor_core::editor::*;
+use zeditor_core::test_support::EditorTestContext;
+
+// --- Multi-cursor editing ---
+
+#[gpui::test]
+fn enter_at_each_cursor(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("aˇbˇc");
+    cx.dispatch_action(Enter);
+    cx.assert_state("a\nˇb\nˇc");
+}
+
+#[gpui::test]
+fn backspace_at_each_cursor_on_one_line(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("abˇcdˇe");
+    cx.dispatch_action(Backspace);
+    cx.assert_state("aˇcˇe");
+}
+
+#[gpui::test]
+fn delete_at_each_cursor(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("ˇab\nˇcd");
+    cx.dispatch_action(Delete);
+    cx.assert_state("ˇb\nˇd");
+}
+
+#[gpui::test]
+fn typing_replaces_each_selection(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("«foo» «bar»\n«baz»");
+    cx.simulate_input("x");
+    cx.assert_state("xˇ xˇ\nxˇ");
+}
+
+#[gpui::test]
+fn paste_at_each_cursor(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("oneˇ twoˇ\nthreeˇ");
+    cx.update(|_, cx| cx.write_to_clipboard(ClipboardItem::new_string("!".into())));
+    cx.dispatch_action(Paste);
+    cx.assert_state("one!ˇ two!ˇ\nthree!ˇ");
+}
+
+#[gpui::test]
+fn multi_line_paste_at_each_cursor_on_one_line(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("aˇbˇc");
+    cx.update(|_, cx| cx.write_to_clipboard(ClipboardItem::new_string("x\ny".into())));
+    cx.dispatch_action(Paste);
+    cx.assert_state("ax\nyˇbx\nyˇc");
+}
+
+#[gpui::test]
+fn add_cursor_down_keeps_the_column(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("abˇc\nabc\na");
+    cx.dispatch_action(AddCursorDown);
+    cx.assert_state("abˇc\nabˇc\na");
+    cx.dispatch_action(AddCursorDown);
+    cx.assert_state("abˇc\nabˇc\naˇ");
+}
+
+#[gpui::test]
+fn cursors_that_meet_merge(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("ˇaˇbc");
+    cx.dispatch_action(Left);
+    cx.assert_state("ˇabc");
+    assert_eq!(cx.cursors().len(), 1);
+
+    cx.set_state("aˇbˇ");
+    cx.dispatch_action(Backspace);
+    cx.assert_state("ˇ");
+    assert_eq!(cx.cursors().len(), 1);
+
+    // Deleting the line break at the first cursor pulls the second onto it
+    cx.set_state("aˇ\nˇb");
+    cx.dispatch_action(Delete);
+    cx.assert_state("aˇ");
+    assert_eq!(cx.cursors().len(), 1);
+}
+
+#[gpui::test]
+fn select_left_at_each_cursor(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("aˇbˇc");
+    cx.dispatch_action(SelectLeft);
+    cx.assert_state("«ˇa»«ˇb»c");
+    cx.dispatch_action(DocumentStart);
+    cx.assert_state("ˇabc");
+    assert_eq!(cx.cursors().len(), 1);
+}
+
+#[gpui::test]
+fn caret_goes_past_text_that_joins_the_next_grapheme(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    // "e" and the combining acute after it make one grapheme
+    cx.set_state("ˇ\u{301}x");
+    cx.simulate_input("e");
+    cx.assert_state("e\u{301}ˇx");
+
+    cx.set_state("ˇ\u{301}x\nˇ\u{301}y");
+    cx.update(|_, cx| cx.write_to_clipboard(ClipboardItem::new_string("1\ne".into())));
+    cx.dispatch_action(Paste);
+    cx.assert_state("1\ne\u{301}ˇx\n1\ne\u{301}ˇy");
+}
+
+// --- Wrapped vertical motion ---
+
+const LONG_LINE: &str = "the quick brown fox jumps over the lazy dog and keeps on running";
+
+fn wrapped_context(cx: &mut TestAppContext) -> EditorTestContext {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_window_size(size(px(240.), px(400.)));
+    cx.set_word_wrap(true);
+    cx
+}
+
+#[gpui::test]
+fn wrapped_down_and_up_keep_the_column(cx: &mut TestAppContext) {
+    let mut cx = wrapped_context(cx);
+    cx.set_state(&format!("the quˇ{}\nab\n{}", &LONG_LINE[6..], LONG_LINE));
+    let rows = cx.editor.read_with(&cx.cx, |editor, _| editor.last_visual_line_counts.clone());
+    assert!(rows[0] > 1, "the first line should wrap at this width: {:?}", rows);
+
+    // Through a short line and back out at the same x
+    cx.dispatch_action(Down);
+    cx.assert_state(&format!("{}\nabˇ\n{}", LONG_LINE, LONG_LINE));
+    cx.dispatch_action(Down);
+    cx.assert_state(&format!("{}\nab\nthe quˇ{}", LONG_LINE, &LONG_LINE[6..]));
+    cx.dispatch_action(Up);
+    cx.dispatch_action(Up);
+    cx.assert_state(&format!("the quˇ{}\nab\n{}", &LONG_LINE[6..], LONG_LINE));
+}
+
+#[gpui::test]
+fn wrapped_select_down_extends_to_the_same_column(cx: &mut TestAppContext) {
+    let mut cx = wrapped_context(cx);
+    cx.set_state(&format!("thˇ{}\n{}", &LONG_LINE[2..], LONG_LINE));
+    cx.dispatch_action(SelectDown);
+    cx.assert_state(&format!("th«{}\nth»{}", &LONG_LINE[2..], &LONG_LINE[2..]));
+    cx.dispatch_action(SelectUp);
+    cx.assert_state(&format!("thˇ{}\n{}", &LONG_LINE[2..], LONG_LINE));
+}
+
+// --- IME ---
+
+#[gpui::test]
+fn ime_composition_over_multi_byte_text(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("日本ˇ");
+
+    cx.ime_compose("に", Some(1..1));
+    cx.assert_state("日本にˇ");
+    assert_eq!(cx.marked_text_range(), Some(2..3));
+
+    cx.ime_compose("にほ", Some(2..2));
+    cx.assert_state("日本にほˇ");
+    assert_eq!(cx.marked_text_range(), Some(2..4));
+
+    cx.ime_commit("日本");
+    cx.assert_state("日本日本ˇ");
+    assert_eq!(cx.marked_text_range(), None);
+}
+
+#[gpui::test]
+fn ime_selection_is_within_the_composition(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("abˇ");
+    cx.ime_compose("かな", Some(1..1));
+    cx.assert_state("abかˇな");
+    cx.ime_compose("かな", Some(0..2));
+    cx.assert_state("ab«かな»");
+    assert_eq!(cx.marked_text_range(), Some(2..4));
+    cx.ime_unmark();
+    assert_eq!(cx.marked_text_range(), None);
+    cx.assert_state("ab«かな»");
+}
+
+#[gpui::test]
+fn ime_composition_replaces_the_selection(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("x«漢字»y");
+    cx.ime_compose("か", None);
+    cx.assert_state("xかˇy");
+    assert_eq!(cx.marked_text_range(), Some(1..2));
+    cx.ime_commit("蚊");
+    cx.assert_state("x蚊ˇy");
+}
+
+#[gpui::test]
+fn ime_ranges_count_surrogate_pairs(cx: &mut TestAppContext) {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_state("xˇ");
+    cx.ime_compose("👍", Some(2..2));
+    cx.assert_state("x👍ˇ");
+    assert_eq!(cx.marked_text_range(), Some(1..3));
+    cx.ime_commit("👍🏽");
+    cx.assert_state("x👍🏽ˇ");
+
+    cx.set_state("a😀bˇ");
+    cx.replace_text_in_range(Some(1..3), "é");
+    cx.assert_state("aéˇb");
+}
@@ -0,0 +1,214 @@
+//! Clicks and vertical motion over lines mixing full-width CJK, emoji ZWJ
+//! sequences and VS16 sequences, which shape to glyphs whose edges don't
+//! line up with grapheme boundaries. Pixel positions come from the editor's
+//! own layout, so these hold whatever the test text system measures.
+
+use gpui::{Modifiers, Pixels, TestAppContext, point, px, size};
+use unicode_segmentation::UnicodeSegmentation;
+use zeditor_core::editor::*;
+use zeditor_core::test_support::{EditorTestContext, marked_text, parse_marked};
+
+/// CJK, a family (ZWJ sequence), and hearts and victory hands with VS16
+const MIXED: &str = "a漢字👨‍👩‍👧b❤️c✌️字";
+const CJK: &str = "漢字漢字漢字漢字";
+const EMOJI: &str = "a👨‍👩‍👧b❤️c✌️d👨‍👩‍👧";
+
+fn context(cx: &mut TestAppContext, word_wrap: bool) -> EditorTestContext {
+    let mut cx = EditorTestContext::new(cx);
+    cx.set_window_size(size(px(800.), px(400.)));
+    cx.set_word_wrap(word_wrap);
+    cx
+}
+
+/// Start and end of each grapheme of `text`, line breaks left out.
+fn graphemes(text: &str) -> Vec<(usize, usize)> {
+    text.grapheme_indices(true)
+        .filter(|(_, g)| *g != "\n")
+        .map(|(start, g)| (start, start + g.len()))
+        .collect()
+}
+
+/// Every caret position in `text`, ends of lines included.
+fn boundaries(text: &str) -> Vec<usize> {
+    let mut boundaries: Vec<usize> = text.grapheme_indices(true).map(|(start, _)| start).collect();
+    boundaries.extend(text.match_indices('\n').map(|(i, _)| i));
+    boundaries.push(text.len());
+    boundaries.sort();
+    boundaries.dedup();
+    boundaries
+}
+
+fn caret(cx: &mut EditorTestContext) -> usize {
+    let (_, cursors) = parse_marked(&cx.state());
+    assert_eq!(cursors.len(), 1);
+    let (anchor, head) = cursors[0];
+    assert_eq!(anchor, head, "expected a caret, got a selection");
+    head
+}
+
+// --- Clicks ---
+
+fn check_clicks_on_boundaries(cx: &mut TestAppContext, word_wrap: bool) {
+    let mut cx = context(cx, word_wrap);
+    let text = format!("{}\n{}", MIXED, EMOJI);
+    cx.set_state(&format!("ˇ{}", text));
+    for offset in boundaries(&text) {
+        cx.click(offset, Modifiers::default());
+        cx.assert_state(&marked_text(&text, &[(offset, offset)]));
+    }
+}
+
+#[gpui::test]
+fn clicks_on_boundaries(cx: &mut TestAppContext) {
+    check_clicks_on_boundaries(cx, false);
+}
+
+#[gpui::test]
+fn clicks_on_boundaries_wrapped(cx: &mut TestAppContext) {
+    check_clicks_on_boundaries(cx, true);
+}
+
+/// A click in the left quarter of a grapheme puts the caret before it, in
+/// the right quarter after it, however many glyphs it's drawn with.
+fn check_clicks_inside_graphemes(cx: &mut EditorTestContext, text: &str) {
+    cx.set_state(&format!("ˇ{}", text));
+    for (start, end) in graphemes(text) {
+        let left = cx.position_for_offset(start);
+        let right = cx.position_for_offset(end);
+        if left.y != right.y {
+            // Ends a wrapped row; its end is drawn on the next one
+            continue;
+        }
+        assert!(right.x > left.x, "{:?} has no width", &text[start..end]);
+        let quarter = (right.x - left.x) / 4.;
+
+        cx.click_at(point(left.x + quarter, left.y), Modifiers::default(), 1);
+        cx.assert_state(&marked_text(text, &[(start, start)]));
+        cx.click_at(point(right.x - quarter, left.y), Modifiers::default(), 1);
+        cx.assert_state(&marked_text(text, &[(end, end)]));
+    }
+}
+
+#[gpui::test]
+fn clicks_inside_graphemes(cx: &mut TestAppContext) {
+    let mut cx = context(cx, false);
+    check_clicks_inside_graphemes(&mut cx, &format!("{}\n{}", MIXED, EMOJI));
+}
+
+#[gpui::test]
+fn clicks_inside_graphemes_wrapped(cx: &mut TestAppContext) {
+    let mut cx = context(cx, true);
+    check_clicks_inside_graphemes(&mut cx, &format!("{}\n{}", MIXED, EMOJI));
+}
+
+#[gpui::test]
+fn clicks_inside_graphemes_on_wrapped_rows(cx: &mut TestAppContext) {
+    let mut cx = context(cx, true);
+    cx.set_window_size(size(px(160.), px(400.)));
+    let text = MIXED.repeat(4);
+    cx.set_state(&format!("ˇ{}", text));
+    let rows = cx.editor.read_with(&cx.cx, |editor, _| editor.last_visual_line_counts.clone());
+    assert!(rows[0] > 1, "the line should wrap at this width: {:?}", rows);
+    check_clicks_inside_graphemes(&mut cx, &text);
+}
+
+// --- Up and down ---
+
+/// From each caret position on `top`, Down lands on the boundary of
+/// `bottom` nearest in x, and Up goes back to where it started.
+fn check_vertical_motion(cx: &mut TestAppContext, word_wrap: bool, top: &str, bottom: &str) {
+    let mut cx = context(cx, word_wrap);
+    let text = format!("{}\n{}", top, bottom);
+    cx.set_state(&format!("ˇ{}", text));
+    let bottom_start = top.len() + 1;
+    let bottom_xs: Vec<(usize, Pixels)> = boundaries(bottom)
+        .into_iter()
+        .map(|b| (bottom_start + b, cx.position_for_offset(bottom_start + b).x))
+        .collect();
+
+    for start in boundaries(top) {
+        cx.set_state(&marked_text(&text, &[(start, start)]));
+        let x = cx.position_for_offset(start).x;
+        let nearest = bottom_xs
+            .iter()
+            .map(|(_, bx)| (*bx - x).abs())
+            .fold(Pixels::MAX, |nearest, d| if d < nearest { d } else { nearest });
+
+        cx.dispatch_action(Down);
+        let landed = caret(&mut cx);
+        let &(_, landed_x) = bottom_xs
+            .iter()
+            .find(|(b, _)| *b == landed)
+            .unwrap_or_else(|| panic!("Down from {} landed inside a grapheme, at {}", start, landed));
+        assert_eq!(
+            (landed_x - x).abs(),
+            nearest,
+            "Down from {} landed at {}, not the nearest boundary",
+            start,
+            landed
+        );
+
+        cx.dispatch_action(Up);
+        cx.assert_state(&marked_text(&text, &[(start, start)]));
+    }
+}
+
+#[gpui::test]
+fn down_from_cjk_to_emoji(cx: &mut TestAppContext) {
+    check_vertical_motion(cx, false, CJK, EMOJI);
+}
+
+#[gpui::test]
+fn down_from_cjk_to_emoji_wrapped(cx: &mut TestAppContext) {
+    check_vertical_motion(cx, true, CJK, EMOJI);
+}
+
+#[gpui::test]
+fn down_from_emoji_to_mixed(cx: &mut TestAppContext) {
+    check_vertical_motion(cx, false, EMOJI, MIXED);
+}
+
+#[gpui::test]
+fn down_from_emoji_to_mixed_wrapped(cx: &mut TestAppContext) {
+    check_vertical_motion(cx, true, EMOJI, MIXED);
+}
+
+// --- Selection rects ---
+
+/// A selection over each grapheme is painted from its start caret to its
+/// end caret; a shift-click inside a grapheme selects all of it.
+fn check_selection_rects(cx: &mut TestAppContext, word_wrap: bool) {
+    let mut cx = context(cx, word_wrap);
+    for (start, end) in graphemes(MIXED) {
+        cx.set_state(&marked_text(MIXED, &[(start, end)]));
+        let left = cx.position_for_offset(start);
+        let right = cx.position_for_offset(end);
+        let rects = cx.selection_rects();
+        assert_eq!(rects.len(), 1, "one rect for {:?}: {:?}", &MIXED[start..end], rects);
+        assert_eq!(rects[0].left(), left.x, "left edge of {:?}", &MIXED[start..end]);
+        assert_eq!(rects[0].right(), right.x, "right edge of {:?}", &MIXED[start..end]);
+        assert!(rects[0].top() < left.y && left.y < rects[0].bottom());
+    }
+
+    let family_start = MIXED.find('👨').unwrap();
+    let family_end = family_start + "👨‍👩‍👧".len();
+    cx.set_state(&format!("ˇ{}", MIXED));
+    let left = cx.position_for_offset(family_start);
+    let right = cx.position_for_offset(family_end);
+    cx.click_at(point(left.x + (right.x - left.x) * 0.75, left.y), Modifiers::shift(), 1);
+    cx.assert_state(&marked_text(MIXED, &[(0, family_end)]));
+    let rects = cx.selection_rects();
+    assert_eq!(rects.len(), 1);
+    assert_eq!(rects[0].left(), cx.position_for_offset(0).x);
+    assert_eq!(rects[0].right(), right.x);
+}
+
+#[gpui::test]
+fn selection_rects_follow_graphemes(cx: &mut TestAppContext) {
+    check_selection_rects(cx, false);
+}
+
+#[gpui::test]
+fn selection_rects_follow_graphemes_wrapped(cx: &mut TestAppContext) {
+    check_selection_rects(cx, true);
+}
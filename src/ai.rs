@@ -0,0 +1,147 @@
+//! Rewriting text through an OpenAI-compatible chat completions endpoint.
+//! Requests go through the system's `curl` on a thread of their own, with
+//! its options fed on stdin so the API key never shows in the process
+//! list. The reply is streamed back piece by piece.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::i18n::{t, t_args};
+use crate::preferences::{AiConfig, AiPrompt};
+
+/// Progress of a running transform.
+pub enum Update {
+    /// The next piece of the reply
+    Chunk(String),
+    Done,
+    Failed(String),
+}
+
+/// Start transforming `text` with `prompt` in the background. Dropping the
+/// receiver cancels the request.
+pub fn start(config: &AiConfig, prompt: &AiPrompt, text: &str) -> Receiver<Update> {
+    let (sender, receiver) = mpsc::channel();
+    let curl_config = curl_config(config, prompt, text);
+    std::thread::spawn(move || {
+        let update = match run(&curl_config, &sender) {
+            Ok(()) => Update::Done,
+            Err(message) => Update::Failed(message),
+        };
+        sender.send(update).ok();
+    });
+    receiver
+}
+
+/// curl options for the request, in its config file syntax.
+fn curl_config(config: &AiConfig, prompt: &AiPrompt, text: &str) -> String {
+    let body = serde_json::json!({
+        "model": config.model,
+        "stream": true,
+        "messages": [
+            {
+                "role": "system",
+                "content": format!(
+                    "{} Reply with the resulting text only, without commentary or quotes.",
+                    prompt.instruction
+                ),
+            },
+            { "role": "user", "content": text },
+        ],
+    });
+    let api_key = Some(config.api_key.clone())
+        .filter(|key| !key.is_empty())
+        .or_else(|| std::env::var("OPENAI_API_KEY").ok());
+
+    let quote = |value: &str| format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut lines = vec![
+        "silent".to_string(),
+        "show-error".to_string(),
+        "no-buffer".to_string(),
+        format!("url = {}", quote(&config.endpoint)),
+        format!("header = {}", quote("Content-Type: application/json")),
+        format!("data-binary = {}", quote(&body.to_string())),
+    ];
+    if let Some(key) = api_key {
+        lines.push(format!("header = {}", quote(&format!("Authorization: Bearer {}", key))));
+    }
+    lines.join("\n")
+}
+
+/// Run curl and pass each piece of the streamed reply to `sender`.
+fn run(curl_config: &str, sender: &Sender<Update>) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| t_args("ai.curl_failed", &[("error", &err)]))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(curl_config.as_bytes())
+            .map_err(|err| t_args("ai.curl_failed", &[("error", &err)]))?;
+    }
+
+    // Server-sent events: one `data: {json}` line per piece. Anything else
+    // is kept in case it's an error body.
+    let mut received = false;
+    let mut other = String::new();
+    let stdout = child.stdout.take().ok_or_else(|| t("ai.curl_no_output").to_string())?;
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            other.push_str(&line);
+            other.push('\n');
+            continue;
+        };
+        if data == "[DONE]" {
+            break;
+        }
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(data) else {
+            continue;
+        };
+        if let Some(message) = error_message(&event) {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(message);
+        }
+        let piece = event["choices"][0]["delta"]["content"].as_str().unwrap_or_default();
+        if piece.is_empty() {
+            continue;
+        }
+        received = true;
+        if sender.send(Update::Chunk(piece.to_string())).is_err() {
+            // Cancelled; reap it so it doesn't linger as a zombie
+            child.kill().ok();
+            child.wait().ok();
+            return Ok(());
+        }
+    }
+
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_string(&mut stderr).ok();
+    }
+    let status = child.wait().map_err(|err| err.to_string())?;
+    if received {
+        return Ok(());
+    }
+    if let Ok(body) = serde_json::from_str::<serde_json::Value>(&other)
+        && let Some(message) = error_message(&body)
+    {
+        return Err(message);
+    }
+    if !status.success() {
+        let message = stderr.trim().trim_start_matches("curl: ");
+        return Err(t_args("ai.request_failed", &[("error", &message)]));
+    }
+    Err(t("ai.empty_reply").to_string())
+}
+
+/// `error.message` of an API error response.
+fn error_message(body: &serde_json::Value) -> Option<String> {
+    let error = body.get("error")?;
+    let message = error["message"].as_str().or_else(|| error.as_str())?;
+    Some(message.to_string())
+}
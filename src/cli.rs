@@ -0,0 +1,173 @@
+//! Command-line options, and handing them to an instance that is already
+//! running. The first instance listens on a Unix socket in the data dir;
+//! later launches send it their request and exit instead of opening a
+//! second popup.
+
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+pub const USAGE: &str = "\
+Usage: zeditor [OPTIONS] [FILE | TEXT...]
+
+Options:
+  --text <TEXT>       Open the popup with TEXT
+  --file <PATH>       Open PATH for editing in its own buffer
+  --empty             Open the popup with an empty buffer
+  --submit-on-close   Submit the buffer when the popup is closed with Escape
+  -h, --help          Print this help
+
+A file opened with --file is saved back with Cmd+S or on submit, and it's
+an error if PATH isn't a readable file. A single existing FILE is opened
+the same way; otherwise any arguments, or piped stdin, become the text.";
+
+/// What a launch asked the popup to do.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CliRequest {
+    /// Replaces the buffer; None keeps what's there
+    pub text: Option<String>,
+    /// Absolute path of a file to open in its own buffer
+    pub file: Option<PathBuf>,
+    pub submit_on_close: bool,
+}
+
+/// Requests from this launch or forwarded by later ones, not yet shown
+static PENDING: Mutex<Option<CliRequest>> = Mutex::new(None);
+
+/// Parse the arguments after the program name. Returns None for a plain
+/// launch that asks for nothing.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<CliRequest>, String> {
+    let mut request = CliRequest::default();
+    let mut words = Vec::new();
+    let mut sources = 0;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--text" => {
+                request.text = Some(args.next().ok_or("--text needs a value")?);
+                sources += 1;
+            }
+            "--file" => {
+                let path = args.next().ok_or("--file needs a path")?;
+                let file = std::fs::canonicalize(&path)
+                    .map_err(|err| format!("Couldn't read {}: {}", path, err))?;
+                if !file.is_file() {
+                    return Err(format!("{} isn't a file", path));
+                }
+                request.file = Some(file);
+                sources += 1;
+            }
+            "--empty" => {
+                request.text = Some(String::new());
+                sources += 1;
+            }
+            "--submit-on-close" => request.submit_on_close = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            // Added by Finder on older macOS
+            arg if arg.starts_with("-psn_") => {}
+            arg if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => words.push(arg),
+        }
+    }
+
+    if let [word] = words.as_slice()
+        && let Ok(path) = std::fs::canonicalize(word)
+        && path.is_file()
+    {
+        // Absolute, as a running instance has its own working directory
+        request.file = Some(path);
+        sources += 1;
+    } else if !words.is_empty() {
+        request.text = Some(words.join(" "));
+        sources += 1;
+    }
+    if sources > 1 {
+        return Err("Give only one of --text, --file, --empty, FILE or TEXT".to_string());
+    }
+    if sources == 0 && !std::io::stdin().is_terminal() {
+        let mut text = String::new();
+        if std::io::stdin().read_to_string(&mut text).is_ok() && !text.is_empty() {
+            request.text = Some(text);
+        }
+    }
+
+    if request.text.is_none() && request.file.is_none() && !request.submit_on_close {
+        return Ok(None);
+    }
+    Ok(Some(request))
+}
+
+fn socket_path() -> PathBuf {
+    crate::preferences::data_dir().join("zeditor.sock")
+}
+
+/// Hand `request` to a running instance. Returns false if there is none.
+#[cfg(unix)]
+pub fn forward(request: &CliRequest) -> bool {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(socket_path()) else {
+        return false;
+    };
+    let Ok(json) = serde_json::to_vec(request) else {
+        return false;
+    };
+    stream.write_all(&json).is_ok()
+}
+
+/// Hand `request` to a running instance. Returns false if there is none.
+#[cfg(not(unix))]
+pub fn forward(_request: &CliRequest) -> bool {
+    false
+}
+
+/// Accept requests from later launches on a background thread.
+#[cfg(unix)]
+pub fn listen() {
+    use std::os::unix::net::UnixListener;
+
+    let path = socket_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    // Nobody answered in `forward`, so any socket file is left over from
+    // an instance that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    let Ok(listener) = UnixListener::bind(&path) else {
+        return;
+    };
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut json = String::new();
+            if stream.read_to_string(&mut json).is_err() {
+                continue;
+            }
+            if let Ok(request) = serde_json::from_str(&json) {
+                queue(request);
+            }
+        }
+    });
+}
+
+/// Accept requests from later launches on a background thread.
+#[cfg(not(unix))]
+pub fn listen() {}
+
+/// Show `request` on the next poll.
+pub fn queue(request: CliRequest) {
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some(request);
+    }
+}
+
+/// The request waiting to be shown, if any.
+pub fn take_request() -> Option<CliRequest> {
+    PENDING.lock().ok()?.take()
+}
@@ -0,0 +1,80 @@
+//! Bounded on-disk history of earlier drafts, newest first.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use zeditor_core::EditorSnapshot;
+
+use crate::preferences::data_dir;
+
+/// Oldest drafts are dropped beyond this many
+const MAX_DRAFTS: usize = 50;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Draft {
+    pub text: String,
+    /// Unix timestamp (seconds) when the draft was recorded
+    pub saved_at: i64,
+    /// Cursors, scroll position and modes the draft was left with, without
+    /// the text; None for drafts recorded before these were kept
+    #[serde(default)]
+    pub state: Option<EditorSnapshot>,
+}
+
+impl Draft {
+    /// First non-blank line, for the picker.
+    pub fn preview(&self) -> &str {
+        self.text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("")
+    }
+
+    pub fn saved_at_label(&self) -> String {
+        chrono::DateTime::from_timestamp(self.saved_at, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%b %-d %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn drafts_path() -> PathBuf {
+    data_dir().join("drafts.json")
+}
+
+pub fn load_drafts() -> Vec<Draft> {
+    std::fs::read_to_string(drafts_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Add the buffer in `snapshot` to the front of the history. Blank text
+/// and repeats of the newest draft are skipped.
+pub fn record_draft(snapshot: &EditorSnapshot) {
+    let text = snapshot.text.as_deref().unwrap_or_default();
+    if text.trim().is_empty() {
+        return;
+    }
+    let mut drafts = load_drafts();
+    if drafts.first().is_some_and(|d| d.text == text) {
+        return;
+    }
+    drafts.insert(
+        0,
+        Draft {
+            text: text.to_string(),
+            saved_at: chrono::Utc::now().timestamp(),
+            state: Some(snapshot.without_text()),
+        },
+    );
+    drafts.truncate(MAX_DRAFTS);
+
+    let path = drafts_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(&drafts) {
+        let _ = std::fs::write(&path, json);
+    }
+}
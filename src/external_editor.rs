@@ -0,0 +1,93 @@
+//! Editing a buffer in another app: the text goes to a temp file, which is
+//! opened with the configured editor and read back whenever it's saved.
+//! Temp files go in a directory of the user's own, readable by them only.
+
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+/// How often the temp file is checked for saves
+pub const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A temp file path not used by any other buffer or running instance, in
+/// the user's runtime or cache directory rather than the shared temp one.
+pub fn temp_path() -> io::Result<PathBuf> {
+    let dir = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no directory for temp files"))?
+        .join("zeditor");
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    let n = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+    Ok(dir.join(format!("zeditor-{}-{}.md", std::process::id(), n)))
+}
+
+/// Write `text` to the temp file at `path`, creating it readable and
+/// writable by the user only.
+pub fn write(path: &Path, text: &str) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(text.as_bytes())
+}
+
+/// Open `path` with `command` (e.g. `zed` or `code --wait`), or with
+/// `$VISUAL`/`$EDITOR` when it's empty, falling back to the system's
+/// default app for the file. The editor isn't waited for.
+pub fn open(path: &Path, command: &str) -> io::Result<()> {
+    let command = Some(command.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .or_else(|| std::env::var("VISUAL").ok().filter(|c| !c.trim().is_empty()))
+        .or_else(|| std::env::var("EDITOR").ok().filter(|c| !c.trim().is_empty()));
+    let mut process = match command {
+        Some(command) => {
+            let mut words = command.split_whitespace();
+            let program = words.next().unwrap_or_default();
+            let mut process = Command::new(program);
+            process.args(words);
+            process
+        }
+        None => system_opener(),
+    };
+    process
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+#[cfg(target_os = "macos")]
+fn system_opener() -> Command {
+    // -t picks the default text editor rather than whatever handles .md
+    let mut process = Command::new("open");
+    process.arg("-t");
+    process
+}
+
+#[cfg(target_os = "windows")]
+fn system_opener() -> Command {
+    let mut process = Command::new("cmd");
+    process.args(["/C", "start", ""]);
+    process
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn system_opener() -> Command {
+    Command::new("xdg-open")
+}
+
+/// When `path` was last written, or None if it's gone.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
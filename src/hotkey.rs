@@ -1,13 +1,22 @@
 // Allow unsafe operations in unsafe fns - this is an FFI-heavy module
 #![allow(unsafe_op_in_unsafe_fn)]
 
+use crate::hotkey_state::{Chord, ChordKey, ExcludedApps, MainPress, Pause};
+use crate::i18n::{t, t_args, t_count_args};
+use crate::platform::{MenuRequest, Placement, Platform};
+use crate::preferences::{
+    EscapeBehavior, HotkeyAction, HotkeyConfig, Preferences, ReduceMotion, ShowAnimation, SubmitBackend, Vibrancy,
+    WindowPosition,
+};
 use cocoa::base::{id, nil};
 use cocoa::foundation::NSString;
+use gpui::{AnyWindowHandle, App, AsyncApp, Window};
 use objc::runtime::Object;
 use objc::{class, msg_send, sel, sel_impl};
 use std::ffi::c_void;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use zeditor_core::EditorHost;
 
 // Carbon Event constants
 const K_VK_ESCAPE: u16 = 0x35; // Virtual key code for Escape
@@ -17,6 +26,46 @@ const K_EVENT_PARAM_DIRECT_OBJECT: u32 = 0x2D2D2D2D; // '----'
 const TYPE_EVENT_HOT_KEY_ID: u32 = 0x686B6964; // 'hkid'
 const NS_KEY_DOWN_MASK: u64 = 1 << 10; // NSEventMaskKeyDown
 
+// Hotkey id of the temporarily registered second key of a chord
+const CHORD_HOTKEY_ID: u32 = 99;
+// Privacy & Security → Accessibility in System Settings
+const ACCESSIBILITY_SETTINGS_URL: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility";
+// How often the permission and secure input are re-checked
+const SYSTEM_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Id of the throwaway registration that tests whether a combination is free
+const TRIAL_HOTKEY_ID: u32 = 98;
+
+// Carbon modifier bits
+const CMD: u32 = 1 << 8;
+const SHIFT: u32 = 1 << 9;
+const ALT: u32 = 1 << 11;
+const CTRL: u32 = 1 << 12;
+
+/// Shortcuts macOS or nearly every app claims, as (key code, modifiers,
+/// owner's text key). Carbon will happily register over most of them.
+const SYSTEM_SHORTCUTS: &[(u32, u32, &str)] = &[
+    (0x31, CMD, "shortcut_owner.spotlight"),
+    (0x31, CMD | ALT, "shortcut_owner.finder_search"),
+    (0x31, CTRL, "shortcut_owner.input_sources"),
+    (0x31, CTRL | CMD, "shortcut_owner.character_viewer"),
+    (0x30, CMD, "shortcut_owner.app_switcher"),
+    (0x35, CMD | ALT, "shortcut_owner.force_quit"),
+    (0x0C, CTRL | CMD, "shortcut_owner.lock_screen"),
+    (0x0C, CMD | SHIFT, "shortcut_owner.log_out"),
+    (0x14, CMD | SHIFT, "shortcut_owner.screenshots"),
+    (0x15, CMD | SHIFT, "shortcut_owner.screenshots"),
+    (0x17, CMD | SHIFT, "shortcut_owner.screenshots"),
+    (0x0C, CMD, "shortcut_owner.quit"),
+    (0x0D, CMD, "shortcut_owner.close_window"),
+    (0x04, CMD, "shortcut_owner.hide"),
+    (0x2E, CMD, "shortcut_owner.minimize"),
+    (0x07, CMD, "shortcut_owner.cut"),
+    (0x08, CMD, "shortcut_owner.copy"),
+    (0x09, CMD, "shortcut_owner.paste"),
+];
+
 // NSWindowAnimationBehavior values
 const NS_WINDOW_ANIMATION_BEHAVIOR_NONE: i64 = 2;
 
@@ -26,6 +75,22 @@ const NS_APPLICATION_DID_RESIGN_ACTIVE_NOTIFICATION: &str = "NSApplicationDidRes
 // NSStatusBar thickness (for menu bar)
 const NS_VARIABLE_STATUS_ITEM_LENGTH: f64 = -1.0;
 
+// Drafts listed in the status menu's "Recent" submenu, and how much of
+// each first line is shown
+const RECENT_DRAFTS: usize = 10;
+/// Apps offered by the paste target picker
+const RECENT_APP_LIMIT: usize = 8;
+const RECENT_PREVIEW_CHARS: usize = 40;
+
+// NSWindowSharingNone: the window server leaves the window out of captures.
+// NSWindowSharingReadOnly is the default, captured like any other window
+const NS_WINDOW_SHARING_NONE: u64 = 0;
+const NS_WINDOW_SHARING_READ_ONLY: u64 = 1;
+
+// Status item icons, bundled in assets/
+const STATUS_ICON: &str = "icons/status.png";
+const STATUS_ICON_ACTIVE: &str = "icons/status-active.png";
+
 // Carbon Event types
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -65,6 +130,7 @@ unsafe extern "C" {
         out_ref: *mut EventHotKeyRef,
     ) -> OSStatus;
     fn UnregisterEventHotKey(in_ref: EventHotKeyRef) -> OSStatus;
+    fn IsSecureEventInputEnabled() -> u8;
     fn InstallEventHandler(
         in_target: EventTargetRef,
         in_handler: EventHandlerProcPtr,
@@ -88,9 +154,31 @@ unsafe extern "C" {
 #[link(name = "ApplicationServices", kind = "framework")]
 unsafe extern "C" {
     fn AXIsProcessTrusted() -> bool;
+    fn CGSessionCopyCurrentDictionary() -> id;
     fn AXIsProcessTrustedWithOptions(options: id) -> bool;
+    fn AXUIElementCreateSystemWide() -> *mut c_void;
+    fn AXUIElementCopyAttributeValue(
+        element: *mut c_void,
+        attribute: id,
+        value: *mut *mut c_void,
+    ) -> i32;
+    fn AXUIElementCopyParameterizedAttributeValue(
+        element: *mut c_void,
+        attribute: id,
+        parameter: *mut c_void,
+        value: *mut *mut c_void,
+    ) -> i32;
+    fn AXUIElementIsAttributeSettable(element: *mut c_void, attribute: id, settable: *mut bool) -> i32;
+    fn AXUIElementSetAttributeValue(element: *mut c_void, attribute: id, value: *mut c_void) -> i32;
+    fn AXValueCreate(value_type: u32, value: *const c_void) -> *mut c_void;
+    fn AXValueGetValue(value: *mut c_void, value_type: u32, out: *mut c_void) -> bool;
+    fn CFRelease(cf: *mut c_void);
 }
 
+// AXValueType for CGRect and CFRange
+const K_AX_VALUE_CG_RECT_TYPE: u32 = 3;
+const K_AX_VALUE_CF_RANGE_TYPE: u32 = 4;
+
 // Global state
 static GLOBAL_STATUS_ITEM: AtomicUsize = AtomicUsize::new(0);
 static GLOBAL_WINDOW: AtomicUsize = AtomicUsize::new(0);
@@ -98,21 +186,340 @@ static GLOBAL_VISIBLE: AtomicUsize = AtomicUsize::new(0);
 static GLOBAL_PREVIOUS_APP: AtomicUsize = AtomicUsize::new(0);
 static GLOBAL_HOTKEY_REF: AtomicUsize = AtomicUsize::new(0);
 static GLOBAL_MENU: AtomicUsize = AtomicUsize::new(0);
+/// The "Recent" submenu, told apart from the scratchpads one in menuNeedsUpdate:
+static RECENT_MENU: AtomicUsize = AtomicUsize::new(0);
 static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
 static OPEN_PREFS_REQUESTED: AtomicBool = AtomicBool::new(false);
+static HISTORY_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set when the system switches between light and dark mode, or turns
+/// Increase Contrast on or off
+static APPEARANCE_CHANGED: AtomicBool = AtomicBool::new(false);
 static SHOW_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Set when this side hid the popup on its own (hotkey, Escape monitor or
+/// losing focus), for the GPUI side to keep the draft
+static HIDDEN: AtomicBool = AtomicBool::new(false);
+/// When set, the popup stays open after another app becomes active
+static PINNED: AtomicBool = AtomicBool::new(false);
+/// When set, the popup hides once another app becomes active
+static HIDE_ON_FOCUS_LOSS: AtomicBool = AtomicBool::new(true);
+/// When set, Escape hides the popup before GPUI sees it; otherwise the
+/// editor's own Escape handling runs
+static ESCAPE_HIDES: AtomicBool = AtomicBool::new(false);
+/// When set, the window slides down from the top of the screen on show
+static SLIDE_ANIMATION: AtomicBool = AtomicBool::new(false);
+/// The reduce motion preference, which can turn the slide off
+static REDUCE_MOTION: Mutex<ReduceMotion> = Mutex::new(ReduceMotion::System);
+/// When set, the caret of the frontmost app is looked up on each hotkey press
+static FOLLOW_CARET: AtomicBool = AtomicBool::new(false);
+/// When set, the status item switches to the filled icon while the popup is shown
+static ACTIVE_STATUS_ICON: AtomicBool = AtomicBool::new(false);
 
 static GLOBAL_ERROR: Mutex<Option<String>> = Mutex::new(None);
+/// Set while the error is the one left when the previous app never came
+/// back to the front, cleared by the next paste that goes through
+static PASTE_FAILED: AtomicBool = AtomicBool::new(false);
 static PENDING_CLIPBOARD: Mutex<Option<String>> = Mutex::new(None);
+/// Scratchpad picked from the status menu; empty for the default one
+static SCRATCHPAD_REQUESTED: Mutex<Option<String>> = Mutex::new(None);
+/// Registered action hotkeys; the hotkey id is the index plus 2 (1 is the
+/// main toggle hotkey)
+static ACTION_HOTKEYS: Mutex<Vec<(usize, HotkeyAction)>> = Mutex::new(Vec::new());
+/// The main hotkey's chord and whether its second key is awaited
+static CHORD: Mutex<Chord> = Mutex::new(Chord::new());
+static CHORD_HUD: AtomicUsize = AtomicUsize::new(0);
+/// Hotkeys Carbon wouldn't register, watched with NSEvent monitors instead:
+/// (key code, Carbon modifiers, hotkey id)
+static FALLBACK_HOTKEYS: Mutex<Vec<(u32, u32, u32)>> = Mutex::new(Vec::new());
+static FALLBACK_MONITOR_INSTALLED: AtomicBool = AtomicBool::new(false);
+/// Set alongside SHOW_REQUESTED by the hotkey that opened the window
+static SHOW_ACTION: Mutex<Option<HotkeyAction>> = Mutex::new(None);
+/// Keys of the main hotkey and the action hotkeys, kept to register them
+/// again after a pause
+static MAIN_HOTKEY: Mutex<Option<(u32, u32)>> = Mutex::new(None);
+static ACTION_HOTKEY_KEYS: Mutex<Vec<(HotkeyAction, u32, u32)>> = Mutex::new(Vec::new());
+/// Whether the hotkeys are unregistered so other apps can use them
+static PAUSE: Mutex<Pause> = Mutex::new(Pause::new());
+/// The blur view behind the popup's content, retained, if vibrancy is on,
+/// and the material it was made with
+static VIBRANCY_VIEW: AtomicUsize = AtomicUsize::new(0);
+static VIBRANCY: Mutex<Option<Vibrancy>> = Mutex::new(None);
+/// Whether the active theme is dark, to pick the blur's appearance
+static DARK_THEME: AtomicBool = AtomicBool::new(true);
+/// Last known Accessibility permission and secure input warning, and when
+/// they were checked
+static ACCESSIBILITY_TRUSTED: AtomicBool = AtomicBool::new(false);
+static SECURE_INPUT_WARNING: Mutex<Option<String>> = Mutex::new(None);
+static LAST_SYSTEM_CHECK: Mutex<Option<std::time::Instant>> = Mutex::new(None);
+/// Set while an "Edit in Zeditor" Services call waits for the popup; a
+/// submit then hands its text back to the call instead of pasting
+static SERVICE_PENDING: AtomicBool = AtomicBool::new(false);
+static SERVICE_RESULT: Mutex<Option<String>> = Mutex::new(None);
+/// Apps in which the hotkeys do nothing
+static EXCLUDED_APPS: Mutex<ExcludedApps> = Mutex::new(ExcludedApps::new());
+/// Process ids of other apps in the order they were last in front, most
+/// recent first
+static RECENT_APPS: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+/// When set, submits write into the focused field through the
+/// Accessibility API before falling back to Cmd+V
+static AX_INSERT: AtomicBool = AtomicBool::new(false);
+/// Text of a submit waiting to be inserted that hasn't touched the
+/// pasteboard yet
+static PENDING_INSERT: Mutex<Option<String>> = Mutex::new(None);
+/// Text of the most recent submit, for "Paste Last Submission"
+static LAST_SUBMISSION: Mutex<Option<String>> = Mutex::new(None);
+/// Delay before the clipboard is put back after a paste; None leaves the
+/// submitted text on it
+static RESTORE_CLIPBOARD_AFTER: Mutex<Option<std::time::Duration>> = Mutex::new(None);
+/// Retained NSArray of NSPasteboardItem copies taken before a submit, and
+/// the pasteboard change count once the submitted text was written
+static SAVED_PASTEBOARD: AtomicUsize = AtomicUsize::new(0);
+static SUBMIT_CHANGE_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Active buffer text, for the "get current draft" script command and
+/// VoiceOver
+static CURRENT_DRAFT: Mutex<String> = Mutex::new(String::new());
+/// Caret or selection in CURRENT_DRAFT as a UTF-16 (location, length)
+static DRAFT_SELECTION: Mutex<(usize, usize)> = Mutex::new((0, 0));
+/// The popup's GPUI view, once it answers accessibility queries
+static ACCESSIBLE_VIEW: AtomicUsize = AtomicUsize::new(0);
+/// Caret bounds (x, y, width, height) in top-left-origin screen coordinates,
+/// captured before the popup takes focus
+static CARET_RECT: Mutex<Option<(f64, f64, f64, f64)>> = Mutex::new(None);
+/// Window frame from before `set_expanded(true)`, restored on collapse
+static EXPANDED_FROM: Mutex<Option<cocoa::foundation::NSRect>> = Mutex::new(None);
 
 /// Check if the preferences window was requested from the menu.
 /// Atomically swaps the flag and returns the old value.
-pub fn is_prefs_requested() -> bool {
+fn is_prefs_requested() -> bool {
     OPEN_PREFS_REQUESTED.swap(false, Ordering::SeqCst)
 }
 
+/// Pin or unpin the popup. A pinned popup is not hidden on focus loss.
+fn set_pinned(pinned: bool) {
+    PINNED.store(pinned, Ordering::SeqCst);
+}
+
+/// Grow the window to 80% of its screen's visible area, centered, or put
+/// it back where it was. Not animated, like showing and hiding.
+///
+/// # Safety
+/// Must be called from the main thread.
+unsafe fn set_expanded(expanded: bool) {
+    use cocoa::foundation::{NSPoint, NSRect, NSSize};
+    const SCREEN_FRACTION: f64 = 0.8;
+
+    let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
+    if ns_window.is_null() {
+        return;
+    }
+    let Ok(mut expanded_from) = EXPANDED_FROM.lock() else {
+        return;
+    };
+    let frame = if expanded {
+        let mut screen: id = msg_send![ns_window, screen];
+        if screen.is_null() {
+            screen = msg_send![class!(NSScreen), mainScreen];
+        }
+        if screen.is_null() {
+            return;
+        }
+        let visible: NSRect = msg_send![screen, visibleFrame];
+        let current: NSRect = msg_send![ns_window, frame];
+        *expanded_from = Some(current);
+        let size = NSSize::new(
+            visible.size.width * SCREEN_FRACTION,
+            visible.size.height * SCREEN_FRACTION,
+        );
+        NSRect::new(
+            NSPoint::new(
+                visible.origin.x + (visible.size.width - size.width) / 2.,
+                visible.origin.y + (visible.size.height - size.height) / 2.,
+            ),
+            size,
+        )
+    } else {
+        let Some(previous) = expanded_from.take() else {
+            return;
+        };
+        previous
+    };
+    let _: () = msg_send![ns_window, setFrame: frame display: true animate: false];
+}
+
+/// Put an NSVisualEffectView behind GPUI's content view so the desktop
+/// shows through blurred, replacing the one from an earlier call with a
+/// different material; None just removes it. It sits in the frame view rather than inside the
+/// content view, where it would cover the Metal layer.
+///
+/// # Safety
+/// `ns_window` must be a valid NSWindow pointer; call from the main thread.
+unsafe fn install_vibrancy(ns_window: *mut Object, vibrancy: Option<Vibrancy>) {
+    if let Ok(mut installed) = VIBRANCY.lock() {
+        if *installed == vibrancy {
+            return;
+        }
+        *installed = vibrancy;
+    }
+    let old = VIBRANCY_VIEW.swap(0, Ordering::SeqCst) as id;
+    if !old.is_null() {
+        let _: () = msg_send![old, removeFromSuperview];
+        let _: () = msg_send![old, release];
+    }
+    let Some(vibrancy) = vibrancy else {
+        return;
+    };
+    // NSVisualEffectMaterial values
+    let material: i64 = match vibrancy {
+        Vibrancy::Menu => 5,
+        Vibrancy::Popover => 6,
+        Vibrancy::Sidebar => 7,
+        Vibrancy::HudWindow => 13,
+        Vibrancy::UnderWindowBackground => 21,
+    };
+    const NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW: i64 = 0;
+    const NS_VISUAL_EFFECT_STATE_ACTIVE: i64 = 1;
+    const NS_VIEW_WIDTH_SIZABLE: u64 = 1 << 1;
+    const NS_VIEW_HEIGHT_SIZABLE: u64 = 1 << 4;
+    const NS_WINDOW_BELOW: i64 = -1;
+
+    let content_view: id = msg_send![ns_window, contentView];
+    if content_view.is_null() {
+        return;
+    }
+    let frame_view: id = msg_send![content_view, superview];
+    if frame_view.is_null() {
+        return;
+    }
+    let bounds: cocoa::foundation::NSRect = msg_send![frame_view, bounds];
+    let effect: id = msg_send![class!(NSVisualEffectView), alloc];
+    let effect: id = msg_send![effect, initWithFrame: bounds];
+    let _: () = msg_send![effect, setMaterial: material];
+    let _: () = msg_send![effect, setBlendingMode: NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW];
+    let _: () = msg_send![effect, setState: NS_VISUAL_EFFECT_STATE_ACTIVE];
+    let _: () = msg_send![effect, setAutoresizingMask: NS_VIEW_WIDTH_SIZABLE | NS_VIEW_HEIGHT_SIZABLE];
+    let _: () = msg_send![effect, setAppearance: vibrancy_appearance()];
+    let _: () = msg_send![
+        frame_view,
+        addSubview: effect
+        positioned: NS_WINDOW_BELOW
+        relativeTo: content_view
+    ];
+    // Keep our reference so a later call can take it out again
+    VIBRANCY_VIEW.store(effect as usize, Ordering::SeqCst);
+}
+
+/// The blur's appearance for the active theme: the other one would wash
+/// out its text.
+///
+/// # Safety
+/// Call from the main thread.
+unsafe fn vibrancy_appearance() -> id {
+    let name = if DARK_THEME.load(Ordering::SeqCst) {
+        "NSAppearanceNameVibrantDark"
+    } else {
+        "NSAppearanceNameVibrantLight"
+    };
+    let name = NSString::alloc(nil).init_str(name);
+    let appearance: id = msg_send![class!(NSAppearance), appearanceNamed: name];
+    let _: () = msg_send![name, release];
+    appearance
+}
+
+/// Make the main hotkey a chord prefix if `config` has a second key, or
+/// a plain combo.
+fn set_main_chord(config: &HotkeyConfig) {
+    if let Ok(mut chord) = CHORD.lock() {
+        chord.set_second(config.chord.as_ref().map(|second| ChordKey {
+            key_code: second.key_code,
+            modifiers: second.modifiers,
+            prefix: config.display_string.clone(),
+        }));
+    }
+}
+
+fn hotkeys_paused() -> bool {
+    PAUSE.lock().is_ok_and(|pause| pause.is_paused())
+}
+
+/// Slide the popup down from the top edge on show and back up on hide.
+fn set_slide_animation(slide: bool) {
+    SLIDE_ANIMATION.store(slide, Ordering::SeqCst);
+}
+
+/// Whether showing and hiding slide right now: the animation is on and
+/// motion isn't reduced.
+fn slides() -> bool {
+    if !SLIDE_ANIMATION.load(Ordering::SeqCst) {
+        return false;
+    }
+    let reduce_motion = REDUCE_MOTION.lock().map(|r| *r).unwrap_or_default();
+    match reduce_motion {
+        ReduceMotion::System => !MacPlatform.reduce_motion(),
+        ReduceMotion::On => false,
+        ReduceMotion::Off => true,
+    }
+}
+
+/// Frame of the window hanging from the top of its screen's visible area,
+/// horizontally centered, and the same frame pushed just above the screen.
+unsafe fn slide_frames(ns_window: *mut Object) -> Option<(cocoa::foundation::NSRect, cocoa::foundation::NSRect)> {
+    use cocoa::foundation::{NSPoint, NSRect};
+
+    let mut screen: id = msg_send![ns_window, screen];
+    if screen.is_null() {
+        screen = msg_send![class!(NSScreen), mainScreen];
+    }
+    if screen.is_null() {
+        return None;
+    }
+    let visible: NSRect = msg_send![screen, visibleFrame];
+    let frame: NSRect = msg_send![ns_window, frame];
+    let x = visible.origin.x + (visible.size.width - frame.size.width) / 2.;
+    let top = visible.origin.y + visible.size.height;
+    let shown = NSRect::new(NSPoint::new(x, top - frame.size.height), frame.size);
+    let hidden = NSRect::new(NSPoint::new(x, top), frame.size);
+    Some((shown, hidden))
+}
+
+unsafe fn slide_in(ns_window: *mut Object) {
+    let Some((shown, hidden)) = slide_frames(ns_window) else {
+        let _: () = msg_send![ns_window, makeKeyAndOrderFront: nil];
+        return;
+    };
+    let _: () = msg_send![ns_window, setFrame: hidden display: false animate: false];
+    let _: () = msg_send![ns_window, makeKeyAndOrderFront: nil];
+    let _: () = msg_send![ns_window, orderFrontRegardless];
+    let _: () = msg_send![ns_window, setFrame: shown display: true animate: true];
+}
+
+/// Hide the window, sliding it up first when the slide animation is on.
+/// The frame is put back afterwards so the remembered position stays on
+/// screen.
+///
+/// # Safety
+/// `ns_window` must be a valid NSWindow pointer; call from the main thread.
+unsafe fn order_out(ns_window: *mut Object) {
+    update_status_icon(false);
+    // Hiding ends a pending "Edit in Zeditor"; unless a submit left a
+    // result, the selection is left alone
+    SERVICE_PENDING.store(false, Ordering::SeqCst);
+    if slides() {
+        if let Some((shown, hidden)) = slide_frames(ns_window) {
+            let _: () = msg_send![ns_window, setFrame: hidden display: true animate: true];
+            let _: () = msg_send![ns_window, orderOut: nil];
+            let _: () = msg_send![ns_window, setFrame: shown display: false animate: false];
+            return;
+        }
+    }
+    let _: () = msg_send![ns_window, orderOut: nil];
+}
+
+/// Look up the frontmost app's caret whenever the hotkey opens the popup.
+fn set_follow_caret(follow: bool) {
+    FOLLOW_CARET.store(follow, Ordering::SeqCst);
+}
+
 /// Get the current error message, if any.
-pub fn get_error() -> Option<String> {
+fn get_error() -> Option<String> {
     GLOBAL_ERROR.lock().ok().and_then(|g| g.clone())
 }
 
@@ -120,24 +527,38 @@ fn set_error(err: Option<String>) {
     if let Ok(mut g) = GLOBAL_ERROR.lock() {
         *g = err;
     }
+    PASTE_FAILED.store(false, Ordering::SeqCst);
     unsafe { update_menu_error() };
 }
 
 /// Take the pre-fetched clipboard text (if any). Returns None if no text was pre-fetched.
 /// This is used by the editor to avoid the slow GPUI clipboard read.
-pub fn take_pending_clipboard() -> Option<String> {
+fn take_pending_clipboard() -> Option<String> {
     PENDING_CLIPBOARD.lock().ok().and_then(|mut g| g.take())
 }
 
+/// Take the scratchpad picked from the status menu, if any. The inner value
+/// is None for the default scratchpad.
+fn take_scratchpad_request() -> Option<Option<String>> {
+    let name = SCRATCHPAD_REQUESTED.lock().ok()?.take()?;
+    Some(if name.is_empty() { None } else { Some(name) })
+}
+
 /// Check if a show-window was requested (hotkey pressed while hidden).
 /// Atomically swaps the flag and returns the old value.
-pub fn is_show_requested() -> bool {
+fn is_show_requested() -> bool {
     SHOW_REQUESTED.swap(false, Ordering::SeqCst)
 }
 
+/// Take the action of the hotkey behind the pending show, if a hotkey
+/// caused it.
+fn take_show_action() -> Option<HotkeyAction> {
+    SHOW_ACTION.lock().ok()?.take()
+}
+
 /// Set initial text and request the window to show.
-/// Used for CLI argument text.
-pub fn set_initial_text(text: String) {
+/// Used for dropped, scripted and Services text.
+fn set_initial_text(text: String) {
     if let Ok(mut pending) = PENDING_CLIPBOARD.lock() {
         *pending = Some(text);
     }
@@ -148,7 +569,7 @@ pub fn set_initial_text(text: String) {
 ///
 /// # Safety
 /// Must be called from the main thread.
-pub unsafe fn show_window_now() {
+unsafe fn show_window_now(placement: Placement) {
     let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
     let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
     if ns_window.is_null() || visible_ptr.is_null() {
@@ -158,7 +579,28 @@ pub unsafe fn show_window_now() {
     let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
     let _: () = msg_send![ns_app, activateIgnoringOtherApps: true];
 
-    let _: () = msg_send![ns_window, center];
+    update_status_icon(true);
+    if slides() {
+        slide_in(ns_window);
+        (*visible_ptr).store(true, Ordering::SeqCst);
+        return;
+    }
+
+    match placement {
+        Placement::Keep => {}
+        Placement::Center => {
+            let _: () = msg_send![ns_window, center];
+        }
+        Placement::Caret => {
+            let caret = CARET_RECT.lock().ok().and_then(|mut g| g.take());
+            match caret {
+                Some(caret) => place_below_caret(ns_window, caret),
+                None => {
+                    let _: () = msg_send![ns_window, center];
+                }
+            }
+        }
+    }
     let _: () = msg_send![ns_window, makeKeyAndOrderFront: nil];
     let _: () = msg_send![ns_window, orderFrontRegardless];
 
@@ -183,9 +625,10 @@ fn version_string() -> String {
 ///
 /// # Safety
 /// `ns_window` must be a valid NSWindow/NSPanel pointer that outlives the monitors.
-pub unsafe fn register_hotkey(ns_window: *mut Object, key_code: u32, modifiers: u32) {
+unsafe fn register_hotkey(ns_window: *mut Object, key_code: u32, modifiers: u32) {
     // Check if we have accessibility permissions, prompt if not
     let trusted = AXIsProcessTrusted();
+    ACCESSIBILITY_TRUSTED.store(trusted, Ordering::SeqCst);
     if !trusted {
         let key: id = NSString::alloc(nil).init_str("AXTrustedCheckOptionPrompt");
         let yes_num: id = msg_send![class!(NSNumber), numberWithBool: true];
@@ -202,6 +645,12 @@ pub unsafe fn register_hotkey(ns_window: *mut Object, key_code: u32, modifiers:
     // Create status bar item with menu
     create_status_item(ns_window, visible.clone());
 
+    // "Edit in Zeditor" in other apps' Services menus
+    register_services_provider();
+
+    // Commands from Zeditor.sdef, for AppleScript and Shortcuts
+    register_script_commands();
+
     // Register Carbon global hotkey
     register_carbon_hotkey(ns_window, visible.clone(), key_code, modifiers);
 
@@ -210,6 +659,12 @@ pub unsafe fn register_hotkey(ns_window: *mut Object, key_code: u32, modifiers:
 
     // Register for app deactivation to auto-hide window
     register_deactivation_observer(ns_window, visible);
+
+    // Remember which apps were in front, for the paste target picker
+    register_activation_tracker();
+
+    // Follow light and dark mode while the popup is hidden
+    register_appearance_observer();
 }
 
 /// Re-registers the global hotkey with new key code and modifiers.
@@ -217,7 +672,14 @@ pub unsafe fn register_hotkey(ns_window: *mut Object, key_code: u32, modifiers:
 ///
 /// # Safety
 /// Must be called from the main thread after `register_hotkey` has been called.
-pub unsafe fn re_register_hotkey(key_code: u32, modifiers: u32) {
+unsafe fn re_register_hotkey(key_code: u32, modifiers: u32) {
+    if let Ok(mut main) = MAIN_HOTKEY.lock() {
+        *main = Some((key_code, modifiers));
+    }
+    if hotkeys_paused() {
+        return;
+    }
+
     // Unregister old hotkey
     let old_ref = GLOBAL_HOTKEY_REF.swap(0, Ordering::SeqCst) as EventHotKeyRef;
     if !old_ref.is_null() {
@@ -241,16 +703,61 @@ pub unsafe fn re_register_hotkey(key_code: u32, modifiers: u32) {
     );
 
     if status != 0 {
-        set_error(Some(format!(
-            "Hotkey registration failed (status: {})",
-            status
-        )));
+        use_fallback_hotkey(1, key_code, modifiers, status);
     } else {
         GLOBAL_HOTKEY_REF.store(hotkey_ref as usize, Ordering::SeqCst);
+        remove_fallback_hotkey(1);
         set_error(None);
     }
 }
 
+/// Replaces the extra action hotkeys, given as (action, key code,
+/// modifiers). Must be called after `register_hotkey`, which installs the
+/// shared event handler.
+///
+/// # Safety
+/// Must be called from the main thread.
+unsafe fn register_action_hotkeys(hotkeys: &[(HotkeyAction, u32, u32)]) {
+    let Ok(mut registered) = ACTION_HOTKEYS.lock() else {
+        return;
+    };
+    for (old_ref, _) in registered.drain(..) {
+        if old_ref != 0 {
+            UnregisterEventHotKey(old_ref as EventHotKeyRef);
+        }
+    }
+    if let Ok(mut fallbacks) = FALLBACK_HOTKEYS.lock() {
+        fallbacks.retain(|&(_, _, id)| id == 1);
+    }
+    if let Ok(mut keys) = ACTION_HOTKEY_KEYS.lock() {
+        *keys = hotkeys.to_vec();
+    }
+    if hotkeys_paused() {
+        return;
+    }
+
+    for (index, &(action, key_code, modifiers)) in hotkeys.iter().enumerate() {
+        let hotkey_id = EventHotKeyID {
+            signature: 0x5A454449, // 'ZEDI'
+            id: index as u32 + 2,
+        };
+        let mut hotkey_ref: EventHotKeyRef = std::ptr::null_mut();
+        let status = RegisterEventHotKey(
+            key_code,
+            modifiers,
+            hotkey_id,
+            GetEventDispatcherTarget(),
+            0,
+            &mut hotkey_ref,
+        );
+        if status != 0 {
+            use_fallback_hotkey(index as u32 + 2, key_code, modifiers, status);
+        }
+        // Keep the slot even on failure so ids stay in step with indices
+        registered.push((hotkey_ref as usize, action));
+    }
+}
+
 unsafe fn register_carbon_hotkey(
     ns_window: *mut Object,
     visible: Arc<AtomicBool>,
@@ -260,6 +767,9 @@ unsafe fn register_carbon_hotkey(
     // Store in globals for the callback
     GLOBAL_WINDOW.store(ns_window as usize, Ordering::SeqCst);
     GLOBAL_VISIBLE.store(Box::into_raw(Box::new(visible)) as usize, Ordering::SeqCst);
+    if let Ok(mut main) = MAIN_HOTKEY.lock() {
+        *main = Some((key_code, modifiers));
+    }
 
     let hotkey_id = EventHotKeyID {
         signature: 0x5A454449, // 'ZEDI'
@@ -280,12 +790,10 @@ unsafe fn register_carbon_hotkey(
     );
 
     if status != 0 {
-        set_error(Some(format!(
-            "Hotkey registration failed (status: {})",
-            status
-        )));
+        use_fallback_hotkey(1, key_code, modifiers, status);
     } else {
         GLOBAL_HOTKEY_REF.store(hotkey_ref as usize, Ordering::SeqCst);
+        remove_fallback_hotkey(1);
     }
 
     // Install the event handler (only once)
@@ -317,11 +825,15 @@ unsafe fn register_escape_monitor(ns_window: *mut Object, visible: Arc<AtomicBoo
     let handler = block::ConcreteBlock::new(move |event: id| -> id {
         unsafe {
             let key_code: u16 = msg_send![event, keyCode];
-            if key_code == K_VK_ESCAPE && visible.load(Ordering::SeqCst) {
+            if key_code == K_VK_ESCAPE
+                && ESCAPE_HIDES.load(Ordering::SeqCst)
+                && visible.load(Ordering::SeqCst)
+            {
                 let ns_window = ns_window as *mut Object;
                 let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
                 if !visible_ptr.is_null() {
                     hide_window(ns_window, &*visible_ptr);
+                    HIDDEN.store(true, Ordering::SeqCst);
                 }
                 return nil;
             }
@@ -358,100 +870,996 @@ extern "C" fn hotkey_handler(
             &mut hotkey_id as *mut EventHotKeyID as *mut c_void,
         );
 
-        if status == 0 && hotkey_id.id == 1 {
-            let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
-            let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
-            if !visible_ptr.is_null() && !ns_window.is_null() {
-                toggle_window(ns_window, &*visible_ptr);
-            }
+        if status == 0 {
+            dispatch_hotkey(hotkey_id.id);
         }
     }
     0
 }
 
-unsafe fn register_deactivation_observer(ns_window: *mut Object, visible: Arc<AtomicBool>) {
-    let ns_window = ns_window as usize;
-
-    let handler = block::ConcreteBlock::new(move |_notification: id| {
-        if visible.load(Ordering::SeqCst) {
-            unsafe {
-                let ns_window = ns_window as *mut Object;
-                let _: () = msg_send![ns_window, orderOut: nil];
+/// Run whatever the hotkey with this id is bound to, whether it came
+/// through Carbon or the fallback event monitor.
+unsafe fn dispatch_hotkey(id: u32) {
+    let excluded = EXCLUDED_APPS
+        .lock()
+        .is_ok_and(|excluded| excluded.blocks(is_visible(), || frontmost_bundle_identifier()));
+    if excluded {
+        return;
+    }
+    match id {
+        CHORD_HOTKEY_ID => {
+            disarm_chord();
+            trigger_hotkey(HotkeyAction::Toggle);
+        }
+        1 => {
+            let press = CHORD.lock().map(|chord| chord.press_main(is_visible()));
+            match press {
+                Ok(MainPress::Toggle) => trigger_hotkey(HotkeyAction::Toggle),
+                Ok(MainPress::Arm(second)) => arm_chord(second),
+                Ok(MainPress::Disarm) => disarm_chord(),
+                Err(_) => {}
             }
-            visible.store(false, Ordering::SeqCst);
         }
-    });
-    let handler = handler.copy();
+        _ => {
+            let action = ACTION_HOTKEYS
+                .lock()
+                .ok()
+                .and_then(|g| g.get((id as usize).wrapping_sub(2)).map(|&(_, action)| action));
+            if let Some(action) = action {
+                trigger_hotkey(action);
+            }
+        }
+    }
+}
 
-    let notification_center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
-    let notification_name =
-        NSString::alloc(nil).init_str(NS_APPLICATION_DID_RESIGN_ACTIVE_NOTIFICATION);
+/// Carbon refused the combo (usually because the system or another app
+/// owns it), so watch key events instead. Global monitors need the
+/// accessibility permission requested in `register_hotkey`.
+unsafe fn use_fallback_hotkey(id: u32, key_code: u32, modifiers: u32, status: OSStatus) {
+    if !AXIsProcessTrusted() {
+        set_error(Some(t_args(
+            "hotkey.registration_failed_status",
+            &[("status", &status), ("hint", &t("paste.accessibility_hint"))],
+        )));
+    }
+    if let Ok(mut fallbacks) = FALLBACK_HOTKEYS.lock() {
+        fallbacks.retain(|&(_, _, existing)| existing != id);
+        fallbacks.push((key_code, modifiers, id));
+    }
+    if FALLBACK_MONITOR_INSTALLED.swap(true, Ordering::SeqCst) {
+        return;
+    }
 
+    // Other apps' key events
+    let global = block::ConcreteBlock::new(move |event: id| unsafe {
+        if let Some(hotkey_id) = fallback_hotkey_for(event) {
+            dispatch_hotkey(hotkey_id);
+        }
+    });
+    let global = global.copy();
     let _: id = msg_send![
-        notification_center,
-        addObserverForName: notification_name
-        object: nil
-        queue: nil
-        usingBlock: &*handler
+        class!(NSEvent),
+        addGlobalMonitorForEventsMatchingMask: NS_KEY_DOWN_MASK
+        handler: &*global
     ];
+    std::mem::forget(global);
 
-    std::mem::forget(handler);
+    // Ours, while the popup has focus
+    let local = block::ConcreteBlock::new(move |event: id| -> id {
+        unsafe {
+            match fallback_hotkey_for(event) {
+                Some(hotkey_id) => {
+                    dispatch_hotkey(hotkey_id);
+                    nil
+                }
+                None => event,
+            }
+        }
+    });
+    let local = local.copy();
+    let _: id = msg_send![
+        class!(NSEvent),
+        addLocalMonitorForEventsMatchingMask: NS_KEY_DOWN_MASK
+        handler: &*local
+    ];
+    std::mem::forget(local);
 }
 
-unsafe fn create_status_item(ns_window: *mut Object, visible: Arc<AtomicBool>) {
-    let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
-    let status_item: id =
-        msg_send![status_bar, statusItemWithLength: NS_VARIABLE_STATUS_ITEM_LENGTH];
+fn remove_fallback_hotkey(id: u32) {
+    if let Ok(mut fallbacks) = FALLBACK_HOTKEYS.lock() {
+        fallbacks.retain(|&(_, _, existing)| existing != id);
+    }
+}
 
-    let button: id = msg_send![status_item, button];
-    let title = NSString::alloc(nil).init_str("Z");
-    let _: () = msg_send![button, setTitle: title];
+/// Id of the fallback hotkey matching a key-down NSEvent, if any.
+unsafe fn fallback_hotkey_for(event: id) -> Option<u32> {
+    // NSEventModifierFlags to Carbon modifier bits
+    const FLAGS: [(u64, u32); 4] = [
+        (1 << 17, 1 << 9),  // shift
+        (1 << 18, 1 << 12), // control
+        (1 << 19, 1 << 11), // option
+        (1 << 20, 1 << 8),  // command
+    ];
+    let key_code: u16 = msg_send![event, keyCode];
+    let flags: u64 = msg_send![event, modifierFlags];
+    let modifiers = FLAGS
+        .iter()
+        .filter(|&&(flag, _)| flags & flag != 0)
+        .fold(0, |mods, &(_, carbon)| mods | carbon);
+    let fallbacks = FALLBACK_HOTKEYS.lock().ok()?;
+    fallbacks
+        .iter()
+        .find(|&&(code, mods, _)| code == key_code as u32 && mods == modifiers)
+        .map(|&(_, _, id)| id)
+}
 
-    // Retain the status item to prevent deallocation
-    let _: id = msg_send![status_item, retain];
+/// Bundle identifier of the frontmost app, to check against the exclusion
+/// list.
+unsafe fn frontmost_bundle_identifier() -> Option<String> {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let app: id = msg_send![workspace, frontmostApplication];
+    bundle_identifier(app)
+}
 
-    let ns_window = ns_window as usize;
-    GLOBAL_STATUS_ITEM.store(status_item as usize, Ordering::SeqCst);
-    GLOBAL_WINDOW.store(ns_window, Ordering::SeqCst);
-    GLOBAL_VISIBLE.store(Box::into_raw(Box::new(visible)) as usize, Ordering::SeqCst);
+unsafe fn bundle_identifier(app: id) -> Option<String> {
+    if app.is_null() {
+        return None;
+    }
+    let bundle_id: id = msg_send![app, bundleIdentifier];
+    if bundle_id.is_null() {
+        return None;
+    }
+    let utf8: *const std::os::raw::c_char = msg_send![bundle_id, UTF8String];
+    Some(std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned())
+}
 
-    // Set up the NSMenu
-    setup_status_menu(status_item);
+/// Regular (Dock) apps other than Zeditor, as (name, bundle identifier),
+/// sorted by name.
+unsafe fn running_apps() -> Vec<(String, String)> {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let apps: id = msg_send![workspace, runningApplications];
+    let current: id = msg_send![class!(NSRunningApplication), currentApplication];
+    let count: u64 = msg_send![apps, count];
+    let mut result = Vec::new();
+    for i in 0..count {
+        let app: id = msg_send![apps, objectAtIndex: i];
+        // NSApplicationActivationPolicyRegular
+        let policy: i64 = msg_send![app, activationPolicy];
+        let is_current: bool = msg_send![app, isEqual: current];
+        if policy != 0 || is_current {
+            continue;
+        }
+        let Some(bundle_id) = bundle_identifier(app) else {
+            continue;
+        };
+        let name: id = msg_send![app, localizedName];
+        let name = if name.is_null() {
+            bundle_id.clone()
+        } else {
+            let utf8: *const std::os::raw::c_char = msg_send![name, UTF8String];
+            std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned()
+        };
+        result.push((name, bundle_id));
+    }
+    result.sort();
+    result.dedup_by(|a, b| a.1 == b.1);
+    result
+}
 
-    // Ensure visible
-    let _: () = msg_send![status_item, setVisible: true];
+fn is_visible() -> bool {
+    let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
+    !visible_ptr.is_null() && unsafe { (*visible_ptr).load(Ordering::SeqCst) }
 }
 
-unsafe fn setup_status_menu(status_item: id) {
-    use objc::declare::ClassDecl;
-    use objc::runtime::{Class, Sel};
+/// Show the popup for `action`, or hide it if it's already shown.
+unsafe fn trigger_hotkey(action: HotkeyAction) {
+    let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
+    let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
+    if visible_ptr.is_null() || ns_window.is_null() {
+        return;
+    }
+    if action == HotkeyAction::PasteLast {
+        paste_last_submission();
+        return;
+    }
+    if !(*visible_ptr).load(Ordering::SeqCst) {
+        if let Ok(mut g) = SHOW_ACTION.lock() {
+            *g = Some(action);
+        }
+        if action == HotkeyAction::ShowClipboard {
+            if let Some(text) = pasteboard_text() {
+                if let Ok(mut pending) = PENDING_CLIPBOARD.lock() {
+                    *pending = Some(text);
+                }
+            }
+        }
+    }
+    toggle_window(ns_window, &*visible_ptr);
+}
 
-    // Create the menu
-    let menu: id = msg_send![class!(NSMenu), alloc];
-    let menu: id = msg_send![menu, initWithTitle: NSString::alloc(nil).init_str("")];
+/// Wait for the second key of the main hotkey's chord: register it as a
+/// temporary hotkey (so the keystroke doesn't reach the frontmost app)
+/// and show a hint until it's pressed or `expire_chord` gives up.
+unsafe fn arm_chord(second: ChordKey) {
+    let hotkey_id = EventHotKeyID {
+        signature: 0x5A454449, // 'ZEDI'
+        id: CHORD_HOTKEY_ID,
+    };
+    let mut hotkey_ref: EventHotKeyRef = std::ptr::null_mut();
+    let status = RegisterEventHotKey(
+        second.key_code,
+        second.modifiers,
+        hotkey_id,
+        GetEventDispatcherTarget(),
+        0,
+        &mut hotkey_ref,
+    );
+    if status != 0 {
+        set_error(Some(t_args("hotkey.chord_registration_failed", &[("status", &status)])));
+        return;
+    }
+    if let Ok(mut chord) = CHORD.lock() {
+        chord.arm(hotkey_ref as usize, std::time::Instant::now());
+    }
+    show_chord_hud(&format!("{} …", second.prefix));
+}
 
-    // 1. Version item (disabled, gray)
-    let version_str = version_string();
-    let version_title = NSString::alloc(nil).init_str(&version_str);
-    let version_item: id = msg_send![class!(NSMenuItem), alloc];
-    let version_item: id = msg_send![
-        version_item,
-        initWithTitle: version_title
-        action: std::ptr::null::<Sel>()
-        keyEquivalent: NSString::alloc(nil).init_str("")
-    ];
-    let _: () = msg_send![version_item, setEnabled: false];
-    let _: () = msg_send![version_item, setTag: 50i64];
-    let _: () = msg_send![menu, addItem: version_item];
+unsafe fn disarm_chord() {
+    let Some(hotkey_ref) = CHORD.lock().ok().and_then(|mut chord| chord.disarm()) else {
+        return;
+    };
+    UnregisterEventHotKey(hotkey_ref as EventHotKeyRef);
+    hide_chord_hud();
+}
 
-    // Separator
-    let sep: id = msg_send![class!(NSMenuItem), separatorItem];
-    let _: () = msg_send![menu, addItem: sep];
+/// Give up on a chord whose second key didn't come in time. Polled from
+/// the GPUI side.
+///
+/// # Safety
+/// Must be called from the main thread.
+unsafe fn expire_chord() {
+    let expired = CHORD.lock().is_ok_and(|chord| chord.expired(std::time::Instant::now()));
+    if expired {
+        disarm_chord();
+    }
+}
 
-    // 2. Error item (hidden by default)
-    let error_title = NSString::alloc(nil).init_str("");
-    let error_item: id = msg_send![class!(NSMenuItem), alloc];
-    let error_item: id = msg_send![
+/// Check a combination against `SYSTEM_SHORTCUTS`, then try registering
+/// it. Combinations Zeditor itself holds count as free.
+unsafe fn hotkey_conflict(key_code: u32, modifiers: u32) -> Option<String> {
+    if let Some(&(_, _, owner)) = SYSTEM_SHORTCUTS
+        .iter()
+        .find(|&&(code, mods, _)| code == key_code && mods == modifiers)
+    {
+        return Some(t_args("hotkey.in_use_by", &[("owner", &t(owner))]));
+    }
+    let ours = MAIN_HOTKEY.lock().ok().is_some_and(|g| *g == Some((key_code, modifiers)))
+        || ACTION_HOTKEY_KEYS
+            .lock()
+            .ok()
+            .is_some_and(|keys| keys.iter().any(|&(_, code, mods)| code == key_code && mods == modifiers));
+    if ours {
+        return None;
+    }
+
+    let hotkey_id = EventHotKeyID {
+        signature: 0x5A454449, // 'ZEDI'
+        id: TRIAL_HOTKEY_ID,
+    };
+    let mut hotkey_ref: EventHotKeyRef = std::ptr::null_mut();
+    let status = RegisterEventHotKey(
+        key_code,
+        modifiers,
+        hotkey_id,
+        GetEventDispatcherTarget(),
+        0,
+        &mut hotkey_ref,
+    );
+    if status != 0 {
+        return Some(t_args("hotkey.in_use", &[("status", &status)]));
+    }
+    UnregisterEventHotKey(hotkey_ref);
+    None
+}
+
+/// Re-check the Accessibility permission and secure input, at most once
+/// per `SYSTEM_POLL`. Once the permission is granted, errors asking for it
+/// are cleared. Polled from the GPUI side.
+unsafe fn poll_system_state() {
+    let Ok(mut last_check) = LAST_SYSTEM_CHECK.lock() else {
+        return;
+    };
+    if last_check.is_some_and(|at| at.elapsed() < SYSTEM_POLL) {
+        return;
+    }
+    *last_check = Some(std::time::Instant::now());
+    drop(last_check);
+
+    let trusted = AXIsProcessTrusted();
+    let was_trusted = ACCESSIBILITY_TRUSTED.swap(trusted, Ordering::SeqCst);
+    if trusted && !was_trusted && get_error().is_some_and(|err| err.contains(t("paste.accessibility_hint"))) {
+        set_error(None);
+    }
+
+    let warning = secure_input_warning();
+    let changed = match SECURE_INPUT_WARNING.lock() {
+        Ok(mut current) if *current != warning => {
+            *current = warning;
+            true
+        }
+        _ => false,
+    };
+    if changed {
+        update_menu_error();
+    }
+}
+
+/// While an app has secure keyboard entry on (password fields, some
+/// terminals), posted keystrokes and key monitors are ignored, so paste
+/// simulation does nothing. Names the app when the session says which.
+unsafe fn secure_input_warning() -> Option<String> {
+    if IsSecureEventInputEnabled() == 0 {
+        return None;
+    }
+    let owner = || -> Option<String> {
+        let session = CGSessionCopyCurrentDictionary();
+        if session.is_null() {
+            return None;
+        }
+        let key = NSString::alloc(nil).init_str("kCGSSessionSecureInputPID");
+        let pid: id = msg_send![session, objectForKey: key];
+        let pid: i32 = if pid.is_null() { 0 } else { msg_send![pid, intValue] };
+        let _: () = msg_send![session, release];
+        let app: id = msg_send![
+            class!(NSRunningApplication),
+            runningApplicationWithProcessIdentifier: pid
+        ];
+        if pid == 0 || app.is_null() {
+            return None;
+        }
+        let name: id = msg_send![app, localizedName];
+        if name.is_null() {
+            return None;
+        }
+        let utf8: *const std::os::raw::c_char = msg_send![name, UTF8String];
+        Some(std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    };
+    Some(match owner() {
+        Some(app) => t_args("paste.secure_input_in", &[("app", &app)]),
+        None => t("paste.secure_input").to_string(),
+    })
+}
+
+/// Unregister every hotkey so other apps can have the keys, until
+/// `resume_hotkeys` or, given a duration, until it runs out.
+unsafe fn pause_hotkeys(duration: Option<std::time::Duration>) {
+    let started = PAUSE
+        .lock()
+        .is_ok_and(|mut pause| pause.pause(duration, std::time::Instant::now()));
+    if started {
+        let main_ref = GLOBAL_HOTKEY_REF.swap(0, Ordering::SeqCst) as EventHotKeyRef;
+        if !main_ref.is_null() {
+            UnregisterEventHotKey(main_ref);
+        }
+        if let Ok(mut registered) = ACTION_HOTKEYS.lock() {
+            for (hotkey_ref, _) in registered.iter_mut() {
+                if *hotkey_ref != 0 {
+                    UnregisterEventHotKey(*hotkey_ref as EventHotKeyRef);
+                    *hotkey_ref = 0;
+                }
+            }
+        }
+        if let Ok(mut fallbacks) = FALLBACK_HOTKEYS.lock() {
+            fallbacks.clear();
+        }
+        disarm_chord();
+    }
+    let resume_title = match duration {
+        Some(d) => {
+            let until = chrono::Local::now() + d;
+            t_args("menu.resume_at", &[("time", &until.format("%H:%M"))])
+        }
+        None => t("menu.resume").to_string(),
+    };
+    update_pause_menu(&resume_title);
+}
+
+unsafe fn resume_hotkeys() {
+    if !PAUSE.lock().is_ok_and(|mut pause| pause.resume()) {
+        return;
+    }
+    if let Some((key_code, modifiers)) = MAIN_HOTKEY.lock().ok().and_then(|g| *g) {
+        re_register_hotkey(key_code, modifiers);
+    }
+    let actions = ACTION_HOTKEY_KEYS.lock().map(|g| g.clone()).unwrap_or_default();
+    register_action_hotkeys(&actions);
+    update_pause_menu(t("menu.resume"));
+}
+
+/// End a timed pause that has run out. Polled from the GPUI side.
+unsafe fn resume_if_due() {
+    let due = PAUSE.lock().is_ok_and(|pause| pause.due(std::time::Instant::now()));
+    if due {
+        resume_hotkeys();
+    }
+}
+
+/// Swap the Pause and Resume menu items and dim the status item while
+/// paused.
+unsafe fn update_pause_menu(resume_title: &str) {
+    let paused = hotkeys_paused();
+    let status_item = GLOBAL_STATUS_ITEM.load(Ordering::SeqCst) as id;
+    if !status_item.is_null() {
+        let button: id = msg_send![status_item, button];
+        let _: () = msg_send![button, setAppearsDisabled: paused];
+    }
+    let menu = GLOBAL_MENU.load(Ordering::SeqCst) as id;
+    if menu.is_null() {
+        return;
+    }
+    let pause_item: id = msg_send![menu, itemWithTag: 270i64];
+    let resume_item: id = msg_send![menu, itemWithTag: 271i64];
+    if pause_item.is_null() || resume_item.is_null() {
+        return;
+    }
+    let _: () = msg_send![pause_item, setHidden: paused];
+    let _: () = msg_send![resume_item, setHidden: !paused];
+    let _: () = msg_send![resume_item, setTitle: NSString::alloc(nil).init_str(resume_title)];
+}
+
+/// Small translucent panel near the bottom of the screen naming the
+/// pending chord prefix. Created on first use and reused.
+unsafe fn show_chord_hud(text: &str) {
+    use cocoa::foundation::{NSPoint, NSRect, NSSize};
+    const NS_WINDOW_STYLE_MASK_NONACTIVATING_PANEL: u64 = 1 << 7;
+    const NS_BACKING_STORE_BUFFERED: u64 = 2;
+    const NS_POP_UP_MENU_WINDOW_LEVEL: i64 = 101;
+    const NS_TEXT_ALIGNMENT_CENTER: i64 = 1;
+
+    let mut panel = CHORD_HUD.load(Ordering::SeqCst) as id;
+    if panel.is_null() {
+        let rect = NSRect::new(NSPoint::new(0., 0.), NSSize::new(180., 44.));
+        panel = msg_send![class!(NSPanel), alloc];
+        panel = msg_send![
+            panel,
+            initWithContentRect: rect
+            styleMask: NS_WINDOW_STYLE_MASK_NONACTIVATING_PANEL
+            backing: NS_BACKING_STORE_BUFFERED
+            defer: false
+        ];
+        let background: id = msg_send![class!(NSColor), colorWithWhite: 0.1f64 alpha: 0.85f64];
+        let _: () = msg_send![panel, setBackgroundColor: background];
+        let _: () = msg_send![panel, setOpaque: false];
+        let _: () = msg_send![panel, setLevel: NS_POP_UP_MENU_WINDOW_LEVEL];
+        let _: () = msg_send![panel, setIgnoresMouseEvents: true];
+        let _: () = msg_send![panel, setAnimationBehavior: NS_WINDOW_ANIMATION_BEHAVIOR_NONE];
+
+        let label: id = msg_send![class!(NSTextField), labelWithString: NSString::alloc(nil).init_str("")];
+        let label_rect = NSRect::new(NSPoint::new(0., 11.), NSSize::new(180., 22.));
+        let _: () = msg_send![label, setFrame: label_rect];
+        let _: () = msg_send![label, setAlignment: NS_TEXT_ALIGNMENT_CENTER];
+        let white: id = msg_send![class!(NSColor), whiteColor];
+        let _: () = msg_send![label, setTextColor: white];
+        let font: id = msg_send![class!(NSFont), systemFontOfSize: 15f64];
+        let _: () = msg_send![label, setFont: font];
+        let content: id = msg_send![panel, contentView];
+        let _: () = msg_send![content, addSubview: label];
+        CHORD_HUD.store(panel as usize, Ordering::SeqCst);
+    }
+
+    let content: id = msg_send![panel, contentView];
+    let subviews: id = msg_send![content, subviews];
+    let label: id = msg_send![subviews, firstObject];
+    let _: () = msg_send![label, setStringValue: NSString::alloc(nil).init_str(text)];
+
+    let screen: id = msg_send![class!(NSScreen), mainScreen];
+    if !screen.is_null() {
+        let visible: NSRect = msg_send![screen, visibleFrame];
+        let frame: NSRect = msg_send![panel, frame];
+        let origin = NSPoint::new(
+            visible.origin.x + (visible.size.width - frame.size.width) / 2.,
+            visible.origin.y + visible.size.height / 5.,
+        );
+        let _: () = msg_send![panel, setFrameOrigin: origin];
+    }
+    let _: () = msg_send![panel, orderFrontRegardless];
+}
+
+unsafe fn hide_chord_hud() {
+    let panel = CHORD_HUD.load(Ordering::SeqCst) as id;
+    if !panel.is_null() {
+        let _: () = msg_send![panel, orderOut: nil];
+    }
+}
+
+unsafe fn register_deactivation_observer(ns_window: *mut Object, visible: Arc<AtomicBool>) {
+    let ns_window = ns_window as usize;
+
+    let handler = block::ConcreteBlock::new(move |_notification: id| {
+        if visible.load(Ordering::SeqCst)
+            && !PINNED.load(Ordering::SeqCst)
+            && HIDE_ON_FOCUS_LOSS.load(Ordering::SeqCst)
+        {
+            unsafe {
+                let ns_window = ns_window as *mut Object;
+                order_out(ns_window);
+            }
+            visible.store(false, Ordering::SeqCst);
+            HIDDEN.store(true, Ordering::SeqCst);
+        }
+    });
+    let handler = handler.copy();
+
+    let notification_center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+    let notification_name =
+        NSString::alloc(nil).init_str(NS_APPLICATION_DID_RESIGN_ACTIVE_NOTIFICATION);
+
+    let _: id = msg_send![
+        notification_center,
+        addObserverForName: notification_name
+        object: nil
+        queue: nil
+        usingBlock: &*handler
+    ];
+
+    std::mem::forget(handler);
+}
+
+/// Keep RECENT_APPS in step with the apps the user switches to.
+unsafe fn register_activation_tracker() {
+    fn note_active(app: id) {
+        if app.is_null() {
+            return;
+        }
+        unsafe {
+            let current: id = msg_send![class!(NSRunningApplication), currentApplication];
+            let is_current: bool = msg_send![app, isEqual: current];
+            if is_current {
+                return;
+            }
+            let pid: i32 = msg_send![app, processIdentifier];
+            if let Ok(mut recent) = RECENT_APPS.lock() {
+                recent.retain(|&p| p != pid);
+                recent.insert(0, pid);
+                recent.truncate(RECENT_APP_LIMIT);
+            }
+        }
+    }
+
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    note_active(msg_send![workspace, frontmostApplication]);
+
+    let handler = block::ConcreteBlock::new(move |notification: id| unsafe {
+        let user_info: id = msg_send![notification, userInfo];
+        let key = NSString::alloc(nil).init_str("NSWorkspaceApplicationKey");
+        let app: id = msg_send![user_info, objectForKey: key];
+        note_active(app);
+    });
+    let handler = handler.copy();
+
+    let notification_center: id = msg_send![workspace, notificationCenter];
+    let notification_name =
+        NSString::alloc(nil).init_str("NSWorkspaceDidActivateApplicationNotification");
+    let _: id = msg_send![
+        notification_center,
+        addObserverForName: notification_name
+        object: nil
+        queue: nil
+        usingBlock: &*handler
+    ];
+
+    std::mem::forget(handler);
+}
+
+/// Flag light/dark mode switches and Increase Contrast changes; the first
+/// only reach GPUI's appearance observers while a window is on screen, the
+/// second never do.
+unsafe fn register_appearance_observer() {
+    let handler = block::ConcreteBlock::new(move |_notification: id| {
+        APPEARANCE_CHANGED.store(true, Ordering::SeqCst);
+    });
+    let handler = handler.copy();
+
+    let notification_center: id =
+        msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+    let notification_name = NSString::alloc(nil).init_str("AppleInterfaceThemeChangedNotification");
+    let _: id = msg_send![
+        notification_center,
+        addObserverForName: notification_name
+        object: nil
+        queue: nil
+        usingBlock: &*handler
+    ];
+    std::mem::forget(handler);
+
+    // Increase Contrast is announced on the workspace's own center
+    let handler = block::ConcreteBlock::new(move |_notification: id| {
+        APPEARANCE_CHANGED.store(true, Ordering::SeqCst);
+    });
+    let handler = handler.copy();
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let workspace_center: id = msg_send![workspace, notificationCenter];
+    let notification_name =
+        NSString::alloc(nil).init_str("NSWorkspaceAccessibilityDisplayOptionsDidChangeNotification");
+    let _: id = msg_send![
+        workspace_center,
+        addObserverForName: notification_name
+        object: nil
+        queue: nil
+        usingBlock: &*handler
+    ];
+
+    std::mem::forget(handler);
+}
+
+/// Recently active apps still running, as (name, process id).
+unsafe fn paste_targets() -> Vec<(String, String)> {
+    let pids = RECENT_APPS.lock().map(|r| r.clone()).unwrap_or_default();
+    pids.into_iter()
+        .filter_map(|pid| {
+            let app: id = msg_send![
+                class!(NSRunningApplication),
+                runningApplicationWithProcessIdentifier: pid
+            ];
+            if app.is_null() {
+                return None;
+            }
+            let terminated: bool = msg_send![app, isTerminated];
+            if terminated {
+                return None;
+            }
+            let name: id = msg_send![app, localizedName];
+            let name = ns_string_to_string(name).unwrap_or_else(|| pid.to_string());
+            Some((name, pid.to_string()))
+        })
+        .collect()
+}
+
+/// Make the app with this process id the one the next submit pastes into.
+unsafe fn set_paste_target(target: &str) {
+    let Ok(pid) = target.parse::<i32>() else {
+        return;
+    };
+    let app: id = msg_send![
+        class!(NSRunningApplication),
+        runningApplicationWithProcessIdentifier: pid
+    ];
+    if app.is_null() {
+        return;
+    }
+    let _: id = msg_send![app, retain];
+    let old = GLOBAL_PREVIOUS_APP.swap(app as usize, Ordering::SeqCst) as id;
+    if !old.is_null() {
+        let _: () = msg_send![old, release];
+    }
+}
+
+unsafe fn create_status_item(ns_window: *mut Object, visible: Arc<AtomicBool>) {
+    let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+    let status_item: id =
+        msg_send![status_bar, statusItemWithLength: NS_VARIABLE_STATUS_ITEM_LENGTH];
+
+    let button: id = msg_send![status_item, button];
+    let image = template_image(STATUS_ICON);
+    if image.is_null() {
+        let title = NSString::alloc(nil).init_str("Z");
+        let _: () = msg_send![button, setTitle: title];
+    } else {
+        let _: () = msg_send![button, setImage: image];
+        let _: () = msg_send![image, release];
+    }
+
+    // Retain the status item to prevent deallocation
+    let _: id = msg_send![status_item, retain];
+
+    let ns_window = ns_window as usize;
+    GLOBAL_STATUS_ITEM.store(status_item as usize, Ordering::SeqCst);
+    GLOBAL_WINDOW.store(ns_window, Ordering::SeqCst);
+    GLOBAL_VISIBLE.store(Box::into_raw(Box::new(visible)) as usize, Ordering::SeqCst);
+
+    // Set up the NSMenu
+    setup_status_menu(status_item);
+
+    // Text and links dropped on the icon open in the popup
+    register_status_drop_target(button);
+
+    // Ensure visible
+    let _: () = msg_send![status_item, setVisible: true];
+}
+
+/// Accept text and URL drags onto the status item. The button's window
+/// forwards NSDraggingDestination calls to its delegate.
+unsafe fn register_status_drop_target(button: id) {
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+
+    let window: id = msg_send![button, window];
+    if window.is_null() {
+        return;
+    }
+
+    let class_name = "ZeditorStatusDropTarget";
+    let target_class = if let Some(cls) = Class::get(class_name) {
+        cls
+    } else {
+        let superclass = Class::get("NSObject").unwrap();
+        let mut decl = ClassDecl::new(class_name, superclass).unwrap();
+
+        extern "C" fn dragging_entered(_self: &Object, _cmd: Sel, info: id) -> u64 {
+            unsafe {
+                let pasteboard: id = msg_send![info, draggingPasteboard];
+                // NSDragOperationCopy, or NSDragOperationNone
+                if dropped_text(pasteboard).is_some() { 1 } else { 0 }
+            }
+        }
+
+        extern "C" fn perform_drag_operation(_self: &Object, _cmd: Sel, info: id) -> bool {
+            unsafe {
+                let pasteboard: id = msg_send![info, draggingPasteboard];
+                let Some(text) = dropped_text(pasteboard) else {
+                    return false;
+                };
+                if !is_visible() {
+                    remember_frontmost_app();
+                }
+                set_initial_text(text);
+                true
+            }
+        }
+
+        decl.add_method(
+            sel!(draggingEntered:),
+            dragging_entered as extern "C" fn(&Object, Sel, id) -> u64,
+        );
+        decl.add_method(
+            sel!(performDragOperation:),
+            perform_drag_operation as extern "C" fn(&Object, Sel, id) -> bool,
+        );
+        decl.register()
+    };
+
+    let target: id = msg_send![target_class, new];
+    let types: id = msg_send![class!(NSMutableArray), array];
+    for uti in ["public.utf8-plain-text", "public.url"] {
+        let _: () = msg_send![types, addObject: NSString::alloc(nil).init_str(uti)];
+    }
+    let _: () = msg_send![window, registerForDraggedTypes: types];
+    let _: () = msg_send![window, setDelegate: target];
+}
+
+/// Text from a drag: plain text if there is any, otherwise the URL.
+unsafe fn dropped_text(pasteboard: id) -> Option<String> {
+    pasteboard_string(pasteboard).or_else(|| {
+        let url_type = NSString::alloc(nil).init_str("public.url");
+        let url: id = msg_send![pasteboard, stringForType: url_type];
+        if url.is_null() {
+            return None;
+        }
+        let bytes = url.UTF8String();
+        if bytes.is_null() {
+            return None;
+        }
+        Some(std::ffi::CStr::from_ptr(bytes).to_string_lossy().into_owned())
+    })
+}
+
+/// Provide the "Edit in Zeditor" service declared under NSServices in
+/// Info.plist.
+unsafe fn register_services_provider() {
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+
+    #[link(name = "AppKit", kind = "framework")]
+    unsafe extern "C" {
+        fn NSUpdateDynamicServices();
+    }
+
+    let class_name = "ZeditorServiceProvider";
+    let provider_class = if let Some(cls) = Class::get(class_name) {
+        cls
+    } else {
+        let superclass = Class::get("NSObject").unwrap();
+        let mut decl = ClassDecl::new(class_name, superclass).unwrap();
+
+        // Show the popup with the selection and keep the call open until
+        // it's submitted or hidden, so the submitted text can go back on
+        // the pasteboard as the service's return value
+        extern "C" fn edit_in_zeditor(_self: &Object, _cmd: Sel, pboard: id, _user_data: id, _error: *mut id) {
+            unsafe {
+                let Some(text) = pasteboard_string(pboard) else {
+                    return;
+                };
+                if !is_visible() {
+                    remember_frontmost_app();
+                }
+                if let Ok(mut result) = SERVICE_RESULT.lock() {
+                    *result = None;
+                }
+                SERVICE_PENDING.store(true, Ordering::SeqCst);
+                set_initial_text(text);
+
+                let run_loop: id = msg_send![class!(NSRunLoop), currentRunLoop];
+                let mode = NSString::alloc(nil).init_str("kCFRunLoopDefaultMode");
+                while SERVICE_PENDING.load(Ordering::SeqCst) {
+                    let until: id = msg_send![class!(NSDate), dateWithTimeIntervalSinceNow: 0.05f64];
+                    let _: bool = msg_send![run_loop, runMode: mode beforeDate: until];
+                }
+
+                if let Some(result) = SERVICE_RESULT.lock().ok().and_then(|mut g| g.take()) {
+                    let string_type = NSString::alloc(nil).init_str("public.utf8-plain-text");
+                    let _: i64 = msg_send![pboard, clearContents];
+                    let _: bool = msg_send![
+                        pboard,
+                        setString: NSString::alloc(nil).init_str(&result)
+                        forType: string_type
+                    ];
+                }
+            }
+        }
+
+        decl.add_method(
+            sel!(editInZeditor:userData:error:),
+            edit_in_zeditor as extern "C" fn(&Object, Sel, id, id, *mut id),
+        );
+        decl.register()
+    };
+
+    let provider: id = msg_send![provider_class, new];
+    let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+    let _: () = msg_send![ns_app, setServicesProvider: provider];
+    NSUpdateDynamicServices();
+}
+
+/// Declare the NSScriptCommand subclasses named in Zeditor.sdef. Cocoa
+/// Scripting looks them up by name when a command arrives.
+unsafe fn register_script_commands() {
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+
+    extern "C" fn show(this: &Object, _cmd: Sel) -> id {
+        unsafe {
+            let args: id = msg_send![this, evaluatedArguments];
+            let key = NSString::alloc(nil).init_str("Text");
+            let text: id = msg_send![args, objectForKey: key];
+            if !is_visible() {
+                remember_frontmost_app();
+            }
+            match ns_string_to_string(text) {
+                Some(text) => set_initial_text(text),
+                None => SHOW_REQUESTED.store(true, Ordering::SeqCst),
+            }
+            nil
+        }
+    }
+
+    extern "C" fn current_draft(_this: &Object, _cmd: Sel) -> id {
+        let draft = CURRENT_DRAFT.lock().map(|d| d.clone()).unwrap_or_default();
+        unsafe {
+            let string = NSString::alloc(nil).init_str(&draft);
+            msg_send![string, autorelease]
+        }
+    }
+
+    extern "C" fn submit(this: &Object, _cmd: Sel) -> id {
+        unsafe {
+            let direct: id = msg_send![this, directParameter];
+            let text = ns_string_to_string(direct)
+                .or_else(|| CURRENT_DRAFT.lock().ok().map(|d| d.clone()))
+                .unwrap_or_default();
+            if !is_visible() {
+                remember_frontmost_app();
+            }
+            submit_and_paste(&text);
+            nil
+        }
+    }
+
+    let commands: [(&str, extern "C" fn(&Object, Sel) -> id); 3] = [
+        ("ZeditorShowCommand", show),
+        ("ZeditorDraftCommand", current_draft),
+        ("ZeditorSubmitCommand", submit),
+    ];
+    for (class_name, perform) in commands {
+        if Class::get(class_name).is_some() {
+            continue;
+        }
+        let superclass = Class::get("NSScriptCommand").unwrap();
+        let mut decl = ClassDecl::new(class_name, superclass).unwrap();
+        decl.add_method(sel!(performDefaultImplementation), perform);
+        decl.register();
+    }
+}
+
+/// A Rust copy of an NSString, or None for nil or anything else.
+unsafe fn ns_string_to_string(object: id) -> Option<String> {
+    if object.is_null() {
+        return None;
+    }
+    let is_string: bool = msg_send![object, isKindOfClass: class!(NSString)];
+    if !is_string {
+        return None;
+    }
+    let bytes = object.UTF8String();
+    if bytes.is_null() {
+        return None;
+    }
+    Some(std::ffi::CStr::from_ptr(bytes).to_string_lossy().into_owned())
+}
+
+/// Load a bundled image as an 18pt template NSImage, which AppKit tints to
+/// suit a light or dark menu bar. Returns nil if the asset is missing or
+/// doesn't decode. The caller owns the image.
+unsafe fn template_image(path: &str) -> id {
+    use rust_embed::RustEmbed;
+
+    let Some(file) = crate::assets::Assets::get(path) else {
+        return nil;
+    };
+    let data: id = msg_send![
+        class!(NSData),
+        dataWithBytes: file.data.as_ptr() as *const c_void
+        length: file.data.len() as u64
+    ];
+    let image: id = msg_send![class!(NSImage), alloc];
+    let image: id = msg_send![image, initWithData: data];
+    if image.is_null() {
+        return nil;
+    }
+    let _: () = msg_send![image, setSize: cocoa::foundation::NSSize::new(18.0, 18.0)];
+    let _: () = msg_send![image, setTemplate: true];
+    image
+}
+
+/// Show the filled status icon while the popup is up, if enabled.
+unsafe fn update_status_icon(visible: bool) {
+    let status_item = GLOBAL_STATUS_ITEM.load(Ordering::SeqCst) as id;
+    if status_item.is_null() {
+        return;
+    }
+    let path = if visible && ACTIVE_STATUS_ICON.load(Ordering::SeqCst) {
+        STATUS_ICON_ACTIVE
+    } else {
+        STATUS_ICON
+    };
+    let image = template_image(path);
+    if image.is_null() {
+        return;
+    }
+    let button: id = msg_send![status_item, button];
+    let _: () = msg_send![button, setImage: image];
+    let _: () = msg_send![image, release];
+}
+
+unsafe fn setup_status_menu(status_item: id) {
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+
+    // Create the menu
+    let menu: id = msg_send![class!(NSMenu), alloc];
+    let menu: id = msg_send![menu, initWithTitle: NSString::alloc(nil).init_str("")];
+
+    // 1. Version item (disabled, gray)
+    let version_str = version_string();
+    let version_title = NSString::alloc(nil).init_str(&version_str);
+    let version_item: id = msg_send![class!(NSMenuItem), alloc];
+    let version_item: id = msg_send![
+        version_item,
+        initWithTitle: version_title
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![version_item, setEnabled: false];
+    let _: () = msg_send![version_item, setTag: 50i64];
+    let _: () = msg_send![menu, addItem: version_item];
+
+    // Separator
+    let sep: id = msg_send![class!(NSMenuItem), separatorItem];
+    let _: () = msg_send![menu, addItem: sep];
+
+    // 2. Error item (hidden by default)
+    let error_title = NSString::alloc(nil).init_str("");
+    let error_item: id = msg_send![class!(NSMenuItem), alloc];
+    let error_item: id = msg_send![
         error_item,
         initWithTitle: error_title
         action: std::ptr::null::<Sel>()
@@ -462,6 +1870,32 @@ unsafe fn setup_status_menu(status_item: id) {
     let _: () = msg_send![error_item, setHidden: true];
     let _: () = msg_send![menu, addItem: error_item];
 
+    // Secure input warning (hidden by default)
+    let secure_item: id = msg_send![class!(NSMenuItem), alloc];
+    let secure_item: id = msg_send![
+        secure_item,
+        initWithTitle: NSString::alloc(nil).init_str("")
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![secure_item, setEnabled: false];
+    let _: () = msg_send![secure_item, setTag: 102i64];
+    let _: () = msg_send![secure_item, setHidden: true];
+    let _: () = msg_send![menu, addItem: secure_item];
+
+    // Config file problems (hidden by default)
+    let config_item: id = msg_send![class!(NSMenuItem), alloc];
+    let config_item: id = msg_send![
+        config_item,
+        initWithTitle: NSString::alloc(nil).init_str("")
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![config_item, setEnabled: false];
+    let _: () = msg_send![config_item, setTag: 103i64];
+    let _: () = msg_send![config_item, setHidden: true];
+    let _: () = msg_send![menu, addItem: config_item];
+
     // Error separator (hidden by default)
     let error_sep: id = msg_send![class!(NSMenuItem), separatorItem];
     let _: () = msg_send![error_sep, setTag: 101i64];
@@ -494,6 +1928,94 @@ unsafe fn setup_status_menu(status_item: id) {
             }
         }
 
+        extern "C" fn menu_scratchpad(_self: &Object, _cmd: Sel, sender: id) {
+            unsafe {
+                let name: id = msg_send![sender, representedObject];
+                let name = if name.is_null() {
+                    String::new()
+                } else {
+                    let utf8: *const std::os::raw::c_char = msg_send![name, UTF8String];
+                    std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned()
+                };
+                remember_frontmost_app();
+                if let Ok(mut requested) = SCRATCHPAD_REQUESTED.lock() {
+                    *requested = Some(name);
+                }
+            }
+        }
+
+        extern "C" fn menu_recent_draft(_self: &Object, _cmd: Sel, sender: id) {
+            unsafe {
+                let text: id = msg_send![sender, representedObject];
+                if text.is_null() {
+                    return;
+                }
+                let utf8: *const std::os::raw::c_char = msg_send![text, UTF8String];
+                let text = std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned();
+                if !is_visible() {
+                    remember_frontmost_app();
+                }
+                set_initial_text(text);
+            }
+        }
+
+        // Rebuild the scratchpads and recent drafts submenus each time they
+        // open so they stay current
+        extern "C" fn menu_needs_update(this: &Object, _cmd: Sel, menu: id) {
+            unsafe {
+                let _: () = msg_send![menu, removeAllItems];
+                let target = this as *const Object as id;
+                if menu as usize == RECENT_MENU.load(Ordering::SeqCst) {
+                    fill_recent_menu(menu, target);
+                    return;
+                }
+                let add_item = |title: &str, name: Option<&str>| {
+                    let item: id = msg_send![class!(NSMenuItem), alloc];
+                    let item: id = msg_send![
+                        item,
+                        initWithTitle: NSString::alloc(nil).init_str(title)
+                        action: sel!(menuScratchpad:)
+                        keyEquivalent: NSString::alloc(nil).init_str("")
+                    ];
+                    let _: () = msg_send![item, setTarget: target];
+                    if let Some(name) = name {
+                        let _: () = msg_send![item, setRepresentedObject: NSString::alloc(nil).init_str(name)];
+                    }
+                    let _: () = msg_send![menu, addItem: item];
+                };
+                add_item(t("menu.scratch"), None);
+                for name in crate::scratch::list_scratchpads() {
+                    add_item(&name, Some(&name));
+                }
+            }
+        }
+
+        extern "C" fn menu_paste_last(_self: &Object, _cmd: Sel, _sender: id) {
+            unsafe { paste_last_submission() };
+        }
+
+        extern "C" fn menu_submission_history(_self: &Object, _cmd: Sel, _sender: id) {
+            unsafe {
+                if !is_visible() {
+                    remember_frontmost_app();
+                }
+            }
+            HISTORY_REQUESTED.store(true, Ordering::SeqCst);
+        }
+
+        // The item's tag is the pause length in minutes, 0 for no limit
+        extern "C" fn menu_pause_hotkey(_self: &Object, _cmd: Sel, sender: id) {
+            unsafe {
+                let minutes: i64 = msg_send![sender, tag];
+                let duration = (minutes > 0).then(|| std::time::Duration::from_secs(minutes as u64 * 60));
+                pause_hotkeys(duration);
+            }
+        }
+
+        extern "C" fn menu_resume_hotkey(_self: &Object, _cmd: Sel, _sender: id) {
+            unsafe { resume_hotkeys() };
+        }
+
         extern "C" fn menu_quit(_self: &Object, _cmd: Sel, _sender: id) {
             unsafe {
                 let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
@@ -509,6 +2031,34 @@ unsafe fn setup_status_menu(status_item: id) {
             sel!(menuPreferences:),
             menu_preferences as extern "C" fn(&Object, Sel, id),
         );
+        decl.add_method(
+            sel!(menuScratchpad:),
+            menu_scratchpad as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuRecentDraft:),
+            menu_recent_draft as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuNeedsUpdate:),
+            menu_needs_update as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuPasteLast:),
+            menu_paste_last as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuSubmissionHistory:),
+            menu_submission_history as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuPauseHotkey:),
+            menu_pause_hotkey as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(menuResumeHotkey:),
+            menu_resume_hotkey as extern "C" fn(&Object, Sel, id),
+        );
         decl.add_method(
             sel!(menuQuit:),
             menu_quit as extern "C" fn(&Object, Sel, id),
@@ -519,7 +2069,7 @@ unsafe fn setup_status_menu(status_item: id) {
 
     let target: id = msg_send![target_class, new];
 
-    let toggle_title = NSString::alloc(nil).init_str("Toggle Editor");
+    let toggle_title = NSString::alloc(nil).init_str(t("menu.toggle"));
     let toggle_item: id = msg_send![class!(NSMenuItem), alloc];
     let toggle_item: id = msg_send![
         toggle_item,
@@ -531,12 +2081,117 @@ unsafe fn setup_status_menu(status_item: id) {
     let _: () = msg_send![toggle_item, setTag: 200i64];
     let _: () = msg_send![menu, addItem: toggle_item];
 
+    // Scratchpads submenu, filled in by menuNeedsUpdate:
+    let scratch_title = NSString::alloc(nil).init_str(t("menu.scratchpads"));
+    let scratch_item: id = msg_send![class!(NSMenuItem), alloc];
+    let scratch_item: id = msg_send![
+        scratch_item,
+        initWithTitle: scratch_title
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let scratch_menu: id = msg_send![class!(NSMenu), alloc];
+    let scratch_menu: id = msg_send![scratch_menu, initWithTitle: scratch_title];
+    let _: () = msg_send![scratch_menu, setDelegate: target];
+    let _: () = msg_send![scratch_item, setSubmenu: scratch_menu];
+    let _: () = msg_send![scratch_item, setTag: 250i64];
+    let _: () = msg_send![menu, addItem: scratch_item];
+
+    // Recent drafts submenu, also filled in by menuNeedsUpdate:
+    let recent_title = NSString::alloc(nil).init_str(t("menu.recent"));
+    let recent_item: id = msg_send![class!(NSMenuItem), alloc];
+    let recent_item: id = msg_send![
+        recent_item,
+        initWithTitle: recent_title
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let recent_menu: id = msg_send![class!(NSMenu), alloc];
+    let recent_menu: id = msg_send![recent_menu, initWithTitle: recent_title];
+    let _: () = msg_send![recent_menu, setDelegate: target];
+    let _: () = msg_send![recent_item, setSubmenu: recent_menu];
+    let _: () = msg_send![recent_item, setTag: 260i64];
+    let _: () = msg_send![menu, addItem: recent_item];
+    RECENT_MENU.store(recent_menu as usize, Ordering::SeqCst);
+
+    // Paste Last Submission
+    let paste_last_item: id = msg_send![class!(NSMenuItem), alloc];
+    let paste_last_item: id = msg_send![
+        paste_last_item,
+        initWithTitle: NSString::alloc(nil).init_str(t("menu.paste_last_submission"))
+        action: sel!(menuPasteLast:)
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![paste_last_item, setTarget: target];
+    let _: () = msg_send![paste_last_item, setTag: 265i64];
+    let _: () = msg_send![menu, addItem: paste_last_item];
+
+    // Submission History
+    let history_item: id = msg_send![class!(NSMenuItem), alloc];
+    let history_item: id = msg_send![
+        history_item,
+        initWithTitle: NSString::alloc(nil).init_str(t("menu.submission_history"))
+        action: sel!(menuSubmissionHistory:)
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![history_item, setTarget: target];
+    let _: () = msg_send![menu, addItem: history_item];
+
+    // Separator
+    let pause_sep: id = msg_send![class!(NSMenuItem), separatorItem];
+    let _: () = msg_send![menu, addItem: pause_sep];
+
+    // Pause Hotkey submenu, one item per pause length
+    let pause_title = NSString::alloc(nil).init_str(t("menu.pause"));
+    let pause_item: id = msg_send![class!(NSMenuItem), alloc];
+    let pause_item: id = msg_send![
+        pause_item,
+        initWithTitle: pause_title
+        action: std::ptr::null::<Sel>()
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let pause_menu: id = msg_send![class!(NSMenu), alloc];
+    let pause_menu: id = msg_send![pause_menu, initWithTitle: pause_title];
+    for (title, minutes) in [
+        (t("menu.pause_until_resumed"), 0i64),
+        (t("menu.pause_5_minutes"), 5),
+        (t("menu.pause_15_minutes"), 15),
+        (t("menu.pause_1_hour"), 60),
+    ] {
+        let item: id = msg_send![class!(NSMenuItem), alloc];
+        let item: id = msg_send![
+            item,
+            initWithTitle: NSString::alloc(nil).init_str(title)
+            action: sel!(menuPauseHotkey:)
+            keyEquivalent: NSString::alloc(nil).init_str("")
+        ];
+        let _: () = msg_send![item, setTarget: target];
+        let _: () = msg_send![item, setTag: minutes];
+        let _: () = msg_send![pause_menu, addItem: item];
+    }
+    let _: () = msg_send![pause_item, setSubmenu: pause_menu];
+    let _: () = msg_send![pause_item, setTag: 270i64];
+    let _: () = msg_send![menu, addItem: pause_item];
+
+    // Resume Hotkey (hidden until paused)
+    let resume_item: id = msg_send![class!(NSMenuItem), alloc];
+    let resume_item: id = msg_send![
+        resume_item,
+        initWithTitle: NSString::alloc(nil).init_str(t("menu.resume"))
+        action: sel!(menuResumeHotkey:)
+        keyEquivalent: NSString::alloc(nil).init_str("")
+    ];
+    let _: () = msg_send![resume_item, setTarget: target];
+    let _: () = msg_send![resume_item, setTag: 271i64];
+    let _: () = msg_send![resume_item, setHidden: true];
+    let _: () = msg_send![menu, addItem: resume_item];
+
     // Separator
     let sep2: id = msg_send![class!(NSMenuItem), separatorItem];
     let _: () = msg_send![menu, addItem: sep2];
 
     // 4. Preferences...
-    let prefs_title = NSString::alloc(nil).init_str("Preferences...");
+    let prefs_title = NSString::alloc(nil).init_str(t("menu.preferences"));
     let prefs_item: id = msg_send![class!(NSMenuItem), alloc];
     let prefs_item: id = msg_send![
         prefs_item,
@@ -553,7 +2208,7 @@ unsafe fn setup_status_menu(status_item: id) {
     let _: () = msg_send![menu, addItem: sep3];
 
     // 5. Quit Zeditor
-    let quit_title = NSString::alloc(nil).init_str("Quit Zeditor");
+    let quit_title = NSString::alloc(nil).init_str(t("menu.quit"));
     let quit_item: id = msg_send![class!(NSMenuItem), alloc];
     let quit_item: id = msg_send![
         quit_item,
@@ -570,6 +2225,47 @@ unsafe fn setup_status_menu(status_item: id) {
 
     // Attach menu to status item
     let _: () = msg_send![status_item, setMenu: menu];
+    update_menu_error();
+}
+
+/// List the newest drafts by their first line. Each item carries the full
+/// text, so picking one still works if the history changes meanwhile.
+unsafe fn fill_recent_menu(menu: id, target: id) {
+    use objc::runtime::Sel;
+
+    let drafts = crate::drafts::load_drafts();
+    if drafts.is_empty() {
+        let item: id = msg_send![class!(NSMenuItem), alloc];
+        let item: id = msg_send![
+            item,
+            initWithTitle: NSString::alloc(nil).init_str(t("menu.no_recent_drafts"))
+            action: std::ptr::null::<Sel>()
+            keyEquivalent: NSString::alloc(nil).init_str("")
+        ];
+        let _: () = msg_send![item, setEnabled: false];
+        let _: () = msg_send![menu, addItem: item];
+        return;
+    }
+    for draft in drafts.iter().take(RECENT_DRAFTS) {
+        let preview = draft.preview();
+        let title = if preview.chars().count() > RECENT_PREVIEW_CHARS {
+            let cut: String = preview.chars().take(RECENT_PREVIEW_CHARS).collect();
+            format!("{}…", cut.trim_end())
+        } else {
+            preview.to_string()
+        };
+        let item: id = msg_send![class!(NSMenuItem), alloc];
+        let item: id = msg_send![
+            item,
+            initWithTitle: NSString::alloc(nil).init_str(&title)
+            action: sel!(menuRecentDraft:)
+            keyEquivalent: NSString::alloc(nil).init_str("")
+        ];
+        let _: () = msg_send![item, setTarget: target];
+        let _: () = msg_send![item, setRepresentedObject: NSString::alloc(nil).init_str(&draft.text)];
+        let _: () = msg_send![item, setToolTip: NSString::alloc(nil).init_str(&draft.saved_at_label())];
+        let _: () = msg_send![menu, addItem: item];
+    }
 }
 
 unsafe fn update_menu_error() {
@@ -585,15 +2281,47 @@ unsafe fn update_menu_error() {
         return;
     }
 
-    if let Some(err) = get_error() {
+    let error = get_error();
+    if let Some(err) = &error {
         let title = NSString::alloc(nil).init_str(&format!("⚠ {}", err));
         let _: () = msg_send![error_item, setTitle: title];
         let _: () = msg_send![error_item, setHidden: false];
-        let _: () = msg_send![error_sep, setHidden: false];
     } else {
         let _: () = msg_send![error_item, setHidden: true];
-        let _: () = msg_send![error_sep, setHidden: true];
     }
+
+    let warning = SECURE_INPUT_WARNING.lock().ok().and_then(|g| g.clone());
+    let secure_item: id = msg_send![menu, itemWithTag: 102i64];
+    if !secure_item.is_null() {
+        if let Some(warning) = &warning {
+            let title = NSString::alloc(nil).init_str(&format!("⚠ {}", warning));
+            let _: () = msg_send![secure_item, setTitle: title];
+        }
+        let _: () = msg_send![secure_item, setHidden: warning.is_none()];
+    }
+    let issues = crate::preferences::config_issues();
+    let config_item: id = msg_send![menu, itemWithTag: 103i64];
+    if !config_item.is_null() {
+        if !issues.is_empty() {
+            let title = format!(
+                "⚠ {}",
+                t_count_args(
+                    "menu.invalid_settings",
+                    issues.len(),
+                    &[("file", &crate::preferences::config_file_name())],
+                )
+            );
+            let title = NSString::alloc(nil).init_str(&title);
+            let _: () = msg_send![config_item, setTitle: title];
+            let tooltip = issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>().join("\n");
+            let _: () = msg_send![config_item, setToolTip: NSString::alloc(nil).init_str(&tooltip)];
+        }
+        let _: () = msg_send![config_item, setHidden: issues.is_empty()];
+    }
+    let _: () = msg_send![
+        error_sep,
+        setHidden: error.is_none() && warning.is_none() && issues.is_empty()
+    ];
 }
 
 /// Hides the window and restores focus to the previous app.
@@ -605,7 +2333,7 @@ pub unsafe fn hide_window(ns_window: *mut Object, visible: &AtomicBool) {
         return;
     }
 
-    let _: () = msg_send![ns_window, orderOut: nil];
+    order_out(ns_window);
     visible.store(false, Ordering::SeqCst);
 
     let prev_app = GLOBAL_PREVIOUS_APP.swap(0, Ordering::SeqCst) as id;
@@ -615,55 +2343,542 @@ pub unsafe fn hide_window(ns_window: *mut Object, visible: &AtomicBool) {
     }
 }
 
-pub unsafe fn toggle_window(ns_window: *mut Object, visible: &AtomicBool) {
-    if visible.load(Ordering::SeqCst) {
-        hide_window(ns_window, visible);
-    } else {
-        // Remember the previous frontmost app for focus restoration on hide
-        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
-        let frontmost_app: id = msg_send![workspace, frontmostApplication];
-        if !frontmost_app.is_null() {
-            let _: id = msg_send![frontmost_app, retain];
-            let old = GLOBAL_PREVIOUS_APP.swap(frontmost_app as usize, Ordering::SeqCst) as id;
-            if !old.is_null() {
-                let _: () = msg_send![old, release];
+pub unsafe fn toggle_window(ns_window: *mut Object, visible: &AtomicBool) {
+    if visible.load(Ordering::SeqCst) {
+        hide_window(ns_window, visible);
+        HIDDEN.store(true, Ordering::SeqCst);
+    } else {
+        remember_frontmost_app();
+        if FOLLOW_CARET.load(Ordering::SeqCst) {
+            let caret = focused_caret_rect();
+            if let Ok(mut g) = CARET_RECT.lock() {
+                *g = caret;
+            }
+        }
+
+        // Signal the GPUI polling task to show the window
+        SHOW_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Ask the focused UI element of the frontmost app where its caret is.
+/// Needs accessibility permission; apps that don't expose text ranges
+/// (or report an empty rect, as some browsers do) yield None.
+unsafe fn focused_caret_rect() -> Option<(f64, f64, f64, f64)> {
+    let focused = focused_element()?;
+
+    let mut range: *mut c_void = std::ptr::null_mut();
+    let attr = NSString::alloc(nil).init_str("AXSelectedTextRange");
+    let status = AXUIElementCopyAttributeValue(focused, attr, &mut range);
+    if status != 0 || range.is_null() {
+        CFRelease(focused);
+        return None;
+    }
+
+    let mut bounds: *mut c_void = std::ptr::null_mut();
+    let attr = NSString::alloc(nil).init_str("AXBoundsForRange");
+    let status = AXUIElementCopyParameterizedAttributeValue(focused, attr, range, &mut bounds);
+    CFRelease(range);
+    CFRelease(focused);
+    if status != 0 || bounds.is_null() {
+        return None;
+    }
+
+    let mut rect = cocoa::foundation::NSRect::new(
+        cocoa::foundation::NSPoint::new(0., 0.),
+        cocoa::foundation::NSSize::new(0., 0.),
+    );
+    let ok = AXValueGetValue(
+        bounds,
+        K_AX_VALUE_CG_RECT_TYPE,
+        &mut rect as *mut cocoa::foundation::NSRect as *mut c_void,
+    );
+    CFRelease(bounds);
+    if !ok || (rect.origin.x == 0. && rect.origin.y == 0. && rect.size.height == 0.) {
+        return None;
+    }
+    Some((rect.origin.x, rect.origin.y, rect.size.width, rect.size.height))
+}
+
+/// CFRange, as wrapped in an AXValue.
+#[repr(C)]
+struct CfRange {
+    location: isize,
+    length: isize,
+}
+
+/// The focused UI element of the frontmost app, retained; release with
+/// CFRelease.
+unsafe fn focused_element() -> Option<*mut c_void> {
+    let system = AXUIElementCreateSystemWide();
+    if system.is_null() {
+        return None;
+    }
+    let mut focused: *mut c_void = std::ptr::null_mut();
+    let attr = NSString::alloc(nil).init_str("AXFocusedUIElement");
+    let status = AXUIElementCopyAttributeValue(system, attr, &mut focused);
+    CFRelease(system);
+    (status == 0 && !focused.is_null()).then_some(focused)
+}
+
+unsafe fn is_settable(element: *mut c_void, attr: id) -> bool {
+    let mut settable = false;
+    AXUIElementIsAttributeSettable(element, attr, &mut settable) == 0 && settable
+}
+
+/// Write `text` over the selection of the focused text field, without the
+/// clipboard or a keystroke. Fields that take neither AXSelectedText nor
+/// AXValue yield false, so the caller can fall back to pasting.
+unsafe fn insert_text(text: &str) -> bool {
+    let Some(focused) = focused_element() else {
+        return false;
+    };
+    let inserted = insert_selected_text(focused, text) || insert_into_value(focused, text);
+    CFRelease(focused);
+    inserted
+}
+
+unsafe fn insert_selected_text(element: *mut c_void, text: &str) -> bool {
+    let attr = NSString::alloc(nil).init_str("AXSelectedText");
+    if !is_settable(element, attr) {
+        return false;
+    }
+    let value = NSString::alloc(nil).init_str(text);
+    AXUIElementSetAttributeValue(element, attr, value as *mut c_void) == 0
+}
+
+/// Splice `text` into the whole value at the selected range, then put the
+/// caret after it.
+unsafe fn insert_into_value(element: *mut c_void, text: &str) -> bool {
+    let value_attr = NSString::alloc(nil).init_str("AXValue");
+    if !is_settable(element, value_attr) {
+        return false;
+    }
+    let mut value: *mut c_void = std::ptr::null_mut();
+    if AXUIElementCopyAttributeValue(element, value_attr, &mut value) != 0 || value.is_null() {
+        return false;
+    }
+    let current = ns_string_to_string(value as id);
+    CFRelease(value);
+    let Some(current) = current else {
+        return false;
+    };
+
+    let range_attr = NSString::alloc(nil).init_str("AXSelectedTextRange");
+    let mut range_value: *mut c_void = std::ptr::null_mut();
+    let mut range = CfRange { location: 0, length: 0 };
+    if AXUIElementCopyAttributeValue(element, range_attr, &mut range_value) != 0 || range_value.is_null() {
+        return false;
+    }
+    let ok = AXValueGetValue(
+        range_value,
+        K_AX_VALUE_CF_RANGE_TYPE,
+        &mut range as *mut CfRange as *mut c_void,
+    );
+    CFRelease(range_value);
+    if !ok {
+        return false;
+    }
+
+    // Accessibility ranges count UTF-16 code units
+    let mut units: Vec<u16> = current.encode_utf16().collect();
+    let start = (range.location.max(0) as usize).min(units.len());
+    let end = (start + range.length.max(0) as usize).min(units.len());
+    let inserted: Vec<u16> = text.encode_utf16().collect();
+    let caret = start + inserted.len();
+    units.splice(start..end, inserted);
+    let updated = NSString::alloc(nil).init_str(&String::from_utf16_lossy(&units));
+    if AXUIElementSetAttributeValue(element, value_attr, updated as *mut c_void) != 0 {
+        return false;
+    }
+
+    let caret_range = CfRange {
+        location: caret as isize,
+        length: 0,
+    };
+    let caret_value = AXValueCreate(
+        K_AX_VALUE_CF_RANGE_TYPE,
+        &caret_range as *const CfRange as *const c_void,
+    );
+    if !caret_value.is_null() {
+        AXUIElementSetAttributeValue(element, range_attr, caret_value);
+        CFRelease(caret_value);
+    }
+    true
+}
+
+/// Put the window's top-left corner just under the caret, or above it when
+/// there's no room below, kept inside the visible frame of that screen.
+unsafe fn place_below_caret(ns_window: *mut Object, caret: (f64, f64, f64, f64)) {
+    use cocoa::foundation::{NSPoint, NSRect};
+    const GAP: f64 = 4.;
+
+    // Accessibility rects are top-left based on the primary screen;
+    // AppKit's are bottom-left based
+    let screens: id = msg_send![class!(NSScreen), screens];
+    let count: usize = msg_send![screens, count];
+    if count == 0 {
+        let _: () = msg_send![ns_window, center];
+        return;
+    }
+    let primary: id = msg_send![screens, objectAtIndex: 0usize];
+    let primary_frame: NSRect = msg_send![primary, frame];
+    let (x, y, _, height) = caret;
+    let caret_top = primary_frame.size.height - y;
+    let caret_bottom = caret_top - height;
+
+    let mut visible = primary_frame;
+    for i in 0..count {
+        let screen: id = msg_send![screens, objectAtIndex: i];
+        let frame: NSRect = msg_send![screen, frame];
+        if x >= frame.origin.x
+            && x < frame.origin.x + frame.size.width
+            && caret_bottom >= frame.origin.y
+            && caret_bottom < frame.origin.y + frame.size.height
+        {
+            visible = msg_send![screen, visibleFrame];
+            break;
+        }
+    }
+
+    let window_frame: NSRect = msg_send![ns_window, frame];
+    let size = window_frame.size;
+    let mut top = caret_bottom - GAP;
+    if top - size.height < visible.origin.y {
+        top = caret_top + GAP + size.height;
+    }
+    let top = top
+        .min(visible.origin.y + visible.size.height)
+        .max(visible.origin.y + size.height);
+    let left = x
+        .min(visible.origin.x + visible.size.width - size.width)
+        .max(visible.origin.x);
+    let _: () = msg_send![ns_window, setFrameTopLeftPoint: NSPoint::new(left, top)];
+}
+
+/// Plain text on the general pasteboard, if any.
+unsafe fn pasteboard_text() -> Option<String> {
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    pasteboard_string(pasteboard)
+}
+
+/// Plain text on `pasteboard`, if any.
+unsafe fn pasteboard_string(pasteboard: id) -> Option<String> {
+    let string_type: id = NSString::alloc(nil).init_str("public.utf8-plain-text");
+    let string: id = msg_send![pasteboard, stringForType: string_type];
+    if string.is_null() {
+        return None;
+    }
+    let bytes = string.UTF8String();
+    if bytes.is_null() {
+        return None;
+    }
+    Some(std::ffi::CStr::from_ptr(bytes).to_string_lossy().into_owned())
+}
+
+/// Remember the previous frontmost app for focus restoration on hide.
+unsafe fn remember_frontmost_app() {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let frontmost_app: id = msg_send![workspace, frontmostApplication];
+    if !frontmost_app.is_null() {
+        let _: id = msg_send![frontmost_app, retain];
+        let old = GLOBAL_PREVIOUS_APP.swap(frontmost_app as usize, Ordering::SeqCst) as id;
+        if !old.is_null() {
+            let _: () = msg_send![old, release];
+        }
+    }
+}
+
+/// Submits text by copying to clipboard, hiding the window, restoring focus,
+/// and simulating Cmd+V to paste into the previous app.
+///
+/// # Safety
+/// Must be called from the main thread with a valid ns_window pointer.
+unsafe fn submit_and_paste(text: &str) {
+    if let Ok(mut last) = LAST_SUBMISSION.lock() {
+        *last = Some(text.to_string());
+    }
+    if SERVICE_PENDING.load(Ordering::SeqCst) {
+        // The Services call replaces the selection itself
+        if let Ok(mut result) = SERVICE_RESULT.lock() {
+            *result = Some(text.to_string());
+        }
+        let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
+        let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
+        if !ns_window.is_null() && !visible_ptr.is_null() {
+            hide_window(ns_window, &*visible_ptr);
+        }
+        return;
+    }
+    let text = text.to_string();
+    let result = std::panic::catch_unwind(move || unsafe { submit_and_paste_inner(&text) });
+    if let Err(e) = result {
+        eprintln!("[submit_and_paste] Panic: {:?}", e);
+    }
+}
+
+/// Paste the last submitted text again, into whichever app is in front,
+/// without showing the popup.
+unsafe fn paste_last_submission() {
+    let Some(text) = LAST_SUBMISSION.lock().ok().and_then(|g| g.clone()) else {
+        return;
+    };
+    if !is_visible() {
+        remember_frontmost_app();
+    }
+    submit_and_paste(&text);
+}
+
+// Store app to release after paste
+static PENDING_RELEASE_APP: AtomicUsize = AtomicUsize::new(0);
+/// Wait before the first paste attempt, doubled for each retry; how many
+/// attempts to make, and how many have been made for the pending paste
+static PASTE_DELAY: Mutex<std::time::Duration> = Mutex::new(std::time::Duration::from_millis(50));
+static PASTE_ATTEMPTS: AtomicUsize = AtomicUsize::new(4);
+static PASTE_ATTEMPT: AtomicUsize = AtomicUsize::new(0);
+
+/// Seconds to wait before paste attempt `attempt`, counting from 0.
+fn paste_delay(attempt: usize) -> f64 {
+    let base = PASTE_DELAY
+        .lock()
+        .map(|d| *d)
+        .unwrap_or(std::time::Duration::from_millis(50));
+    base.as_secs_f64() * 2f64.powi(attempt.min(16) as i32)
+}
+
+/// Whether `app` (an NSRunningApplication) is the frontmost app.
+unsafe fn is_frontmost(app: id) -> bool {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let frontmost: id = msg_send![workspace, frontmostApplication];
+    if frontmost.is_null() {
+        return false;
+    }
+    let frontmost_pid: i32 = msg_send![frontmost, processIdentifier];
+    let pid: i32 = msg_send![app, processIdentifier];
+    frontmost_pid == pid
+}
+
+/// Put `text` on the pasteboard and hide, handing focus back to the
+/// previous app without pasting.
+unsafe fn copy_submission(text: &str) {
+    if let Ok(mut last) = LAST_SUBMISSION.lock() {
+        *last = Some(text.to_string());
+    }
+    write_pasteboard(text);
+    let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
+    let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
+    if !ns_window.is_null() && !visible_ptr.is_null() {
+        hide_window(ns_window, &*visible_ptr);
+    }
+}
+
+unsafe fn write_pasteboard(text: &str) {
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    let _: () = msg_send![pasteboard, clearContents];
+    let ns_string: id = NSString::alloc(nil).init_str(text);
+    let string_type: id = NSString::alloc(nil).init_str("public.utf8-plain-text");
+    let _: bool = msg_send![pasteboard, setString: ns_string forType: string_type];
+}
+
+/// Replace the pasteboard with `plain`, `html`, and RTF converted from the
+/// HTML by AppKit.
+unsafe fn write_rich_pasteboard(plain: &str, html: &str) {
+    use cocoa::foundation::NSRange;
+
+    write_pasteboard(plain);
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    // The charset tag keeps AppKit from reading the UTF-8 as Latin-1
+    let document = format!("<meta charset=\"utf-8\">{}", html);
+    let _: bool = msg_send![
+        pasteboard,
+        setString: NSString::alloc(nil).init_str(&document)
+        forType: NSString::alloc(nil).init_str("public.html")
+    ];
+
+    let data: id = msg_send![
+        class!(NSData),
+        dataWithBytes: document.as_ptr() as *const std::ffi::c_void
+        length: document.len() as u64
+    ];
+    let attributed: id = msg_send![class!(NSAttributedString), alloc];
+    let attributed: id = msg_send![attributed, initWithHTML: data documentAttributes: nil];
+    if attributed.is_null() {
+        return;
+    }
+    let length: u64 = msg_send![attributed, length];
+    let empty: id = msg_send![class!(NSDictionary), dictionary];
+    let rtf: id = msg_send![
+        attributed,
+        RTFFromRange: NSRange::new(0, length)
+        documentAttributes: empty
+    ];
+    if !rtf.is_null() {
+        let _: bool = msg_send![
+            pasteboard,
+            setData: rtf
+            forType: NSString::alloc(nil).init_str("public.rtf")
+        ];
+    }
+    let _: () = msg_send![attributed, release];
+}
+
+/// The word around byte `offset` of `text` per CFStringTokenizer, which
+/// uses dictionaries for languages written without spaces (Japanese,
+/// Chinese, Thai).
+unsafe fn tokenizer_word_at(text: &str, offset: usize) -> Option<std::ops::Range<usize>> {
+    #[repr(C)]
+    struct CFRange {
+        location: isize,
+        length: isize,
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFLocaleCopyCurrent() -> *mut c_void;
+        fn CFStringTokenizerCreate(
+            alloc: *const c_void,
+            string: id,
+            range: CFRange,
+            options: usize,
+            locale: *mut c_void,
+        ) -> *mut c_void;
+        fn CFStringTokenizerGoToTokenAtIndex(tokenizer: *mut c_void, index: isize) -> usize;
+        fn CFStringTokenizerGetCurrentTokenRange(tokenizer: *mut c_void) -> CFRange;
+    }
+    // kCFStringTokenizerUnitWord; kCFStringTokenizerTokenNone
+    const UNIT_WORD: usize = 0;
+    const TOKEN_NONE: usize = 0;
+
+    let units: Vec<u16> = text.encode_utf16().collect();
+    let index = text[..offset.min(text.len())].encode_utf16().count();
+    let string = NSString::alloc(nil).init_str(text);
+    let locale = CFLocaleCopyCurrent();
+    let full = CFRange { location: 0, length: units.len() as isize };
+    let tokenizer = CFStringTokenizerCreate(std::ptr::null(), string, full, UNIT_WORD, locale);
+    let mut found = None;
+    if !tokenizer.is_null() {
+        // A click just past a word's last character still means that word
+        let mut token = CFStringTokenizerGoToTokenAtIndex(tokenizer, index as isize);
+        if token == TOKEN_NONE && index > 0 {
+            token = CFStringTokenizerGoToTokenAtIndex(tokenizer, index as isize - 1);
+        }
+        if token != TOKEN_NONE {
+            let range = CFStringTokenizerGetCurrentTokenRange(tokenizer);
+            let start = range.location as usize;
+            let end = start + range.length as usize;
+            let to_bytes = |units16: &[u16]| String::from_utf16_lossy(units16).len();
+            found = Some(to_bytes(&units[..start])..to_bytes(&units[..end]));
+        }
+        CFRelease(tokenizer);
+    }
+    CFRelease(locale);
+    let _: () = msg_send![string, release];
+    found
+}
+
+/// The HTML flavor of the pasteboard, as put there by browsers and word
+/// processors.
+unsafe fn read_pasteboard_html() -> Option<String> {
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    let html: id = msg_send![
+        pasteboard,
+        stringForType: NSString::alloc(nil).init_str("public.html")
+    ];
+    ns_string_to_string(html)
+}
+
+/// Copy every item on the general pasteboard, in all its types, into
+/// SAVED_PASTEBOARD.
+unsafe fn save_pasteboard() {
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    let items: id = msg_send![pasteboard, pasteboardItems];
+    let copies: id = msg_send![class!(NSMutableArray), new];
+    let count: u64 = if items.is_null() { 0 } else { msg_send![items, count] };
+    for i in 0..count {
+        let item: id = msg_send![items, objectAtIndex: i];
+        let copy: id = msg_send![class!(NSPasteboardItem), new];
+        let types: id = msg_send![item, types];
+        let type_count: u64 = msg_send![types, count];
+        for j in 0..type_count {
+            let uti: id = msg_send![types, objectAtIndex: j];
+            let data: id = msg_send![item, dataForType: uti];
+            if !data.is_null() {
+                let _: bool = msg_send![copy, setData: data forType: uti];
             }
         }
+        let _: () = msg_send![copies, addObject: copy];
+        let _: () = msg_send![copy, release];
+    }
+    let old = SAVED_PASTEBOARD.swap(copies as usize, Ordering::SeqCst) as id;
+    if !old.is_null() {
+        let _: () = msg_send![old, release];
+    }
+}
 
-        // Signal the GPUI polling task to show the window
-        SHOW_REQUESTED.store(true, Ordering::SeqCst);
+/// Put the saved items back, unless something else has been copied since
+/// the submit.
+unsafe fn restore_pasteboard() {
+    let saved = SAVED_PASTEBOARD.swap(0, Ordering::SeqCst) as id;
+    if saved.is_null() {
+        return;
+    }
+    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+    let change_count: i64 = msg_send![pasteboard, changeCount];
+    if change_count as usize == SUBMIT_CHANGE_COUNT.load(Ordering::SeqCst) {
+        let _: i64 = msg_send![pasteboard, clearContents];
+        let count: u64 = msg_send![saved, count];
+        if count > 0 {
+            let _: bool = msg_send![pasteboard, writeObjects: saved];
+        }
     }
+    let _: () = msg_send![saved, release];
 }
 
-/// Submits text by copying to clipboard, hiding the window, restoring focus,
-/// and simulating Cmd+V to paste into the previous app.
-///
-/// # Safety
-/// Must be called from the main thread with a valid ns_window pointer.
-pub unsafe fn submit_and_paste(text: &str) {
-    let text = text.to_string();
-    let result = std::panic::catch_unwind(move || unsafe { submit_and_paste_inner(&text) });
-    if let Err(e) = result {
-        eprintln!("[submit_and_paste] Panic: {:?}", e);
+/// Write the submitted text to the pasteboard, first saving what was there
+/// when it's to be restored after the paste.
+unsafe fn put_submission_on_pasteboard(text: &str) {
+    let restore = RESTORE_CLIPBOARD_AFTER.lock().ok().and_then(|r| *r).is_some();
+    if restore {
+        save_pasteboard();
+    }
+    write_pasteboard(text);
+    if restore {
+        let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+        let change_count: i64 = msg_send![pasteboard, changeCount];
+        SUBMIT_CHANGE_COUNT.store(change_count as usize, Ordering::SeqCst);
     }
 }
 
-// Store app to release after paste
-static PENDING_RELEASE_APP: AtomicUsize = AtomicUsize::new(0);
+/// Insert the text of a submit that hasn't touched the pasteboard yet, or
+/// put it there for the paste when the focused field won't take it.
+/// Returns whether it was inserted.
+unsafe fn insert_pending() -> bool {
+    let Some(text) = PENDING_INSERT.lock().ok().and_then(|mut p| p.take()) else {
+        return false;
+    };
+    if AXIsProcessTrusted() && insert_text(&text) {
+        return true;
+    }
+    put_submission_on_pasteboard(&text);
+    false
+}
 
 unsafe fn submit_and_paste_inner(text: &str) {
-    let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
-    let _: () = msg_send![pasteboard, clearContents];
-    let ns_string: id = NSString::alloc(nil).init_str(text);
-    let string_type: id = NSString::alloc(nil).init_str("public.utf8-plain-text");
-    let _: bool = msg_send![pasteboard, setString: ns_string forType: string_type];
+    if AX_INSERT.load(Ordering::SeqCst) {
+        // Only goes on the pasteboard if inserting it fails
+        if let Ok(mut pending) = PENDING_INSERT.lock() {
+            *pending = Some(text.to_string());
+        }
+    } else {
+        put_submission_on_pasteboard(text);
+    }
 
     let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
     let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
     let prev_app = GLOBAL_PREVIOUS_APP.swap(0, Ordering::SeqCst) as id;
 
-    if !ns_window.is_null() && !visible_ptr.is_null() {
-        let _: () = msg_send![ns_window, orderOut: nil];
+    if !ns_window.is_null() && !visible_ptr.is_null() && (*visible_ptr).load(Ordering::SeqCst) {
+        order_out(ns_window);
         (*visible_ptr).store(false, Ordering::SeqCst);
     }
 
@@ -692,9 +2907,59 @@ unsafe fn schedule_paste_with_delay() {
             return;
         };
 
-        extern "C" fn do_paste(_self: &Object, _cmd: Sel) {
+        extern "C" fn do_paste(this: &Object, _cmd: Sel) {
             let result = std::panic::catch_unwind(|| unsafe {
-                simulate_paste();
+                let prev_app = PENDING_RELEASE_APP.load(Ordering::SeqCst) as id;
+                // A keystroke sent mid-switch lands in whatever app is
+                // still in front, so wait for the target
+                if !prev_app.is_null() && !is_frontmost(prev_app) {
+                    let attempt = PASTE_ATTEMPT.fetch_add(1, Ordering::SeqCst) + 1;
+                    if attempt < PASTE_ATTEMPTS.load(Ordering::SeqCst) {
+                        let _: bool = msg_send![prev_app, activateWithOptions: 2u64];
+                        let _: () = msg_send![
+                            this,
+                            performSelector: sel!(doPaste)
+                            withObject: nil
+                            afterDelay: paste_delay(attempt)
+                        ];
+                        return;
+                    }
+                    if let Some(text) = PENDING_INSERT.lock().ok().and_then(|mut p| p.take()) {
+                        write_pasteboard(&text);
+                    }
+                    let name: id = msg_send![prev_app, localizedName];
+                    let name = ns_string_to_string(name).unwrap_or_else(|| t("paste.previous_app").to_string());
+                    set_error(Some(t_args("paste.failed", &[("app", &name)])));
+                    PASTE_FAILED.store(true, Ordering::SeqCst);
+                } else if insert_pending() {
+                    if PASTE_FAILED.load(Ordering::SeqCst) {
+                        set_error(None);
+                    }
+                } else if let Some(warning) = secure_input_warning() {
+                    // Without the permission, or under secure input, the
+                    // keystroke is dropped silently
+                    set_error(Some(t_args("paste.copied_warning", &[("warning", &warning)])));
+                } else if AXIsProcessTrusted() {
+                    if PASTE_FAILED.load(Ordering::SeqCst) {
+                        set_error(None);
+                    }
+                    simulate_paste();
+                    // The text stays on the clipboard when it couldn't be
+                    // pasted
+                    if let Some(delay) = RESTORE_CLIPBOARD_AFTER.lock().ok().and_then(|r| *r) {
+                        let _: () = msg_send![
+                            this,
+                            performSelector: sel!(restoreClipboard)
+                            withObject: nil
+                            afterDelay: delay.as_secs_f64()
+                        ];
+                    }
+                } else {
+                    set_error(Some(t_args(
+                        "paste.copied_needs_permission",
+                        &[("hint", &t("paste.accessibility_hint"))],
+                    )));
+                }
 
                 let prev_app = PENDING_RELEASE_APP.swap(0, Ordering::SeqCst) as id;
                 if !prev_app.is_null() {
@@ -706,20 +2971,29 @@ unsafe fn schedule_paste_with_delay() {
             }
         }
 
+        extern "C" fn restore_clipboard(_self: &Object, _cmd: Sel) {
+            unsafe { restore_pasteboard() };
+        }
+
         decl.add_method(
             sel!(doPaste),
             do_paste as extern "C" fn(&Object, Sel),
         );
+        decl.add_method(
+            sel!(restoreClipboard),
+            restore_clipboard as extern "C" fn(&Object, Sel),
+        );
 
         decl.register()
     };
 
+    PASTE_ATTEMPT.store(0, Ordering::SeqCst);
     let helper: id = msg_send![helper_class, new];
     let _: () = msg_send![
         helper,
         performSelector: sel!(doPaste)
         withObject: nil
-        afterDelay: 0.05f64
+        afterDelay: paste_delay(0)
     ];
 }
 
@@ -763,3 +3037,531 @@ unsafe fn simulate_paste() {
 
     CFRelease(source);
 }
+
+/// Copy the preferences read outside GPUI into their statics.
+fn apply_preferences(prefs: &Preferences) {
+    set_slide_animation(prefs.window.animation == ShowAnimation::SlideDown);
+    if let Ok(mut reduce_motion) = REDUCE_MOTION.lock() {
+        *reduce_motion = prefs.window.reduce_motion;
+    }
+    set_follow_caret(prefs.window.position == WindowPosition::Caret);
+    ACTIVE_STATUS_ICON.store(prefs.status_item.active_icon, Ordering::SeqCst);
+    HIDE_ON_FOCUS_LOSS.store(prefs.behavior.hide_on_focus_loss, Ordering::SeqCst);
+    ESCAPE_HIDES.store(prefs.behavior.escape == EscapeBehavior::Immediate, Ordering::SeqCst);
+    if let Ok(mut restore) = RESTORE_CLIPBOARD_AFTER.lock() {
+        *restore = prefs.submit.restore_clipboard.then(|| {
+            std::time::Duration::from_millis(prefs.submit.restore_clipboard_delay_ms)
+        });
+    }
+    if let Ok(mut delay) = PASTE_DELAY.lock() {
+        *delay = std::time::Duration::from_millis(prefs.submit.paste_delay_ms);
+    }
+    PASTE_ATTEMPTS.store(prefs.submit.paste_attempts.max(1), Ordering::SeqCst);
+    AX_INSERT.store(prefs.submit.backend == SubmitBackend::Accessibility, Ordering::SeqCst);
+    let ns_window = GLOBAL_WINDOW.load(Ordering::SeqCst) as *mut Object;
+    if !ns_window.is_null() {
+        unsafe { apply_window_preferences(ns_window, prefs) };
+    }
+    // Saving them clears what was wrong with the config file
+    unsafe { update_menu_error() };
+}
+
+/// Bring the popup window's own settings in line with `prefs`, at install
+/// and whenever they change.
+///
+/// # Safety
+/// `ns_window` must be a valid NSWindow pointer; call from the main thread.
+unsafe fn apply_window_preferences(ns_window: *mut Object, prefs: &Preferences) {
+    set_window_opaque(ns_window, prefs.window.background_opacity() >= 1.0);
+    install_vibrancy(ns_window, prefs.window.vibrancy);
+    let sharing = if prefs.window.hide_from_capture {
+        NS_WINDOW_SHARING_NONE
+    } else {
+        NS_WINDOW_SHARING_READ_ONLY
+    };
+    let _: () = msg_send![ns_window, setSharingType: sharing];
+}
+
+/// Let a translucent background show what's behind the window, or fill it
+/// in again. GPUI only sets this when it opens the window.
+///
+/// # Safety
+/// `ns_window` must be a valid NSWindow pointer; call from the main thread.
+unsafe fn set_window_opaque(ns_window: *mut Object, opaque: bool) {
+    let _: () = msg_send![ns_window, setOpaque: opaque];
+    let color: id = if opaque {
+        msg_send![class!(NSColor), windowBackgroundColor]
+    } else {
+        msg_send![class!(NSColor), clearColor]
+    };
+    let _: () = msg_send![ns_window, setBackgroundColor: color];
+    // The Metal layer GPUI draws into decides whether its clear color
+    // blends with what's behind
+    let content_view: id = msg_send![ns_window, contentView];
+    if !content_view.is_null() {
+        let layer: id = msg_send![content_view, layer];
+        if !layer.is_null() {
+            let _: () = msg_send![layer, setOpaque: opaque];
+        }
+    }
+}
+
+/// The NSView GPUI draws a window into.
+fn ns_view_of(window: &Window) -> Option<*mut Object> {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    let handle = window.window_handle().ok()?;
+    let RawWindowHandle::AppKit(appkit) = handle.as_raw() else {
+        return None;
+    };
+    Some(appkit.ns_view.as_ptr() as *mut Object)
+}
+
+/// The NSWindow behind a GPUI window.
+fn ns_window_of(window: &Window) -> Option<*mut Object> {
+    let ns_view = ns_view_of(window)?;
+    Some(unsafe { msg_send![ns_view, window] })
+}
+
+/// The draft as NSString indexes it.
+fn draft_utf16() -> Vec<u16> {
+    CURRENT_DRAFT.lock().map(|d| d.encode_utf16().collect()).unwrap_or_default()
+}
+
+/// Line (from 0) holding UTF-16 index `index` of `units`.
+fn line_for_index(units: &[u16], index: usize) -> usize {
+    units[..index.min(units.len())].iter().filter(|&&u| u == b'\n' as u16).count()
+}
+
+/// UTF-16 (location, length) of line `line` of `units`, its newline
+/// included.
+fn range_for_line(units: &[u16], line: usize) -> (usize, usize) {
+    let mut start = 0;
+    for _ in 0..line {
+        match units[start..].iter().position(|&u| u == b'\n' as u16) {
+            Some(newline) => start += newline + 1,
+            None => return (units.len(), 0),
+        }
+    }
+    let end = units[start..]
+        .iter()
+        .position(|&u| u == b'\n' as u16)
+        .map_or(units.len(), |newline| start + newline + 1);
+    (start, end - start)
+}
+
+/// An autoreleased NSString, for returning from an Objective-C getter.
+unsafe fn autoreleased_string(text: &str) -> id {
+    let string = NSString::alloc(nil).init_str(text);
+    msg_send![string, autorelease]
+}
+
+/// Let VoiceOver read the popup's view as a text area holding the draft.
+/// GPUI's view doesn't speak the accessibility protocol, so the view is
+/// switched to a subclass that does, answering from CURRENT_DRAFT and
+/// DRAFT_SELECTION.
+unsafe fn install_accessibility(ns_view: id) {
+    use cocoa::foundation::{NSRange, NSRect};
+    use objc::declare::ClassDecl;
+    use objc::runtime::{BOOL, Class, Sel, YES};
+
+    #[link(name = "objc")]
+    unsafe extern "C" {
+        fn object_getClass(object: id) -> *const Class;
+        fn object_setClass(object: id, class: *const Class) -> *const Class;
+    }
+
+    let class_name = "ZeditorAccessibleView";
+    let view_class = if let Some(cls) = Class::get(class_name) {
+        cls
+    } else {
+        let superclass = &*object_getClass(ns_view);
+        let Some(mut decl) = ClassDecl::new(class_name, superclass) else {
+            return;
+        };
+
+        extern "C" fn is_element(_: &Object, _: Sel) -> BOOL {
+            YES
+        }
+        extern "C" fn role(_: &Object, _: Sel) -> id {
+            unsafe { autoreleased_string("AXTextArea") }
+        }
+        extern "C" fn label(_: &Object, _: Sel) -> id {
+            unsafe { autoreleased_string(t("accessibility.draft")) }
+        }
+        extern "C" fn value(_: &Object, _: Sel) -> id {
+            let draft = CURRENT_DRAFT.lock().map(|d| d.clone()).unwrap_or_default();
+            unsafe { autoreleased_string(&draft) }
+        }
+        extern "C" fn number_of_characters(_: &Object, _: Sel) -> i64 {
+            draft_utf16().len() as i64
+        }
+        extern "C" fn selected_text_range(_: &Object, _: Sel) -> NSRange {
+            let (location, length) = DRAFT_SELECTION.lock().map(|s| *s).unwrap_or_default();
+            NSRange::new(location as u64, length as u64)
+        }
+        extern "C" fn selected_text(_: &Object, _: Sel) -> id {
+            let units = draft_utf16();
+            let (location, length) = DRAFT_SELECTION.lock().map(|s| *s).unwrap_or_default();
+            let start = location.min(units.len());
+            let end = (location + length).min(units.len());
+            unsafe { autoreleased_string(&String::from_utf16_lossy(&units[start..end])) }
+        }
+        extern "C" fn visible_character_range(_: &Object, _: Sel) -> NSRange {
+            NSRange::new(0, draft_utf16().len() as u64)
+        }
+        extern "C" fn insertion_point_line(_: &Object, _: Sel) -> i64 {
+            let (location, _) = DRAFT_SELECTION.lock().map(|s| *s).unwrap_or_default();
+            line_for_index(&draft_utf16(), location) as i64
+        }
+        extern "C" fn line_for(_: &Object, _: Sel, index: i64) -> i64 {
+            line_for_index(&draft_utf16(), index.max(0) as usize) as i64
+        }
+        extern "C" fn range_for(_: &Object, _: Sel, line: i64) -> NSRange {
+            let (location, length) = range_for_line(&draft_utf16(), line.max(0) as usize);
+            NSRange::new(location as u64, length as u64)
+        }
+        extern "C" fn string_for(_: &Object, _: Sel, range: NSRange) -> id {
+            let units = draft_utf16();
+            let start = (range.location as usize).min(units.len());
+            let end = (start + range.length as usize).min(units.len());
+            unsafe { autoreleased_string(&String::from_utf16_lossy(&units[start..end])) }
+        }
+        // Glyph positions aren't known outside GPUI; point at the view
+        extern "C" fn frame_for(this: &Object, _: Sel, _range: NSRange) -> NSRect {
+            unsafe {
+                let bounds: NSRect = msg_send![this, bounds];
+                let in_window: NSRect = msg_send![this, convertRect: bounds toView: nil];
+                let window: id = msg_send![this, window];
+                msg_send![window, convertRectToScreen: in_window]
+            }
+        }
+
+        decl.add_method(sel!(isAccessibilityElement), is_element as extern "C" fn(&Object, Sel) -> BOOL);
+        decl.add_method(sel!(accessibilityRole), role as extern "C" fn(&Object, Sel) -> id);
+        decl.add_method(sel!(accessibilityLabel), label as extern "C" fn(&Object, Sel) -> id);
+        decl.add_method(sel!(accessibilityValue), value as extern "C" fn(&Object, Sel) -> id);
+        decl.add_method(
+            sel!(accessibilityNumberOfCharacters),
+            number_of_characters as extern "C" fn(&Object, Sel) -> i64,
+        );
+        decl.add_method(
+            sel!(accessibilitySelectedTextRange),
+            selected_text_range as extern "C" fn(&Object, Sel) -> NSRange,
+        );
+        decl.add_method(sel!(accessibilitySelectedText), selected_text as extern "C" fn(&Object, Sel) -> id);
+        decl.add_method(
+            sel!(accessibilityVisibleCharacterRange),
+            visible_character_range as extern "C" fn(&Object, Sel) -> NSRange,
+        );
+        decl.add_method(
+            sel!(accessibilityInsertionPointLineNumber),
+            insertion_point_line as extern "C" fn(&Object, Sel) -> i64,
+        );
+        decl.add_method(sel!(accessibilityLineForIndex:), line_for as extern "C" fn(&Object, Sel, i64) -> i64);
+        decl.add_method(
+            sel!(accessibilityRangeForLine:),
+            range_for as extern "C" fn(&Object, Sel, i64) -> NSRange,
+        );
+        decl.add_method(
+            sel!(accessibilityStringForRange:),
+            string_for as extern "C" fn(&Object, Sel, NSRange) -> id,
+        );
+        decl.add_method(
+            sel!(accessibilityFrameForRange:),
+            frame_for as extern "C" fn(&Object, Sel, NSRange) -> NSRect,
+        );
+        decl.register()
+    };
+    object_setClass(ns_view, view_class);
+    ACCESSIBLE_VIEW.store(ns_view as usize, Ordering::SeqCst);
+}
+
+/// Have VoiceOver speak `message`, interrupting what it's reading.
+unsafe fn post_announcement(message: &str) {
+    #[link(name = "AppKit", kind = "framework")]
+    unsafe extern "C" {
+        fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, user_info: id);
+    }
+
+    let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+    let info: id = msg_send![class!(NSMutableDictionary), dictionary];
+    let _: () = msg_send![
+        info,
+        setObject: NSString::alloc(nil).init_str(message)
+        forKey: NSString::alloc(nil).init_str("AXAnnouncementKey")
+    ];
+    // NSAccessibilityPriorityHigh
+    let priority: id = msg_send![class!(NSNumber), numberWithInteger: 90i64];
+    let _: () = msg_send![info, setObject: priority forKey: NSString::alloc(nil).init_str("AXPriorityKey")];
+    NSAccessibilityPostNotificationWithUserInfo(
+        ns_app,
+        NSString::alloc(nil).init_str("AXAnnouncementRequested"),
+        info,
+    );
+}
+
+/// Tell assistive tech that the draft's value or selection changed.
+unsafe fn post_accessibility_notification(name: &str) {
+    #[link(name = "AppKit", kind = "framework")]
+    unsafe extern "C" {
+        fn NSAccessibilityPostNotification(element: id, notification: id);
+    }
+
+    let view = ACCESSIBLE_VIEW.load(Ordering::SeqCst) as id;
+    if !view.is_null() {
+        NSAccessibilityPostNotification(view, NSString::alloc(nil).init_str(name));
+    }
+}
+
+/// Carbon hotkeys, the status bar menu and Cmd+V paste simulation.
+pub struct MacPlatform;
+
+impl EditorHost for MacPlatform {
+    fn reduce_motion(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            msg_send![workspace, accessibilityDisplayShouldReduceMotion]
+        }
+    }
+
+    fn announce(&self, message: &str) {
+        unsafe { post_announcement(message) };
+    }
+
+    fn set_draft_selection(&self, selection: std::ops::Range<usize>) {
+        if let Ok(mut current) = DRAFT_SELECTION.lock() {
+            *current = (selection.start, selection.len());
+        }
+        unsafe { post_accessibility_notification("AXSelectedTextChanged") };
+    }
+
+    fn write_rich_text(&self, plain: &str, html: &str, _cx: &mut App) {
+        unsafe { write_rich_pasteboard(plain, html) };
+    }
+
+    fn word_at(&self, text: &str, offset: usize) -> Option<std::ops::Range<usize>> {
+        unsafe { tokenizer_word_at(text, offset) }
+    }
+
+    fn read_html(&self) -> Option<String> {
+        unsafe { read_pasteboard_html() }
+    }
+
+    fn text(&self, key: &str) -> Option<String> {
+        crate::i18n::lookup(key).map(str::to_string)
+    }
+}
+
+impl Platform for MacPlatform {
+    fn install(&self, window: &mut Window, prefs: &Preferences) {
+        use cocoa::appkit::NSApplicationActivationPolicy::NSApplicationActivationPolicyAccessory;
+
+        apply_preferences(prefs);
+        self.set_excluded_apps(&prefs.excluded_apps);
+        set_main_chord(&prefs.hotkey);
+        let action_hotkeys: Vec<_> = prefs
+            .hotkeys
+            .iter()
+            .map(|h| (h.action, h.hotkey.key_code, h.hotkey.modifiers))
+            .collect();
+
+        unsafe {
+            // Set activation policy to Accessory (no Dock icon)
+            let ns_app: id = msg_send![class!(NSApplication), sharedApplication];
+            let _: () = msg_send![
+                ns_app,
+                setActivationPolicy: NSApplicationActivationPolicyAccessory as i64
+            ];
+
+            let Some(ns_window) = ns_window_of(window) else {
+                return;
+            };
+            if let Some(ns_view) = ns_view_of(window) {
+                install_accessibility(ns_view);
+            }
+            let _: () = msg_send![ns_window, setLevel: 3i64];
+            // Borderless popups aren't resizable by default;
+            // add NSWindowStyleMaskResizable so the edges drag
+            let style_mask: u64 = msg_send![ns_window, styleMask];
+            let _: () = msg_send![ns_window, setStyleMask: style_mask | (1 << 3)];
+            let _: () = msg_send![
+                ns_window,
+                setMinSize: cocoa::foundation::NSSize::new(crate::MIN_WINDOW_WIDTH, crate::MIN_WINDOW_HEIGHT)
+            ];
+            apply_window_preferences(ns_window, prefs);
+            register_hotkey(ns_window, prefs.hotkey.key_code, prefs.hotkey.modifiers);
+            register_action_hotkeys(&action_hotkeys);
+        }
+    }
+
+    fn set_hotkey(&self, config: &HotkeyConfig) {
+        unsafe { re_register_hotkey(config.key_code, config.modifiers) };
+        set_main_chord(config);
+    }
+
+    fn take_show_request(&self) -> Option<HotkeyAction> {
+        is_show_requested().then(|| take_show_action().unwrap_or(HotkeyAction::Toggle))
+    }
+
+    fn take_hidden(&self) -> bool {
+        HIDDEN.swap(false, Ordering::SeqCst)
+    }
+
+    fn take_menu_request(&self) -> Option<MenuRequest> {
+        if is_prefs_requested() {
+            return Some(MenuRequest::Preferences);
+        }
+        if HISTORY_REQUESTED.swap(false, Ordering::SeqCst) {
+            return Some(MenuRequest::SubmissionHistory);
+        }
+        take_scratchpad_request().map(MenuRequest::Scratchpad)
+    }
+
+    fn take_pending_clipboard(&self) -> Option<String> {
+        take_pending_clipboard()
+    }
+
+    fn take_appearance_change(&self) -> bool {
+        APPEARANCE_CHANGED.swap(false, Ordering::SeqCst)
+    }
+
+    fn increase_contrast(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            msg_send![workspace, accessibilityDisplayShouldIncreaseContrast]
+        }
+    }
+
+    fn text_scale(&self) -> f32 {
+        // The body text style follows the Text Size accessibility setting
+        // and is 13pt at the normal size
+        unsafe {
+            let style = NSString::alloc(nil).init_str("NSCTFontBodyUsage");
+            let font: id = msg_send![class!(NSFont), preferredFontForTextStyle: style options: nil];
+            if font.is_null() {
+                return 1.0;
+            }
+            let size: f64 = msg_send![font, pointSize];
+            (size / 13.0) as f32
+        }
+    }
+
+    fn tick(&self) {
+        unsafe {
+            expire_chord();
+            resume_if_due();
+            poll_system_state();
+        }
+    }
+
+    fn show(&self, _window: AnyWindowHandle, placement: Placement, _cx: &mut AsyncApp) {
+        unsafe { show_window_now(placement) };
+    }
+
+    fn hide(&self, window: &mut Window) {
+        let Some(ns_window) = ns_window_of(window) else {
+            return;
+        };
+        // Through hide_window, as the Escape monitor used to, so the
+        // previous app gets focus back
+        let visible_ptr = GLOBAL_VISIBLE.load(Ordering::SeqCst) as *mut Arc<AtomicBool>;
+        unsafe {
+            if visible_ptr.is_null() {
+                order_out(ns_window);
+            } else {
+                hide_window(ns_window, &*visible_ptr);
+            }
+        }
+    }
+
+    fn set_expanded(&self, expanded: bool) {
+        unsafe { set_expanded(expanded) };
+    }
+
+    fn set_pinned(&self, pinned: bool) {
+        set_pinned(pinned);
+    }
+
+    fn apply_preferences(&self, prefs: &Preferences) {
+        apply_preferences(prefs);
+    }
+
+    fn set_dark_theme(&self, dark: bool) {
+        if DARK_THEME.swap(dark, Ordering::SeqCst) == dark {
+            return;
+        }
+        let effect = VIBRANCY_VIEW.load(Ordering::SeqCst) as id;
+        if !effect.is_null() {
+            unsafe {
+                let _: () = msg_send![effect, setAppearance: vibrancy_appearance()];
+            }
+        }
+    }
+
+    fn submit_and_paste(&self, text: String, _window: &mut Window, _cx: &mut App) {
+        unsafe { submit_and_paste(&text) };
+    }
+
+    fn submit_to_clipboard(&self, text: String, _window: &mut Window, _cx: &mut App) {
+        unsafe { copy_submission(&text) };
+    }
+
+    fn paste_target_name(&self) -> Option<String> {
+        unsafe {
+            let app = GLOBAL_PREVIOUS_APP.load(Ordering::SeqCst) as id;
+            if app.is_null() {
+                return None;
+            }
+            ns_string_to_string(msg_send![app, localizedName])
+        }
+    }
+
+    fn paste_targets(&self) -> Vec<(String, String)> {
+        unsafe { paste_targets() }
+    }
+
+    fn submit_and_paste_to(&self, text: String, target: &str, _window: &mut Window, _cx: &mut App) {
+        unsafe {
+            set_paste_target(target);
+            submit_and_paste(&text);
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        get_error()
+    }
+
+    fn hotkey_conflict(&self, key_code: u32, modifiers: u32) -> Option<String> {
+        unsafe { hotkey_conflict(key_code, modifiers) }
+    }
+
+    fn accessibility_trusted(&self) -> Option<bool> {
+        Some(unsafe { AXIsProcessTrusted() })
+    }
+
+    fn paste_blocked(&self) -> Option<String> {
+        unsafe { secure_input_warning() }
+    }
+
+    fn set_excluded_apps(&self, apps: &[String]) {
+        if let Ok(mut excluded) = EXCLUDED_APPS.lock() {
+            excluded.set(apps);
+        }
+    }
+
+    fn running_apps(&self) -> Option<Vec<(String, String)>> {
+        Some(unsafe { running_apps() })
+    }
+
+    fn set_current_draft(&self, text: &str) {
+        if let Ok(mut draft) = CURRENT_DRAFT.lock() {
+            text.clone_into(&mut draft);
+        }
+        unsafe { post_accessibility_notification("AXValueChanged") };
+    }
+
+    fn accessibility_settings_url(&self) -> Option<&'static str> {
+        Some(ACCESSIBILITY_SETTINGS_URL)
+    }
+}
@@ -0,0 +1,459 @@
+//! Global hotkey and show/hide for Linux. Wayland sessions go through the
+//! XDG Desktop Portal's GlobalShortcuts interface; X11 sessions grab the key
+//! on the root window. Either way the listener runs on its own thread and
+//! raises flags that the GPUI side polls, as hotkey.rs does on macOS. The
+//! connection or portal session stays reachable from the GPUI side, so a
+//! new hotkey replaces the old one without a restart.
+
+use anyhow::Context as _;
+use ashpd::desktop::Session;
+use ashpd::desktop::global_shortcuts::GlobalShortcuts;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use x11rb::protocol::xproto::ModMask;
+use x11rb::rust_connection::RustConnection;
+
+use gpui::{AnyWindowHandle, App, AsyncApp, ClipboardItem, Window};
+use zeditor_core::EditorHost;
+
+use crate::i18n::{t, t_args};
+use crate::platform::{Placement, Platform};
+use crate::preferences::{HotkeyAction, HotkeyConfig, Preferences};
+
+// Carbon modifier bits, as stored in HotkeyConfig
+const CARBON_CMD: u32 = 1 << 8;
+const CARBON_SHIFT: u32 = 1 << 9;
+const CARBON_ALT: u32 = 1 << 11;
+const CARBON_CTRL: u32 = 1 << 12;
+
+/// Id the portal reports back when our shortcut fires
+const SHORTCUT_ID: &str = "toggle";
+
+static SHOW_REQUESTED: AtomicBool = AtomicBool::new(false);
+static HIDE_REQUESTED: AtomicBool = AtomicBool::new(false);
+static VISIBLE: AtomicBool = AtomicBool::new(false);
+static GLOBAL_ERROR: Mutex<Option<String>> = Mutex::new(None);
+static LISTENER: Mutex<Option<Listener>> = Mutex::new(None);
+
+/// What the listener thread has registered, shared with it.
+enum Listener {
+    /// The connection it waits on, and the root window grab: (modifiers,
+    /// keycode)
+    X11 {
+        conn: Arc<RustConnection>,
+        root: u32,
+        grab: (ModMask, u8),
+    },
+    /// The proxy it receives activations on, and the session our shortcut
+    /// is bound in; None while it's being replaced
+    Portal {
+        portal: Arc<GlobalShortcuts<'static>>,
+        session: Option<Session<'static, GlobalShortcuts<'static>>>,
+    },
+}
+
+/// Check if a show was requested (hotkey pressed while hidden).
+/// Atomically swaps the flag and returns the old value.
+fn is_show_requested() -> bool {
+    SHOW_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Check if a hide was requested (hotkey pressed while shown).
+/// Atomically swaps the flag and returns the old value.
+fn is_hide_requested() -> bool {
+    HIDE_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Record whether the popup is on screen, so the hotkey knows which way
+/// to toggle.
+fn set_visible(visible: bool) {
+    VISIBLE.store(visible, Ordering::SeqCst);
+}
+
+/// Get the current error message, if any.
+fn get_error() -> Option<String> {
+    GLOBAL_ERROR.lock().ok().and_then(|g| g.clone())
+}
+
+fn set_error(err: Option<String>) {
+    if let Ok(mut g) = GLOBAL_ERROR.lock() {
+        *g = err;
+    }
+}
+
+/// Show why the hotkey couldn't be registered, with the whole chain of
+/// causes.
+fn registration_failed(err: &anyhow::Error) {
+    set_error(Some(t_args("hotkey.registration_failed", &[("error", &format!("{:#}", err))])));
+}
+
+fn toggle() {
+    if VISIBLE.load(Ordering::SeqCst) {
+        HIDE_REQUESTED.store(true, Ordering::SeqCst);
+    } else {
+        SHOW_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Start listening for the hotkey on a background thread. The recorded
+/// Cmd modifier maps to the Super key.
+fn register_hotkey(config: &HotkeyConfig) {
+    let shortcut = match Shortcut::from_config(config) {
+        Ok(shortcut) => shortcut,
+        Err(message) => {
+            set_error(Some(message));
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        let result = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            pollster::block_on(listen_portal(&shortcut))
+        } else {
+            listen_x11(&shortcut)
+        };
+        if let Err(err) = result {
+            registration_failed(&err);
+        }
+    });
+}
+
+/// A hotkey in a form both backends can use: Carbon modifier bits plus the
+/// lowercase key name from the display string ("e", "space", "f1", ...).
+struct Shortcut {
+    modifiers: u32,
+    key: String,
+}
+
+impl Shortcut {
+    fn from_config(config: &HotkeyConfig) -> Result<Self, String> {
+        if let Some(chord) = &config.chord {
+            return Err(t_args(
+                "hotkey.chords_unsupported",
+                &[("first", &config.display_string), ("second", &chord.display_string)],
+            ));
+        }
+        let key = config.display_string.rsplit('+').next().unwrap_or_default().trim().to_lowercase();
+        if key.is_empty() {
+            return Err(t_args("hotkey.unsupported", &[("hotkey", &config.display_string)]));
+        }
+        Ok(Self {
+            modifiers: config.modifiers,
+            key,
+        })
+    }
+
+    /// Trigger string in the shortcuts spec format, e.g. "LOGO+SHIFT+e".
+    fn portal_trigger(&self) -> String {
+        let mut trigger = String::new();
+        for (bit, name) in [
+            (CARBON_CTRL, "CTRL+"),
+            (CARBON_ALT, "ALT+"),
+            (CARBON_SHIFT, "SHIFT+"),
+            (CARBON_CMD, "LOGO+"),
+        ] {
+            if self.modifiers & bit != 0 {
+                trigger.push_str(name);
+            }
+        }
+        trigger.push_str(&self.key);
+        trigger
+    }
+
+    fn x11_modifiers(&self) -> ModMask {
+        let mut mask = ModMask::from(0u16);
+        for (bit, modifier) in [
+            (CARBON_CTRL, ModMask::CONTROL),
+            (CARBON_ALT, ModMask::M1),
+            (CARBON_SHIFT, ModMask::SHIFT),
+            (CARBON_CMD, ModMask::M4),
+        ] {
+            if self.modifiers & bit != 0 {
+                mask = mask | modifier;
+            }
+        }
+        mask
+    }
+
+    /// X keysym for the key; Latin-1 characters are their own keysym.
+    fn keysym(&self) -> Option<u32> {
+        match self.key.as_str() {
+            "space" => Some(0x20),
+            "escape" => Some(0xff1b),
+            key if key.len() > 1 && key.starts_with('f') => {
+                let n: u32 = key[1..].parse().ok()?;
+                (1..=12).contains(&n).then(|| 0xffbe + n - 1)
+            }
+            key => {
+                let mut chars = key.chars();
+                let c = chars.next()?;
+                (chars.next().is_none() && c.is_ascii()).then_some(c as u32)
+            }
+        }
+    }
+}
+
+async fn listen_portal(shortcut: &Shortcut) -> anyhow::Result<()> {
+    use futures_util::StreamExt;
+
+    let portal = Arc::new(GlobalShortcuts::new().await?);
+    let session = bind_portal(&portal, shortcut).await?;
+    if let Ok(mut listener) = LISTENER.lock() {
+        *listener = Some(Listener::Portal {
+            portal: portal.clone(),
+            session: Some(session),
+        });
+    }
+    set_error(None);
+
+    // Activations come through the proxy whichever session is current
+    let mut activated = portal.receive_activated().await?;
+    while let Some(event) = activated.next().await {
+        if event.shortcut_id() == SHORTCUT_ID {
+            toggle();
+        }
+    }
+    Ok(())
+}
+
+/// Bind `shortcut` in a new portal session.
+async fn bind_portal(
+    portal: &GlobalShortcuts<'static>,
+    shortcut: &Shortcut,
+) -> anyhow::Result<Session<'static, GlobalShortcuts<'static>>> {
+    use ashpd::desktop::global_shortcuts::NewShortcut;
+
+    let session = portal.create_session().await?;
+    let trigger = shortcut.portal_trigger();
+    let new_shortcut = NewShortcut::new(SHORTCUT_ID, t("menu.toggle_shortcut"))
+        .preferred_trigger(trigger.as_str());
+    portal
+        .bind_shortcuts(&session, &[new_shortcut], None)
+        .await?
+        .response()?;
+    Ok(session)
+}
+
+/// Close the old portal session and bind `shortcut` in a new one, on a
+/// thread of its own as the portal calls block.
+fn rebind_portal(
+    portal: Arc<GlobalShortcuts<'static>>,
+    old: Option<Session<'static, GlobalShortcuts<'static>>>,
+    shortcut: Shortcut,
+) {
+    std::thread::spawn(move || {
+        let result = pollster::block_on(async {
+            if let Some(old) = old {
+                old.close().await.ok();
+            }
+            bind_portal(&portal, &shortcut).await
+        });
+        match result {
+            Ok(session) => {
+                if let Ok(mut listener) = LISTENER.lock()
+                    && let Some(Listener::Portal { session: current, .. }) = listener.as_mut()
+                {
+                    *current = Some(session);
+                }
+                set_error(None);
+            }
+            Err(err) => registration_failed(&err),
+        }
+    });
+}
+
+fn listen_x11(shortcut: &Shortcut) -> anyhow::Result<()> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::Event;
+
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let conn = Arc::new(conn);
+    let root = conn.setup().roots[screen_num].root;
+    let grab = x11_grab_for(&conn, shortcut)?;
+    grab_keys(&conn, root, grab)?;
+    if let Ok(mut listener) = LISTENER.lock() {
+        *listener = Some(Listener::X11 {
+            conn: conn.clone(),
+            root,
+            grab,
+        });
+    }
+    set_error(None);
+
+    loop {
+        if let Event::KeyPress(_) = conn.wait_for_event()? {
+            toggle();
+        }
+    }
+}
+
+/// Modifiers and keycode to grab for `shortcut`.
+fn x11_grab_for(conn: &RustConnection, shortcut: &Shortcut) -> anyhow::Result<(ModMask, u8)> {
+    let keysym = shortcut.keysym().context(t("hotkey.no_keysym"))?;
+    let keycode = keycode_for(conn, keysym)?.context(t("hotkey.key_not_on_keyboard"))?;
+    Ok((shortcut.x11_modifiers(), keycode))
+}
+
+/// Caps Lock and Num Lock count as modifiers to X; every combination is
+/// grabbed so the hotkey works with them on.
+fn lock_combinations() -> [ModMask; 4] {
+    [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+fn grab_keys(conn: &RustConnection, root: u32, (modifiers, keycode): (ModMask, u8)) -> anyhow::Result<()> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode};
+
+    for locks in lock_combinations() {
+        let grabbed = conn
+            .grab_key(true, root, modifiers | locks, keycode, GrabMode::ASYNC, GrabMode::ASYNC)?
+            .check();
+        if let Err(err) = grabbed {
+            // Don't leave the combinations that did work grabbed
+            ungrab_keys(conn, root, (modifiers, keycode)).ok();
+            return Err(err).context(t("hotkey.taken"));
+        }
+    }
+    conn.flush()?;
+    Ok(())
+}
+
+fn ungrab_keys(conn: &RustConnection, root: u32, (modifiers, keycode): (ModMask, u8)) -> anyhow::Result<()> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    for locks in lock_combinations() {
+        conn.ungrab_key(keycode, root, modifiers | locks)?;
+    }
+    conn.flush()?;
+    Ok(())
+}
+
+/// Swap the root window grab over to `shortcut`, keeping the old one when
+/// the new one can't be had.
+fn regrab_x11(conn: &RustConnection, root: u32, grab: &mut (ModMask, u8), shortcut: &Shortcut) {
+    let new_grab = match x11_grab_for(conn, shortcut) {
+        Ok(new_grab) => new_grab,
+        Err(err) => {
+            registration_failed(&err);
+            return;
+        }
+    };
+    ungrab_keys(conn, root, *grab).ok();
+    match grab_keys(conn, root, new_grab) {
+        Ok(()) => {
+            *grab = new_grab;
+            set_error(None);
+        }
+        Err(err) => {
+            grab_keys(conn, root, *grab).ok();
+            registration_failed(&err);
+        }
+    }
+}
+
+/// First keycode whose mapping produces `keysym`.
+fn keycode_for(conn: &impl x11rb::connection::Connection, keysym: u32) -> anyhow::Result<Option<u8>> {
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+    let per_keycode = mapping.keysyms_per_keycode.max(1) as usize;
+    Ok(mapping
+        .keysyms
+        .chunks(per_keycode)
+        .position(|syms| syms.contains(&keysym))
+        .map(|i| min + i as u8))
+}
+
+/// GNOME's Large Text / text scaling factor, 1.0 when it can't be read.
+fn text_scaling_factor() -> f32 {
+    std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "text-scaling-factor"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8_lossy(&output.stdout).trim().parse().ok())
+        .filter(|factor: &f32| *factor > 0.)
+        .unwrap_or(1.0)
+}
+
+/// Portal or X11 key grabs. There's no tray menu, and submitting only puts
+/// the text on the clipboard: neither backend can type into other windows.
+pub struct LinuxPlatform;
+
+impl EditorHost for LinuxPlatform {
+    fn text(&self, key: &str) -> Option<String> {
+        crate::i18n::lookup(key).map(str::to_string)
+    }
+}
+
+impl Platform for LinuxPlatform {
+    fn install(&self, _window: &mut Window, prefs: &Preferences) {
+        register_hotkey(&prefs.hotkey);
+    }
+
+    fn set_hotkey(&self, config: &HotkeyConfig) {
+        let shortcut = match Shortcut::from_config(config) {
+            Ok(shortcut) => shortcut,
+            Err(message) => {
+                set_error(Some(message));
+                return;
+            }
+        };
+        let Ok(mut listener) = LISTENER.lock() else {
+            return;
+        };
+        if listener.is_none() {
+            // The first registration failed; try again from scratch
+            drop(listener);
+            register_hotkey(config);
+            return;
+        }
+        match listener.as_mut() {
+            Some(Listener::X11 { conn, root, grab }) => regrab_x11(conn, *root, grab, &shortcut),
+            Some(Listener::Portal { portal, session }) => {
+                rebind_portal(portal.clone(), session.take(), shortcut)
+            }
+            None => {}
+        }
+    }
+
+    fn take_show_request(&self) -> Option<HotkeyAction> {
+        is_show_requested().then_some(HotkeyAction::Toggle)
+    }
+
+    fn take_hide_request(&self) -> bool {
+        is_hide_requested()
+    }
+
+    fn show(&self, window: AnyWindowHandle, _placement: Placement, cx: &mut AsyncApp) {
+        window
+            .update(cx, |_, window, _| window.activate_window())
+            .ok();
+        set_visible(true);
+    }
+
+    fn hide(&self, window: &mut Window) {
+        window.minimize_window();
+        set_visible(false);
+    }
+
+    fn text_scale(&self) -> f32 {
+        text_scaling_factor()
+    }
+
+    fn submit_and_paste(&self, text: String, window: &mut Window, cx: &mut App) {
+        cx.write_to_clipboard(ClipboardItem::new_string(text));
+        self.hide(window);
+    }
+
+    fn error(&self) -> Option<String> {
+        get_error()
+    }
+}
@@ -0,0 +1,230 @@
+//! What the macOS hotkeys decide, kept apart from the Carbon and AppKit
+//! calls in hotkey.rs: chords, pausing and excluded apps. Each is a plain
+//! struct that hotkey.rs keeps behind a static and acts on.
+
+use std::time::{Duration, Instant};
+
+/// How long to wait for the second key of a chord
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Second key of the main hotkey's chord.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChordKey {
+    pub key_code: u32,
+    pub modifiers: u32,
+    /// Display string of the main hotkey, for the hint
+    pub prefix: String,
+}
+
+/// What a press of the main hotkey should do.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MainPress {
+    /// Show or hide the popup
+    Toggle,
+    /// Register the chord's second key and wait for it
+    Arm(ChordKey),
+    /// Give up on the pending chord
+    Disarm,
+}
+
+/// The main hotkey's chord, if it has one, and whether its second key is
+/// being waited for.
+#[derive(Debug, Default)]
+pub struct Chord {
+    second: Option<ChordKey>,
+    /// Temporary hotkey ref of the armed second key, and when it expires
+    armed: Option<(usize, Instant)>,
+}
+
+impl Chord {
+    pub const fn new() -> Self {
+        Self {
+            second: None,
+            armed: None,
+        }
+    }
+
+    /// Make the main hotkey a chord prefix for `second`, or a plain combo.
+    pub fn set_second(&mut self, second: Option<ChordKey>) {
+        self.second = second;
+    }
+
+    /// Decide what the main hotkey does. A chord only starts while the
+    /// popup is hidden, and the prefix again cancels a pending one.
+    pub fn press_main(&self, visible: bool) -> MainPress {
+        match &self.second {
+            Some(_) if visible => MainPress::Toggle,
+            Some(_) if self.armed.is_some() => MainPress::Disarm,
+            Some(second) => MainPress::Arm(second.clone()),
+            None => MainPress::Toggle,
+        }
+    }
+
+    /// The second key was registered as `hotkey_ref` at `now`.
+    pub fn arm(&mut self, hotkey_ref: usize, now: Instant) {
+        self.armed = Some((hotkey_ref, now + CHORD_TIMEOUT));
+    }
+
+    /// Stop waiting; the hotkey ref to unregister, if one was armed.
+    pub fn disarm(&mut self) -> Option<usize> {
+        self.armed.take().map(|(hotkey_ref, _)| hotkey_ref)
+    }
+
+    /// Whether the second key was waited for and didn't come in time.
+    pub fn expired(&self, now: Instant) -> bool {
+        self.armed.is_some_and(|(_, deadline)| now >= deadline)
+    }
+}
+
+/// Whether the hotkeys are unregistered so other apps can use them, and
+/// until when.
+#[derive(Debug, Default)]
+pub struct Pause {
+    paused: bool,
+    resume_at: Option<Instant>,
+}
+
+impl Pause {
+    pub const fn new() -> Self {
+        Self {
+            paused: false,
+            resume_at: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause until `resume`, or given a duration, until it runs out from
+    /// `now`. Pausing again only moves the end. True if this started the
+    /// pause, so the hotkeys need unregistering.
+    pub fn pause(&mut self, duration: Option<Duration>, now: Instant) -> bool {
+        self.resume_at = duration.map(|d| now + d);
+        !std::mem::replace(&mut self.paused, true)
+    }
+
+    /// True if this ended a pause, so the hotkeys need registering again.
+    pub fn resume(&mut self) -> bool {
+        self.resume_at = None;
+        std::mem::replace(&mut self.paused, false)
+    }
+
+    /// Whether a timed pause has run out by `now`.
+    pub fn due(&self, now: Instant) -> bool {
+        self.paused && self.resume_at.is_some_and(|at| now >= at)
+    }
+}
+
+/// Apps, by bundle identifier, in which the hotkeys do nothing.
+#[derive(Debug, Default)]
+pub struct ExcludedApps(Vec<String>);
+
+impl ExcludedApps {
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn set(&mut self, apps: &[String]) {
+        self.0 = apps.to_vec();
+    }
+
+    /// Whether a hotkey should be ignored. Hiding the popup always works;
+    /// `frontmost` is only asked for when there's a list to check.
+    pub fn blocks(&self, visible: bool, frontmost: impl FnOnce() -> Option<String>) -> bool {
+        if visible || self.0.is_empty() {
+            return false;
+        }
+        frontmost().is_some_and(|bundle_id| self.0.contains(&bundle_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord() -> Chord {
+        let mut chord = Chord::new();
+        chord.set_second(Some(ChordKey {
+            key_code: 14,
+            modifiers: 0,
+            prefix: "Cmd+K".to_string(),
+        }));
+        chord
+    }
+
+    #[test]
+    fn plain_hotkey_toggles() {
+        assert_eq!(Chord::new().press_main(false), MainPress::Toggle);
+    }
+
+    #[test]
+    fn chord_arms_while_hidden_and_toggles_while_shown() {
+        let chord = chord();
+        assert!(matches!(chord.press_main(false), MainPress::Arm(key) if key.key_code == 14));
+        assert_eq!(chord.press_main(true), MainPress::Toggle);
+    }
+
+    #[test]
+    fn prefix_again_cancels_armed_chord() {
+        let mut chord = chord();
+        chord.arm(7, Instant::now());
+        assert_eq!(chord.press_main(false), MainPress::Disarm);
+        assert_eq!(chord.disarm(), Some(7));
+        assert_eq!(chord.disarm(), None);
+        assert!(matches!(chord.press_main(false), MainPress::Arm(_)));
+    }
+
+    #[test]
+    fn armed_chord_expires_after_timeout() {
+        let mut chord = chord();
+        let now = Instant::now();
+        assert!(!chord.expired(now));
+        chord.arm(7, now);
+        assert!(!chord.expired(now + CHORD_TIMEOUT / 2));
+        assert!(chord.expired(now + CHORD_TIMEOUT));
+    }
+
+    #[test]
+    fn pause_and_resume_report_transitions() {
+        let mut pause = Pause::new();
+        let now = Instant::now();
+        assert!(!pause.resume());
+        assert!(pause.pause(None, now));
+        assert!(!pause.pause(None, now));
+        assert!(pause.is_paused());
+        assert!(pause.resume());
+        assert!(!pause.is_paused());
+    }
+
+    #[test]
+    fn timed_pause_is_due_once_it_runs_out() {
+        let mut pause = Pause::new();
+        let now = Instant::now();
+        pause.pause(Some(Duration::from_secs(60)), now);
+        assert!(!pause.due(now + Duration::from_secs(59)));
+        assert!(pause.due(now + Duration::from_secs(60)));
+        // Pausing again without a duration holds until resumed
+        pause.pause(None, now);
+        assert!(!pause.due(now + Duration::from_secs(3600)));
+        pause.resume();
+        assert!(!pause.due(now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn excluded_app_blocks_only_while_hidden() {
+        let mut excluded = ExcludedApps::new();
+        excluded.set(&["com.example.Game".to_string()]);
+        let game = || Some("com.example.Game".to_string());
+        assert!(excluded.blocks(false, game));
+        assert!(!excluded.blocks(true, game));
+        assert!(!excluded.blocks(false, || Some("com.example.Notes".to_string())));
+        assert!(!excluded.blocks(false, || None));
+    }
+
+    #[test]
+    fn empty_exclusion_list_skips_the_lookup() {
+        let excluded = ExcludedApps::new();
+        assert!(!excluded.blocks(false, || panic!("looked up the frontmost app")));
+    }
+}
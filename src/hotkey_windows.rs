@@ -0,0 +1,517 @@
+//! Global hotkey, notification-area icon and paste simulation for Windows,
+//! mirroring what hotkey.rs does on macOS. A listener thread owns a hidden
+//! message window that receives `WM_HOTKEY` and tray icon messages, and
+//! raises flags that the GPUI side polls.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Registry::{HKEY_CURRENT_USER, RRF_RT_REG_DWORD, RegGetValueW};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    HOT_KEY_MODIFIERS, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, KEYBDINPUT,
+    KEYEVENTF_KEYUP, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN, RegisterHotKey,
+    SendInput, UnregisterHotKey, VIRTUAL_KEY, VK_CONTROL,
+};
+use windows::Win32::UI::Shell::{
+    NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NOTIFYICONDATAW, Shell_NotifyIconW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DispatchMessageW,
+    GetCursorPos, GetForegroundWindow, GetMessageW, HWND_MESSAGE, IDI_APPLICATION, LoadIconW,
+    MF_SEPARATOR, MF_STRING, MSG, PostMessageW, RegisterClassW, SetForegroundWindow, TPM_RETURNCMD,
+    TPM_RIGHTBUTTON, TrackPopupMenu, TranslateMessage, WINDOW_EX_STYLE, WINDOW_STYLE, WM_APP,
+    WM_CONTEXTMENU, WM_HOTKEY, WM_LBUTTONUP, WM_RBUTTONUP, WNDCLASSW,
+};
+use windows::core::{HSTRING, PCWSTR, w};
+
+use gpui::{AnyWindowHandle, App, AsyncApp, ClipboardItem, Window};
+use zeditor_core::EditorHost;
+
+use crate::i18n::{t, t_args};
+use crate::platform::{MenuRequest, Placement, Platform};
+use crate::preferences::{ActionHotkey, HotkeyAction, HotkeyConfig, Preferences};
+
+// Carbon modifier bits, as stored in HotkeyConfig
+const CARBON_CMD: u32 = 1 << 8;
+const CARBON_SHIFT: u32 = 1 << 9;
+const CARBON_ALT: u32 = 1 << 11;
+const CARBON_CTRL: u32 = 1 << 12;
+
+const HOTKEY_ID: i32 = 1;
+/// Action hotkeys get ids from here on, in the order of `Preferences::hotkeys`
+const FIRST_ACTION_HOTKEY_ID: i32 = 2;
+/// Callback message for tray icon clicks
+const WM_TRAY: u32 = WM_APP + 1;
+/// Asks the listener thread to swap the hotkey; wparam is the virtual key,
+/// lparam the modifiers
+const WM_SET_HOTKEY: u32 = WM_APP + 2;
+
+// Tray menu item ids
+const MENU_TOGGLE: usize = 1;
+const MENU_PREFERENCES: usize = 2;
+const MENU_QUIT: usize = 3;
+
+static SHOW_REQUESTED: AtomicBool = AtomicBool::new(false);
+static HIDE_REQUESTED: AtomicBool = AtomicBool::new(false);
+static OPEN_PREFS_REQUESTED: AtomicBool = AtomicBool::new(false);
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static VISIBLE: AtomicBool = AtomicBool::new(false);
+/// Window that was in front when the hotkey fired, to paste back into
+static PREVIOUS_WINDOW: AtomicIsize = AtomicIsize::new(0);
+static GLOBAL_ERROR: Mutex<Option<String>> = Mutex::new(None);
+/// The listener thread's message window, once it exists
+static MESSAGE_WINDOW: AtomicIsize = AtomicIsize::new(0);
+/// Action of each registered action hotkey, indexed by id minus
+/// `FIRST_ACTION_HOTKEY_ID`
+static ACTION_HOTKEYS: Mutex<Vec<HotkeyAction>> = Mutex::new(Vec::new());
+/// Action of the hotkey that last asked to show the popup
+static SHOW_ACTION: Mutex<Option<HotkeyAction>> = Mutex::new(None);
+
+/// Check if a show was requested (hotkey or tray click while hidden).
+/// Atomically swaps the flag and returns the old value.
+fn is_show_requested() -> bool {
+    SHOW_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Check if a hide was requested (hotkey or tray click while shown).
+/// Atomically swaps the flag and returns the old value.
+fn is_hide_requested() -> bool {
+    HIDE_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Check if the preferences window was requested from the tray menu.
+/// Atomically swaps the flag and returns the old value.
+fn is_prefs_requested() -> bool {
+    OPEN_PREFS_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Check if Quit was picked from the tray menu.
+fn is_quit_requested() -> bool {
+    QUIT_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Record whether the popup is on screen, so the hotkey knows which way
+/// to toggle.
+fn set_visible(visible: bool) {
+    VISIBLE.store(visible, Ordering::SeqCst);
+}
+
+/// Get the current error message, if any.
+fn get_error() -> Option<String> {
+    GLOBAL_ERROR.lock().ok().and_then(|g| g.clone())
+}
+
+fn set_error(err: Option<String>) {
+    if let Ok(mut g) = GLOBAL_ERROR.lock() {
+        *g = err;
+    }
+}
+
+fn toggle() {
+    toggle_for(HotkeyAction::Toggle);
+}
+
+/// Show the popup for `action`, or hide it if it's already shown.
+fn toggle_for(action: HotkeyAction) {
+    if VISIBLE.load(Ordering::SeqCst) {
+        HIDE_REQUESTED.store(true, Ordering::SeqCst);
+    } else {
+        let foreground = unsafe { GetForegroundWindow() };
+        PREVIOUS_WINDOW.store(foreground.0 as isize, Ordering::SeqCst);
+        if let Ok(mut g) = SHOW_ACTION.lock() {
+            *g = Some(action);
+        }
+        SHOW_REQUESTED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Virtual key and modifiers for `config`. `RegisterHotKey` takes single
+/// keystrokes only, so chords are refused rather than registering just
+/// their first key.
+fn hotkey_keys(config: &HotkeyConfig) -> Result<(u32, HOT_KEY_MODIFIERS), String> {
+    if let Some(chord) = &config.chord {
+        return Err(t_args(
+            "hotkey.chords_unsupported",
+            &[("first", &config.display_string), ("second", &chord.display_string)],
+        ));
+    }
+    let vk = virtual_key(&config.display_string)
+        .ok_or_else(|| t_args("hotkey.unsupported", &[("hotkey", &config.display_string)]))?;
+    Ok((vk, hotkey_modifiers(config.modifiers)))
+}
+
+/// Start the listener thread: register the hotkeys, add the tray icon and
+/// pump messages. The recorded Cmd modifier maps to the Windows key.
+fn register_hotkey(config: &HotkeyConfig, actions: Vec<ActionHotkey>) {
+    let (vk, modifiers) = match hotkey_keys(config) {
+        Ok(keys) => keys,
+        Err(message) => {
+            set_error(Some(message));
+            return;
+        }
+    };
+    std::thread::spawn(move || unsafe {
+        if let Err(err) = run_message_loop(vk, modifiers, &actions) {
+            set_error(Some(t_args("hotkey.registration_failed", &[("error", &err)])));
+        }
+    });
+}
+
+unsafe fn run_message_loop(
+    vk: u32,
+    modifiers: HOT_KEY_MODIFIERS,
+    actions: &[ActionHotkey],
+) -> windows::core::Result<()> {
+    let instance = GetModuleHandleW(None)?;
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        hInstance: instance.into(),
+        lpszClassName: w!("ZeditorMessageWindow"),
+        ..Default::default()
+    };
+    RegisterClassW(&class);
+    let hwnd = CreateWindowExW(
+        WINDOW_EX_STYLE::default(),
+        w!("ZeditorMessageWindow"),
+        w!("Zeditor"),
+        WINDOW_STYLE::default(),
+        0,
+        0,
+        0,
+        0,
+        Some(HWND_MESSAGE),
+        None,
+        Some(instance.into()),
+        None,
+    )?;
+
+    MESSAGE_WINDOW.store(hwnd.0 as isize, Ordering::SeqCst);
+    add_tray_icon(hwnd);
+    match RegisterHotKey(Some(hwnd), HOTKEY_ID, modifiers | MOD_NOREPEAT, vk) {
+        Ok(()) => set_error(None),
+        Err(err) => set_error(Some(t_args("hotkey.registration_failed", &[("error", &err)]))),
+    }
+    register_action_hotkeys(hwnd, actions);
+
+    let mut msg = MSG::default();
+    while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+        let _ = TranslateMessage(&msg);
+        DispatchMessageW(&msg);
+    }
+    Ok(())
+}
+
+/// Register the action hotkeys on the message window. Failures are
+/// reported but keep their id, so ids stay in step with `ACTION_HOTKEYS`.
+/// Pasting the last submission and loading the clipboard need the macOS
+/// pasteboard handling, so those actions are reported as unsupported.
+unsafe fn register_action_hotkeys(hwnd: HWND, actions: &[ActionHotkey]) {
+    let Ok(mut registered) = ACTION_HOTKEYS.lock() else {
+        return;
+    };
+    for (index, hotkey) in actions.iter().enumerate() {
+        let id = FIRST_ACTION_HOTKEY_ID + index as i32;
+        registered.push(hotkey.action);
+        let (vk, modifiers) = match hotkey.action {
+            HotkeyAction::PasteLast | HotkeyAction::ShowClipboard => {
+                set_error(Some(t_args(
+                    "hotkey.action_unsupported",
+                    &[("hotkey", &hotkey.hotkey.display_string)],
+                )));
+                continue;
+            }
+            _ => match hotkey_keys(&hotkey.hotkey) {
+                Ok(keys) => keys,
+                Err(message) => {
+                    set_error(Some(message));
+                    continue;
+                }
+            },
+        };
+        if let Err(err) = RegisterHotKey(Some(hwnd), id, modifiers | MOD_NOREPEAT, vk) {
+            set_error(Some(t_args("hotkey.registration_failed", &[("error", &err)])));
+        }
+    }
+}
+
+unsafe fn add_tray_icon(hwnd: HWND) {
+    let mut data = NOTIFYICONDATAW {
+        cbSize: std::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: 1,
+        uFlags: NIF_ICON | NIF_MESSAGE | NIF_TIP,
+        uCallbackMessage: WM_TRAY,
+        hIcon: LoadIconW(None, IDI_APPLICATION).unwrap_or_default(),
+        ..Default::default()
+    };
+    let tip: Vec<u16> = "Zeditor".encode_utf16().collect();
+    data.szTip[..tip.len()].copy_from_slice(&tip);
+    if !Shell_NotifyIconW(NIM_ADD, &data).as_bool() {
+        set_error(Some(t("hotkey.tray_icon_failed").to_string()));
+    }
+}
+
+unsafe fn show_tray_menu(hwnd: HWND) {
+    let Ok(menu) = CreatePopupMenu() else {
+        return;
+    };
+    let toggle_label = HSTRING::from(if VISIBLE.load(Ordering::SeqCst) {
+        t("menu.hide")
+    } else {
+        t("menu.show")
+    });
+    let preferences_label = HSTRING::from(t("menu.preferences"));
+    let quit_label = HSTRING::from(t("menu.quit"));
+    let _ = AppendMenuW(menu, MF_STRING, MENU_TOGGLE, PCWSTR(toggle_label.as_ptr()));
+    let _ = AppendMenuW(menu, MF_STRING, MENU_PREFERENCES, PCWSTR(preferences_label.as_ptr()));
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, None);
+    let _ = AppendMenuW(menu, MF_STRING, MENU_QUIT, PCWSTR(quit_label.as_ptr()));
+
+    let mut cursor = POINT::default();
+    let _ = GetCursorPos(&mut cursor);
+    // Without this the menu doesn't close when clicking elsewhere
+    let _ = SetForegroundWindow(hwnd);
+    let picked = TrackPopupMenu(
+        menu,
+        TPM_RETURNCMD | TPM_RIGHTBUTTON,
+        cursor.x,
+        cursor.y,
+        None,
+        hwnd,
+        None,
+    );
+    let _ = DestroyMenu(menu);
+
+    match picked.0 as usize {
+        MENU_TOGGLE => toggle(),
+        MENU_PREFERENCES => OPEN_PREFS_REQUESTED.store(true, Ordering::SeqCst),
+        MENU_QUIT => QUIT_REQUESTED.store(true, Ordering::SeqCst),
+        _ => {}
+    }
+}
+
+unsafe extern "system" fn window_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_HOTKEY if wparam.0 as i32 == HOTKEY_ID => {
+            toggle();
+            LRESULT(0)
+        }
+        WM_HOTKEY => {
+            let index = wparam.0 as i32 - FIRST_ACTION_HOTKEY_ID;
+            let action = ACTION_HOTKEYS
+                .lock()
+                .ok()
+                .and_then(|actions| usize::try_from(index).ok().and_then(|i| actions.get(i).copied()));
+            if let Some(action) = action {
+                toggle_for(action);
+            }
+            LRESULT(0)
+        }
+        WM_SET_HOTKEY => {
+            let _ = UnregisterHotKey(Some(hwnd), HOTKEY_ID);
+            let modifiers = HOT_KEY_MODIFIERS(lparam.0 as u32);
+            match RegisterHotKey(Some(hwnd), HOTKEY_ID, modifiers | MOD_NOREPEAT, wparam.0 as u32) {
+                Ok(()) => set_error(None),
+                Err(err) => set_error(Some(t_args("hotkey.registration_failed", &[("error", &err)]))),
+            }
+            LRESULT(0)
+        }
+        WM_TRAY => {
+            match lparam.0 as u32 {
+                WM_LBUTTONUP => toggle(),
+                WM_RBUTTONUP | WM_CONTEXTMENU => show_tray_menu(hwnd),
+                _ => {}
+            }
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn hotkey_modifiers(carbon: u32) -> HOT_KEY_MODIFIERS {
+    let mut modifiers = HOT_KEY_MODIFIERS(0);
+    for (bit, modifier) in [
+        (CARBON_CTRL, MOD_CONTROL),
+        (CARBON_ALT, MOD_ALT),
+        (CARBON_SHIFT, MOD_SHIFT),
+        (CARBON_CMD, MOD_WIN),
+    ] {
+        if carbon & bit != 0 {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+/// Windows virtual-key code for the key at the end of a display string
+/// like "Cmd+Shift+E".
+fn virtual_key(display_string: &str) -> Option<u32> {
+    let key = display_string.rsplit('+').next()?.trim().to_uppercase();
+    match key.as_str() {
+        "SPACE" => Some(0x20),
+        "ESCAPE" => Some(0x1B),
+        "-" => Some(0xBD),
+        "=" => Some(0xBB),
+        "[" => Some(0xDB),
+        "]" => Some(0xDD),
+        "\\" => Some(0xDC),
+        ";" => Some(0xBA),
+        "'" => Some(0xDE),
+        "," => Some(0xBC),
+        "." => Some(0xBE),
+        "/" => Some(0xBF),
+        "`" => Some(0xC0),
+        key if key.len() > 1 && key.starts_with('F') => {
+            let n: u32 = key[1..].parse().ok()?;
+            (1..=12).contains(&n).then(|| 0x70 + n - 1)
+        }
+        key => {
+            let mut chars = key.chars();
+            let c = chars.next()?;
+            (chars.next().is_none() && c.is_ascii_alphanumeric()).then_some(c as u32)
+        }
+    }
+}
+
+/// Bring back the window that was in front before the popup opened and
+/// send it Ctrl+V. The text must already be on the clipboard.
+fn paste_into_previous_window() {
+    let previous = PREVIOUS_WINDOW.swap(0, Ordering::SeqCst);
+    unsafe {
+        if previous != 0 {
+            let _ = SetForegroundWindow(HWND(previous as *mut _));
+        }
+        let key = |vk: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS| INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: vk,
+                    dwFlags: flags,
+                    ..Default::default()
+                },
+            },
+        };
+        let v = VIRTUAL_KEY(b'V' as u16);
+        let inputs = [
+            key(VK_CONTROL, KEYBD_EVENT_FLAGS(0)),
+            key(v, KEYBD_EVENT_FLAGS(0)),
+            key(v, KEYEVENTF_KEYUP),
+            key(VK_CONTROL, KEYEVENTF_KEYUP),
+        ];
+        SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+    }
+}
+
+/// The "Make text bigger" accessibility setting, stored as a percentage.
+fn text_scale_factor() -> f32 {
+    let mut percent: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let result = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!("Software\\Microsoft\\Accessibility"),
+            w!("TextScaleFactor"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut percent as *mut u32 as *mut _),
+            Some(&mut size),
+        )
+    };
+    if result.is_ok() && percent > 0 {
+        percent as f32 / 100.
+    } else {
+        1.0
+    }
+}
+
+/// `RegisterHotKey`, a notification-area icon and Ctrl+V paste simulation.
+pub struct WindowsPlatform;
+
+impl EditorHost for WindowsPlatform {
+    fn text(&self, key: &str) -> Option<String> {
+        crate::i18n::lookup(key).map(str::to_string)
+    }
+}
+
+impl Platform for WindowsPlatform {
+    fn install(&self, _window: &mut Window, prefs: &Preferences) {
+        register_hotkey(&prefs.hotkey, prefs.hotkeys.clone());
+    }
+
+    fn set_hotkey(&self, config: &HotkeyConfig) {
+        let hwnd = MESSAGE_WINDOW.load(Ordering::SeqCst);
+        if hwnd == 0 {
+            // The first registration failed; action hotkeys need a restart
+            // anyway, like on macOS
+            register_hotkey(config, Vec::new());
+            return;
+        }
+        let (vk, modifiers) = match hotkey_keys(config) {
+            Ok(keys) => keys,
+            Err(message) => {
+                set_error(Some(message));
+                return;
+            }
+        };
+        unsafe {
+            let _ = PostMessageW(
+                Some(HWND(hwnd as *mut _)),
+                WM_SET_HOTKEY,
+                WPARAM(vk as usize),
+                LPARAM(modifiers.0 as isize),
+            );
+        }
+    }
+
+    fn take_show_request(&self) -> Option<HotkeyAction> {
+        is_show_requested().then(|| {
+            SHOW_ACTION
+                .lock()
+                .ok()
+                .and_then(|mut g| g.take())
+                .unwrap_or(HotkeyAction::Toggle)
+        })
+    }
+
+    fn take_hide_request(&self) -> bool {
+        is_hide_requested()
+    }
+
+    fn take_menu_request(&self) -> Option<MenuRequest> {
+        if is_prefs_requested() {
+            Some(MenuRequest::Preferences)
+        } else if is_quit_requested() {
+            Some(MenuRequest::Quit)
+        } else {
+            None
+        }
+    }
+
+    fn show(&self, window: AnyWindowHandle, _placement: Placement, cx: &mut AsyncApp) {
+        window
+            .update(cx, |_, window, _| window.activate_window())
+            .ok();
+        set_visible(true);
+    }
+
+    fn hide(&self, window: &mut Window) {
+        window.minimize_window();
+        set_visible(false);
+    }
+
+    fn text_scale(&self) -> f32 {
+        text_scale_factor()
+    }
+
+    fn submit_and_paste(&self, text: String, window: &mut Window, cx: &mut App) {
+        cx.write_to_clipboard(ClipboardItem::new_string(text));
+        self.hide(window);
+        paste_into_previous_window();
+    }
+
+    fn error(&self) -> Option<String> {
+        get_error()
+    }
+}
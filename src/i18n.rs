@@ -0,0 +1,140 @@
+//! The UI's text in the user's language. Strings are looked up by key in a
+//! locale table: `locales/<code>.json` among the app's assets, with
+//! `locales/<code>.json` in the data directory added over it, so a
+//! translation can be tried out (or an installed one touched up) without a
+//! rebuild. Keys a table lacks fall back to English.
+//!
+//! A table is a flat JSON object of key to text. `{name}` marks a value
+//! filled in by the app; counted strings come as `key.one` and
+//! `key.other`. `language.name` is the language's own name for the
+//! preferences picker. The editor crate asks for its `editor.*` keys
+//! through `EditorHost::text`.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{Mutex, RwLock};
+
+use crate::assets::Assets;
+use crate::preferences::data_dir;
+
+type Table = HashMap<String, &'static str>;
+
+/// Tables of the current language and of English
+static CURRENT: RwLock<Option<(&'static Table, &'static Table)>> = RwLock::new(None);
+/// Tables loaded so far by language code, so switching back and forth
+/// doesn't load (and leak) them again
+static LOADED: Mutex<Option<HashMap<String, &'static Table>>> = Mutex::new(None);
+
+/// Use `code` (like `de` or `pt-BR`), or the system's language when it's
+/// empty. Falls back from a regional table to the plain language, then to
+/// English.
+pub fn set_language(code: &str) {
+    let requested = Some(code.trim().to_string())
+        .filter(|code| !code.is_empty())
+        .or_else(sys_locale::get_locale)
+        .unwrap_or_default()
+        .replace('_', "-");
+    let base = requested.split('-').next().unwrap_or_default().to_string();
+    let english = table("en").unwrap_or_else(|| &*Box::leak(Box::default()));
+    let current = [requested, base]
+        .into_iter()
+        .find_map(|code| table(&code))
+        .unwrap_or(english);
+    *CURRENT.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((current, english));
+}
+
+/// Loaded table for `code`, or None when there's no such locale.
+fn table(code: &str) -> Option<&'static Table> {
+    if code.is_empty() {
+        return None;
+    }
+    let mut loaded = LOADED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let loaded = loaded.get_or_insert_with(HashMap::new);
+    if let Some(table) = loaded.get(code) {
+        return Some(table);
+    }
+
+    let bundled = Assets::get(&format!("locales/{}.json", code))
+        .and_then(|file| serde_json::from_slice::<HashMap<String, String>>(&file.data).ok());
+    let user = std::fs::read_to_string(data_dir().join("locales").join(format!("{}.json", code)))
+        .ok()
+        .and_then(|data| serde_json::from_str::<HashMap<String, String>>(&data).ok());
+    if bundled.is_none() && user.is_none() {
+        return None;
+    }
+    let table: Table = bundled
+        .into_iter()
+        .chain(user)
+        .flatten()
+        .map(|(key, text)| (key, &*Box::leak(text.into_boxed_str())))
+        .collect();
+    let table: &'static Table = Box::leak(Box::new(table));
+    loaded.insert(code.to_string(), table);
+    Some(table)
+}
+
+/// Text for `key` in the current language or English, if either has it.
+pub fn lookup(key: &str) -> Option<&'static str> {
+    let (current, english) = (*CURRENT.read().unwrap_or_else(|poisoned| poisoned.into_inner()))?;
+    current.get(key).or_else(|| english.get(key)).copied()
+}
+
+/// Text for `key`, or the key itself when no table has it.
+pub fn t(key: &'static str) -> &'static str {
+    lookup(key).unwrap_or(key)
+}
+
+/// `t(key)` with each `{name}` replaced by its value in `args`.
+pub fn t_args(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    fill(t(key), args)
+}
+
+/// `key.one` or `key.other` for `count`, with `{count}` filled in.
+pub fn t_count(key: &str, count: usize) -> String {
+    t_count_args(key, count, &[])
+}
+
+/// `t_count(key, count)` with each `{name}` replaced by its value in `args`
+/// too.
+pub fn t_count_args(key: &str, count: usize, args: &[(&str, &dyn Display)]) -> String {
+    let key = format!("{}.{}", key, if count == 1 { "one" } else { "other" });
+    let text = fill(lookup(&key).unwrap_or(key.as_str()), &[("count", &count)]);
+    fill(&text, args)
+}
+
+fn fill(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = text.to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
+
+/// (code, own name) of each language there's a table for, English first.
+pub fn languages() -> Vec<(String, String)> {
+    let bundled = Assets::iter().filter_map(|path| {
+        let name = path.strip_prefix("locales/")?.strip_suffix(".json")?;
+        Some(name.to_string())
+    });
+    let user = std::fs::read_dir(data_dir().join("locales"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_string)
+        });
+    let mut codes: Vec<String> = bundled.chain(user).collect();
+    codes.sort_by_key(|code| (code != "en", code.to_lowercase()));
+    codes.dedup();
+    codes
+        .into_iter()
+        .filter_map(|code| {
+            let name = table(&code)?.get("language.name").map(|name| name.to_string());
+            Some((code.clone(), name.unwrap_or(code)))
+        })
+        .collect()
+}
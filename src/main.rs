@@ -1,193 +1,2605 @@
+mod ai;
 mod assets;
-mod editor;
+mod cli;
+mod diff;
+mod drafts;
+mod external_editor;
 #[cfg(target_os = "macos")]
 mod hotkey;
+#[cfg(any(target_os = "macos", test))]
+mod hotkey_state;
+#[cfg(target_os = "linux")]
+mod hotkey_linux;
+#[cfg(target_os = "windows")]
+mod hotkey_windows;
+mod i18n;
+mod platform;
 mod preferences;
 mod preferences_window;
+mod recovery;
+mod scratch;
+mod scripts;
+mod statistics;
+mod submissions;
+mod submit_hook;
+mod templates;
 mod theme;
 
+use std::collections::{HashMap, HashSet};
+
 use assets::*;
-use editor::*;
+use cli::CliRequest;
+use diff::DiffLine;
+use drafts::*;
+use gpui::prelude::FluentBuilder;
 use gpui::*;
+use i18n::{t, t_args, t_count};
+use platform::{MenuRequest, Placement};
 use preferences::*;
 use preferences_window::*;
+use scratch::*;
+use statistics::TextStatistics;
+use submissions::*;
+use templates::Transform;
 use theme::*;
+use zeditor_core::EditorSnapshot;
+use zeditor_core::editor::*;
+
+actions!(
+    popup_editor,
+    [
+        Quit,
+        Escape,
+        SubmitAndPaste,
+        SubmitAndPasteOtherNewline,
+        SubmitToClipboard,
+        ShowSubmitTemplates,
+        SelectPreviousTemplate,
+        SelectNextTemplate,
+        SubmitWithTemplate,
+        ShowSubmissionHistory,
+        SelectPreviousSubmission,
+        SelectNextSubmission,
+        Resubmit,
+        CopySubmission,
+        ShowPasteTargets,
+        SelectPreviousPasteTarget,
+        SelectNextPasteTarget,
+        PasteIntoTarget,
+        OpenPreferences,
+        ShowStatistics,
+        ShowDiff,
+        NewBuffer,
+        CloseBuffer,
+        ShowDraftHistory,
+        SelectPreviousDraft,
+        SelectNextDraft,
+        RestoreDraft,
+        ShowScratchpads,
+        SelectPreviousScratchpad,
+        SelectNextScratchpad,
+        OpenScratchpad,
+        TogglePin,
+        ExpandCompact,
+        ExpandWindow,
+        SaveFile,
+        OpenInExternalEditor,
+        TransformWithAI,
+        SelectPreviousAiPrompt,
+        SelectNextAiPrompt,
+        RunAiPrompt,
+        ShowScripts,
+        SelectPreviousScript,
+        SelectNextScript,
+        RunSelectedScript,
+    ]
+);
+
+/// Switch to the buffer at this index (cmd-1..9).
+#[derive(Clone, PartialEq, Action)]
+#[action(namespace = popup_editor, no_json)]
+pub struct ActivateBuffer(pub usize);
+
+/// Run a user script by name on the selections or buffer, as bound in
+/// `script_bindings`.
+#[derive(Clone, PartialEq, Action)]
+#[action(namespace = popup_editor, no_json)]
+pub struct RunScript(pub String);
+
+/// Most buffers the popup holds at once; cmd-1..9 reach them all
+const MAX_BUFFERS: usize = 9;
+
+/// Quiet period after the last edit before the buffer is written to disk
+const AUTOSAVE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Longest an edit goes without crash recovery seeing it. Snapshots are
+/// O(document), so they're taken at most this often rather than per event.
+const RECOVERY_DELAY: std::time::Duration = std::time::Duration::from_millis(1000);
+
+/// How often a running AI transform's reply is moved into the buffer
+const AI_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(30);
+
+/// Height of the compact quick-entry row
+const COMPACT_HEIGHT: f32 = 52.;
+
+/// Smallest size the popup can be resized to
+const MIN_WINDOW_WIDTH: f64 = 320.;
+const MIN_WINDOW_HEIGHT: f64 = 160.;
+
+pub struct PopupEditor {
+    /// Independent buffers shown as tabs; kept across hide/show
+    buffers: Vec<Entity<MultiLineEditor>>,
+    /// Editor preferences as last applied to the buffers
+    editor_prefs: EditorConfig,
+    active_buffer: usize,
+    last_clipboard_hash: u64,
+    show_statistics: bool,
+    show_diff: bool,
+    /// Bumped per buffer on each edit, so only the last one in a pause saves
+    autosave_epochs: HashMap<EntityId, usize>,
+    /// Buffers changed since crash recovery last took their snapshot
+    recovery_pending: HashSet<EntityId>,
+    geometry_epoch: usize,
+    /// Stay open when another app is activated
+    pinned: bool,
+    /// Spotlight-style single input row instead of the full editor
+    compact: bool,
+    /// Grown to most of the screen; the previous frame is kept on the
+    /// macOS side
+    expanded: bool,
+    /// Why submitting won't paste, checked each time the popup is shown
+    paste_warning: Option<String>,
+    /// Escape submits instead of just hiding, for `--submit-on-close`
+    submit_on_close: bool,
+    /// Why the last file open or save failed
+    file_error: Option<String>,
+    /// Escape was pressed once with unsaved file changes; the next one
+    /// hides anyway
+    confirm_close: bool,
+    /// Open draft history picker: (drafts, highlighted row)
+    draft_picker: Option<(Vec<Draft>, usize)>,
+    draft_picker_focus: FocusHandle,
+    draft_picker_scroll: ScrollHandle,
+    /// Open scratchpad picker: (typed filter, highlighted row)
+    scratchpad_picker: Option<(String, usize)>,
+    scratchpad_picker_focus: FocusHandle,
+    /// Open submission history: (submissions, highlighted row)
+    submission_picker: Option<(Vec<Submission>, usize)>,
+    submission_picker_focus: FocusHandle,
+    submission_picker_scroll: ScrollHandle,
+    /// Open template picker: highlighted row, where row 0 is plain text
+    /// and the rest follow the templates in Preferences
+    template_picker: Option<usize>,
+    template_picker_focus: FocusHandle,
+    /// Open paste target picker: (apps as (name, id), highlighted row)
+    paste_target_picker: Option<(Vec<(String, String)>, usize)>,
+    paste_target_focus: FocusHandle,
+    /// Open AI prompt picker: highlighted row
+    ai_picker: Option<usize>,
+    ai_picker_focus: FocusHandle,
+    /// Bumped to stop a running AI transform from writing more
+    ai_epoch: usize,
+    /// An AI transform is streaming into the buffer
+    ai_running: bool,
+    /// Why the last AI transform failed
+    ai_error: Option<String>,
+    /// Open script picker: (script names, highlighted row)
+    script_picker: Option<(Vec<String>, usize)>,
+    script_picker_focus: FocusHandle,
+    /// Why the last script run failed
+    script_error: Option<String>,
+    /// Why the submit hook failed last time it ran
+    hook_error: Option<String>,
+    /// Words in the active buffer for the header; None to count them again
+    word_count: Option<usize>,
+    /// Buffers a crash left behind, until they're restored or put in the
+    /// draft history
+    recovered: Vec<EditorSnapshot>,
+}
+
+impl PopupEditor {
+    fn new(cx: &mut Context<Self>) -> Self {
+        let editor = cx.new(MultiLineEditor::new);
+        cx.subscribe(&editor, Self::on_editor_event).detach();
+
+        // Restore the last session's draft. Treat the current clipboard as
+        // already seen so the first show doesn't replace the draft with it.
+        let mut last_clipboard_hash = 0;
+        if let Some(session) = load_session() {
+            editor.update(cx, |editor, cx| editor.restore_snapshot(&session, cx));
+            last_clipboard_hash = cx
+                .read_from_clipboard()
+                .and_then(|item| item.text())
+                .map(|t| Self::hash_str(&t))
+                .unwrap_or(0);
+        }
+
+        cx.on_app_quit(|this, cx| {
+            this.save_session(cx);
+            async {}
+        })
+        .detach();
+        cx.observe_global::<Preferences>(Self::on_preferences_changed)
+            .detach();
+
+        Self {
+            buffers: vec![editor],
+            editor_prefs: cx.global::<Preferences>().editor.clone(),
+            active_buffer: 0,
+            last_clipboard_hash,
+            show_statistics: false,
+            show_diff: false,
+            autosave_epochs: HashMap::new(),
+            recovery_pending: HashSet::new(),
+            geometry_epoch: 0,
+            pinned: false,
+            compact: false,
+            expanded: false,
+            paste_warning: None,
+            submit_on_close: false,
+            file_error: None,
+            confirm_close: false,
+            draft_picker: None,
+            draft_picker_focus: cx.focus_handle(),
+            draft_picker_scroll: ScrollHandle::new(),
+            scratchpad_picker: None,
+            scratchpad_picker_focus: cx.focus_handle(),
+            submission_picker: None,
+            submission_picker_focus: cx.focus_handle(),
+            submission_picker_scroll: ScrollHandle::new(),
+            template_picker: None,
+            template_picker_focus: cx.focus_handle(),
+            paste_target_picker: None,
+            paste_target_focus: cx.focus_handle(),
+            ai_picker: None,
+            ai_picker_focus: cx.focus_handle(),
+            ai_epoch: 0,
+            ai_running: false,
+            ai_error: None,
+            script_picker: None,
+            script_picker_focus: cx.focus_handle(),
+            script_error: None,
+            hook_error: None,
+            word_count: None,
+            recovered: recovery::load_recovery(),
+        }
+    }
+
+    /// Carry editor preferences changed in the preferences window over to
+    /// the open buffers.
+    fn on_preferences_changed(&mut self, cx: &mut Context<Self>) {
+        let prefs = cx.global::<Preferences>().editor.clone();
+        if prefs == self.editor_prefs {
+            return;
+        }
+        let old = std::mem::replace(&mut self.editor_prefs, prefs.clone());
+        let scale = cx.global::<TextScale>().0;
+        for buffer in &self.buffers {
+            buffer.update(cx, |editor, cx| {
+                if prefs.word_wrap != old.word_wrap {
+                    editor.word_wrap = prefs.word_wrap;
+                    editor.scroll_offset.x = px(0.);
+                }
+                if prefs.font_size != old.font_size {
+                    editor.set_font_size(prefs.font_size * scale, cx);
+                }
+                // Font, tab stops and the gutter are read at paint time
+                cx.notify();
+            });
+        }
+    }
+
+    /// Follow a change to the system's text size: buffers keep their zoom
+    /// relative to the new default size.
+    fn update_text_scale(&mut self, cx: &mut Context<Self>) {
+        let Some(old) = TextScale::update(cx) else {
+            return;
+        };
+        let new = cx.global::<TextScale>().0;
+        for buffer in &self.buffers {
+            buffer.update(cx, |editor, cx| {
+                editor.set_font_size(editor.font_size / old * new, cx);
+            });
+        }
+    }
+
+    /// Store the popup's size and position in Preferences once the user
+    /// stops moving or resizing it.
+    fn on_window_bounds_changed(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        // Neither the compact row nor the expanded frame is the size to
+        // come back to
+        if self.compact || self.expanded {
+            return;
+        }
+        let bounds = window.bounds();
+        self.geometry_epoch += 1;
+        let epoch = self.geometry_epoch;
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            cx.background_executor().timer(AUTOSAVE_DELAY).await;
+            this.update(cx, |this, cx| {
+                if this.geometry_epoch != epoch {
+                    return;
+                }
+                let mut prefs = cx.global::<Preferences>().clone();
+                prefs.window.width = bounds.size.width.into();
+                prefs.window.height = bounds.size.height.into();
+                prefs.window.origin = Some((bounds.origin.x.into(), bounds.origin.y.into()));
+                cx.set_global(prefs.clone());
+                save_preferences(&prefs);
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Where the popup should appear each time it is shown.
+    fn placement_on_show(cx: &App) -> Placement {
+        match cx.global::<Preferences>().window.position {
+            WindowPosition::Center => Placement::Center,
+            WindowPosition::RestoreLast => Placement::Keep,
+            WindowPosition::Caret => Placement::Caret,
+        }
+    }
+
+    /// Put the buffer in the draft history and save the session, as the
+    /// popup is hidden.
+    fn keep_draft(&self, cx: &App) {
+        record_draft(&self.editor().read(cx).snapshot());
+        self.save_session(cx);
+    }
+
+    /// Remember the scratch buffer with its caret and scroll position.
+    fn save_session(&self, cx: &App) {
+        if let Some(editor) = self.default_buffer(cx) {
+            save_session(&editor.read(cx).snapshot());
+        }
+    }
+
+    /// The buffer kept in the scratch file: the first that's neither a
+    /// named scratchpad nor a file. Other unnamed tabs aren't saved.
+    fn default_buffer(&self, cx: &App) -> Option<&Entity<MultiLineEditor>> {
+        self.buffers.iter().find(|buffer| {
+            let editor = buffer.read(cx);
+            editor.scratchpad.is_none() && editor.file.is_none()
+        })
+    }
+
+    /// Switch to the default buffer, opening one when every tab holds a
+    /// file or scratchpad, so new text doesn't land in a file. False when
+    /// there's no room for another tab.
+    fn activate_default_buffer(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        if self.editor().read(cx).file.is_none() {
+            return true;
+        }
+        let default = self.default_buffer(cx).cloned();
+        if let Some(index) = default.and_then(|default| self.buffers.iter().position(|buffer| *buffer == default)) {
+            self.activate(index, window, cx);
+            return true;
+        }
+        if self.buffers.len() >= MAX_BUFFERS {
+            return false;
+        }
+        self.new_buffer(&NewBuffer, window, cx);
+        true
+    }
+
+    /// Write the edited scratch buffer or scratchpad to its file once edits
+    /// to it pause, and announce submits.
+    fn on_editor_event(
+        &mut self,
+        editor: Entity<MultiLineEditor>,
+        event: &EditorEvent,
+        cx: &mut Context<Self>,
+    ) {
+        match event {
+            EditorEvent::ContentChanged { .. } => {
+                if editor == *self.editor() {
+                    platform::current().set_current_draft(&editor.read(cx).lines.join("\n"));
+                    self.word_count = None;
+                }
+                self.track_for_recovery(&editor, cx);
+                self.confirm_close = false;
+                // Files are only written by an explicit save
+                if editor.read(cx).file.is_some() {
+                    return;
+                }
+                let id = editor.entity_id();
+                let epoch = self.autosave_epochs.entry(id).or_default();
+                *epoch += 1;
+                let epoch = *epoch;
+                cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+                    cx.background_executor().timer(AUTOSAVE_DELAY).await;
+                    this.update(cx, |this, cx| {
+                        if this.autosave_epochs.get(&id) != Some(&epoch) {
+                            return;
+                        }
+                        let is_default = this.default_buffer(cx) == Some(&editor);
+                        let editor = editor.read(cx);
+                        let text = editor.lines.join("\n");
+                        match &editor.scratchpad {
+                            Some(name) => save_scratchpad(name, &text),
+                            None if is_default => {
+                                save_scratch(&text);
+                                save_session(&editor.snapshot());
+                            }
+                            None => {}
+                        }
+                    })
+                    .ok();
+                })
+                .detach();
+            }
+            EditorEvent::Submitted => platform::current().announce(t("announce.submitted")),
+            EditorEvent::SelectionChanged => self.track_for_recovery(&editor, cx),
+            EditorEvent::CursorCountChanged { .. } => {}
+        }
+    }
+
+    /// Have crash recovery take a snapshot of `editor` soon. The first
+    /// change after a snapshot schedules the next one, so steady typing is
+    /// still picked up every `RECOVERY_DELAY`.
+    fn track_for_recovery(&mut self, editor: &Entity<MultiLineEditor>, cx: &mut Context<Self>) {
+        let idle = self.recovery_pending.is_empty();
+        self.recovery_pending.insert(editor.entity_id());
+        if !idle {
+            return;
+        }
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            cx.background_executor().timer(RECOVERY_DELAY).await;
+            this.update(cx, |this, cx| {
+                for id in std::mem::take(&mut this.recovery_pending) {
+                    if let Some(buffer) = this.buffers.iter().find(|b| b.entity_id() == id) {
+                        recovery::track(id, buffer.read(cx).snapshot());
+                    }
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn editor(&self) -> &Entity<MultiLineEditor> {
+        &self.buffers[self.active_buffer]
+    }
+
+    fn new_buffer(&mut self, _: &NewBuffer, window: &mut Window, cx: &mut Context<Self>) {
+        if self.buffers.len() >= MAX_BUFFERS {
+            return;
+        }
+        let editor = cx.new(MultiLineEditor::new);
+        cx.subscribe(&editor, Self::on_editor_event).detach();
+        self.buffers.push(editor);
+        self.activate(self.buffers.len() - 1, window, cx);
+    }
+
+    /// Close the active buffer. The last one is cleared instead.
+    fn close_buffer(&mut self, _: &CloseBuffer, window: &mut Window, cx: &mut Context<Self>) {
+        if self.buffers.len() == 1 {
+            self.editor().update(cx, |editor, cx| {
+                editor.reset_with_text(None, cx);
+            });
+            return;
+        }
+        let closed = self.buffers.remove(self.active_buffer);
+        recovery::forget(closed.entity_id());
+        let index = self.active_buffer.min(self.buffers.len() - 1);
+        self.activate(index, window, cx);
+    }
+
+    fn activate_buffer(&mut self, action: &ActivateBuffer, window: &mut Window, cx: &mut Context<Self>) {
+        if action.0 < self.buffers.len() {
+            self.activate(action.0, window, cx);
+        }
+    }
+
+    fn activate(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_buffer = index;
+        self.word_count = None;
+        self.show_diff = false;
+        self.confirm_close = false;
+        let compact = self.compact;
+        self.editor().update(cx, |editor, _| {
+            editor.single_line = compact;
+            editor.reported_cursor = None;
+        });
+        platform::current().set_current_draft(&self.editor().read(cx).lines.join("\n"));
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    /// Open the buffers a crash left behind in new tabs, as many as there
+    /// are free tabs for; the rest go to the draft history.
+    fn restore_recovered(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.buffers.len() >= MAX_BUFFERS || self.recovered.is_empty() {
+            return;
+        }
+        recovery::discard_recovery();
+        for snapshot in std::mem::take(&mut self.recovered) {
+            if self.buffers.len() >= MAX_BUFFERS {
+                record_draft(&snapshot);
+                continue;
+            }
+            let editor = cx.new(|cx| {
+                let mut editor = MultiLineEditor::new(cx);
+                editor.restore_snapshot(&snapshot, cx);
+                editor
+            });
+            cx.subscribe(&editor, Self::on_editor_event).detach();
+            self.buffers.push(editor);
+        }
+        self.activate(self.buffers.len() - 1, window, cx);
+    }
+
+    /// Put the buffers a crash left behind in the draft history instead.
+    fn shelve_recovered(&mut self, cx: &mut Context<Self>) {
+        if self.recovered.is_empty() {
+            return;
+        }
+        for snapshot in std::mem::take(&mut self.recovered) {
+            record_draft(&snapshot);
+        }
+        recovery::discard_recovery();
+        cx.notify();
+    }
+
+    /// Header bar, doubling as the drag handle for the borderless popup.
+    /// Its segments follow the header preferences.
+    fn render_header(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let header = &cx.global::<Preferences>().header;
+        let editor = self.editor().read(cx);
+
+        div()
+            .on_mouse_down(MouseButton::Left, |_, window, _| {
+                window.start_window_move();
+            })
+            .flex()
+            .flex_row()
+            .items_center()
+            .justify_between()
+            .w_full()
+            .h(px(32.))
+            .px(px(12.))
+            .border_b_1()
+            .border_color(theme.surface0)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(8.))
+                    .when(header.title, |el| {
+                        el.child(
+                            div()
+                                .text_size(scaled_text(13., cx))
+                                .text_color(theme.subtext0)
+                                .child(t("header.title")),
+                        )
+                    })
+                    .when(header.scratchpad_name, |el| {
+                        let name = editor
+                            .file_name()
+                            .or_else(|| editor.scratchpad.clone())
+                            .unwrap_or_else(|| t("header.scratch").into());
+                        el.child(
+                            div()
+                                .text_size(scaled_text(11., cx))
+                                .text_color(theme.overlay1)
+                                .child(name),
+                        )
+                        .when(editor.is_dirty(), |el| {
+                            el.child(
+                                div()
+                                    .text_size(scaled_text(11., cx))
+                                    .text_color(theme.accent)
+                                    .child(t("header.unsaved")),
+                            )
+                        })
+                    })
+                    .when(header.hint, |el| {
+                        el.child(
+                            div()
+                                .text_size(scaled_text(11., cx))
+                                .text_color(theme.overlay0)
+                                .child(t("header.hint")),
+                        )
+                    }),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(8.))
+                    .when(editor.read_only, |el| {
+                        el.child(
+                            div()
+                                .px(px(6.))
+                                .rounded(px(4.))
+                                .bg(theme.surface1)
+                                .text_size(scaled_text(10., cx))
+                                .text_color(theme.accent)
+                                .child(t("header.read_only")),
+                        )
+                    })
+                    .when(header.word_count, |el| {
+                        let words = self.word_count.unwrap_or_default();
+                        el.child(
+                            div()
+                                .text_size(scaled_text(11., cx))
+                                .text_color(theme.overlay0)
+                                .child(t_count("header.words", words)),
+                        )
+                    })
+                    .when(header.status, |el| {
+                        el.child(
+                            div()
+                                .text_size(scaled_text(11., cx))
+                                .text_color(theme.overlay0)
+                                .child(editor.status_text()),
+                        )
+                    })
+                    .when(header.pin_button, |el| {
+                        el.child(
+                            div()
+                                .id("pin-button")
+                                .px(px(6.))
+                                .rounded(px(4.))
+                                .cursor(CursorStyle::PointingHand)
+                                .text_size(scaled_text(10., cx))
+                                .hover(|s| s.bg(theme.surface1))
+                                .when(self.pinned, |el| el.bg(theme.surface1).text_color(theme.accent))
+                                .when(!self.pinned, |el| el.text_color(theme.overlay0))
+                                .child(if self.pinned { t("header.pinned") } else { t("header.pin") })
+                                // Handled on mouse-down so the header doesn't start a drag
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _, window, cx| {
+                                        cx.stop_propagation();
+                                        this.toggle_pin(&TogglePin, window, cx);
+                                    }),
+                                ),
+                        )
+                    })
+                    .when(header.submit_button, |el| {
+                        el.child(
+                            div()
+                                .id("submit-button")
+                                .px(px(6.))
+                                .rounded(px(4.))
+                                .cursor(CursorStyle::PointingHand)
+                                .text_size(scaled_text(10., cx))
+                                .bg(theme.surface1)
+                                .text_color(theme.accent)
+                                .hover(|s| s.bg(theme.surface2))
+                                .child(t("header.paste"))
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _, window, cx| {
+                                        cx.stop_propagation();
+                                        this.submit_and_paste(&SubmitAndPaste, window, cx);
+                                    }),
+                                ),
+                        )
+                    }),
+            )
+    }
+
+    /// Status bar under the editor. Wrap and mode toggle on click.
+    fn render_footer(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let editor = self.editor().read(cx);
+        let cursors = editor.cursors.len();
+        let segment = || {
+            div()
+                .px(px(6.))
+                .rounded(px(4.))
+                .text_size(scaled_text(10., cx))
+                .text_color(theme.overlay0)
+        };
+
+        div()
+            .flex()
+            .flex_row()
+            .items_center()
+            .justify_between()
+            .w_full()
+            .h(px(22.))
+            .px(px(6.))
+            .border_t_1()
+            .border_color(theme.surface0)
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(2.))
+                    .child(
+                        segment()
+                            .id("footer-wrap")
+                            .cursor(CursorStyle::PointingHand)
+                            .hover(|s| s.bg(theme.surface1))
+                            .child(if editor.word_wrap { t("footer.wrap") } else { t("footer.no_wrap") })
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.editor().update(cx, |editor, cx| {
+                                    editor.toggle_word_wrap(&ToggleWordWrap, window, cx);
+                                });
+                            })),
+                    )
+                    .child(segment().child(editor.line_ending.label()))
+                    .child(segment().child(t_count("footer.cursors", cursors)))
+                    .child(
+                        segment()
+                            .id("footer-mode")
+                            .cursor(CursorStyle::PointingHand)
+                            .hover(|s| s.bg(theme.surface1))
+                            .when(editor.read_only, |el| el.text_color(theme.accent))
+                            .child(if editor.read_only { t("footer.read_only") } else { t("footer.edit") })
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.editor().update(cx, |editor, cx| {
+                                    editor.toggle_read_only(&ToggleReadOnly, window, cx);
+                                });
+                            })),
+                    )
+                    .when(self.ai_running, |el| {
+                        el.child(
+                            segment()
+                                .id("footer-ai")
+                                .cursor(CursorStyle::PointingHand)
+                                .hover(|s| s.bg(theme.surface1))
+                                .text_color(theme.accent)
+                                .child(t("footer.ai_running"))
+                                .on_click(cx.listener(|this, _, _, cx| this.stop_ai_transform(cx))),
+                        )
+                    }),
+            )
+            .child(
+                segment()
+                    .id("footer-submit")
+                    .cursor(CursorStyle::PointingHand)
+                    .hover(|s| s.bg(theme.surface1).text_color(theme.accent))
+                    .child(t("footer.paste"))
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.submit_and_paste(&SubmitAndPaste, window, cx);
+                    })),
+            )
+    }
+
+    /// Slim strip of buffer tabs, shown once there is more than one buffer.
+    fn render_tab_bar(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        div()
+            .flex()
+            .flex_row()
+            .w_full()
+            .h(px(26.))
+            .px(px(8.))
+            .gap(px(2.))
+            .items_end()
+            .bg(theme.mantle)
+            .border_b_1()
+            .border_color(theme.surface0)
+            .children(self.buffers.iter().enumerate().map(|(i, buffer)| {
+                let active = i == self.active_buffer;
+                div()
+                    .id(("buffer-tab", i))
+                    .flex()
+                    .items_center()
+                    .h(px(22.))
+                    .max_w(px(140.))
+                    .px(px(10.))
+                    .rounded_t(px(4.))
+                    .overflow_hidden()
+                    .text_size(scaled_text(11., cx))
+                    .when(active, |el| el.bg(theme.base).text_color(theme.text))
+                    .when(!active, |el| el.text_color(theme.overlay1))
+                    .child(format!(
+                        "{}  {}{}",
+                        i + 1,
+                        buffer.read(cx).title(),
+                        if buffer.read(cx).is_dirty() { " ●" } else { "" }
+                    ))
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.activate(i, window, cx);
+                    }))
+            }))
+    }
+
+    /// Switch to the named scratchpad, opening it in a new buffer if it isn't
+    /// open yet. `None` is the default scratchpad in the first unnamed buffer.
+    fn open_scratchpad(&mut self, name: Option<String>, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.read(cx).scratchpad == name)
+        {
+            self.activate(index, window, cx);
+            return;
+        }
+        let Some(name) = name else {
+            return;
+        };
+        if self.buffers.len() >= MAX_BUFFERS {
+            return;
+        }
+        let text = load_scratchpad(&name).unwrap_or_default();
+        let editor = cx.new(|cx| {
+            let mut editor = MultiLineEditor::new(cx);
+            editor.restore_text(&text, cx);
+            editor.scratchpad = Some(name);
+            editor
+        });
+        cx.subscribe(&editor, Self::on_editor_event).detach();
+        self.buffers.push(editor);
+        self.activate(self.buffers.len() - 1, window, cx);
+    }
+
+    fn show_scratchpads(&mut self, _: &ShowScratchpads, window: &mut Window, cx: &mut Context<Self>) {
+        if self.scratchpad_picker.is_some() {
+            self.close_scratchpad_picker(window, cx);
+            return;
+        }
+        self.scratchpad_picker = Some((String::new(), 0));
+        window.focus(&self.scratchpad_picker_focus, cx);
+        cx.notify();
+    }
+
+    fn close_scratchpad_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.scratchpad_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    /// Saved scratchpads matching the typed filter.
+    fn filtered_scratchpads(query: &str) -> Vec<String> {
+        let query = query.to_lowercase();
+        list_scratchpads()
+            .into_iter()
+            .filter(|name| name.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    fn on_scratchpad_picker_key(&mut self, event: &KeyDownEvent, _window: &mut Window, cx: &mut Context<Self>) {
+        let Some((query, selected)) = &mut self.scratchpad_picker else {
+            return;
+        };
+        let keystroke = &event.keystroke;
+        if keystroke.modifiers.platform || keystroke.modifiers.control {
+            return;
+        }
+        if keystroke.key == "backspace" {
+            query.pop();
+        } else if let Some(text) = &keystroke.key_char
+            && !text.chars().any(char::is_control)
+        {
+            query.push_str(text);
+        } else {
+            return;
+        }
+        *selected = 0;
+        cx.stop_propagation();
+        cx.notify();
+    }
+
+    fn select_previous_scratchpad(&mut self, _: &SelectPreviousScratchpad, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = &mut self.scratchpad_picker {
+            *selected = selected.saturating_sub(1);
+            cx.notify();
+        }
+    }
+
+    fn select_next_scratchpad(&mut self, _: &SelectNextScratchpad, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((query, selected)) = &mut self.scratchpad_picker {
+            let count = Self::filtered_scratchpads(query).len();
+            *selected = (*selected + 1).min(count.saturating_sub(1));
+            cx.notify();
+        }
+    }
+
+    /// Open the highlighted scratchpad, or create one named after the filter
+    /// when nothing matches.
+    fn confirm_scratchpad(&mut self, _: &OpenScratchpad, window: &mut Window, cx: &mut Context<Self>) {
+        let Some((query, selected)) = self.scratchpad_picker.clone() else {
+            return;
+        };
+        let name = match Self::filtered_scratchpads(&query).into_iter().nth(selected) {
+            Some(name) => name,
+            None => sanitize_scratchpad_name(&query),
+        };
+        if name.is_empty() {
+            return;
+        }
+        self.close_scratchpad_picker(window, cx);
+        self.open_scratchpad(Some(name), window, cx);
+    }
+
+    fn render_scratchpad_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let (query, selected) = self.scratchpad_picker.clone().unwrap_or_default();
+        let names = Self::filtered_scratchpads(&query);
+        let new_name = sanitize_scratchpad_name(&query);
+
+        div()
+            .absolute()
+            .top(px(12.))
+            .left(px(60.))
+            .right(px(60.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(2.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .key_context("ScratchpadPicker")
+            .track_focus(&self.scratchpad_picker_focus)
+            .on_key_down(cx.listener(Self::on_scratchpad_picker_key))
+            .on_action(cx.listener(Self::select_previous_scratchpad))
+            .on_action(cx.listener(Self::select_next_scratchpad))
+            .on_action(cx.listener(Self::confirm_scratchpad))
+            .child(
+                div()
+                    .px(px(8.))
+                    .py(px(4.))
+                    .mb(px(4.))
+                    .rounded(px(4.))
+                    .bg(theme.surface0)
+                    .when(query.is_empty(), |el| {
+                        el.text_color(theme.overlay0).child(t("scratchpads.placeholder"))
+                    })
+                    .when(!query.is_empty(), |el| el.text_color(theme.text).child(query.clone())),
+            )
+            .children(names.iter().enumerate().map(|(i, name)| {
+                let name = name.clone();
+                div()
+                    .id(("scratchpad", i))
+                    .px(px(8.))
+                    .py(px(3.))
+                    .rounded(px(4.))
+                    .text_color(theme.text)
+                    .when(i == selected, |el| el.bg(theme.surface1))
+                    .child(name.clone())
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.close_scratchpad_picker(window, cx);
+                        this.open_scratchpad(Some(name.clone()), window, cx);
+                    }))
+            }))
+            .when(names.is_empty() && !new_name.is_empty(), |el| {
+                el.child(
+                    div()
+                        .px(px(8.))
+                        .py(px(3.))
+                        .rounded(px(4.))
+                        .bg(theme.surface1)
+                        .text_color(theme.subtext0)
+                        .child(t_args("scratchpads.create", &[("name", &new_name)])),
+                )
+            })
+    }
+
+    /// Set up the editor for the hotkey action that is showing the popup.
+    fn prepare_show(&mut self, action: HotkeyAction, window: &mut Window, cx: &mut Context<Self>) {
+        let compact = action == HotkeyAction::Compact || cx.global::<Preferences>().window.compact;
+        self.set_compact(compact, window, cx);
+        self.update_text_scale(cx);
+        self.paste_warning = platform::current().paste_blocked();
+        match action {
+            // The clipboard was put in the pending slot by the hotkey handler
+            HotkeyAction::Toggle | HotkeyAction::Compact | HotkeyAction::ShowClipboard => {
+                if cx.global::<Preferences>().behavior.clear_on_hide
+                    && self.editor().read(cx).file.is_none()
+                {
+                    record_draft(&self.editor().read(cx).snapshot());
+                    self.editor().update(cx, |editor, cx| {
+                        editor.reset_with_text(None, cx);
+                    });
+                }
+                self.on_show(window, cx)
+            }
+            HotkeyAction::NewDraft => {
+                if !self.activate_default_buffer(window, cx) {
+                    return;
+                }
+                record_draft(&self.editor().read(cx).snapshot());
+                self.editor().update(cx, |editor, cx| {
+                    editor.reset_with_text(None, cx);
+                });
+            }
+            // Pastes without showing the popup
+            HotkeyAction::PasteLast => {}
+        }
+    }
+
+    /// Switch to the buffer holding `path`, or load the file into a new
+    /// one.
+    fn open_file(&mut self, path: std::path::PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.read(cx).file.as_ref() == Some(&path))
+        {
+            self.activate(index, window, cx);
+            return;
+        }
+        if self.buffers.len() >= MAX_BUFFERS {
+            self.file_error = Some(t_args("error.too_many_buffers", &[("path", &path.display())]));
+            return;
+        }
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) => {
+                self.file_error =
+                    Some(t_args("error.read_file", &[("path", &path.display()), ("error", &err)]));
+                return;
+            }
+        };
+        let editor = cx.new(|cx| {
+            let mut editor = MultiLineEditor::new(cx);
+            editor.open_file(path, text, cx);
+            editor
+        });
+        cx.subscribe(&editor, Self::on_editor_event).detach();
+        self.buffers.push(editor);
+        self.activate(self.buffers.len() - 1, window, cx);
+    }
+
+    /// Write the active buffer back to its file, if it has one. Returns
+    /// false when the write failed.
+    fn save_active_file(&mut self, cx: &mut Context<Self>) -> bool {
+        let result = self.editor().update(cx, |editor, cx| editor.save_file(cx));
+        self.file_error = result.err().map(|err| t_args("error.save_file", &[("error", &err)]));
+        self.confirm_close = false;
+        cx.notify();
+        self.file_error.is_none()
+    }
+
+    fn save_file(&mut self, _: &SaveFile, _window: &mut Window, cx: &mut Context<Self>) {
+        self.save_active_file(cx);
+    }
+
+    /// Hand the active buffer to another app through a temp file, and
+    /// start reading the file back whenever it's saved there.
+    fn open_in_external_editor(&mut self, _: &OpenInExternalEditor, _window: &mut Window, cx: &mut Context<Self>) {
+        let editor = self.editor().clone();
+        let (text, watched) = {
+            let editor = editor.read(cx);
+            (editor.lines.join("\n"), editor.external_file.clone())
+        };
+        let path = match watched.clone().map_or_else(external_editor::temp_path, Ok) {
+            Ok(path) => path,
+            Err(err) => {
+                self.file_error = Some(t_args("error.external_editor", &[("error", &err)]));
+                cx.notify();
+                return;
+            }
+        };
+        if let Err(err) = external_editor::write(&path, &text) {
+            self.file_error = Some(t_args("error.write_file", &[("path", &path.display()), ("error", &err)]));
+            cx.notify();
+            return;
+        }
+        let command = cx.global::<Preferences>().editor.external_editor.clone();
+        if let Err(err) = external_editor::open(&path, &command) {
+            self.file_error = Some(t_args("error.external_editor", &[("error", &err)]));
+            if watched.is_none() {
+                std::fs::remove_file(&path).ok();
+            }
+            cx.notify();
+            return;
+        }
+        self.file_error = None;
+        cx.notify();
+        if watched.is_some() {
+            return;
+        }
+
+        editor.update(cx, |editor, _| editor.external_file = Some(path.clone()));
+        let editor = editor.downgrade();
+        let mut last_modified = external_editor::modified(&path);
+        cx.spawn(async move |_, cx: &mut AsyncApp| {
+            loop {
+                cx.background_executor().timer(external_editor::POLL_INTERVAL).await;
+                let modified = external_editor::modified(&path);
+                // Stop once the buffer is closed or given other text
+                let watching = editor
+                    .update(cx, |editor, cx| {
+                        if editor.external_file.as_ref() != Some(&path) {
+                            return false;
+                        }
+                        if modified == last_modified {
+                            return true;
+                        }
+                        last_modified = modified;
+                        let Ok(text) = std::fs::read_to_string(&path) else {
+                            return true;
+                        };
+                        // Editors tend to end the file with a newline the
+                        // buffer didn't have
+                        let current = editor.lines.join("\n");
+                        if text == current || text == format!("{}\n", current) {
+                            return true;
+                        }
+                        let text = text.strip_suffix('\n').unwrap_or(&text);
+                        editor.replace_text(text, cx);
+                        true
+                    })
+                    .unwrap_or(false);
+                if !watching {
+                    std::fs::remove_file(&path).ok();
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Set up the editor for a request from the command line, made by this
+    /// launch or forwarded from a later one.
+    fn open_cli_request(&mut self, request: CliRequest, window: &mut Window, cx: &mut Context<Self>) {
+        let compact = cx.global::<Preferences>().window.compact;
+        self.set_compact(compact, window, cx);
+        self.update_text_scale(cx);
+        self.paste_warning = platform::current().paste_blocked();
+        self.file_error = None;
+        if let Some(path) = request.file {
+            self.open_file(path, window, cx);
+        } else if let Some(text) = request.text
+            && self.activate_default_buffer(window, cx)
+        {
+            record_draft(&self.editor().read(cx).snapshot());
+            self.editor().update(cx, |editor, cx| {
+                editor.reset_with_text(Some(text), cx);
+            });
+        }
+        self.submit_on_close = request.submit_on_close;
+    }
+
+    /// Called when the window is about to show. Reads clipboard, checks if it
+    /// changed since last open. If changed, replaces editor contents. If same,
+    /// keeps existing editor state.
+    fn on_show(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        // Check for text handed over by the platform side first
+        if let Some(initial_text) = platform::current().take_pending_clipboard() {
+            if !self.activate_default_buffer(window, cx) {
+                return;
+            }
+            let hash = Self::hash_str(&initial_text);
+            self.last_clipboard_hash = hash;
+            record_draft(&self.editor().read(cx).snapshot());
+            self.editor().update(cx, |editor, cx| {
+                editor.reset_with_text(Some(initial_text), cx);
+            });
+            return;
+        }
+
+        // A file tab keeps its text; it would be saved over the file
+        if !cx.global::<Preferences>().behavior.prefill_clipboard || self.editor().read(cx).file.is_some() {
+            return;
+        }
+        let clipboard_text = cx
+            .read_from_clipboard()
+            .and_then(|item| item.text().map(|t| t.to_string()));
+
+        let current_hash = clipboard_text
+            .as_ref()
+            .map(|t| Self::hash_str(t))
+            .unwrap_or(0);
+
+        if current_hash != self.last_clipboard_hash {
+            self.last_clipboard_hash = current_hash;
+            record_draft(&self.editor().read(cx).snapshot());
+            self.editor().update(cx, |editor, cx| {
+                editor.reset_with_text(clipboard_text, cx);
+            });
+        }
+        // else: clipboard unchanged, keep editor contents
+    }
+
+    fn hash_str(s: &str) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        s.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn escape(&mut self, _: &Escape, window: &mut Window, cx: &mut Context<Self>) {
+        if self.draft_picker.is_some() {
+            self.close_draft_picker(window, cx);
+            return;
+        }
+        if self.scratchpad_picker.is_some() {
+            self.close_scratchpad_picker(window, cx);
+            return;
+        }
+        if self.paste_target_picker.is_some() {
+            self.close_paste_target_picker(window, cx);
+            return;
+        }
+        if self.template_picker.is_some() {
+            self.close_template_picker(window, cx);
+            return;
+        }
+        if self.submission_picker.is_some() {
+            self.close_submission_picker(window, cx);
+            return;
+        }
+        if self.ai_picker.is_some() {
+            self.close_ai_picker(window, cx);
+            return;
+        }
+        if self.script_picker.is_some() {
+            self.close_script_picker(window, cx);
+            return;
+        }
+        if self.ai_running {
+            self.stop_ai_transform(cx);
+            return;
+        }
+        if self.show_statistics || self.show_diff {
+            self.show_statistics = false;
+            self.show_diff = false;
+            cx.notify();
+            return;
+        }
+        let staged = cx.global::<Preferences>().behavior.escape == EscapeBehavior::TwoStage;
+        let editor = self.editor().read(cx);
+        if staged && editor.has_multiple_cursors() {
+            // Stage 1: collapse to single cursor
+            self.editor().update(cx, |editor, cx| {
+                editor.collapse_to_primary_cursor(cx);
+            });
+        } else if staged && editor.is_dirty() && !self.confirm_close {
+            // Stage 2 with unsaved changes: ask first
+            self.confirm_close = true;
+            cx.notify();
+        } else if self.submit_on_close {
+            self.submit_and_paste(&SubmitAndPaste, window, cx);
+        } else {
+            self.confirm_close = false;
+            // Stage 2: hide the popup
+            self.keep_draft(cx);
+            platform::current().hide(window);
+        }
+    }
+
+    fn show_draft_history(&mut self, _: &ShowDraftHistory, window: &mut Window, cx: &mut Context<Self>) {
+        if self.draft_picker.is_some() {
+            self.close_draft_picker(window, cx);
+            return;
+        }
+        self.draft_picker = Some((load_drafts(), 0));
+        window.focus(&self.draft_picker_focus, cx);
+        cx.notify();
+    }
+
+    fn close_draft_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.draft_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    fn select_previous_draft(&mut self, _: &SelectPreviousDraft, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = &mut self.draft_picker {
+            *selected = selected.saturating_sub(1);
+            self.draft_picker_scroll.scroll_to_item(*selected);
+            cx.notify();
+        }
+    }
+
+    fn select_next_draft(&mut self, _: &SelectNextDraft, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((drafts, selected)) = &mut self.draft_picker {
+            *selected = (*selected + 1).min(drafts.len().saturating_sub(1));
+            self.draft_picker_scroll.scroll_to_item(*selected);
+            cx.notify();
+        }
+    }
+
+    fn restore_draft(&mut self, _: &RestoreDraft, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = self.draft_picker {
+            self.restore_draft_at(selected, window, cx);
+        }
+    }
+
+    /// Replace the buffer with a draft from the history. The text being
+    /// replaced is recorded first so it can be restored in turn.
+    fn restore_draft_at(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(draft) = self
+            .draft_picker
+            .as_ref()
+            .and_then(|(drafts, _)| drafts.get(index).cloned())
+        else {
+            return;
+        };
+        record_draft(&self.editor().read(cx).snapshot());
+        self.editor().update(cx, |editor, cx| {
+            editor.restore_text(&draft.text, cx);
+            if let Some(state) = &draft.state {
+                editor.restore_snapshot(state, cx);
+            }
+        });
+        self.close_draft_picker(window, cx);
+    }
+
+    fn render_draft_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let (drafts, selected) = self
+            .draft_picker
+            .as_ref()
+            .map(|(drafts, selected)| (drafts.as_slice(), *selected))
+            .unwrap_or_default();
+
+        div()
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .bg(theme.surface0)
+            .key_context("DraftHistory")
+            .track_focus(&self.draft_picker_focus)
+            .on_action(cx.listener(Self::select_previous_draft))
+            .on_action(cx.listener(Self::select_next_draft))
+            .on_action(cx.listener(Self::restore_draft))
+            .child(
+                div()
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t("drafts.title")),
+            )
+            .when(drafts.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_size(scaled_text(12., cx))
+                        .text_color(theme.overlay1)
+                        .child(t("drafts.empty")),
+                )
+            })
+            .child(
+                div()
+                    .id("draft-list")
+                    .flex()
+                    .flex_col()
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .track_scroll(&self.draft_picker_scroll)
+                    .children(drafts.iter().enumerate().map(|(i, draft)| {
+                        div()
+                            .id(("draft", i))
+                            .flex()
+                            .flex_row()
+                            .justify_between()
+                            .gap(px(12.))
+                            .px(px(8.))
+                            .py(px(4.))
+                            .rounded(px(4.))
+                            .text_size(scaled_text(12., cx))
+                            .when(i == selected, |el| el.bg(theme.surface1))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_color(theme.text)
+                                    .child(draft.preview().to_string()),
+                            )
+                            .child(
+                                div()
+                                    .text_color(theme.overlay1)
+                                    .child(draft.saved_at_label()),
+                            )
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.restore_draft_at(i, window, cx);
+                            }))
+                    })),
+            )
+    }
+
+    /// Switch between the compact quick-entry row and the full editor,
+    /// resizing the window to match.
+    fn set_compact(&mut self, compact: bool, window: &mut Window, cx: &mut Context<Self>) {
+        if compact == self.compact {
+            return;
+        }
+        self.compact = compact;
+        self.editor().update(cx, |editor, cx| {
+            editor.single_line = compact;
+            cx.notify();
+        });
+        let window_prefs = &cx.global::<Preferences>().window;
+        let new_size = if compact {
+            size(window.bounds().size.width, px(COMPACT_HEIGHT))
+        } else {
+            size(px(window_prefs.width), px(window_prefs.height))
+        };
+        window.resize(new_size);
+        cx.notify();
+    }
+
+    fn expand_compact(&mut self, _: &ExpandCompact, window: &mut Window, cx: &mut Context<Self>) {
+        self.set_compact(false, window, cx);
+    }
+
+    /// Grow to most of the screen for long documents, or back to the
+    /// previous frame.
+    fn expand_window(&mut self, _: &ExpandWindow, _window: &mut Window, cx: &mut Context<Self>) {
+        if self.compact {
+            return;
+        }
+        self.expanded = !self.expanded;
+        platform::current().set_expanded(self.expanded);
+        cx.notify();
+    }
+
+    fn toggle_pin(&mut self, _: &TogglePin, _window: &mut Window, cx: &mut Context<Self>) {
+        self.pinned = !self.pinned;
+        platform::current().set_pinned(self.pinned);
+        cx.notify();
+    }
+
+    fn show_statistics(&mut self, _: &ShowStatistics, _window: &mut Window, cx: &mut Context<Self>) {
+        self.show_statistics = !self.show_statistics;
+        cx.notify();
+    }
+
+    /// Overlay with counts for the selection, or the whole buffer.
+    fn render_statistics(&self, cx: &App) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let editor = self.editor().read(cx);
+        let stats = TextStatistics::for_text(&editor.get_submit_text());
+        let scope = if editor.has_selection() { t("statistics.selection") } else { t("statistics.document") };
+
+        div()
+            .absolute()
+            .top(px(12.))
+            .right(px(12.))
+            .w(px(240.))
+            .p(px(12.))
+            .flex()
+            .flex_col()
+            .gap(px(4.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .child(
+                div()
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(scope),
+            )
+            .children(stats.rows().into_iter().map(|(label, value)| {
+                div()
+                    .flex()
+                    .flex_row()
+                    .justify_between()
+                    .child(div().text_color(theme.subtext0).child(label))
+                    .child(div().text_color(theme.text).child(value))
+            }))
+    }
+
+    /// Toggle the diff against the pre-filled text. Does nothing when the
+    /// editor was opened empty.
+    fn show_diff(&mut self, _: &ShowDiff, _window: &mut Window, cx: &mut Context<Self>) {
+        if self.editor().read(cx).original_lines.is_none() {
+            return;
+        }
+        self.show_diff = !self.show_diff;
+        cx.notify();
+    }
+
+    /// Read-only view of the buffer against the pre-filled text, with added
+    /// and removed lines highlighted.
+    fn render_diff(&self, cx: &App) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let editor = self.editor().read(cx);
+        let original = editor.original_lines.as_deref().unwrap_or_default();
+        let diff = diff::diff_lines(original, &editor.lines);
+        let added = diff.iter().filter(|l| matches!(l, DiffLine::Added(_))).count();
+        let removed = diff.iter().filter(|l| matches!(l, DiffLine::Removed(_))).count();
+
+        div()
+            .id("diff-view")
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .p(px(8.))
+            .overflow_y_scroll()
+            .bg(theme.surface0)
+            .font_family(cx.global::<Preferences>().editor.font_family.clone())
+            .text_size(scaled_text(13., cx))
+            .child(
+                div()
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t_args("diff.title", &[("added", &added), ("removed", &removed)])),
+            )
+            .children(diff.into_iter().map(|line| {
+                let (marker, text, background) = match line {
+                    DiffLine::Unchanged(text) => (" ", text, None),
+                    DiffLine::Added(text) => ("+", text, Some(with_alpha(theme.success, 0.2))),
+                    DiffLine::Removed(text) => ("-", text, Some(with_alpha(theme.error, 0.2))),
+                };
+                div()
+                    .flex()
+                    .flex_row()
+                    .w_full()
+                    .min_h(px(20.))
+                    .when_some(background, |el, bg| el.bg(bg))
+                    .child(div().w(px(16.)).text_color(theme.overlay0).child(marker))
+                    .child(div().text_color(theme.text).child(text.to_string()))
+            }))
+    }
+
+    /// Save and record the buffer, and return the text to submit, run
+    /// through `transform`. None when the buffer's file couldn't be saved.
+    /// `other_newline` flips the trailing newline preference for this
+    /// submit.
+    fn take_submission(
+        &mut self,
+        other_newline: bool,
+        transform: Option<Transform>,
+        cx: &mut Context<Self>,
+    ) -> Option<String> {
+        if !self.save_active_file(cx) {
+            return None;
+        }
+        self.submit_on_close = false;
+        record_draft(&self.editor().read(cx).snapshot());
+        self.save_session(cx);
+        let editor = self.editor().read(cx);
+        let mut text = editor.get_submit_text();
+        if let Some(transform) = transform {
+            text = transform.apply(&text);
+        }
+        if cx.global::<Preferences>().submit.trailing_newline != other_newline {
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+        } else if text.ends_with('\n') {
+            text.pop();
+        }
+        Some(editor.line_ending.apply(text))
+    }
+
+    /// Add a submit to the history, unless it's switched off.
+    fn log_submission(text: &str, target: Option<String>, cx: &App) {
+        let submit = &cx.global::<Preferences>().submit;
+        if submit.history {
+            record_submission(text, target, submit.history_limit);
+        }
+    }
+
+    /// Log `text` once it went out to `target`, and empty the buffer if
+    /// the preferences ask for it.
+    fn finish_submit(&mut self, text: &str, target: Option<String>, cx: &mut Context<Self>) {
+        Self::log_submission(text, target, cx);
+        self.editor().update(cx, |editor, cx| editor.submitted(cx));
+        if !cx.global::<Preferences>().submit.clear_after || self.editor().read(cx).file.is_some() {
+            return;
+        }
+        // The submitted text is on the clipboard now; don't load it back in
+        // on the next show
+        self.last_clipboard_hash = Self::hash_str(text);
+        self.editor().update(cx, |editor, cx| {
+            editor.reset_with_text(None, cx);
+        });
+    }
+
+    /// Hand `text` to the submit hook, if one is set, in the background.
+    /// Returns true when the hook takes the place of pasting, in which case
+    /// the popup is hidden and the submit finished here.
+    fn run_submit_hook(
+        &mut self,
+        text: &str,
+        target: Option<String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let submit = &cx.global::<Preferences>().submit;
+        if submit.hook.trim().is_empty() {
+            return false;
+        }
+        let instead = submit.hook_mode == HookMode::Instead;
+        let command = submit.hook.clone();
+        let input = text.to_string();
+        let hook_target = if instead { None } else { target };
+        let run = cx.background_spawn(async move {
+            submit_hook::run(&command, &input, hook_target.as_deref())
+        });
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            let result = run.await;
+            this.update(cx, |this, cx| {
+                this.hook_error = result.err();
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+
+        if !instead {
+            return false;
+        }
+        platform::current().hide(window);
+        self.finish_submit(text, Some(t("submissions.hook_target").to_string()), cx);
+        true
+    }
+
+    fn submit_and_paste(&mut self, _: &SubmitAndPaste, window: &mut Window, cx: &mut Context<Self>) {
+        self.submit(false, window, cx);
+    }
+
+    fn submit_and_paste_other_newline(
+        &mut self,
+        _: &SubmitAndPasteOtherNewline,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.submit(true, window, cx);
+    }
+
+    fn submit(&mut self, other_newline: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let transform = cx.global::<Preferences>().submit.default_transform();
+        let Some(text) = self.take_submission(other_newline, transform, cx) else {
+            return;
+        };
+        let target = platform::current().paste_target_name();
+        if self.run_submit_hook(&text, target.clone(), window, cx) {
+            return;
+        }
+        platform::current().submit_and_paste(text.clone(), window, cx);
+        self.finish_submit(&text, target, cx);
+    }
+
+    /// Stage the text on the clipboard and hide, without pasting it anywhere.
+    fn submit_to_clipboard(&mut self, _: &SubmitToClipboard, window: &mut Window, cx: &mut Context<Self>) {
+        let transform = cx.global::<Preferences>().submit.default_transform();
+        let Some(text) = self.take_submission(false, transform, cx) else {
+            return;
+        };
+        if self.run_submit_hook(&text, Some("Clipboard".to_string()), window, cx) {
+            return;
+        }
+        platform::current().submit_to_clipboard(text.clone(), window, cx);
+        self.finish_submit(&text, Some("Clipboard".to_string()), cx);
+    }
+
+    fn show_submission_history(&mut self, _: &ShowSubmissionHistory, window: &mut Window, cx: &mut Context<Self>) {
+        if self.submission_picker.is_some() {
+            self.close_submission_picker(window, cx);
+            return;
+        }
+        self.open_submission_history(window, cx);
+    }
+
+    fn open_submission_history(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.submission_picker = Some((load_submissions(), 0));
+        window.focus(&self.submission_picker_focus, cx);
+        cx.notify();
+    }
+
+    fn close_submission_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.submission_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    fn select_previous_submission(
+        &mut self,
+        _: &SelectPreviousSubmission,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some((_, selected)) = &mut self.submission_picker {
+            *selected = selected.saturating_sub(1);
+            self.submission_picker_scroll.scroll_to_item(*selected);
+            cx.notify();
+        }
+    }
+
+    fn select_next_submission(&mut self, _: &SelectNextSubmission, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((submissions, selected)) = &mut self.submission_picker {
+            *selected = (*selected + 1).min(submissions.len().saturating_sub(1));
+            self.submission_picker_scroll.scroll_to_item(*selected);
+            cx.notify();
+        }
+    }
+
+    fn selected_submission(&self, index: usize) -> Option<Submission> {
+        self.submission_picker
+            .as_ref()
+            .and_then(|(submissions, _)| submissions.get(index).cloned())
+    }
+
+    fn resubmit(&mut self, _: &Resubmit, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = self.submission_picker {
+            self.resubmit_at(selected, window, cx);
+        }
+    }
+
+    /// Paste an earlier submission again, leaving the buffer alone.
+    fn resubmit_at(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(submission) = self.selected_submission(index) else {
+            return;
+        };
+        self.close_submission_picker(window, cx);
+        let target = platform::current().paste_target_name();
+        platform::current().submit_and_paste(submission.text.clone(), window, cx);
+        Self::log_submission(&submission.text, target, cx);
+    }
+
+    fn copy_submission(&mut self, _: &CopySubmission, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = self.submission_picker {
+            self.copy_submission_at(selected, window, cx);
+        }
+    }
+
+    fn copy_submission_at(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(submission) = self.selected_submission(index) else {
+            return;
+        };
+        cx.write_to_clipboard(ClipboardItem::new_string(submission.text));
+        self.close_submission_picker(window, cx);
+    }
+
+    fn render_submission_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let (submissions, selected) = self
+            .submission_picker
+            .as_ref()
+            .map(|(submissions, selected)| (submissions.as_slice(), *selected))
+            .unwrap_or_default();
+        let action = |id: (&'static str, usize), label: &'static str| {
+            div()
+                .id(id)
+                .px(px(6.))
+                .rounded(px(4.))
+                .text_size(scaled_text(10., cx))
+                .text_color(theme.overlay1)
+                .cursor(CursorStyle::PointingHand)
+                .hover(|s| s.bg(theme.surface2).text_color(theme.accent))
+                .child(label)
+        };
 
-#[cfg(target_os = "macos")]
-use raw_window_handle::HasWindowHandle;
-#[cfg(target_os = "macos")]
-use objc::{msg_send, sel, sel_impl};
+        div()
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .bg(theme.surface0)
+            .key_context("SubmissionHistory")
+            .track_focus(&self.submission_picker_focus)
+            .on_action(cx.listener(Self::select_previous_submission))
+            .on_action(cx.listener(Self::select_next_submission))
+            .on_action(cx.listener(Self::resubmit))
+            .on_action(cx.listener(Self::copy_submission))
+            .child(
+                div()
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t("submissions.title")),
+            )
+            .when(submissions.is_empty(), |el| {
+                el.child(
+                    div()
+                        .text_size(scaled_text(12., cx))
+                        .text_color(theme.overlay1)
+                        .child(t("submissions.empty")),
+                )
+            })
+            .child(
+                div()
+                    .id("submission-list")
+                    .flex()
+                    .flex_col()
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .track_scroll(&self.submission_picker_scroll)
+                    .children(submissions.iter().enumerate().map(|(i, submission)| {
+                        div()
+                            .id(("submission", i))
+                            .flex()
+                            .flex_row()
+                            .items_center()
+                            .gap(px(12.))
+                            .px(px(8.))
+                            .py(px(4.))
+                            .rounded(px(4.))
+                            .text_size(scaled_text(12., cx))
+                            .when(i == selected, |el| el.bg(theme.surface1))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_color(theme.text)
+                                    .child(submission.preview().to_string()),
+                            )
+                            .when_some(submission.target.clone(), |el, target| {
+                                el.child(div().text_color(theme.overlay0).child(target))
+                            })
+                            .child(
+                                div()
+                                    .text_color(theme.overlay1)
+                                    .child(submission.submitted_at_label()),
+                            )
+                            .child(action(("submission-copy", i), t("submissions.copy")).on_click(cx.listener(
+                                move |this, _, window, cx| {
+                                    cx.stop_propagation();
+                                    this.copy_submission_at(i, window, cx);
+                                },
+                            )))
+                            .child(action(("submission-resubmit", i), t("submissions.resubmit")).on_click(
+                                cx.listener(move |this, _, window, cx| {
+                                    cx.stop_propagation();
+                                    this.resubmit_at(i, window, cx);
+                                }),
+                            ))
+                    })),
+            )
+    }
 
-actions!(popup_editor, [Quit, Escape, SubmitAndPaste, OpenPreferences]);
+    fn show_submit_templates(&mut self, _: &ShowSubmitTemplates, window: &mut Window, cx: &mut Context<Self>) {
+        if self.template_picker.is_some() {
+            self.close_template_picker(window, cx);
+            return;
+        }
+        self.template_picker = Some(0);
+        window.focus(&self.template_picker_focus, cx);
+        cx.notify();
+    }
 
-pub struct PopupEditor {
-    editor: Entity<MultiLineEditor>,
-    last_clipboard_hash: u64,
-}
+    fn close_template_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.template_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
 
-impl PopupEditor {
-    fn new(cx: &mut Context<Self>) -> Self {
-        let editor = cx.new(MultiLineEditor::new);
-        Self {
-            editor,
-            last_clipboard_hash: 0,
+    fn select_previous_template(&mut self, _: &SelectPreviousTemplate, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(selected) = &mut self.template_picker {
+            *selected = selected.saturating_sub(1);
+            cx.notify();
         }
     }
 
-    /// Called when the window is about to show. Reads clipboard, checks if it
-    /// changed since last open. If changed, replaces editor contents. If same,
-    /// keeps existing editor state.
-    fn on_show(&mut self, cx: &mut Context<Self>) {
-        // Check for CLI/pipe initial text first
-        #[cfg(target_os = "macos")]
-        if let Some(initial_text) = hotkey::take_pending_clipboard() {
-            let hash = Self::hash_str(&initial_text);
-            self.last_clipboard_hash = hash;
-            self.editor.update(cx, |editor, cx| {
-                editor.reset_with_text(Some(initial_text), cx);
-            });
+    fn select_next_template(&mut self, _: &SelectNextTemplate, _window: &mut Window, cx: &mut Context<Self>) {
+        let count = cx.global::<Preferences>().submit.templates.len();
+        if let Some(selected) = &mut self.template_picker {
+            *selected = (*selected + 1).min(count);
+            cx.notify();
+        }
+    }
+
+    fn submit_with_template(&mut self, _: &SubmitWithTemplate, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(selected) = self.template_picker {
+            self.submit_with_template_at(selected, window, cx);
+        }
+    }
+
+    /// Submit through the template in this picker row; row 0 submits the
+    /// text unchanged, whatever the default template.
+    fn submit_with_template_at(&mut self, row: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let templates = &cx.global::<Preferences>().submit.templates;
+        let transform = match row.checked_sub(1) {
+            None => None,
+            Some(index) => match templates.get(index) {
+                Some(template) => Some(template.transform.clone()),
+                None => return,
+            },
+        };
+        self.close_template_picker(window, cx);
+        let Some(text) = self.take_submission(false, transform, cx) else {
+            return;
+        };
+        let target = platform::current().paste_target_name();
+        if self.run_submit_hook(&text, target.clone(), window, cx) {
             return;
         }
+        platform::current().submit_and_paste(text.clone(), window, cx);
+        self.finish_submit(&text, target, cx);
+    }
 
-        let clipboard_text = cx
-            .read_from_clipboard()
-            .and_then(|item| item.text().map(|t| t.to_string()));
+    fn render_template_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let selected = self.template_picker.unwrap_or_default();
+        let names = std::iter::once(t("templates.plain_text").to_string()).chain(
+            cx.global::<Preferences>()
+                .submit
+                .templates
+                .iter()
+                .map(|t| t.name.clone()),
+        );
 
-        let current_hash = clipboard_text
-            .as_ref()
-            .map(|t| Self::hash_str(t))
-            .unwrap_or(0);
+        div()
+            .absolute()
+            .top(px(12.))
+            .left(px(60.))
+            .right(px(60.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(2.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .key_context("TemplatePicker")
+            .track_focus(&self.template_picker_focus)
+            .on_action(cx.listener(Self::select_previous_template))
+            .on_action(cx.listener(Self::select_next_template))
+            .on_action(cx.listener(Self::submit_with_template))
+            .child(
+                div()
+                    .px(px(8.))
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t("templates.title")),
+            )
+            .children(names.enumerate().map(|(i, name)| {
+                div()
+                    .id(("template", i))
+                    .px(px(8.))
+                    .py(px(3.))
+                    .rounded(px(4.))
+                    .text_color(theme.text)
+                    .when(i == selected, |el| el.bg(theme.surface1))
+                    .child(name)
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.submit_with_template_at(i, window, cx);
+                    }))
+            }))
+    }
 
-        if current_hash != self.last_clipboard_hash {
-            self.last_clipboard_hash = current_hash;
-            self.editor.update(cx, |editor, cx| {
-                editor.reset_with_text(clipboard_text, cx);
-            });
+    fn transform_with_ai(&mut self, _: &TransformWithAI, window: &mut Window, cx: &mut Context<Self>) {
+        if self.ai_picker.is_some() {
+            self.close_ai_picker(window, cx);
+            return;
         }
-        // else: clipboard unchanged, keep editor contents
+        let ai = &cx.global::<Preferences>().ai;
+        self.ai_error = if !ai.enabled {
+            Some(t_args("error.ai_disabled", &[("config", &config_file_name())]))
+        } else if ai.prompts.is_empty() {
+            Some(t_args("error.ai_no_prompts", &[("config", &config_file_name())]))
+        } else {
+            None
+        };
+        if self.ai_error.is_none() {
+            self.ai_picker = Some(0);
+            window.focus(&self.ai_picker_focus, cx);
+        }
+        cx.notify();
     }
 
-    fn hash_str(s: &str) -> u64 {
-        use std::hash::{Hash, Hasher};
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        s.hash(&mut hasher);
-        hasher.finish()
+    fn close_ai_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.ai_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
     }
 
-    fn escape(&mut self, _: &Escape, window: &mut Window, cx: &mut Context<Self>) {
-        let editor = self.editor.read(cx);
-        if editor.has_multiple_cursors() {
-            // Stage 1: collapse to single cursor
-            self.editor.update(cx, |editor, cx| {
-                editor.collapse_to_primary_cursor(cx);
-            });
-        } else {
-            // Stage 2: hide the popup
-            hide_window(window);
+    fn select_previous_ai_prompt(&mut self, _: &SelectPreviousAiPrompt, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(selected) = &mut self.ai_picker {
+            *selected = selected.saturating_sub(1);
+            cx.notify();
+        }
+    }
+
+    fn select_next_ai_prompt(&mut self, _: &SelectNextAiPrompt, _window: &mut Window, cx: &mut Context<Self>) {
+        let count = cx.global::<Preferences>().ai.prompts.len();
+        if let Some(selected) = &mut self.ai_picker {
+            *selected = (*selected + 1).min(count.saturating_sub(1));
+            cx.notify();
+        }
+    }
+
+    fn run_ai_prompt(&mut self, _: &RunAiPrompt, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(selected) = self.ai_picker {
+            self.run_ai_prompt_at(selected, window, cx);
+        }
+    }
+
+    /// Send the selection, or the whole buffer, through the prompt in this
+    /// picker row and stream the reply in its place.
+    fn run_ai_prompt_at(&mut self, row: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let ai = cx.global::<Preferences>().ai.clone();
+        let Some(prompt) = ai.prompts.get(row).cloned() else {
+            return;
+        };
+        self.close_ai_picker(window, cx);
+        let editor = self.editor().clone();
+        if editor.read(cx).read_only {
+            return;
+        }
+        let text = editor.update(cx, |editor, cx| editor.select_for_replacement(cx));
+        if text.trim().is_empty() {
+            return;
+        }
+        // The reply goes where the selection was, wherever the caret is
+        // moved meanwhile; the buffer can't be edited until it's done
+        let mut target = editor.update(cx, |editor, cx| {
+            editor.read_only = true;
+            cx.notify();
+            let cursor = &editor.cursors[0];
+            let head = editor.flat_offset(&cursor.position);
+            let anchor = cursor.anchor.as_ref().map_or(head, |anchor| editor.flat_offset(anchor));
+            head.min(anchor)..head.max(anchor)
+        });
+
+        self.ai_epoch += 1;
+        let epoch = self.ai_epoch;
+        self.ai_running = true;
+        self.ai_error = None;
+        cx.notify();
+        let updates = ai::start(&ai, &prompt, &text);
+        cx.spawn_in(window, async move |this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+            loop {
+                cx.background_executor().timer(AI_POLL_INTERVAL).await;
+                let mut reply = String::new();
+                let mut finished = None;
+                loop {
+                    match updates.try_recv() {
+                        Ok(ai::Update::Chunk(piece)) => reply.push_str(&piece),
+                        Ok(ai::Update::Done) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                            finished = Some(None);
+                            break;
+                        }
+                        Ok(ai::Update::Failed(message)) => {
+                            finished = Some(Some(message));
+                            break;
+                        }
+                        Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                    }
+                }
+                let running = this
+                    .update_in(cx, |this, window, cx| {
+                        if this.ai_epoch != epoch {
+                            editor.update(cx, |editor, cx| {
+                                editor.read_only = false;
+                                cx.notify();
+                            });
+                            return false;
+                        }
+                        // The first piece replaces the selection, the rest
+                        // follow it
+                        if !reply.is_empty() {
+                            editor.update(cx, |editor, cx| {
+                                editor.cursors = vec![Cursor {
+                                    position: editor.position_from_flat(target.end),
+                                    anchor: Some(editor.position_from_flat(target.start)),
+                                }];
+                                editor.read_only = false;
+                                editor.insert_text_at_cursors(&reply, window, cx);
+                                editor.read_only = true;
+                                let end = editor.flat_offset(&editor.cursors[0].position);
+                                target = end..end;
+                            });
+                        }
+                        let Some(error) = finished.take() else {
+                            return true;
+                        };
+                        editor.update(cx, |editor, cx| {
+                            editor.read_only = false;
+                            cx.notify();
+                        });
+                        this.ai_running = false;
+                        this.ai_error = error;
+                        cx.notify();
+                        false
+                    })
+                    .unwrap_or(false);
+                if !running {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Leave what an AI transform wrote so far and drop the rest.
+    fn stop_ai_transform(&mut self, cx: &mut Context<Self>) {
+        self.ai_epoch += 1;
+        self.ai_running = false;
+        cx.notify();
+    }
+
+    fn render_ai_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let selected = self.ai_picker.unwrap_or_default();
+        let has_selection = self.editor().read(cx).has_selection();
+
+        div()
+            .absolute()
+            .top(px(12.))
+            .left(px(60.))
+            .right(px(60.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(2.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .key_context("AiPromptPicker")
+            .track_focus(&self.ai_picker_focus)
+            .on_action(cx.listener(Self::select_previous_ai_prompt))
+            .on_action(cx.listener(Self::select_next_ai_prompt))
+            .on_action(cx.listener(Self::run_ai_prompt))
+            .child(
+                div()
+                    .px(px(8.))
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(if has_selection { t("ai.transform_selection") } else { t("ai.transform_buffer") }),
+            )
+            .children(cx.global::<Preferences>().ai.prompts.iter().enumerate().map(|(i, prompt)| {
+                div()
+                    .id(("ai-prompt", i))
+                    .px(px(8.))
+                    .py(px(3.))
+                    .rounded(px(4.))
+                    .text_color(theme.text)
+                    .when(i == selected, |el| el.bg(theme.surface1))
+                    .child(prompt.name.clone())
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.run_ai_prompt_at(i, window, cx);
+                    }))
+            }))
+    }
+
+    fn show_scripts(&mut self, _: &ShowScripts, window: &mut Window, cx: &mut Context<Self>) {
+        if self.script_picker.is_some() {
+            self.close_script_picker(window, cx);
+            return;
+        }
+        let names = scripts::list_scripts();
+        if names.is_empty() {
+            self.script_error = Some(t_args(
+                "error.no_scripts",
+                &[("path", &scripts::scripts_dir().display())],
+            ));
+            cx.notify();
+            return;
         }
+        self.script_error = None;
+        self.script_picker = Some((names, 0));
+        window.focus(&self.script_picker_focus, cx);
+        cx.notify();
     }
 
-    #[cfg(target_os = "macos")]
-    fn submit_and_paste(&mut self, _: &SubmitAndPaste, _window: &mut Window, cx: &mut Context<Self>) {
-        let text = self.editor.read(cx).get_submit_text();
-        unsafe {
-            hotkey::submit_and_paste(&text);
+    fn close_script_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.script_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    fn select_previous_script(&mut self, _: &SelectPreviousScript, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = &mut self.script_picker {
+            *selected = selected.saturating_sub(1);
+            cx.notify();
+        }
+    }
+
+    fn select_next_script(&mut self, _: &SelectNextScript, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((names, selected)) = &mut self.script_picker {
+            *selected = (*selected + 1).min(names.len().saturating_sub(1));
+            cx.notify();
+        }
+    }
+
+    fn run_selected_script(&mut self, _: &RunSelectedScript, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((names, selected)) = &self.script_picker
+            && let Some(name) = names.get(*selected).cloned()
+        {
+            self.close_script_picker(window, cx);
+            self.run_script_named(&name, window, cx);
+        }
+    }
+
+    fn run_script(&mut self, action: &RunScript, window: &mut Window, cx: &mut Context<Self>) {
+        self.run_script_named(&action.0, window, cx);
+    }
+
+    /// Replace each selection, or the whole buffer, with what the script
+    /// returns for it. Selections it fails on stay selected and marked.
+    /// The script runs in the background, with the buffer read-only until
+    /// it's done.
+    fn run_script_named(&mut self, name: &str, window: &mut Window, cx: &mut Context<Self>) {
+        let editor = self.editor().clone();
+        if editor.read(cx).read_only {
+            return;
+        }
+        let (texts, cursors) = editor.update(cx, |editor, cx| {
+            let texts = editor.selection_texts_or_buffer(cx);
+            editor.read_only = true;
+            cx.notify();
+            (texts, editor.cursors.clone())
+        });
+        self.script_error = None;
+        cx.notify();
+        let name = name.to_string();
+        let run = cx.background_spawn(async move { scripts::run(&name, &texts) });
+        cx.spawn_in(window, async move |this: WeakEntity<Self>, cx: &mut AsyncWindowContext| {
+            let (converted, error) = run.await;
+            this.update_in(cx, |this, window, cx| {
+                // Back to the selections the texts came from
+                editor.update(cx, |editor, cx| {
+                    editor.read_only = false;
+                    editor.cursors = cursors;
+                    editor.replace_selections(converted, window, cx);
+                    cx.notify();
+                });
+                this.script_error = error;
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn render_script_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let Some((names, selected)) = &self.script_picker else {
+            return div();
+        };
+        let selected = *selected;
+
+        div()
+            .absolute()
+            .top(px(12.))
+            .left(px(60.))
+            .right(px(60.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(2.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .key_context("ScriptPicker")
+            .track_focus(&self.script_picker_focus)
+            .on_action(cx.listener(Self::select_previous_script))
+            .on_action(cx.listener(Self::select_next_script))
+            .on_action(cx.listener(Self::run_selected_script))
+            .child(
+                div()
+                    .px(px(8.))
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t("scripts.title")),
+            )
+            .children(names.iter().enumerate().map(|(i, name)| {
+                let script = name.clone();
+                div()
+                    .id(("script", i))
+                    .px(px(8.))
+                    .py(px(3.))
+                    .rounded(px(4.))
+                    .text_color(theme.text)
+                    .when(i == selected, |el| el.bg(theme.surface1))
+                    .child(name.clone())
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.close_script_picker(window, cx);
+                        this.run_script_named(&script, window, cx);
+                    }))
+            }))
+    }
+
+    /// Pick which recently used app to paste into. Submits normally where
+    /// the platform can't offer a choice.
+    fn show_paste_targets(&mut self, _: &ShowPasteTargets, window: &mut Window, cx: &mut Context<Self>) {
+        if self.paste_target_picker.is_some() {
+            self.close_paste_target_picker(window, cx);
+            return;
+        }
+        let targets = platform::current().paste_targets();
+        if targets.is_empty() {
+            self.submit(false, window, cx);
+            return;
+        }
+        self.paste_target_picker = Some((targets, 0));
+        window.focus(&self.paste_target_focus, cx);
+        cx.notify();
+    }
+
+    fn close_paste_target_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.paste_target_picker = None;
+        let focus = self.editor().read(cx).focus_handle.clone();
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    fn select_previous_paste_target(
+        &mut self,
+        _: &SelectPreviousPasteTarget,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some((_, selected)) = &mut self.paste_target_picker {
+            *selected = selected.saturating_sub(1);
+            cx.notify();
+        }
+    }
+
+    fn select_next_paste_target(&mut self, _: &SelectNextPasteTarget, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((targets, selected)) = &mut self.paste_target_picker {
+            *selected = (*selected + 1).min(targets.len().saturating_sub(1));
+            cx.notify();
+        }
+    }
+
+    fn paste_into_target(&mut self, _: &PasteIntoTarget, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((_, selected)) = self.paste_target_picker {
+            self.paste_into_target_at(selected, window, cx);
+        }
+    }
+
+    fn paste_into_target_at(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some((name, target)) = self
+            .paste_target_picker
+            .as_ref()
+            .and_then(|(targets, _)| targets.get(index).cloned())
+        else {
+            return;
+        };
+        self.close_paste_target_picker(window, cx);
+        let transform = cx.global::<Preferences>().submit.default_transform();
+        let Some(text) = self.take_submission(false, transform, cx) else {
+            return;
+        };
+        if self.run_submit_hook(&text, Some(name.clone()), window, cx) {
+            return;
         }
+        platform::current().submit_and_paste_to(text.clone(), &target, window, cx);
+        self.finish_submit(&text, Some(name), cx);
     }
 
-    #[cfg(not(target_os = "macos"))]
-    fn submit_and_paste(&mut self, _: &SubmitAndPaste, _window: &mut Window, _cx: &mut Context<Self>) {
-        // No-op on other platforms
+    fn render_paste_target_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let (targets, selected) = self
+            .paste_target_picker
+            .as_ref()
+            .map(|(targets, selected)| (targets.as_slice(), *selected))
+            .unwrap_or_default();
+
+        div()
+            .absolute()
+            .top(px(12.))
+            .left(px(60.))
+            .right(px(60.))
+            .p(px(8.))
+            .flex()
+            .flex_col()
+            .gap(px(2.))
+            .rounded(px(8.))
+            .bg(theme.mantle)
+            .border_1()
+            .border_color(theme.surface1)
+            .text_size(scaled_text(12., cx))
+            .key_context("PasteTargetPicker")
+            .track_focus(&self.paste_target_focus)
+            .on_action(cx.listener(Self::select_previous_paste_target))
+            .on_action(cx.listener(Self::select_next_paste_target))
+            .on_action(cx.listener(Self::paste_into_target))
+            .child(
+                div()
+                    .px(px(8.))
+                    .pb(px(4.))
+                    .text_size(scaled_text(11., cx))
+                    .text_color(theme.overlay0)
+                    .child(t("paste_targets.title")),
+            )
+            .children(targets.iter().enumerate().map(|(i, (name, _))| {
+                div()
+                    .id(("paste-target", i))
+                    .px(px(8.))
+                    .py(px(3.))
+                    .rounded(px(4.))
+                    .text_color(theme.text)
+                    .when(i == selected, |el| el.bg(theme.surface1))
+                    .child(name.clone())
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.paste_into_target_at(i, window, cx);
+                    }))
+            }))
     }
 
-    #[cfg(target_os = "macos")]
     fn open_preferences(&mut self, _: &OpenPreferences, _window: &mut Window, cx: &mut Context<Self>) {
         open_preferences_window(cx);
     }
-
-    #[cfg(not(target_os = "macos"))]
-    fn open_preferences(&mut self, _: &OpenPreferences, _window: &mut Window, _cx: &mut Context<Self>) {}
 }
 
 impl Render for PopupEditor {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
+        let opacity = cx.global::<Preferences>().window.background_opacity();
+        let show_header = cx.global::<Preferences>().header.visible;
+        let show_footer = cx.global::<Preferences>().window.footer;
+        if show_header && cx.global::<Preferences>().header.word_count && self.word_count.is_none() {
+            let text = self.editor().read(cx).get_submit_text();
+            self.word_count = Some(TextStatistics::for_text(&text).words);
+        }
 
         div()
             .key_context("PopupEditor")
-            .track_focus(&self.editor.read(cx).focus_handle)
+            .track_focus(&self.editor().read(cx).focus_handle)
             .on_action(cx.listener(Self::escape))
             .on_action(cx.listener(Self::submit_and_paste))
+            .on_action(cx.listener(Self::submit_and_paste_other_newline))
+            .on_action(cx.listener(Self::submit_to_clipboard))
+            .on_action(cx.listener(Self::show_paste_targets))
+            .on_action(cx.listener(Self::show_submit_templates))
+            .on_action(cx.listener(Self::show_submission_history))
             .on_action(cx.listener(Self::open_preferences))
+            .on_action(cx.listener(Self::show_statistics))
+            .on_action(cx.listener(Self::show_diff))
+            .on_action(cx.listener(Self::new_buffer))
+            .on_action(cx.listener(Self::close_buffer))
+            .on_action(cx.listener(Self::activate_buffer))
+            .on_action(cx.listener(Self::show_draft_history))
+            .on_action(cx.listener(Self::show_scratchpads))
+            .on_action(cx.listener(Self::toggle_pin))
+            .on_action(cx.listener(Self::expand_compact))
+            .on_action(cx.listener(Self::expand_window))
+            .on_action(cx.listener(Self::save_file))
+            .on_action(cx.listener(Self::open_in_external_editor))
+            .on_action(cx.listener(Self::transform_with_ai))
+            .on_action(cx.listener(Self::show_scripts))
+            .on_action(cx.listener(Self::run_script))
             .flex()
             .flex_col()
             .size_full()
-            .bg(theme.base)
+            .bg(translucent(theme.base, opacity))
             .text_color(theme.text)
             .overflow_hidden()
-            .child(
-                // Header bar
-                div()
-                    .flex()
-                    .flex_row()
-                    .items_center()
-                    .justify_between()
-                    .w_full()
-                    .h(px(32.))
-                    .px(px(12.))
-                    .border_b_1()
-                    .border_color(theme.surface0)
-                    .child(
+            .when(!self.compact && show_header, |el| el.child(self.render_header(cx)))
+            .when(self.buffers.len() > 1 && !self.compact, |el| el.child(self.render_tab_bar(cx)))
+            .when(self.confirm_close, |el| {
+                let name = self.editor().read(cx).file_name().unwrap_or_default();
+                el.child(
+                    div()
+                        .flex_none()
+                        .w_full()
+                        .px(px(12.))
+                        .py(px(4.))
+                        .bg(theme.surface0)
+                        .text_size(scaled_text(11., cx))
+                        .text_color(theme.accent)
+                        .child(t_args("banner.unsaved_changes", &[("name", &name)])),
+                )
+            })
+            .when(!self.recovered.is_empty(), |el| {
+                let button = |id: &'static str, label: &'static str| {
+                    div()
+                        .id(id)
+                        .px(px(6.))
+                        .rounded(px(4.))
+                        .cursor(CursorStyle::PointingHand)
+                        .hover(|s| s.bg(theme.surface1))
+                        .child(label)
+                };
+                el.child(
+                    div()
+                        .flex_none()
+                        .flex()
+                        .flex_row()
+                        .items_center()
+                        .gap(px(8.))
+                        .w_full()
+                        .px(px(12.))
+                        .py(px(4.))
+                        .bg(theme.surface0)
+                        .text_size(scaled_text(11., cx))
+                        .text_color(theme.accent)
+                        .child(t("banner.recovered"))
+                        .child(button("recovery-restore", t("banner.recovered_restore")).on_click(cx.listener(
+                            |this, _, window, cx| this.restore_recovered(window, cx),
+                        )))
+                        .child(button("recovery-shelve", t("banner.recovered_shelve")).on_click(
+                            cx.listener(|this, _, _, cx| this.shelve_recovered(cx)),
+                        )),
+                )
+            })
+            .when_some(
+                self.file_error
+                    .clone()
+                    .or_else(|| self.ai_error.clone())
+                    .or_else(|| self.script_error.clone())
+                    .or_else(|| self.hook_error.clone())
+                    .or_else(|| self.paste_warning.clone()),
+                |el, warning| {
+                    el.child(
                         div()
-                            .text_size(px(13.))
-                            .text_color(theme.subtext0)
-                            .child("Zeditor"),
+                            .flex_none()
+                            .w_full()
+                            .px(px(12.))
+                            .py(px(4.))
+                            .bg(with_alpha(theme.error, 0.12))
+                            .text_size(scaled_text(11., cx))
+                            .text_color(theme.error)
+                            .child(warning),
                     )
-                    .child(
-                        div()
-                            .text_size(px(11.))
-                            .text_color(theme.overlay0)
-                            .child(self.editor.read(cx).status_text()),
-                    ),
+                },
             )
             .child(
                 // Editor area
                 div()
+                    .relative()
                     .flex()
                     .flex_1()
                     .w_full()
                     .overflow_hidden()
-                    .child(self.editor.clone()),
+                    .child(self.editor().clone())
+                    .when(self.show_diff, |el| el.child(self.render_diff(cx)))
+                    .when(self.draft_picker.is_some(), |el| el.child(self.render_draft_picker(cx)))
+                    .when(self.scratchpad_picker.is_some(), |el| {
+                        el.child(self.render_scratchpad_picker(cx))
+                    })
+                    .when(self.submission_picker.is_some(), |el| {
+                        el.child(self.render_submission_picker(cx))
+                    })
+                    .when(self.template_picker.is_some(), |el| {
+                        el.child(self.render_template_picker(cx))
+                    })
+                    .when(self.paste_target_picker.is_some(), |el| {
+                        el.child(self.render_paste_target_picker(cx))
+                    })
+                    .when(self.ai_picker.is_some(), |el| el.child(self.render_ai_picker(cx)))
+                    .when(self.script_picker.is_some(), |el| el.child(self.render_script_picker(cx)))
+                    .when(self.show_statistics, |el| el.child(self.render_statistics(cx))),
             )
+            .when(!self.compact && show_footer, |el| el.child(self.render_footer(cx)))
     }
 }
 
 impl Focusable for PopupEditor {
     fn focus_handle(&self, cx: &App) -> FocusHandle {
-        self.editor.read(cx).focus_handle.clone()
+        self.editor().read(cx).focus_handle.clone()
     }
 }
 
 fn main() {
-    // Check for CLI text argument or piped stdin
-    #[cfg(target_os = "macos")]
-    {
-        let args: Vec<String> = std::env::args().collect();
-        if args.len() > 1 {
-            let text = args[1..].join(" ");
-            hotkey::set_initial_text(text);
-        } else {
-            unsafe extern "C" { fn isatty(fd: i32) -> i32; }
-            let is_tty = unsafe { isatty(0) != 0 };
-            if !is_tty {
-                use std::io::Read;
-                let mut text = String::new();
-                if std::io::stdin().read_to_string(&mut text).is_ok() && !text.is_empty() {
-                    hotkey::set_initial_text(text);
-                }
-            }
+    // Text from arguments or piped stdin goes to the running instance if
+    // there is one
+    let request = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(request) => request,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
         }
+    };
+    if let Some(request) = &request
+        && cli::forward(request)
+    {
+        return;
+    }
+    cli::listen();
+    if let Some(request) = request {
+        cli::queue(request);
     }
+    recovery::install_panic_hook();
 
     Application::new().with_assets(Assets).run(|cx: &mut App| {
         // Load embedded fonts
@@ -208,47 +2620,60 @@ fn main() {
         cx.bind_keys([
             // App-level keybindings
             KeyBinding::new("escape", Escape, Some("PopupEditor")),
-            KeyBinding::new("cmd-enter", SubmitAndPaste, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-enter", SubmitAndPasteOtherNewline, Some("PopupEditor")),
+            KeyBinding::new("cmd-alt-enter", SubmitToClipboard, Some("PopupEditor")),
+            KeyBinding::new("cmd-ctrl-enter", ShowPasteTargets, Some("PopupEditor")),
+            KeyBinding::new("ctrl-enter", ShowSubmitTemplates, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-y", ShowSubmissionHistory, Some("PopupEditor")),
+            KeyBinding::new("up", SelectPreviousSubmission, Some("SubmissionHistory")),
+            KeyBinding::new("down", SelectNextSubmission, Some("SubmissionHistory")),
+            KeyBinding::new("enter", Resubmit, Some("SubmissionHistory")),
+            KeyBinding::new("cmd-c", CopySubmission, Some("SubmissionHistory")),
+            KeyBinding::new("up", SelectPreviousTemplate, Some("TemplatePicker")),
+            KeyBinding::new("down", SelectNextTemplate, Some("TemplatePicker")),
+            KeyBinding::new("enter", SubmitWithTemplate, Some("TemplatePicker")),
+            KeyBinding::new("up", SelectPreviousPasteTarget, Some("PasteTargetPicker")),
+            KeyBinding::new("down", SelectNextPasteTarget, Some("PasteTargetPicker")),
+            KeyBinding::new("enter", PasteIntoTarget, Some("PasteTargetPicker")),
+            KeyBinding::new("up", SelectPreviousAiPrompt, Some("AiPromptPicker")),
+            KeyBinding::new("down", SelectNextAiPrompt, Some("AiPromptPicker")),
+            KeyBinding::new("enter", RunAiPrompt, Some("AiPromptPicker")),
+            KeyBinding::new("up", SelectPreviousScript, Some("ScriptPicker")),
+            KeyBinding::new("down", SelectNextScript, Some("ScriptPicker")),
+            KeyBinding::new("enter", RunSelectedScript, Some("ScriptPicker")),
             KeyBinding::new("cmd-,", OpenPreferences, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-i", ShowStatistics, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-d", ShowDiff, Some("PopupEditor")),
+            KeyBinding::new("cmd-t", NewBuffer, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-h", ShowDraftHistory, Some("PopupEditor")),
+            KeyBinding::new("up", SelectPreviousDraft, Some("DraftHistory")),
+            KeyBinding::new("down", SelectNextDraft, Some("DraftHistory")),
+            KeyBinding::new("enter", RestoreDraft, Some("DraftHistory")),
+            KeyBinding::new("cmd-p", ShowScratchpads, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-p", TogglePin, Some("PopupEditor")),
+            KeyBinding::new("cmd-shift-f", ExpandWindow, Some("PopupEditor")),
+            KeyBinding::new("cmd-s", SaveFile, Some("PopupEditor")),
+            KeyBinding::new("cmd-alt-e", OpenInExternalEditor, Some("PopupEditor")),
+            KeyBinding::new("cmd-alt-t", TransformWithAI, Some("PopupEditor")),
+            KeyBinding::new("cmd-alt-p", ShowScripts, Some("PopupEditor")),
+            KeyBinding::new("up", SelectPreviousScratchpad, Some("ScratchpadPicker")),
+            KeyBinding::new("down", SelectNextScratchpad, Some("ScratchpadPicker")),
+            KeyBinding::new("enter", OpenScratchpad, Some("ScratchpadPicker")),
+            KeyBinding::new("cmd-w", CloseBuffer, Some("PopupEditor")),
+            KeyBinding::new("cmd-1", ActivateBuffer(0), Some("PopupEditor")),
+            KeyBinding::new("cmd-2", ActivateBuffer(1), Some("PopupEditor")),
+            KeyBinding::new("cmd-3", ActivateBuffer(2), Some("PopupEditor")),
+            KeyBinding::new("cmd-4", ActivateBuffer(3), Some("PopupEditor")),
+            KeyBinding::new("cmd-5", ActivateBuffer(4), Some("PopupEditor")),
+            KeyBinding::new("cmd-6", ActivateBuffer(5), Some("PopupEditor")),
+            KeyBinding::new("cmd-7", ActivateBuffer(6), Some("PopupEditor")),
+            KeyBinding::new("cmd-8", ActivateBuffer(7), Some("PopupEditor")),
+            KeyBinding::new("cmd-9", ActivateBuffer(8), Some("PopupEditor")),
             KeyBinding::new("cmd-q", Quit, None),
-            // Editor keybindings
-            KeyBinding::new("backspace", Backspace, Some("MultiLineEditor")),
-            KeyBinding::new("delete", Delete, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-backspace", DeleteToStart, Some("MultiLineEditor")),
-            KeyBinding::new("alt-backspace", DeleteWordBackward, Some("MultiLineEditor")),
-            KeyBinding::new("left", Left, Some("MultiLineEditor")),
-            KeyBinding::new("right", Right, Some("MultiLineEditor")),
-            KeyBinding::new("up", Up, Some("MultiLineEditor")),
-            KeyBinding::new("down", Down, Some("MultiLineEditor")),
-            KeyBinding::new("shift-left", SelectLeft, Some("MultiLineEditor")),
-            KeyBinding::new("shift-right", SelectRight, Some("MultiLineEditor")),
-            KeyBinding::new("shift-up", SelectUp, Some("MultiLineEditor")),
-            KeyBinding::new("shift-down", SelectDown, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-a", SelectAll, Some("MultiLineEditor")),
-            KeyBinding::new("home", Home, Some("MultiLineEditor")),
-            KeyBinding::new("end", End, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-left", Home, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-right", End, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-up", DocumentStart, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-down", DocumentEnd, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-shift-left", SelectHome, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-shift-right", SelectEnd, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-shift-up", SelectDocumentStart, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-shift-down", SelectDocumentEnd, Some("MultiLineEditor")),
-            KeyBinding::new("alt-left", WordLeft, Some("MultiLineEditor")),
-            KeyBinding::new("alt-right", WordRight, Some("MultiLineEditor")),
-            KeyBinding::new("alt-shift-left", SelectWordLeft, Some("MultiLineEditor")),
-            KeyBinding::new("alt-shift-right", SelectWordRight, Some("MultiLineEditor")),
-            KeyBinding::new("enter", Enter, Some("MultiLineEditor")),
-            KeyBinding::new("alt-up", MoveLineUp, Some("MultiLineEditor")),
-            KeyBinding::new("alt-down", MoveLineDown, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-alt-up", AddCursorUp, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-alt-down", AddCursorDown, Some("MultiLineEditor")),
-            KeyBinding::new("ctrl-cmd-space", ShowCharacterPalette, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-v", Paste, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-c", Copy, Some("MultiLineEditor")),
-            KeyBinding::new("cmd-x", Cut, Some("MultiLineEditor")),
-            KeyBinding::new("alt-z", ToggleWordWrap, Some("MultiLineEditor")),
+        ]);
+        cx.bind_keys(zeditor_core::key_bindings());
+        cx.bind_keys([
+            KeyBinding::new("shift-enter", ExpandCompact, Some("MultiLineEditor && single_line")),
             // Preferences window keybindings
             KeyBinding::new("escape", ClosePreferences, Some("PreferencesWindow")),
             KeyBinding::new("cmd-w", ClosePreferences, Some("PreferencesWindow")),
@@ -259,20 +2684,58 @@ fn main() {
         // Initialize preferences (before theme, so hotkey config is available)
         Preferences::init(cx);
 
+        // Submit keys come from the preferences
+        let submit = cx.global::<Preferences>().submit.clone();
+        let mut submit_bindings = vec![KeyBinding::new(
+            submit.keystroke(),
+            SubmitAndPaste,
+            Some("PopupEditor"),
+        )];
+        if submit.enter_in_compact {
+            submit_bindings.push(KeyBinding::new(
+                "enter",
+                SubmitAndPaste,
+                Some("MultiLineEditor && single_line"),
+            ));
+        }
+        cx.bind_keys(submit_bindings);
+        let script_bindings = cx.global::<Preferences>().script_bindings.clone();
+        cx.bind_keys(script_bindings.into_iter().map(|(keystroke, script)| {
+            KeyBinding::new(&keystroke, RunScript(script), Some("PopupEditor"))
+        }));
+
         // Initialize theme
         Theme::init(cx);
+        TextScale::init(cx);
 
-        // Create popup window
+        // The editor reaches the system through the platform, and reads its
+        // settings and colors from globals of its own
+        zeditor_core::set_host(platform::current());
+        share_with_editor(cx);
+        follow_language(cx);
+        follow_theme(cx);
+
+        // Create popup window at its remembered size, and position if set
+        let window_prefs = cx.global::<Preferences>().window.clone();
+        let window_size = size(px(window_prefs.width), px(window_prefs.height));
+        let window_bounds = match window_prefs.origin {
+            Some((x, y)) if window_prefs.position == WindowPosition::RestoreLast => {
+                Bounds::new(point(px(x), px(y)), window_size)
+            }
+            _ => Bounds::centered(None, window_size, cx),
+        };
         let options = WindowOptions {
-            window_bounds: Some(WindowBounds::Windowed(Bounds::centered(
-                None,
-                size(px(600.), px(400.)),
-                cx,
-            ))),
+            window_bounds: Some(WindowBounds::Windowed(window_bounds)),
             titlebar: None,
             show: false,
             focus: false,
             kind: WindowKind::PopUp,
+            // Let the translucent background show what's behind the window
+            window_background: if window_prefs.background_opacity() < 1.0 {
+                WindowBackgroundAppearance::Transparent
+            } else {
+                WindowBackgroundAppearance::Opaque
+            },
             ..Default::default()
         };
 
@@ -281,96 +2744,104 @@ fn main() {
                 cx.new(|cx| {
                     let popup = PopupEditor::new(cx);
                     // Focus the editor
-                    let focus = popup.editor.read(cx).focus_handle.clone();
+                    let focus = popup.editor().read(cx).focus_handle.clone();
                     window.focus(&focus, cx);
+                    cx.observe_window_bounds(window, |this, window, cx| {
+                        this.on_window_bounds_changed(window, cx);
+                    })
+                    .detach();
+                    cx.observe_window_appearance(window, |_, window, cx| {
+                        Theme::update(window.appearance(), cx);
+                    })
+                    .detach();
                     popup
                 })
             })
             .unwrap();
 
-        // macOS-specific: set accessory activation policy and adjust window level
-        #[cfg(target_os = "macos")]
-        {
-            use cocoa::appkit::NSApplicationActivationPolicy::NSApplicationActivationPolicyAccessory;
-            use objc::{class, msg_send, sel, sel_impl};
-
-            unsafe {
-                // Set activation policy to Accessory (no Dock icon)
-                let ns_app: cocoa::base::id =
-                    msg_send![class!(NSApplication), sharedApplication];
-                let _: () = msg_send![
-                    ns_app,
-                    setActivationPolicy: NSApplicationActivationPolicyAccessory as i64
-                ];
-            }
-
-            // Read hotkey config from preferences
-            let prefs = cx.global::<Preferences>();
-            let key_code = prefs.hotkey.key_code;
-            let modifiers = prefs.hotkey.modifiers;
-
-            // Get NSWindow from the GPUI window handle
-            window_handle
-                .update(cx, |_root, window, _cx| {
-                    if let Ok(handle) = window.window_handle() {
-                        let raw = handle.as_raw();
-                        if let raw_window_handle::RawWindowHandle::AppKit(appkit) = raw {
-                            let ns_view = appkit.ns_view.as_ptr() as *mut objc::runtime::Object;
-                            unsafe {
-                                let ns_window: *mut objc::runtime::Object =
-                                    msg_send![ns_view, window];
-                                let _: () = msg_send![ns_window, setLevel: 3i64];
-                                hotkey::register_hotkey(ns_window, key_code, modifiers);
-                            }
-                        }
-                    }
-                })
-                .ok();
-
-            // Poll for preferences window requests from the menu bar
-            cx.spawn(async move |cx: &mut AsyncApp| {
-                loop {
-                    cx.background_executor()
-                        .timer(std::time::Duration::from_millis(100))
-                        .await;
-                    if hotkey::is_prefs_requested() {
-                        cx.update(|cx| {
-                            open_preferences_window(cx);
-                        });
-                    }
-                }
+        // Hotkeys and the menu bar or tray item
+        let platform = platform::current();
+        window_handle
+            .update(cx, |_root, window, cx| {
+                platform.install(window, cx.global::<Preferences>());
             })
-            .detach();
+            .ok();
 
-            // Poll for show-window requests: check clipboard, then show
-            cx.spawn(async move |cx: &mut AsyncApp| {
-                loop {
-                    cx.background_executor()
-                        .timer(std::time::Duration::from_millis(10))
-                        .await;
-                    if hotkey::is_show_requested() {
-                        window_handle.update(cx, |root: &mut PopupEditor, _window, cx| {
-                            root.on_show(cx);
-                        }).ok();
-                        unsafe { hotkey::show_window_now() };
+        // Poll for hotkey presses and menu picks
+        cx.spawn(async move |cx: &mut AsyncApp| {
+            loop {
+                cx.background_executor()
+                    .timer(std::time::Duration::from_millis(10))
+                    .await;
+                platform.tick();
+                if platform.take_appearance_change() {
+                    cx.update(|cx| Theme::update(cx.window_appearance(), cx));
+                    window_handle
+                        .update(cx, |root: &mut PopupEditor, _, cx| root.update_text_scale(cx))
+                        .ok();
+                }
+                // Before any show, which may replace the text
+                if platform.take_hidden() {
+                    window_handle
+                        .update(cx, |root: &mut PopupEditor, _, cx| root.keep_draft(cx))
+                        .ok();
+                }
+                if let Some(action) = platform.take_show_request() {
+                    let placement = window_handle.update(cx, |root: &mut PopupEditor, window, cx| {
+                        root.prepare_show(action, window, cx);
+                        PopupEditor::placement_on_show(cx)
+                    }).unwrap_or(Placement::Center);
+                    platform.show(window_handle.into(), placement, cx);
+                }
+                if platform.take_hide_request() {
+                    window_handle
+                        .update(cx, |root: &mut PopupEditor, window, cx| {
+                            root.keep_draft(cx);
+                            platform.hide(window);
+                        })
+                        .ok();
+                }
+                if let Some(request) = cli::take_request() {
+                    let placement = window_handle.update(cx, |root: &mut PopupEditor, window, cx| {
+                        root.open_cli_request(request, window, cx);
+                        PopupEditor::placement_on_show(cx)
+                    }).unwrap_or(Placement::Center);
+                    platform.show(window_handle.into(), placement, cx);
+                }
+                match platform.take_menu_request() {
+                    Some(MenuRequest::Preferences) => cx.update(open_preferences_window),
+                    Some(MenuRequest::Quit) => cx.update(|cx| cx.quit()),
+                    Some(MenuRequest::SubmissionHistory) => {
+                        let placement = window_handle.update(cx, |root: &mut PopupEditor, window, cx| {
+                            root.open_submission_history(window, cx);
+                            PopupEditor::placement_on_show(cx)
+                        }).unwrap_or(Placement::Center);
+                        platform.show(window_handle.into(), placement, cx);
                     }
+                    Some(MenuRequest::Scratchpad(name)) => {
+                        let placement = window_handle.update(cx, |root: &mut PopupEditor, window, cx| {
+                            root.open_scratchpad(name, window, cx);
+                            PopupEditor::placement_on_show(cx)
+                        }).unwrap_or(Placement::Center);
+                        platform.show(window_handle.into(), placement, cx);
+                    }
+                    None => {}
                 }
-            })
-            .detach();
-        }
+            }
+        })
+        .detach();
     });
 }
 
-#[cfg(target_os = "macos")]
 fn open_preferences_window(cx: &mut App) {
     let options = WindowOptions {
         window_bounds: Some(WindowBounds::Windowed(Bounds::centered(
             None,
-            size(px(400.), px(250.)),
+            size(px(440.), px(520.)),
             cx,
         ))),
         titlebar: Some(TitlebarOptions {
-            title: Some("Preferences".into()),
+            title: Some(t("preferences.title").into()),
             ..Default::default()
         }),
         show: true,
@@ -386,23 +2857,48 @@ fn open_preferences_window(cx: &mut App) {
     });
 }
 
-#[cfg(target_os = "macos")]
-fn hide_window(window: &mut Window) {
-    if let Ok(handle) = window.window_handle() {
-        let raw = handle.as_raw();
-        if let raw_window_handle::RawWindowHandle::AppKit(appkit) = raw {
-            let ns_view = appkit.ns_view.as_ptr() as *mut objc::runtime::Object;
-            unsafe {
-                let ns_window: *mut objc::runtime::Object = msg_send![ns_view, window];
-                let _: () = msg_send![ns_window, orderOut: cocoa::base::nil];
-            }
-        }
+/// Hand the editor its share of the preferences and theme, now and again
+/// whenever they or the system's text size change.
+fn share_with_editor(app: &mut App) {
+    fn share_settings(app: &mut App) {
+        let settings = app.global::<Preferences>().editor_settings(app.global::<TextScale>().0);
+        app.set_global(settings);
     }
+    fn share_theme(app: &mut App) {
+        let theme = app.global::<Theme>().editor_theme();
+        app.set_global(theme);
+    }
+    share_settings(app);
+    share_theme(app);
+    app.observe_global::<Preferences>(share_settings).detach();
+    app.observe_global::<TextScale>(share_settings).detach();
+    app.observe_global::<Theme>(share_theme).detach();
+}
+
+/// Switch the UI's language when the preference changes. Open windows
+/// redraw in it at once; menus built at launch keep theirs until restart.
+fn follow_language(app: &mut App) {
+    let mut language = app.global::<Preferences>().language.clone();
+    app.observe_global::<Preferences>(move |app| {
+        let prefs = app.global::<Preferences>();
+        if prefs.language == language {
+            return;
+        }
+        language = prefs.language.clone();
+        i18n::set_language(&language);
+        app.refresh_windows();
+    })
+    .detach();
 }
 
-#[cfg(not(target_os = "macos"))]
-fn hide_window(_window: &mut Window) {
-    // No-op on other platforms
+/// Tell the platform side whether the theme is dark, now and whenever
+/// the theme changes.
+fn follow_theme(app: &mut App) {
+    fn share(app: &mut App) {
+        platform::current().set_dark_theme(app.global::<Theme>().name.is_dark());
+    }
+    share(app);
+    app.observe_global::<Theme>(share).detach();
 }
 
 fn quit(_: &Quit, app: &mut App) {
@@ -0,0 +1,234 @@
+//! What the popup needs from the desktop around it: a global hotkey, a menu
+//! bar or tray menu, showing and hiding, and pasting into the app that was
+//! in front. Each OS implements `Platform` over its own FFI module
+//! (hotkey.rs, hotkey_linux.rs, hotkey_windows.rs); the GPUI side only
+//! talks to the trait. What the editor itself needs of the system is its
+//! `EditorHost` supertrait.
+
+use gpui::{AnyWindowHandle, App, AsyncApp, ClipboardItem, Window};
+use zeditor_core::EditorHost;
+
+use crate::preferences::{HotkeyAction, HotkeyConfig, Preferences};
+
+/// Where `Platform::show` puts the popup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// Wherever it was last placed
+    Keep,
+    /// Centered on the screen
+    Center,
+    /// Just below the text caret of the app that was frontmost, falling
+    /// back to centering when the caret can't be found
+    Caret,
+}
+
+/// Something picked from the menu bar or tray menu.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuRequest {
+    Preferences,
+    Quit,
+    /// Open a scratchpad by name, or the unnamed one
+    Scratchpad(Option<String>),
+    SubmissionHistory,
+}
+
+pub trait Platform: EditorHost {
+    /// Register the global hotkeys from `prefs` and put up the menu bar or
+    /// tray item. Called once, with the popup window.
+    fn install(&self, window: &mut Window, prefs: &Preferences);
+
+    /// Swap in a new main hotkey after it was changed in preferences.
+    fn set_hotkey(&self, config: &HotkeyConfig);
+
+    /// The hotkey action behind a pending show request, if one came in
+    /// since the last call.
+    fn take_show_request(&self) -> Option<HotkeyAction>;
+
+    /// Whether the hotkey asked to hide the popup since the last call.
+    fn take_hide_request(&self) -> bool {
+        false
+    }
+
+    /// Whether the popup was hidden without the GPUI side asking, by the
+    /// hotkey, Escape or losing focus, since the last call.
+    fn take_hidden(&self) -> bool {
+        false
+    }
+
+    /// The menu item picked since the last call, if any.
+    fn take_menu_request(&self) -> Option<MenuRequest> {
+        None
+    }
+
+    /// Text handed over to open in the popup since the last call: the
+    /// clipboard for a hotkey that loads it, or dropped, scripted or
+    /// Services text.
+    fn take_pending_clipboard(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether the system switched between light and dark mode since the
+    /// last call.
+    fn take_appearance_change(&self) -> bool {
+        false
+    }
+
+    /// Whether the system asks for higher contrast (macOS's Increase
+    /// Contrast). Changes to it count as appearance changes.
+    fn increase_contrast(&self) -> bool {
+        false
+    }
+
+    /// Text size multiplier from the system's accessibility settings, 1.0
+    /// at the normal size.
+    fn text_scale(&self) -> f32 {
+        1.0
+    }
+
+    /// Called on every poll so time-limited hotkey state can lapse.
+    fn tick(&self) {}
+
+    /// Bring the popup to the front. Called outside any window update, as
+    /// ordering the window front can re-enter GPUI.
+    fn show(&self, window: AnyWindowHandle, placement: Placement, cx: &mut AsyncApp);
+
+    /// Put the popup away and hand focus back to the previous app.
+    fn hide(&self, window: &mut Window);
+
+    /// Grow the popup to most of its screen, or put it back where it was.
+    fn set_expanded(&self, _expanded: bool) {}
+
+    /// Keep the popup open when another app is activated.
+    fn set_pinned(&self, _pinned: bool) {}
+
+    /// Put `text` on the clipboard, hide the popup and paste into the app
+    /// that was in front before it.
+    fn submit_and_paste(&self, text: String, window: &mut Window, cx: &mut App);
+
+    /// Name of the app the next submit pastes into, if known.
+    fn paste_target_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Apps that submitting could paste into, as (name, identifier), most
+    /// recently used first. Empty where only the previous app can be
+    /// pasted into.
+    fn paste_targets(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Like `submit_and_paste`, but into the app picked from
+    /// `paste_targets`.
+    fn submit_and_paste_to(&self, text: String, _target: &str, window: &mut Window, cx: &mut App) {
+        self.submit_and_paste(text, window, cx);
+    }
+
+    /// Put `text` on the clipboard and hide the popup, leaving the paste
+    /// to the user.
+    fn submit_to_clipboard(&self, text: String, window: &mut Window, cx: &mut App) {
+        cx.write_to_clipboard(ClipboardItem::new_string(text));
+        self.hide(window);
+    }
+
+    /// Why the hotkey couldn't be registered, if it couldn't.
+    fn error(&self) -> Option<String>;
+
+    /// Who else already uses a key combination (Carbon key code and
+    /// modifiers), checked while recording a new hotkey.
+    fn hotkey_conflict(&self, _key_code: u32, _modifiers: u32) -> Option<String> {
+        None
+    }
+
+    /// Whether the app holds the permission paste simulation needs, or None
+    /// where the OS has no such permission.
+    fn accessibility_trusted(&self) -> Option<bool> {
+        None
+    }
+
+    /// Settings page where that permission is granted.
+    fn accessibility_settings_url(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Why pasting into other apps won't work right now, if it won't.
+    fn paste_blocked(&self) -> Option<String> {
+        None
+    }
+
+    /// Pick up preferences the platform side keeps its own copy of, after
+    /// they were changed in the preferences window.
+    fn apply_preferences(&self, _prefs: &Preferences) {}
+
+    /// Whether the active theme is dark, whenever it changes, for anything
+    /// the platform draws behind the editor.
+    fn set_dark_theme(&self, _dark: bool) {}
+
+    /// Apps, by identifier, in which the hotkeys should do nothing.
+    fn set_excluded_apps(&self, _apps: &[String]) {}
+
+    /// Running apps that could be excluded, as (name, identifier), or None
+    /// where excluding apps isn't supported.
+    fn running_apps(&self) -> Option<Vec<(String, String)>> {
+        None
+    }
+
+    /// Text of the active buffer, kept current for scripts that ask for it
+    /// and for screen readers.
+    fn set_current_draft(&self, _text: &str) {}
+}
+
+/// The platform the app is running on.
+#[cfg(target_os = "macos")]
+pub fn current() -> &'static dyn Platform {
+    &crate::hotkey::MacPlatform
+}
+
+/// The platform the app is running on.
+#[cfg(target_os = "linux")]
+pub fn current() -> &'static dyn Platform {
+    &crate::hotkey_linux::LinuxPlatform
+}
+
+/// The platform the app is running on.
+#[cfg(target_os = "windows")]
+pub fn current() -> &'static dyn Platform {
+    &crate::hotkey_windows::WindowsPlatform
+}
+
+/// The platform the app is running on.
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub fn current() -> &'static dyn Platform {
+    &Unsupported
+}
+
+/// No global hotkey or menu; the popup behaves like a plain window.
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+struct Unsupported;
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+impl EditorHost for Unsupported {
+    fn text(&self, key: &str) -> Option<String> {
+        crate::i18n::lookup(key).map(str::to_string)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+impl Platform for Unsupported {
+    fn install(&self, _window: &mut Window, _prefs: &Preferences) {}
+
+    fn set_hotkey(&self, _config: &HotkeyConfig) {}
+
+    fn take_show_request(&self) -> Option<HotkeyAction> {
+        None
+    }
+
+    fn show(&self, _window: AnyWindowHandle, _placement: Placement, _cx: &mut AsyncApp) {}
+
+    fn hide(&self, _window: &mut Window) {}
+
+    fn submit_and_paste(&self, _text: String, _window: &mut Window, _cx: &mut App) {}
+
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
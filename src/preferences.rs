@@ -1,12 +1,35 @@
-use gpui::{App, Global};
+use gpui::{App, Global, Keystroke, WindowAppearance};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use zeditor_core::EditorSettings;
+pub use zeditor_core::settings::{CursorShape, EditorConfig, LineHeight, WordMotion};
+
+use crate::i18n::{t, t_args};
+use crate::templates::{SubmitTemplate, Transform, default_templates};
+use crate::theme::ThemeName;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HotkeyConfig {
     pub key_code: u32,
     pub modifiers: u32,
     pub display_string: String,
+    /// Second step of a chord such as Cmd+K then Z. The combo above is
+    /// then only the prefix.
+    #[serde(default)]
+    pub chord: Option<ChordKey>,
+}
+
+/// The key pressed after a chord prefix. Modifiers are optional here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChordKey {
+    pub key_code: u32,
+    pub modifiers: u32,
+    pub display_string: String,
 }
 
 impl Default for HotkeyConfig {
@@ -15,47 +38,775 @@ impl Default for HotkeyConfig {
             key_code: 0x0E,      // 'E'
             modifiers: (1 << 8) | (1 << 9), // Cmd + Shift
             display_string: "Cmd+Shift+E".to_string(),
+            chord: None,
+        }
+    }
+}
+
+/// What a global hotkey does when pressed while the popup is hidden.
+/// Pressing any but `PasteLast` while it's shown hides it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    /// Show, pre-filled from the clipboard when it changed
+    Toggle,
+    /// Show with an empty buffer
+    NewDraft,
+    /// Show as the compact quick-entry row
+    Compact,
+    /// Show with the clipboard loaded and selected, even if it hasn't
+    /// changed since last time
+    ShowClipboard,
+    /// Paste the last submitted text into the frontmost app, without
+    /// showing the popup
+    PasteLast,
+}
+
+/// An extra global hotkey bound to an action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionHotkey {
+    pub action: HotkeyAction,
+    #[serde(flatten)]
+    pub hotkey: HotkeyConfig,
+}
+
+/// Where the popup appears when shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowPosition {
+    /// Centered on the screen every time
+    #[default]
+    Center,
+    /// Wherever it was last moved to
+    RestoreLast,
+    /// Next to the text caret of the frontmost app, centered when the
+    /// caret can't be found
+    Caret,
+}
+
+/// How the popup appears and disappears.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShowAnimation {
+    /// Show and hide instantly
+    #[default]
+    None,
+    /// Slide down from the top edge of the screen and back up, Quake-style
+    SlideDown,
+}
+
+/// Whether animations are skipped in favor of instant changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReduceMotion {
+    /// Follow the system's Reduce Motion setting (macOS only)
+    #[default]
+    System,
+    On,
+    Off,
+}
+
+/// Which of the light and dark themes is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppearanceMode {
+    /// Follow the system's light or dark mode
+    System,
+    Light,
+    #[default]
+    Dark,
+}
+
+/// How a submit gets its text into the previous app.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmitBackend {
+    /// Put the text on the clipboard and simulate Cmd+V
+    #[default]
+    Paste,
+    /// Write it into the focused text field through the Accessibility API,
+    /// leaving the clipboard alone; falls back to pasting for fields that
+    /// don't accept it (macOS only)
+    Accessibility,
+}
+
+/// Whether submitted text is still pasted once the submit hook has it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookMode {
+    /// Run the hook, then paste as usual
+    #[default]
+    Before,
+    /// The hook is the only place the text goes
+    Instead,
+}
+
+/// macOS material for the blurred background behind the editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Vibrancy {
+    HudWindow,
+    Popover,
+    Menu,
+    Sidebar,
+    UnderWindowBackground,
+}
+
+/// Most of the theme's base color kept over the blur so text stays
+/// readable against bright desktops.
+const VIBRANCY_TINT: f32 = 0.85;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub position: WindowPosition,
+    /// Last size, kept when the popup is resized
+    pub width: f32,
+    pub height: f32,
+    /// Last top-left corner in screen coordinates, once the popup has moved
+    pub origin: Option<(f32, f32)>,
+    /// Background opacity from 0.2 (mostly see-through) to 1.0 (opaque)
+    pub opacity: f32,
+    pub appearance: AppearanceMode,
+    /// Palettes for light and dark mode
+    pub light_theme: ThemeName,
+    pub dark_theme: ThemeName,
+    /// Switch to the high-contrast palettes while the system's Increase
+    /// Contrast setting is on (macOS only)
+    pub follow_increase_contrast: bool,
+    /// Open as a single input row (Enter submits, shift-enter expands)
+    pub compact: bool,
+    /// Status bar under the editor with wrap, line ending and mode
+    pub footer: bool,
+    /// Blur what's behind the window with this material (macOS only)
+    pub vibrancy: Option<Vibrancy>,
+    pub animation: ShowAnimation,
+    /// Drop the cursor fade, caret glide, smooth scrolling and the slide
+    /// animation
+    pub reduce_motion: ReduceMotion,
+    /// Keep the popup out of screenshots, screen shares and recordings
+    /// (macOS only)
+    pub hide_from_capture: bool,
+}
+
+impl WindowConfig {
+    /// The theme in use while the system is in `system` mode, with its
+    /// Increase Contrast setting as given.
+    pub fn theme_for(&self, system: WindowAppearance, increase_contrast: bool) -> ThemeName {
+        let dark = match self.appearance {
+            AppearanceMode::System => {
+                matches!(system, WindowAppearance::Dark | WindowAppearance::VibrantDark)
+            }
+            AppearanceMode::Light => false,
+            AppearanceMode::Dark => true,
+        };
+        if increase_contrast && self.follow_increase_contrast {
+            ThemeName::high_contrast(dark)
+        } else if dark {
+            self.dark_theme
+        } else {
+            self.light_theme
+        }
+    }
+
+    /// Whether animations are off, or None to follow the system's Reduce
+    /// Motion setting.
+    pub fn reduce_motion(&self) -> Option<bool> {
+        match self.reduce_motion {
+            ReduceMotion::System => None,
+            ReduceMotion::On => Some(true),
+            ReduceMotion::Off => Some(false),
+        }
+    }
+
+    /// Opacity for the themed backgrounds; capped when vibrancy is on so
+    /// the blur shows through.
+    pub fn background_opacity(&self) -> f32 {
+        if self.vibrancy.is_some() {
+            self.opacity.min(VIBRANCY_TINT)
+        } else {
+            self.opacity
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            position: WindowPosition::default(),
+            width: 600.0,
+            height: 400.0,
+            origin: None,
+            opacity: 1.0,
+            appearance: AppearanceMode::default(),
+            light_theme: ThemeName::Latte,
+            dark_theme: ThemeName::Mocha,
+            follow_increase_contrast: true,
+            compact: false,
+            footer: true,
+            vibrancy: None,
+            animation: ShowAnimation::default(),
+            reduce_motion: ReduceMotion::default(),
+            hide_from_capture: false,
+        }
+    }
+}
+
+/// What the header bar above the editor shows.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeaderConfig {
+    /// Hide the whole bar (the window can't be dragged then)
+    pub visible: bool,
+    pub title: bool,
+    /// Cursor position and selection counts
+    pub status: bool,
+    /// Key hints for submitting and closing
+    pub hint: bool,
+    pub word_count: bool,
+    pub pin_button: bool,
+    pub submit_button: bool,
+    /// Name of the scratchpad being edited
+    pub scratchpad_name: bool,
+}
+
+impl Default for HeaderConfig {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            title: true,
+            status: true,
+            hint: false,
+            word_count: false,
+            pin_button: true,
+            submit_button: false,
+            scratchpad_name: false,
+        }
+    }
+}
+
+/// What submitting the buffer does. Read at launch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubmitConfig {
+    /// Keystroke that submits, in GPUI's format (e.g. "cmd-enter")
+    pub keybinding: String,
+    /// Plain Enter submits in the compact row; otherwise it inserts a
+    /// newline there too
+    pub enter_in_compact: bool,
+    /// Empty the buffer once it's submitted (file buffers are kept)
+    pub clear_after: bool,
+    /// End submitted text with a newline; otherwise one is trimmed off.
+    /// Cmd+Shift+Enter submits the other way.
+    pub trailing_newline: bool,
+    /// Put back what was on the clipboard once the submitted text has been
+    /// pasted (macOS only)
+    pub restore_clipboard: bool,
+    /// How long after the paste to restore it, giving slow apps time to
+    /// read the submitted text
+    pub restore_clipboard_delay_ms: u64,
+    pub backend: SubmitBackend,
+    /// Wait after handing focus back before pasting, doubled on each retry
+    /// (macOS only)
+    pub paste_delay_ms: u64,
+    /// Tries at pasting while the previous app hasn't come to the front,
+    /// before giving up and leaving the text on the clipboard
+    pub paste_attempts: usize,
+    /// Transforms to pick from with Ctrl+Enter
+    pub templates: Vec<SubmitTemplate>,
+    /// Name of the template every plain submit goes through
+    pub default_template: Option<String>,
+    /// Log submitted text for the submission history
+    pub history: bool,
+    /// Oldest submissions are dropped from the log beyond this many
+    pub history_limit: usize,
+    /// Shell command run with each submitted text on stdin (and the app it
+    /// went to in `ZEDITOR_TARGET`); empty runs nothing
+    pub hook: String,
+    pub hook_mode: HookMode,
+}
+
+impl SubmitConfig {
+    /// Transform of the default template, if one is set and exists.
+    pub fn default_transform(&self) -> Option<Transform> {
+        let name = self.default_template.as_ref()?;
+        self.templates
+            .iter()
+            .find(|t| &t.name == name)
+            .map(|t| t.transform.clone())
+    }
+
+    /// `keybinding`, or the default when it doesn't parse.
+    pub fn keystroke(&self) -> &str {
+        if is_keystroke(&self.keybinding) { &self.keybinding } else { "cmd-enter" }
+    }
+}
+
+impl Default for SubmitConfig {
+    fn default() -> Self {
+        Self {
+            keybinding: "cmd-enter".to_string(),
+            enter_in_compact: true,
+            clear_after: false,
+            trailing_newline: false,
+            restore_clipboard: true,
+            restore_clipboard_delay_ms: 500,
+            backend: SubmitBackend::default(),
+            paste_delay_ms: 50,
+            paste_attempts: 4,
+            templates: default_templates(),
+            default_template: None,
+            history: true,
+            history_limit: 200,
+            hook: String::new(),
+            hook_mode: HookMode::default(),
+        }
+    }
+}
+
+/// A named instruction to pick from with `TransformWithAI`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AiPrompt {
+    pub name: String,
+    /// What the model is asked to do with the text
+    pub instruction: String,
+}
+
+/// OpenAI-compatible chat endpoint for `TransformWithAI`. Nothing is sent
+/// anywhere until `enabled` is set.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiConfig {
+    pub enabled: bool,
+    /// Chat completions URL; a local server (Ollama, LM Studio) works too
+    pub endpoint: String,
+    pub model: String,
+    /// Bearer token; empty reads `OPENAI_API_KEY`, and none is sent when
+    /// that's unset either
+    pub api_key: String,
+    pub prompts: Vec<AiPrompt>,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        let prompt = |name: &str, instruction: &str| AiPrompt {
+            name: name.to_string(),
+            instruction: instruction.to_string(),
+        };
+        Self {
+            enabled: false,
+            endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            api_key: String::new(),
+            prompts: vec![
+                prompt(
+                    "Fix grammar",
+                    "Fix spelling, grammar and punctuation. Keep the wording, tone and formatting otherwise.",
+                ),
+                prompt("Summarize", "Summarize the text in a few sentences."),
+                prompt("Translate to English", "Translate the text into English."),
+            ],
+        }
+    }
+}
+
+/// Whether `text` is a keystroke, or a sequence of them, in GPUI's format.
+pub fn is_keystroke(text: &str) -> bool {
+    !text.trim().is_empty() && text.split_whitespace().all(|part| Keystroke::parse(part).is_ok())
+}
+
+/// The menu bar status item.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatusItemConfig {
+    /// Switch to the filled icon while the popup is on screen
+    pub active_icon: bool,
+}
+
+impl Default for StatusItemConfig {
+    fn default() -> Self {
+        Self { active_icon: true }
+    }
+}
+
+/// What Escape does once no picker or overlay is open.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EscapeBehavior {
+    /// First collapse extra cursors and confirm unsaved file changes, then
+    /// hide
+    #[default]
+    TwoStage,
+    /// Hide straight away
+    Immediate,
+}
+
+/// How the popup behaves as it's shown and hidden.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviorConfig {
+    /// Hide when another app becomes active, unless pinned (macOS only)
+    pub hide_on_focus_loss: bool,
+    pub escape: EscapeBehavior,
+    /// Come back to an empty buffer after hiding; the old text goes to the
+    /// draft history (file buffers are kept)
+    pub clear_on_hide: bool,
+    /// Load the clipboard into the buffer on show when it changed since the
+    /// popup last saw it
+    pub prefill_clipboard: bool,
+}
+
+impl Default for BehaviorConfig {
+    fn default() -> Self {
+        Self {
+            hide_on_focus_loss: true,
+            escape: EscapeBehavior::default(),
+            clear_on_hide: false,
+            prefill_clipboard: true,
         }
     }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Preferences {
     pub hotkey: HotkeyConfig,
+    /// Further hotkeys, each registered alongside `hotkey`
+    pub hotkeys: Vec<ActionHotkey>,
+    pub editor: EditorConfig,
+    pub window: WindowConfig,
+    pub header: HeaderConfig,
+    pub behavior: BehaviorConfig,
+    pub submit: SubmitConfig,
+    pub status_item: StatusItemConfig,
+    pub ai: AiConfig,
+    /// Language of the UI as a locale code like `de` or `pt-BR`; empty to
+    /// follow the system
+    pub language: String,
+    /// Keystrokes that run a script from the scripts folder, by its file
+    /// name without `.lua`, e.g. `{"cmd-alt-u": "uppercase"}`. Read at
+    /// launch.
+    pub script_bindings: BTreeMap<String, String>,
+    /// Bundle identifiers of apps (games, VMs) in which the hotkeys are
+    /// ignored
+    pub excluded_apps: Vec<String>,
 }
 
 
 impl Global for Preferences {}
 
-fn config_path() -> PathBuf {
+/// Directory holding Zeditor's config and saved text
+/// (`~/Library/Application Support/Zeditor` on macOS).
+pub fn data_dir() -> PathBuf {
     dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("Zeditor")
-        .join("config.json")
 }
 
-pub fn load_preferences() -> Preferences {
-    let path = config_path();
-    if let Ok(data) = std::fs::read_to_string(&path) {
-        serde_json::from_str(&data).unwrap_or_default()
+/// A setting in the config file that couldn't be used as written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigIssue {
+    /// Dotted path of the setting, e.g. `editor.font_size`
+    pub setting: String,
+    pub problem: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.setting, self.problem)
+    }
+}
+
+/// What was wrong with the config file when it was last read; cleared once
+/// the preferences are saved over it
+static CONFIG_ISSUES: Mutex<Vec<ConfigIssue>> = Mutex::new(Vec::new());
+
+/// Settings from the config file that were replaced by their defaults.
+pub fn config_issues() -> Vec<ConfigIssue> {
+    CONFIG_ISSUES.lock().map(|issues| issues.clone()).unwrap_or_default()
+}
+
+fn set_config_issues(issues: Vec<ConfigIssue>) {
+    if let Ok(mut current) = CONFIG_ISSUES.lock() {
+        *current = issues;
+    }
+}
+
+/// Syntax of the config file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+/// The config file and its syntax: `config.toml` when the user made one,
+/// otherwise `config.json`. Saving writes back to whichever was found.
+fn config_path() -> (PathBuf, ConfigFormat) {
+    let toml = data_dir().join("config.toml");
+    if toml.is_file() {
+        (toml, ConfigFormat::Toml)
     } else {
-        Preferences::default()
+        (data_dir().join("config.json"), ConfigFormat::Json)
     }
 }
 
+/// Name of the config file, as issues refer to it.
+pub fn config_file_name() -> String {
+    let (path, _) = config_path();
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn load_preferences() -> Preferences {
+    let (path, format) = config_path();
+    let Ok(data) = std::fs::read_to_string(&path) else {
+        return Preferences::default();
+    };
+    let mut issues = Vec::new();
+    let value: Result<serde_json::Value, String> = match format {
+        ConfigFormat::Json => serde_json::from_str(&data).map_err(|err| err.to_string()),
+        ConfigFormat::Toml => toml::from_str(&data).map_err(|err| err.to_string()),
+    };
+    let mut prefs = match value {
+        Ok(value) => salvage_preferences(value, &mut issues),
+        Err(err) => {
+            issues.push(ConfigIssue {
+                setting: config_file_name(),
+                problem: format!("couldn't be read, so every setting is at its default ({})", err),
+            });
+            Preferences::default()
+        }
+    };
+    prefs.validate(&mut issues);
+    set_config_issues(issues);
+    prefs
+}
+
+/// Read `value` as preferences, leaving settings that don't fit their
+/// type at their defaults, a section and then a field at a time, rather
+/// than losing the whole file to one bad value.
+fn salvage_preferences(value: serde_json::Value, issues: &mut Vec<ConfigIssue>) -> Preferences {
+    use serde_json::{Map, Value};
+
+    if let Ok(prefs) = serde_json::from_value(value.clone()) {
+        return prefs;
+    }
+    let Value::Object(sections) = value else {
+        issues.push(ConfigIssue {
+            setting: config_file_name(),
+            problem: "isn't a table of settings".to_string(),
+        });
+        return Preferences::default();
+    };
+    // Every section takes defaults for what it leaves out, so each value
+    // can be tried on its own
+    let fits = |section: &str, value: Value| {
+        let mut trial = Map::new();
+        trial.insert(section.to_string(), value);
+        serde_json::from_value::<Preferences>(Value::Object(trial)).err()
+    };
+
+    let mut kept = Map::new();
+    for (section, value) in sections {
+        let Some(err) = fits(&section, value.clone()) else {
+            kept.insert(section, value);
+            continue;
+        };
+        let Value::Object(fields) = value else {
+            issues.push(ConfigIssue { setting: section, problem: err.to_string() });
+            continue;
+        };
+        let mut kept_fields = Map::new();
+        for (field, value) in fields {
+            let mut single = Map::new();
+            single.insert(field.clone(), value.clone());
+            match fits(&section, Value::Object(single)) {
+                None => {
+                    kept_fields.insert(field, value);
+                }
+                Some(err) => issues.push(ConfigIssue {
+                    setting: format!("{}.{}", section, field),
+                    problem: err.to_string(),
+                }),
+            }
+        }
+        kept.insert(section, Value::Object(kept_fields));
+    }
+    serde_json::from_value(Value::Object(kept)).unwrap_or_default()
+}
+
 pub fn save_preferences(prefs: &Preferences) {
-    let path = config_path();
+    let (path, format) = config_path();
     if let Some(parent) = path.parent() {
         let _ = std::fs::create_dir_all(parent);
     }
-    if let Ok(json) = serde_json::to_string_pretty(prefs) {
-        let _ = std::fs::write(&path, json);
+    let text = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(prefs).ok(),
+        ConfigFormat::Toml => toml::to_string_pretty(prefs).ok(),
+    };
+    if let Some(text) = text
+        && std::fs::write(&path, text).is_ok()
+    {
+        // The file now only holds values that were accepted
+        set_config_issues(Vec::new());
     }
 }
 
+/// Version written into settings exports, bumped if their layout changes
+const SETTINGS_EXPORT_VERSION: u32 = 1;
+
+/// All settings in one file, for moving them to another machine. The
+/// version field also tells an export apart from any other JSON.
+#[derive(Serialize, Deserialize)]
+struct SettingsExport {
+    zeditor_settings: u32,
+    preferences: Preferences,
+}
+
+/// Write every setting (hotkeys, key bindings, themes, templates) to `path`.
+pub fn export_preferences(prefs: &Preferences, path: &Path) -> Result<(), String> {
+    let mut preferences = prefs.clone();
+    // Exports get shared; the API key stays behind
+    preferences.ai.api_key.clear();
+    let export = SettingsExport {
+        zeditor_settings: SETTINGS_EXPORT_VERSION,
+        preferences,
+    };
+    let json = serde_json::to_string_pretty(&export).map_err(|err| err.to_string())?;
+    std::fs::write(path, json)
+        .map_err(|err| t_args("error.write_file", &[("path", &path.display()), ("error", &err)]))
+}
+
+/// Read settings written by `export_preferences`.
+pub fn import_preferences(path: &Path) -> Result<Preferences, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|err| t_args("error.read_file", &[("path", &path.display()), ("error", &err)]))?;
+    let export: SettingsExport = serde_json::from_str(&json)
+        .map_err(|_| t_args("error.not_an_export", &[("path", &path.display())]))?;
+    if export.zeditor_settings > SETTINGS_EXPORT_VERSION {
+        return Err(t("error.newer_export").to_string());
+    }
+    Ok(export.preferences)
+}
+
 impl Preferences {
     pub fn init(app: &mut App) {
         let prefs = load_preferences();
+        let installed = app.text_system().all_font_names();
+        if !installed.iter().any(|name| *name == prefs.editor.font_family) {
+            // GPUI falls back to another font by itself; just say so
+            let mut issues = config_issues();
+            issues.push(ConfigIssue {
+                setting: "editor.font_family".to_string(),
+                problem: format!("\"{}\" isn't installed", prefs.editor.font_family),
+            });
+            set_config_issues(issues);
+        }
+        crate::i18n::set_language(&prefs.language);
         app.set_global(prefs);
     }
+
+    /// What the editor reads of these preferences, with fonts scaled by
+    /// `text_scale`.
+    pub fn editor_settings(&self, text_scale: f32) -> EditorSettings {
+        EditorSettings {
+            config: self.editor.clone(),
+            reduce_motion: self.window.reduce_motion(),
+            background_opacity: self.window.background_opacity(),
+            text_scale,
+        }
+    }
+
+    /// Put settings outside the range the app can work with back to their
+    /// defaults, noting each in `issues`.
+    fn validate(&mut self, issues: &mut Vec<ConfigIssue>) {
+        let defaults = Preferences::default();
+        let editor = &mut self.editor;
+        check_in_range(issues, "editor.font_size", &mut editor.font_size, 8.0..=48.0, defaults.editor.font_size);
+        check_in_range(issues, "editor.tab_size", &mut editor.tab_size, 1..=16, defaults.editor.tab_size);
+        check_in_range(
+            issues,
+            "editor.cursor_width",
+            &mut editor.cursor_width,
+            0.5..=8.0,
+            defaults.editor.cursor_width,
+        );
+        check_in_range(
+            issues,
+            "editor.scroll_past_end",
+            &mut editor.scroll_past_end,
+            0.0..=1.0,
+            defaults.editor.scroll_past_end,
+        );
+        let (setting, height, range) = match editor.line_height {
+            LineHeight::Pixels(height) => ("editor.line_height.pixels", height, 8.0..=96.0),
+            LineHeight::Relative(factor) => ("editor.line_height.relative", factor, 0.8..=4.0),
+        };
+        check_range(issues, setting, &mut editor.line_height, height, range, defaults.editor.line_height);
+        check_in_range(issues, "window.opacity", &mut self.window.opacity, 0.2..=1.0, defaults.window.opacity);
+        check_in_range(
+            issues,
+            "submit.paste_attempts",
+            &mut self.submit.paste_attempts,
+            1..=20,
+            defaults.submit.paste_attempts,
+        );
+        if self.submit.keystroke() != self.submit.keybinding {
+            issues.push(ConfigIssue {
+                setting: "submit.keybinding".to_string(),
+                problem: format!("\"{}\" isn't a keystroke", self.submit.keybinding),
+            });
+            self.submit.keybinding = defaults.submit.keybinding;
+        }
+        if let Some(name) = &self.submit.default_template
+            && !self.submit.templates.iter().any(|t| &t.name == name)
+        {
+            issues.push(ConfigIssue {
+                setting: "submit.default_template".to_string(),
+                problem: format!("there's no template named \"{}\"", name),
+            });
+            self.submit.default_template = None;
+        }
+        self.script_bindings.retain(|keystroke, _| {
+            let valid = is_keystroke(keystroke);
+            if !valid {
+                issues.push(ConfigIssue {
+                    setting: format!("script_bindings.{}", keystroke),
+                    problem: format!("\"{}\" isn't a keystroke", keystroke),
+                });
+            }
+            valid
+        });
+    }
+}
+
+/// Reset `value` to `default` if it's outside `range`.
+fn check_in_range<T: PartialOrd + fmt::Display + Copy>(
+    issues: &mut Vec<ConfigIssue>,
+    setting: &str,
+    value: &mut T,
+    range: RangeInclusive<T>,
+    default: T,
+) {
+    let current = *value;
+    check_range(issues, setting, value, current, range, default);
+}
+
+/// Reset `target` to `default` if `value`, read from it, is outside `range`.
+fn check_range<T, N: PartialOrd + fmt::Display>(
+    issues: &mut Vec<ConfigIssue>,
+    setting: &str,
+    target: &mut T,
+    value: N,
+    range: RangeInclusive<N>,
+    default: T,
+) {
+    if !range.contains(&value) {
+        issues.push(ConfigIssue {
+            setting: setting.to_string(),
+            problem: format!("{} is outside {} to {}", value, range.start(), range.end()),
+        });
+        *target = default;
+    }
 }
@@ -1,33 +1,198 @@
 use gpui::prelude::FluentBuilder;
 use gpui::*;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use crate::preferences::{save_preferences, HotkeyConfig, Preferences};
-use crate::theme::Theme;
+use crate::i18n::{self, t, t_args};
+use crate::platform;
+use crate::preferences::{
+    config_file_name, config_issues, export_preferences, import_preferences, save_preferences,
+    AppearanceMode, ChordKey, ConfigIssue, CursorShape, EscapeBehavior, HookMode, HotkeyConfig,
+    LineHeight, Preferences, ReduceMotion, SubmitBackend, WordMotion,
+};
+use crate::theme::{Theme, ThemeName, with_alpha};
 
-#[cfg(target_os = "macos")]
-use crate::hotkey;
+/// How often the Accessibility permission is re-read while the window is open
+const ACCESSIBILITY_POLL: std::time::Duration = std::time::Duration::from_secs(1);
 
-actions!(preferences_window, [ClosePreferences, SavePreferences, ToggleRecording]);
+/// Monospace fonts offered in the Editor tab, when installed
+const EDITOR_FONTS: &[&str] = &[
+    "JetBrains Mono",
+    "SF Mono",
+    "Menlo",
+    "Monaco",
+    "Fira Code",
+    "Cascadia Code",
+    "Source Code Pro",
+    "IBM Plex Mono",
+    "Hack",
+    "Consolas",
+    "DejaVu Sans Mono",
+    "Ubuntu Mono",
+];
+
+/// Pages of the preferences window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PreferencesTab {
+    General,
+    Editor,
+    Behavior,
+}
+
+impl PreferencesTab {
+    const ALL: [PreferencesTab; 3] = [
+        PreferencesTab::General,
+        PreferencesTab::Editor,
+        PreferencesTab::Behavior,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PreferencesTab::General => t("preferences.tab.general"),
+            PreferencesTab::Editor => t("preferences.tab.editor"),
+            PreferencesTab::Behavior => t("preferences.tab.behavior"),
+        }
+    }
+}
+
+/// What a "Restore Defaults" button puts back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ResetScope {
+    All,
+    Hotkey,
+    KeyBindings,
+    Theme,
+    Editor,
+}
+
+impl ResetScope {
+    /// The settings as named in the confirmation and the undo toast.
+    fn description(self) -> &'static str {
+        match self {
+            ResetScope::All => t("preferences.reset.all"),
+            ResetScope::Hotkey => t("preferences.reset.hotkey"),
+            ResetScope::KeyBindings => t("preferences.reset.key_bindings"),
+            ResetScope::Theme => t("preferences.reset.theme"),
+            ResetScope::Editor => t("preferences.reset.editor"),
+        }
+    }
+
+    fn apply(self, prefs: &mut Preferences) {
+        let defaults = Preferences::default();
+        match self {
+            ResetScope::All => *prefs = defaults,
+            ResetScope::Hotkey => prefs.hotkey = defaults.hotkey,
+            ResetScope::KeyBindings => {
+                prefs.hotkeys = defaults.hotkeys;
+                prefs.submit.keybinding = defaults.submit.keybinding;
+                prefs.submit.enter_in_compact = defaults.submit.enter_in_compact;
+                prefs.script_bindings = defaults.script_bindings;
+            }
+            ResetScope::Theme => {
+                prefs.window.appearance = defaults.window.appearance;
+                prefs.window.light_theme = defaults.window.light_theme;
+                prefs.window.dark_theme = defaults.window.dark_theme;
+            }
+            ResetScope::Editor => prefs.editor = defaults.editor,
+        }
+    }
+}
+
+/// How long the undo toast stays up after restoring defaults
+const UNDO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(8);
+
+actions!(
+    preferences_window,
+    [ClosePreferences, SavePreferences, ToggleRecording, ToggleChordRecording, ClearChord]
+);
 
 pub struct PreferencesWindow {
     focus_handle: FocusHandle,
+    tab: PreferencesTab,
+    /// Installed fonts from EDITOR_FONTS
+    editor_fonts: Vec<String>,
+    /// Languages there's a locale table for: (code, own name)
+    languages: Vec<(String, String)>,
     recording: bool,
     current_hotkey: HotkeyConfig,
     recorded_key_code: Option<u32>,
     recorded_modifiers: u32,
     recorded_display: String,
+    /// Waiting for the second key of a chord
+    recording_chord: bool,
+    /// Chord step as edited here; saved together with the combo
+    chord: Option<ChordKey>,
+    chord_changed: bool,
+    /// Who else uses the recorded combination, if anyone
+    conflict: Option<String>,
+    /// Accessibility permission, where the platform has one
+    accessibility: Option<bool>,
+    /// Bundle identifiers the hotkeys are ignored in; saved as edited
+    excluded_apps: Vec<String>,
+    /// Whether the platform can exclude apps at all
+    exclusion_supported: bool,
+    /// Open "Add App" list of running apps: (name, bundle identifier)
+    app_picker: Option<Vec<(String, String)>>,
+    /// Outcome of the last settings export or import
+    transfer_status: Option<String>,
+    /// Defaults waiting for the user to confirm restoring them
+    confirm_reset: Option<ResetScope>,
+    /// Toast message and the settings from before the last restore
+    reset_undo: Option<(String, Preferences)>,
+    /// Bumped per restore, so an older toast's timeout leaves a newer one
+    reset_generation: usize,
 }
 
 impl PreferencesWindow {
     pub fn new(cx: &mut Context<Self>) -> Self {
+        // Follow the permission so the section flips as soon as it's granted
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            loop {
+                cx.background_executor().timer(ACCESSIBILITY_POLL).await;
+                let trusted = platform::current().accessibility_trusted();
+                let alive = this.update(cx, |this, cx| {
+                    if this.accessibility != trusted {
+                        this.accessibility = trusted;
+                        cx.notify();
+                    }
+                });
+                if alive.is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+
+        let installed = cx.text_system().all_font_names();
+        let editor_fonts = EDITOR_FONTS
+            .iter()
+            .filter(|font| installed.iter().any(|name| name == *font))
+            .map(|font| font.to_string())
+            .collect();
+
         let prefs = cx.global::<Preferences>();
         Self {
             focus_handle: cx.focus_handle(),
+            tab: PreferencesTab::General,
+            editor_fonts,
+            languages: i18n::languages(),
             recording: false,
             current_hotkey: prefs.hotkey.clone(),
             recorded_key_code: None,
             recorded_modifiers: 0,
             recorded_display: String::new(),
+            recording_chord: false,
+            chord: prefs.hotkey.chord.clone(),
+            chord_changed: false,
+            conflict: None,
+            accessibility: platform::current().accessibility_trusted(),
+            excluded_apps: prefs.excluded_apps.clone(),
+            exclusion_supported: platform::current().running_apps().is_some(),
+            app_picker: None,
+            transfer_status: None,
+            confirm_reset: None,
+            reset_undo: None,
+            reset_generation: 0,
         }
     }
 
@@ -43,24 +208,46 @@ impl PreferencesWindow {
             self.recorded_display.clear();
         } else {
             self.recording = true;
+            self.recording_chord = false;
             self.recorded_key_code = None;
             self.recorded_modifiers = 0;
             self.recorded_display.clear();
         }
+        self.conflict = None;
+        cx.notify();
+    }
+
+    fn toggle_chord_recording(&mut self, _: &ToggleChordRecording, _window: &mut Window, cx: &mut Context<Self>) {
+        self.recording_chord = !self.recording_chord;
+        self.recording = false;
+        cx.notify();
+    }
+
+    fn clear_chord(&mut self, _: &ClearChord, _window: &mut Window, cx: &mut Context<Self>) {
+        self.chord = None;
+        self.chord_changed = true;
+        self.recording_chord = false;
         cx.notify();
     }
 
     fn save(&mut self, _: &SavePreferences, _window: &mut Window, cx: &mut Context<Self>) {
-        let Some(key_code) = self.recorded_key_code else {
+        if self.recorded_key_code.is_none() && !self.chord_changed {
             return;
+        }
+        let (key_code, modifiers, display) = match self.recorded_key_code {
+            Some(key_code) => (key_code, self.recorded_modifiers, self.recorded_display.clone()),
+            None => (
+                self.current_hotkey.key_code,
+                self.current_hotkey.modifiers,
+                self.current_hotkey.display_string.clone(),
+            ),
         };
-        let modifiers = self.recorded_modifiers;
-        let display = self.recorded_display.clone();
 
         let new_config = HotkeyConfig {
             key_code,
             modifiers,
             display_string: display,
+            chord: self.chord.clone(),
         };
 
         let mut prefs = cx.global::<Preferences>().clone();
@@ -68,25 +255,40 @@ impl PreferencesWindow {
         cx.set_global(prefs.clone());
         save_preferences(&prefs);
 
-        #[cfg(target_os = "macos")]
-        unsafe {
-            hotkey::re_register_hotkey(key_code, modifiers);
-        }
+        platform::current().set_hotkey(&new_config);
 
         self.current_hotkey = new_config;
+        self.chord_changed = false;
         self.recording = false;
         self.recorded_key_code = None;
         self.recorded_modifiers = 0;
         self.recorded_display.clear();
+        self.conflict = None;
         cx.notify();
     }
 
     fn on_key_down(&mut self, event: &KeyDownEvent, _window: &mut Window, cx: &mut Context<Self>) {
-        if !self.recording {
+        let keystroke = &event.keystroke;
+
+        // The second step of a chord may be a bare key
+        if self.recording_chord {
+            let Some((key_code, modifiers, display_string)) = carbon_hotkey(keystroke) else {
+                return;
+            };
+            self.chord = Some(ChordKey {
+                key_code,
+                modifiers,
+                display_string,
+            });
+            self.chord_changed = true;
+            self.recording_chord = false;
+            cx.notify();
             return;
         }
 
-        let keystroke = &event.keystroke;
+        if !self.recording {
+            return;
+        }
 
         if !keystroke.modifiers.platform
             && !keystroke.modifiers.alt
@@ -95,56 +297,942 @@ impl PreferencesWindow {
             return;
         }
 
-        let Some(vk) = gpui_key_to_vk(&keystroke.key) else {
+        let Some((vk, carbon_mods, display)) = carbon_hotkey(keystroke) else {
             return;
         };
 
-        let mut carbon_mods: u32 = 0;
-        if keystroke.modifiers.platform {
-            carbon_mods |= 1 << 8;
-        }
-        if keystroke.modifiers.shift {
-            carbon_mods |= 1 << 9;
-        }
-        if keystroke.modifiers.alt {
-            carbon_mods |= 1 << 11;
-        }
-        if keystroke.modifiers.control {
-            carbon_mods |= 1 << 12;
-        }
+        self.conflict = platform::current().hotkey_conflict(vk, carbon_mods);
+        self.recorded_key_code = Some(vk);
+        self.recorded_modifiers = carbon_mods;
+        self.recorded_display = display;
+        self.recording = false;
+        cx.notify();
+    }
+}
 
-        let mut display = String::new();
-        if keystroke.modifiers.control {
-            display.push_str("Ctrl+");
-        }
-        if keystroke.modifiers.alt {
-            display.push_str("Alt+");
+/// Carbon key code, Carbon modifier mask and display string for a keystroke.
+fn carbon_hotkey(keystroke: &Keystroke) -> Option<(u32, u32, String)> {
+    let vk = gpui_key_to_vk(&keystroke.key)?;
+
+    let mut carbon_mods: u32 = 0;
+    if keystroke.modifiers.platform {
+        carbon_mods |= 1 << 8;
+    }
+    if keystroke.modifiers.shift {
+        carbon_mods |= 1 << 9;
+    }
+    if keystroke.modifiers.alt {
+        carbon_mods |= 1 << 11;
+    }
+    if keystroke.modifiers.control {
+        carbon_mods |= 1 << 12;
+    }
+
+    let mut display = String::new();
+    if keystroke.modifiers.control {
+        display.push_str("Ctrl+");
+    }
+    if keystroke.modifiers.alt {
+        display.push_str("Alt+");
+    }
+    if keystroke.modifiers.shift {
+        display.push_str("Shift+");
+    }
+    if keystroke.modifiers.platform {
+        display.push_str("Cmd+");
+    }
+    display.push_str(&keystroke.key.to_uppercase());
+
+    Some((vk, carbon_mods, display))
+}
+
+impl PreferencesWindow {
+    /// Ask where to save an export of all settings, and write it there.
+    fn export_settings(&mut self, cx: &mut Context<Self>) {
+        let directory = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        let path = cx.prompt_for_new_path(&directory, Some("Zeditor Settings.json"));
+        let prefs = cx.global::<Preferences>().clone();
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            let Ok(Ok(Some(path))) = path.await else {
+                return;
+            };
+            let status = match export_preferences(&prefs, &path) {
+                Ok(()) => t_args("preferences.exported", &[("path", &path.display())]),
+                Err(err) => err,
+            };
+            this.update(cx, |this, cx| {
+                this.transfer_status = Some(status);
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Ask for an export to read, and replace every setting with it.
+    fn import_settings(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: None,
+        });
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let imported = import_preferences(&path);
+            this.update(cx, |this, cx| match imported {
+                Ok(prefs) => this.apply_imported(prefs, &path, cx),
+                Err(err) => {
+                    this.transfer_status = Some(err);
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn apply_imported(&mut self, mut prefs: Preferences, path: &Path, cx: &mut Context<Self>) {
+        // Exports leave the API key out; keep the one set here
+        if prefs.ai.api_key.is_empty() {
+            prefs.ai.api_key = cx.global::<Preferences>().ai.api_key.clone();
         }
-        if keystroke.modifiers.shift {
-            display.push_str("Shift+");
+        let hotkeys_changed = further_hotkeys_differ(&prefs, cx.global::<Preferences>());
+        self.replace_prefs(prefs, cx);
+        let status = if hotkeys_changed {
+            t_args("preferences.imported_restart", &[("path", &path.display())])
+        } else {
+            t_args("preferences.imported", &[("path", &path.display())])
+        };
+        self.transfer_status = Some(status);
+    }
+
+    /// Swap in a whole new set of preferences, including the parts the
+    /// platform and this window keep their own copies of.
+    fn replace_prefs(&mut self, prefs: Preferences, cx: &mut Context<Self>) {
+        platform::current().set_hotkey(&prefs.hotkey);
+        platform::current().set_excluded_apps(&prefs.excluded_apps);
+        self.current_hotkey = prefs.hotkey.clone();
+        self.chord = prefs.hotkey.chord.clone();
+        self.chord_changed = false;
+        self.recording = false;
+        self.recording_chord = false;
+        self.recorded_key_code = None;
+        self.excluded_apps = prefs.excluded_apps.clone();
+        self.app_picker = None;
+        self.update_prefs(cx, |current| *current = prefs);
+        Theme::update(cx.window_appearance(), cx);
+    }
+
+    fn ask_restore_defaults(&mut self, scope: ResetScope, cx: &mut Context<Self>) {
+        self.confirm_reset = Some(scope);
+        cx.notify();
+    }
+
+    fn cancel_restore_defaults(&mut self, cx: &mut Context<Self>) {
+        self.confirm_reset = None;
+        cx.notify();
+    }
+
+    /// Restore the confirmed defaults, keeping what they replaced for the
+    /// undo toast.
+    fn restore_defaults(&mut self, cx: &mut Context<Self>) {
+        let Some(scope) = self.confirm_reset.take() else {
+            return;
+        };
+        let previous = cx.global::<Preferences>().clone();
+        let mut prefs = previous.clone();
+        scope.apply(&mut prefs);
+        let message = if further_hotkeys_differ(&prefs, &previous) {
+            t_args("preferences.restored_restart", &[("settings", &scope.description())])
+        } else {
+            t_args("preferences.restored", &[("settings", &scope.description())])
+        };
+        self.replace_prefs(prefs, cx);
+        self.reset_undo = Some((message, previous));
+        self.reset_generation += 1;
+
+        let generation = self.reset_generation;
+        cx.spawn(async move |this: WeakEntity<Self>, cx: &mut AsyncApp| {
+            cx.background_executor().timer(UNDO_TIMEOUT).await;
+            this.update(cx, |this, cx| {
+                if this.reset_generation == generation {
+                    this.reset_undo = None;
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn undo_restore_defaults(&mut self, cx: &mut Context<Self>) {
+        if let Some((_, previous)) = self.reset_undo.take() {
+            self.replace_prefs(previous, cx);
         }
-        if keystroke.modifiers.platform {
-            display.push_str("Cmd+");
+    }
+
+    /// Settings from the config file that were replaced by defaults, until
+    /// the preferences are saved over it.
+    fn render_config_issues(&self, issues: Vec<ConfigIssue>, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(
+                div()
+                    .text_size(px(11.))
+                    .text_color(theme.overlay0)
+                    .child(
+                        t_args("preferences.config_problems", &[("file", &config_file_name())]).to_uppercase(),
+                    ),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(6.))
+                    .p(px(12.))
+                    .rounded(px(8.))
+                    .bg(theme.base)
+                    .border_1()
+                    .border_color(theme.error)
+                    .children(issues.into_iter().map(|issue| {
+                        div()
+                            .flex()
+                            .flex_col()
+                            .child(div().text_size(px(12.)).child(issue.setting))
+                            .child(
+                                div()
+                                    .text_size(px(11.))
+                                    .text_color(theme.error)
+                                    .child(issue.problem),
+                            )
+                    }))
+                    .child(
+                        div()
+                            .text_size(px(11.))
+                            .text_color(theme.subtext0)
+                            .child(t("preferences.config_problems_hint")),
+                    ),
+            )
+    }
+
+    /// Submit keystroke and further hotkeys, which have no recorder here
+    /// yet but can be put back to their defaults.
+    fn render_key_bindings(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let prefs = cx.global::<Preferences>();
+        let further = match prefs.hotkeys.len() {
+            0 => t("preferences.none").to_string(),
+            n => n.to_string(),
+        };
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(section_header(t("preferences.key_bindings"), ResetScope::KeyBindings, cx))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(10.))
+                    .p(px(12.))
+                    .rounded(px(8.))
+                    .bg(theme.base)
+                    .border_1()
+                    .border_color(theme.surface0)
+                    .child(setting_row(
+                        t("preferences.submit_keystroke"),
+                        div()
+                            .text_size(px(12.))
+                            .text_color(theme.subtext0)
+                            .child(prefs.submit.keystroke().to_string()),
+                    ))
+                    .child(setting_row(
+                        t("preferences.enter_in_compact"),
+                        switch("enter-in-compact", prefs.submit.enter_in_compact, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.submit.enter_in_compact = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.further_hotkeys"),
+                        div().text_size(px(12.)).text_color(theme.subtext0).child(further),
+                    )),
+            )
+    }
+
+    fn toggle_app_picker(&mut self, cx: &mut Context<Self>) {
+        self.app_picker = match self.app_picker {
+            Some(_) => None,
+            None => {
+                let mut apps = platform::current().running_apps().unwrap_or_default();
+                apps.retain(|(_, bundle_id)| !self.excluded_apps.contains(bundle_id));
+                Some(apps)
+            }
+        };
+        cx.notify();
+    }
+
+    fn add_excluded_app(&mut self, bundle_id: String, cx: &mut Context<Self>) {
+        self.excluded_apps.push(bundle_id);
+        self.app_picker = None;
+        self.save_excluded_apps(cx);
+    }
+
+    fn remove_excluded_app(&mut self, index: usize, cx: &mut Context<Self>) {
+        if index < self.excluded_apps.len() {
+            self.excluded_apps.remove(index);
+            self.save_excluded_apps(cx);
         }
-        display.push_str(&keystroke.key.to_uppercase());
+    }
 
-        self.recorded_key_code = Some(vk);
-        self.recorded_modifiers = carbon_mods;
-        self.recorded_display = display;
-        self.recording = false;
+    fn save_excluded_apps(&mut self, cx: &mut Context<Self>) {
+        let mut prefs = cx.global::<Preferences>().clone();
+        prefs.excluded_apps = self.excluded_apps.clone();
+        cx.set_global(prefs.clone());
+        save_preferences(&prefs);
+        platform::current().set_excluded_apps(&self.excluded_apps);
         cx.notify();
     }
+
+    /// Apps the hotkeys are ignored in, with a picker of running apps to
+    /// add from.
+    fn render_excluded_apps(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let picker_open = self.app_picker.is_some();
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_size(px(11.))
+                            .text_color(theme.overlay0)
+                            .child(t("preferences.excluded_apps")),
+                    )
+                    .child(
+                        div()
+                            .id("add-app-btn")
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .h(px(24.))
+                            .px(px(10.))
+                            .rounded(px(5.))
+                            .bg(theme.surface1)
+                            .hover(|s| s.bg(theme.surface2))
+                            .cursor(CursorStyle::PointingHand)
+                            .text_size(px(12.))
+                            .text_color(theme.subtext0)
+                            .on_click(cx.listener(|this, _, _, cx| this.toggle_app_picker(cx)))
+                            .child(if picker_open {
+                                t("preferences.cancel")
+                            } else {
+                                t("preferences.add_app")
+                            }),
+                    ),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .p(px(4.))
+                    .rounded(px(8.))
+                    .bg(theme.base)
+                    .border_1()
+                    .border_color(if picker_open { theme.accent } else { theme.surface0 })
+                    .text_size(px(12.))
+                    .map(|el| match &self.app_picker {
+                        Some(apps) if apps.is_empty() => el.child(
+                            div()
+                                .px(px(8.))
+                                .py(px(4.))
+                                .text_color(theme.overlay0)
+                                .child(t("preferences.no_running_apps")),
+                        ),
+                        Some(apps) => el.children(apps.iter().enumerate().map(|(i, (name, bundle_id))| {
+                            let bundle_id = bundle_id.clone();
+                            div()
+                                .id(("running-app", i))
+                                .flex()
+                                .flex_row()
+                                .gap(px(8.))
+                                .px(px(8.))
+                                .py(px(4.))
+                                .rounded(px(5.))
+                                .hover(|s| s.bg(theme.surface0))
+                                .cursor(CursorStyle::PointingHand)
+                                .child(name.clone())
+                                .child(div().text_color(theme.overlay0).child(bundle_id.clone()))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.add_excluded_app(bundle_id.clone(), cx);
+                                }))
+                        })),
+                        None if self.excluded_apps.is_empty() => el.child(
+                            div()
+                                .px(px(8.))
+                                .py(px(4.))
+                                .text_color(theme.overlay0)
+                                .child(t("preferences.no_excluded_apps")),
+                        ),
+                        None => el.children(self.excluded_apps.iter().enumerate().map(|(i, bundle_id)| {
+                            div()
+                                .flex()
+                                .flex_row()
+                                .items_center()
+                                .px(px(8.))
+                                .py(px(4.))
+                                .child(div().flex_1().child(bundle_id.clone()))
+                                .child(
+                                    div()
+                                        .id(("remove-app", i))
+                                        .px(px(6.))
+                                        .rounded(px(4.))
+                                        .hover(|s| s.bg(theme.surface1))
+                                        .cursor(CursorStyle::PointingHand)
+                                        .text_color(theme.subtext0)
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.remove_excluded_app(i, cx);
+                                        }))
+                                        .child(t("preferences.remove")),
+                                )
+                        })),
+                    }),
+            )
+    }
+
+    /// Change the preferences and save them; the popup picks the change
+    /// up from the global, the platform side from `apply_preferences`.
+    fn update_prefs(&mut self, cx: &mut Context<Self>, update: impl FnOnce(&mut Preferences)) {
+        let mut prefs = cx.global::<Preferences>().clone();
+        update(&mut prefs);
+        cx.set_global(prefs.clone());
+        save_preferences(&prefs);
+        platform::current().apply_preferences(&prefs);
+        cx.notify();
+    }
+
+    /// Make `name` the light or dark theme, whichever it is.
+    fn set_theme(&mut self, name: ThemeName, cx: &mut Context<Self>) {
+        self.update_prefs(cx, |prefs| {
+            if name.is_dark() {
+                prefs.window.dark_theme = name;
+            } else {
+                prefs.window.light_theme = name;
+            }
+        });
+        Theme::update(cx.window_appearance(), cx);
+    }
+
+    fn set_appearance(&mut self, mode: AppearanceMode, cx: &mut Context<Self>) {
+        self.update_prefs(cx, |prefs| prefs.window.appearance = mode);
+        Theme::update(cx.window_appearance(), cx);
+    }
+
+    /// Step through the system language and the translated ones.
+    fn cycle_language(&mut self, delta: i32, cx: &mut Context<Self>) {
+        let current = &cx.global::<Preferences>().language;
+        let count = self.languages.len() as i32 + 1;
+        let index = match self.languages.iter().position(|(code, _)| code == current) {
+            Some(index) => index as i32 + 1,
+            None => 0,
+        };
+        let index = (index + delta).rem_euclid(count);
+        let language = match index {
+            0 => String::new(),
+            i => self.languages[i as usize - 1].0.clone(),
+        };
+        self.update_prefs(cx, |prefs| prefs.language = language);
+    }
+
+    /// Light/dark mode choice, and the built-in palettes as swatches;
+    /// picking either applies it at once. The UI language sits with them.
+    fn render_theme_picker(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let prefs = cx.global::<Preferences>();
+        let window_prefs = &prefs.window;
+        let mode = window_prefs.appearance;
+        let (light, dark) = (window_prefs.light_theme, window_prefs.dark_theme);
+        let language = match self.languages.iter().find(|(code, _)| *code == prefs.language) {
+            Some((_, name)) => name.clone(),
+            None if prefs.language.is_empty() => t("preferences.system").to_string(),
+            None => prefs.language.clone(),
+        };
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(8.))
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_size(px(11.))
+                            .text_color(theme.overlay0)
+                            .child(t("preferences.theme")),
+                    )
+                    .child(reset_link(ResetScope::Theme, cx))
+                    .child(segmented(
+                        "appearance-mode",
+                        &[
+                            (AppearanceMode::System, t("preferences.system")),
+                            (AppearanceMode::Light, t("preferences.light")),
+                            (AppearanceMode::Dark, t("preferences.dark")),
+                        ],
+                        mode,
+                        cx,
+                        |this, mode, cx| this.set_appearance(mode, cx),
+                    )),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .flex_wrap()
+                    .gap(px(8.))
+                    .children(ThemeName::ALL.into_iter().enumerate().map(|(i, name)| {
+                        let palette = Theme::named(name);
+                        let selected = name == light || name == dark;
+                        div()
+                            .id(("theme", i))
+                            .flex()
+                            .flex_row()
+                            .items_center()
+                            .gap(px(8.))
+                            .w(px(110.))
+                            .p(px(8.))
+                            .rounded(px(8.))
+                            .bg(palette.base)
+                            .border_1()
+                            .border_color(if selected { theme.accent } else { theme.surface0 })
+                            .cursor(CursorStyle::PointingHand)
+                            .hover(|s| s.border_color(theme.accent))
+                            .on_click(cx.listener(move |this, _, _, cx| this.set_theme(name, cx)))
+                            .child(
+                                div()
+                                    .flex()
+                                    .flex_row()
+                                    .child(div().size(px(10.)).rounded_full().bg(palette.text))
+                                    .child(div().size(px(10.)).rounded_full().bg(palette.surface1)),
+                            )
+                            .child(
+                                div()
+                                    .text_size(px(12.))
+                                    .text_color(palette.text)
+                                    .child(name.label()),
+                            )
+                    })),
+            )
+            .child(
+                div()
+                    .text_size(px(11.))
+                    .text_color(theme.overlay0)
+                    .child(t("preferences.theme_hint")),
+            )
+            .child(setting_row(
+                t("preferences.follow_increase_contrast"),
+                switch(
+                    "follow-increase-contrast",
+                    window_prefs.follow_increase_contrast,
+                    cx,
+                    |this, on, cx| {
+                        this.update_prefs(cx, |prefs| prefs.window.follow_increase_contrast = on);
+                        Theme::update(cx.window_appearance(), cx);
+                    },
+                ),
+            ))
+            .child(setting_row(
+                t("preferences.reduce_motion"),
+                segmented(
+                    "reduce-motion",
+                    &[
+                        (ReduceMotion::System, t("preferences.system")),
+                        (ReduceMotion::On, t("preferences.on")),
+                        (ReduceMotion::Off, t("preferences.off")),
+                    ],
+                    window_prefs.reduce_motion,
+                    cx,
+                    |this, mode, cx| this.update_prefs(cx, |prefs| prefs.window.reduce_motion = mode),
+                ),
+            ))
+            .child(setting_row(
+                t("preferences.language"),
+                stepper("language", language, cx, |this, delta, cx| this.cycle_language(delta, cx)),
+            ))
+    }
+
+    /// Step through the installed editor fonts.
+    fn cycle_font(&mut self, delta: i32, cx: &mut Context<Self>) {
+        if self.editor_fonts.is_empty() {
+            return;
+        }
+        let current = &cx.global::<Preferences>().editor.font_family;
+        let count = self.editor_fonts.len() as i32;
+        let index = match self.editor_fonts.iter().position(|font| font == current) {
+            Some(index) => (index as i32 + delta).rem_euclid(count),
+            None => 0,
+        };
+        let font = self.editor_fonts[index as usize].clone();
+        self.update_prefs(cx, |prefs| prefs.editor.font_family = font);
+    }
+
+    /// Defaults for new buffers, and how every buffer is drawn.
+    fn render_editor_settings(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let editor = &cx.global::<Preferences>().editor;
+        let line_height = match editor.line_height {
+            LineHeight::Pixels(height) => format!("{} px", height),
+            LineHeight::Relative(factor) => format!("{:.1}×", factor),
+        };
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(section_header(t("preferences.editor"), ResetScope::Editor, cx))
+            .child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap(px(10.))
+                    .p(px(12.))
+                    .rounded(px(8.))
+                    .bg(theme.base)
+                    .border_1()
+                    .border_color(theme.surface0)
+                    .child(setting_row(
+                        t("preferences.font"),
+                        stepper("font", editor.font_family.clone(), cx, |this, delta, cx| {
+                            this.cycle_font(delta, cx)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.font_size"),
+                        stepper("font-size", editor.font_size.to_string(), cx, |this, delta, cx| {
+                            this.update_prefs(cx, |prefs| {
+                                prefs.editor.font_size = (prefs.editor.font_size + delta as f32).clamp(8., 48.);
+                            })
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.line_height"),
+                        stepper("line-height", line_height, cx, |this, delta, cx| {
+                            this.update_prefs(cx, |prefs| {
+                                prefs.editor.line_height = match prefs.editor.line_height {
+                                    LineHeight::Pixels(height) => {
+                                        LineHeight::Pixels((height + 2. * delta as f32).clamp(8., 96.))
+                                    }
+                                    LineHeight::Relative(factor) => {
+                                        LineHeight::Relative((factor + 0.1 * delta as f32).clamp(0.8, 4.))
+                                    }
+                                };
+                            })
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.tab_size"),
+                        stepper("tab-size", editor.tab_size.to_string(), cx, |this, delta, cx| {
+                            this.update_prefs(cx, |prefs| {
+                                prefs.editor.tab_size = (prefs.editor.tab_size as i32 + delta).clamp(1, 16) as usize;
+                            })
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.indent_with"),
+                        segmented(
+                            "indent",
+                            &[(false, t("preferences.spaces")), (true, t("preferences.tabs"))],
+                            editor.hard_tabs,
+                            cx,
+                            |this, hard_tabs, cx| this.update_prefs(cx, |prefs| prefs.editor.hard_tabs = hard_tabs),
+                        ),
+                    ))
+                    .child(setting_row(
+                        t("preferences.cursor"),
+                        segmented(
+                            "cursor-shape",
+                            &[
+                                (CursorShape::Bar, t("preferences.cursor_bar")),
+                                (CursorShape::Block, t("preferences.cursor_block")),
+                                (CursorShape::Underline, t("preferences.cursor_underline")),
+                            ],
+                            editor.cursor_shape,
+                            cx,
+                            |this, shape, cx| this.update_prefs(cx, |prefs| prefs.editor.cursor_shape = shape),
+                        ),
+                    ))
+                    .child(setting_row(
+                        t("preferences.word_motion"),
+                        segmented(
+                            "word-motion",
+                            &[
+                                (WordMotion::Unicode, t("preferences.word_motion_unicode")),
+                                (WordMotion::Programmer, t("preferences.word_motion_programmer")),
+                            ],
+                            editor.word_motion,
+                            cx,
+                            |this, words, cx| this.update_prefs(cx, |prefs| prefs.editor.word_motion = words),
+                        ),
+                    ))
+                    .child(setting_row(
+                        t("preferences.tint_secondary_cursors"),
+                        switch("tint-secondary-cursors", editor.tint_secondary_cursors, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.editor.tint_secondary_cursors = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.word_wrap"),
+                        switch("word-wrap", editor.word_wrap, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.editor.word_wrap = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.syntax_highlighting"),
+                        switch("syntax-highlighting", editor.syntax_highlighting, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.editor.syntax_highlighting = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.paste_html_as_markdown"),
+                        switch("paste-html-as-markdown", editor.paste_html_as_markdown, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.editor.paste_html_as_markdown = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.line_numbers"),
+                        switch("line-numbers", editor.line_numbers, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.editor.line_numbers = on)
+                        }),
+                    )),
+            )
+    }
+
+    /// How the popup shows, hides and submits.
+    fn render_behavior_settings(&self, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        let prefs = cx.global::<Preferences>();
+        let (behavior, submit) = (&prefs.behavior, &prefs.submit);
+        let section = |title: &'static str, rows: Div| {
+            div()
+                .flex()
+                .flex_col()
+                .gap(px(10.))
+                .child(
+                    div()
+                        .text_size(px(11.))
+                        .text_color(theme.overlay0)
+                        .child(title),
+                )
+                .child(
+                    rows.flex()
+                        .flex_col()
+                        .gap(px(10.))
+                        .p(px(12.))
+                        .rounded(px(8.))
+                        .bg(theme.base)
+                        .border_1()
+                        .border_color(theme.surface0),
+                )
+        };
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(20.))
+            .child(section(
+                t("preferences.show_and_hide"),
+                div()
+                    .child(setting_row(
+                        t("preferences.hide_on_focus_loss"),
+                        switch("hide-on-focus-loss", behavior.hide_on_focus_loss, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.behavior.hide_on_focus_loss = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.escape"),
+                        segmented(
+                            "escape",
+                            &[
+                                (EscapeBehavior::TwoStage, t("preferences.escape_two_stage")),
+                                (EscapeBehavior::Immediate, t("preferences.escape_immediate")),
+                            ],
+                            behavior.escape,
+                            cx,
+                            |this, escape, cx| this.update_prefs(cx, |prefs| prefs.behavior.escape = escape),
+                        ),
+                    ))
+                    .child(setting_row(
+                        t("preferences.clear_on_hide"),
+                        switch("clear-on-hide", behavior.clear_on_hide, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.behavior.clear_on_hide = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.prefill_clipboard"),
+                        switch("prefill-clipboard", behavior.prefill_clipboard, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.behavior.prefill_clipboard = on)
+                        }),
+                    )),
+            ))
+            .child(section(
+                t("preferences.submit"),
+                div()
+                    .child(setting_row(
+                        t("preferences.submit_backend"),
+                        segmented(
+                            "submit-backend",
+                            &[
+                                (SubmitBackend::Paste, t("preferences.backend_paste")),
+                                (SubmitBackend::Accessibility, t("preferences.backend_accessibility")),
+                            ],
+                            submit.backend,
+                            cx,
+                            |this, backend, cx| this.update_prefs(cx, |prefs| prefs.submit.backend = backend),
+                        ),
+                    ))
+                    .when(!submit.hook.trim().is_empty(), |el| {
+                        el.child(setting_row(
+                            t("preferences.submit_hook"),
+                            segmented(
+                                "hook-mode",
+                                &[
+                                    (HookMode::Before, t("preferences.hook_before")),
+                                    (HookMode::Instead, t("preferences.hook_instead")),
+                                ],
+                                submit.hook_mode,
+                                cx,
+                                |this, mode, cx| this.update_prefs(cx, |prefs| prefs.submit.hook_mode = mode),
+                            ),
+                        ))
+                    })
+                    .child(setting_row(
+                        t("preferences.trailing_newline"),
+                        switch("trailing-newline", submit.trailing_newline, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.submit.trailing_newline = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.clear_after_submit"),
+                        switch("clear-after", submit.clear_after, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.submit.clear_after = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.restore_clipboard"),
+                        switch("restore-clipboard", submit.restore_clipboard, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.submit.restore_clipboard = on)
+                        }),
+                    ))
+                    .child(setting_row(
+                        t("preferences.keep_history"),
+                        switch("submit-history", submit.history, cx, |this, on, cx| {
+                            this.update_prefs(cx, |prefs| prefs.submit.history = on)
+                        }),
+                    )),
+            ))
+    }
+
+    /// Whether paste simulation is allowed, with a shortcut to the
+    /// settings page that allows it.
+    fn render_accessibility(&self, trusted: bool, cx: &Context<Self>) -> impl IntoElement {
+        let theme = cx.global::<Theme>();
+        div()
+            .flex()
+            .flex_col()
+            .gap(px(10.))
+            .child(
+                div()
+                    .text_size(px(11.))
+                    .text_color(theme.overlay0)
+                    .child(t("preferences.accessibility")),
+            )
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .gap(px(10.))
+                    .p(px(12.))
+                    .rounded(px(8.))
+                    .bg(theme.base)
+                    .border_1()
+                    .border_color(theme.surface0)
+                    .child(
+                        div()
+                            .size(px(8.))
+                            .rounded_full()
+                            .bg(if trusted { theme.success } else { theme.error }),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_size(px(12.))
+                            .child(if trusted {
+                                t("preferences.accessibility_granted")
+                            } else {
+                                t("preferences.accessibility_denied")
+                            }),
+                    )
+                    .when_some(
+                        platform::current()
+                            .accessibility_settings_url()
+                            .filter(|_| !trusted),
+                        |el, url| {
+                            el.child(
+                                div()
+                                    .id("accessibility-btn")
+                                    .flex()
+                                    .items_center()
+                                    .justify_center()
+                                    .h(px(28.))
+                                    .px(px(12.))
+                                    .rounded(px(5.))
+                                    .bg(theme.surface1)
+                                    .hover(|s| s.bg(theme.surface2))
+                                    .cursor(CursorStyle::PointingHand)
+                                    .text_size(px(12.))
+                                    .text_color(theme.subtext0)
+                                    .on_click(move |_, _, cx| cx.open_url(url))
+                                    .child(t("preferences.open_settings")),
+                            )
+                        },
+                    ),
+            )
+    }
 }
 
 impl Render for PreferencesWindow {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.global::<Theme>();
-        let has_recorded = self.recorded_key_code.is_some();
+        let has_recorded = self.recorded_key_code.is_some() || self.chord_changed;
         let recording = self.recording;
+        let recording_chord = self.recording_chord;
+        let chord_display = if recording_chord {
+            t("preferences.waiting_for_input").to_string()
+        } else {
+            self.chord
+                .as_ref()
+                .map_or(t("preferences.none").to_string(), |chord| chord.display_string.clone())
+        };
 
         let hotkey_display = if recording {
-            "Waiting for input...".to_string()
-        } else if has_recorded {
+            t("preferences.waiting_for_input").to_string()
+        } else if self.recorded_key_code.is_some() {
             self.recorded_display.clone()
         } else {
             self.current_hotkey.display_string.clone()
@@ -156,147 +1244,307 @@ impl Render for PreferencesWindow {
             .on_action(cx.listener(Self::close))
             .on_action(cx.listener(Self::toggle_recording))
             .on_action(cx.listener(Self::save))
+            .on_action(cx.listener(Self::toggle_chord_recording))
+            .on_action(cx.listener(Self::clear_chord))
             .on_key_down(cx.listener(Self::on_key_down))
             .flex()
             .flex_col()
             .size_full()
             .bg(theme.mantle)
             .text_color(theme.text)
+            // Tab bar
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .gap(px(4.))
+                    .px(px(24.))
+                    .pt(px(12.))
+                    .border_b_1()
+                    .border_color(theme.surface0)
+                    .children(PreferencesTab::ALL.into_iter().enumerate().map(|(i, tab)| {
+                        let selected = tab == self.tab;
+                        div()
+                            .id(("preferences-tab", i))
+                            .px(px(12.))
+                            .py(px(6.))
+                            .border_b_2()
+                            .border_color(if selected { theme.accent } else { transparent_black().into() })
+                            .text_size(px(12.))
+                            .text_color(if selected { theme.text } else { theme.subtext0 })
+                            .hover(|s| s.text_color(theme.text))
+                            .cursor(CursorStyle::PointingHand)
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.tab = tab;
+                                cx.notify();
+                            }))
+                            .child(tab.label())
+                    })),
+            )
             .child(
-                // Main content area with generous padding
+                // Main content area with generous padding; scrolls when
+                // the running-apps list is long
                 div()
+                    .id("preferences-content")
                     .flex()
                     .flex_col()
                     .flex_1()
+                    .overflow_y_scroll()
                     .p(px(24.))
                     .gap(px(20.))
-                    // Section: Global Hotkey
-                    .child(
-                        div()
-                            .flex()
-                            .flex_col()
-                            .gap(px(10.))
-                            // Section header
-                            .child(
-                                div()
-                                    .flex()
-                                    .flex_row()
-                                    .items_center()
-                                    .gap(px(6.))
-                                    .child(
-                                        div()
-                                            .text_size(px(11.))
-                                            .text_color(theme.overlay0)
-                                            .child("GLOBAL HOTKEY"),
-                                    ),
-                            )
-                            // Hotkey row: display + button
-                            .child(
-                                div()
-                                    .flex()
-                                    .flex_row()
-                                    .items_center()
-                                    .gap(px(10.))
-                                    .p(px(12.))
-                                    .rounded(px(8.))
-                                    .bg(theme.base)
-                                    .border_1()
-                                    .border_color(if recording {
-                                        theme.accent
-                                    } else {
-                                        theme.surface0
-                                    })
-                                    // Hotkey badge
-                                    .child(
-                                        div()
-                                            .flex()
-                                            .flex_1()
-                                            .items_center()
-                                            .child(
+                    .when(self.tab == PreferencesTab::General, |el| {
+                        let issues = config_issues();
+                        el.when(!issues.is_empty(), |el| {
+                            el.child(self.render_config_issues(issues, cx))
+                        })
+                        // Section: Global Hotkey
+                        .child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap(px(10.))
+                                // Section header
+                                .child(
+                                    div()
+                                        .flex()
+                                        .flex_row()
+                                        .items_center()
+                                        .gap(px(6.))
+                                        .child(
+                                            div()
+                                                .flex_1()
+                                                .text_size(px(11.))
+                                                .text_color(theme.overlay0)
+                                                .child(t("preferences.global_hotkey")),
+                                        )
+                                        .child(reset_link(ResetScope::Hotkey, cx)),
+                                )
+                                // Hotkey row: display + button
+                                .child(
+                                    div()
+                                        .flex()
+                                        .flex_row()
+                                        .items_center()
+                                        .gap(px(10.))
+                                        .p(px(12.))
+                                        .rounded(px(8.))
+                                        .bg(theme.base)
+                                        .border_1()
+                                        .border_color(if recording {
+                                            theme.accent
+                                        } else {
+                                            theme.surface0
+                                        })
+                                        // Hotkey badge
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .flex_1()
+                                                .items_center()
+                                                .child(
+                                                    div()
+                                                        .flex()
+                                                        .items_center()
+                                                        .justify_center()
+                                                        .h(px(28.))
+                                                        .px(px(10.))
+                                                        .rounded(px(5.))
+                                                        .bg(if recording {
+                                                            theme.surface0
+                                                        } else {
+                                                            theme.surface1
+                                                        })
+                                                        .text_size(px(12.))
+                                                        .text_color(if recording {
+                                                            theme.overlay1
+                                                        } else {
+                                                            theme.text
+                                                        })
+                                                        .child(hotkey_display),
+                                                ),
+                                        )
+                                        // Action button
+                                        .child(
+                                            div()
+                                                .id("record-btn")
+                                                .flex()
+                                                .items_center()
+                                                .justify_center()
+                                                .h(px(28.))
+                                                .px(px(12.))
+                                                .rounded(px(5.))
+                                                .bg(if recording {
+                                                    theme.surface2
+                                                } else {
+                                                    theme.surface1
+                                                })
+                                                .hover(|s| s.bg(theme.surface2))
+                                                .cursor(CursorStyle::PointingHand)
+                                                .text_size(px(12.))
+                                                .text_color(theme.subtext0)
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.toggle_recording(
+                                                        &ToggleRecording,
+                                                        window,
+                                                        cx,
+                                                    );
+                                                }))
+                                                .child(if recording {
+                                                    t("preferences.cancel")
+                                                } else {
+                                                    t("preferences.record")
+                                                }),
+                                        ),
+                                )
+                                // Chord row: optional second key after the combo
+                                .child(
+                                    div()
+                                        .flex()
+                                        .flex_row()
+                                        .items_center()
+                                        .gap(px(10.))
+                                        .px(px(12.))
+                                        .child(
+                                            div()
+                                                .text_size(px(11.))
+                                                .text_color(theme.overlay0)
+                                                .child(t("preferences.chord_then")),
+                                        )
+                                        .child(
+                                            div()
+                                                .flex()
+                                                .flex_1()
+                                                .items_center()
+                                                .child(
+                                                    div()
+                                                        .flex()
+                                                        .items_center()
+                                                        .justify_center()
+                                                        .h(px(24.))
+                                                        .px(px(8.))
+                                                        .rounded(px(5.))
+                                                        .border_1()
+                                                        .border_color(if recording_chord {
+                                                            theme.accent
+                                                        } else {
+                                                            theme.surface0
+                                                        })
+                                                        .text_size(px(12.))
+                                                        .text_color(if self.chord.is_some() || recording_chord {
+                                                            theme.text
+                                                        } else {
+                                                            theme.overlay0
+                                                        })
+                                                        .child(chord_display),
+                                                ),
+                                        )
+                                        .when(self.chord.is_some() && !recording_chord, |el| {
+                                            el.child(
                                                 div()
+                                                    .id("clear-chord-btn")
                                                     .flex()
                                                     .items_center()
                                                     .justify_center()
-                                                    .h(px(28.))
+                                                    .h(px(24.))
                                                     .px(px(10.))
                                                     .rounded(px(5.))
-                                                    .bg(if recording {
-                                                        theme.surface0
-                                                    } else {
-                                                        theme.surface1
-                                                    })
+                                                    .hover(|s| s.bg(theme.surface1))
+                                                    .cursor(CursorStyle::PointingHand)
                                                     .text_size(px(12.))
-                                                    .text_color(if recording {
-                                                        theme.overlay1
-                                                    } else {
-                                                        theme.text
-                                                    })
-                                                    .child(hotkey_display),
-                                            ),
+                                                    .text_color(theme.subtext0)
+                                                    .on_click(cx.listener(|this, _, window, cx| {
+                                                        this.clear_chord(&ClearChord, window, cx);
+                                                    }))
+                                                    .child(t("preferences.clear")),
+                                            )
+                                        })
+                                        .child(
+                                            div()
+                                                .id("record-chord-btn")
+                                                .flex()
+                                                .items_center()
+                                                .justify_center()
+                                                .h(px(24.))
+                                                .px(px(10.))
+                                                .rounded(px(5.))
+                                                .bg(theme.surface1)
+                                                .hover(|s| s.bg(theme.surface2))
+                                                .cursor(CursorStyle::PointingHand)
+                                                .text_size(px(12.))
+                                                .text_color(theme.subtext0)
+                                                .on_click(cx.listener(|this, _, window, cx| {
+                                                    this.toggle_chord_recording(
+                                                        &ToggleChordRecording,
+                                                        window,
+                                                        cx,
+                                                    );
+                                                }))
+                                                .child(if recording_chord {
+                                                    t("preferences.cancel")
+                                                } else {
+                                                    t("preferences.add_key")
+                                                }),
+                                        ),
+                                )
+                                // Helper text
+                                .child(
+                                    div()
+                                        .text_size(px(11.))
+                                        .text_color(theme.overlay0)
+                                        .child(if recording {
+                                            t("preferences.hint_recording")
+                                        } else if recording_chord {
+                                            t("preferences.hint_recording_chord")
+                                        } else if has_recorded {
+                                            t("preferences.hint_recorded")
+                                        } else {
+                                            t("preferences.hint_idle")
+                                        }),
+                                )
+                                .when_some(self.conflict.clone(), |el, conflict| {
+                                    el.child(
+                                        div()
+                                            .text_size(px(11.))
+                                            .text_color(theme.error)
+                                            .child(conflict),
                                     )
-                                    // Action button
+                                }),
+                        )
+                        .child(self.render_key_bindings(cx))
+                        .child(self.render_theme_picker(cx))
+                        .when_some(self.accessibility, |el, trusted| {
+                            el.child(self.render_accessibility(trusted, cx))
+                        })
+                        .when(self.exclusion_supported, |el| {
+                            el.child(self.render_excluded_apps(cx))
+                        })
+                        // Error display
+                        .when_some(platform::current().error(), |el, err| {
+                            el.child(
+                                div()
+                                    .flex()
+                                    .flex_row()
+                                    .items_center()
+                                    .gap(px(6.))
+                                    .p(px(10.))
+                                    .rounded(px(6.))
+                                    .bg(with_alpha(theme.error, 0.12))
+                                    .border_1()
+                                    .border_color(with_alpha(theme.error, 0.25))
                                     .child(
                                         div()
-                                            .id("record-btn")
-                                            .flex()
-                                            .items_center()
-                                            .justify_center()
-                                            .h(px(28.))
-                                            .px(px(12.))
-                                            .rounded(px(5.))
-                                            .bg(if recording {
-                                                theme.surface2
-                                            } else {
-                                                theme.surface1
-                                            })
-                                            .hover(|s| s.bg(theme.surface2))
-                                            .cursor(CursorStyle::PointingHand)
-                                            .text_size(px(12.))
-                                            .text_color(theme.subtext0)
-                                            .on_click(cx.listener(|this, _, window, cx| {
-                                                this.toggle_recording(
-                                                    &ToggleRecording,
-                                                    window,
-                                                    cx,
-                                                );
-                                            }))
-                                            .child(if recording { "Cancel" } else { "Record" }),
+                                            .text_size(px(11.))
+                                            .text_color(theme.error)
+                                            .child(err),
                                     ),
                             )
-                            // Helper text
-                            .child(
-                                div()
-                                    .text_size(px(11.))
-                                    .text_color(theme.overlay0)
-                                    .child(if recording {
-                                        "Press a key combination with at least one modifier (Cmd, Alt, Ctrl)"
-                                    } else if has_recorded {
-                                        "New hotkey recorded. Save to apply."
-                                    } else {
-                                        "Click Record to change the hotkey"
-                                    }),
-                            ),
-                    )
-                    // Error display
-                    .when_some(get_hotkey_error(), |el, err| {
-                        el.child(
-                            div()
-                                .flex()
-                                .flex_row()
-                                .items_center()
-                                .gap(px(6.))
-                                .p(px(10.))
-                                .rounded(px(6.))
-                                .bg(rgba(0xf3838320))
-                                .border_1()
-                                .border_color(rgba(0xf3838340))
-                                .child(
-                                    div()
-                                        .text_size(px(11.))
-                                        .text_color(rgb(0xf38383))
-                                        .child(err),
-                                ),
-                        )
+                        })
+                    })
+                    .when(self.tab == PreferencesTab::Editor, |el| {
+                        el.child(self.render_editor_settings(cx))
+                    })
+                    .when(self.tab == PreferencesTab::Behavior, |el| {
+                        el.child(self.render_behavior_settings(cx))
                     }),
             )
             // Bottom bar
@@ -313,6 +1561,44 @@ impl Render for PreferencesWindow {
                     .border_color(theme.surface0)
                     .bg(theme.base)
                     .gap(px(8.))
+                    .map(|el| {
+                        let status = |text: String| {
+                            div()
+                                .flex_1()
+                                .overflow_hidden()
+                                .text_ellipsis()
+                                .whitespace_nowrap()
+                                .text_size(px(11.))
+                                .text_color(theme.subtext0)
+                                .child(text)
+                        };
+                        if let Some(scope) = self.confirm_reset {
+                            let question = t_args("preferences.confirm_restore", &[("settings", &scope.description())]);
+                            el.child(status(question))
+                                .child(bar_button("cancel-reset-btn", t("preferences.cancel"), cx, |this, cx| {
+                                    this.cancel_restore_defaults(cx)
+                                }))
+                                .child(bar_button("confirm-reset-btn", t("preferences.restore"), cx, |this, cx| {
+                                    this.restore_defaults(cx)
+                                }))
+                        } else if let Some((message, _)) = &self.reset_undo {
+                            el.child(status(message.clone()))
+                                .child(bar_button("undo-reset-btn", t("preferences.undo"), cx, |this, cx| {
+                                    this.undo_restore_defaults(cx)
+                                }))
+                        } else {
+                            el.child(bar_button("export-btn", t("preferences.export"), cx, |this, cx| {
+                                this.export_settings(cx)
+                            }))
+                                .child(bar_button("import-btn", t("preferences.import"), cx, |this, cx| {
+                                    this.import_settings(cx)
+                                }))
+                                .child(bar_button("reset-all-btn", t("preferences.restore_defaults"), cx, |this, cx| {
+                                    this.ask_restore_defaults(ResetScope::All, cx)
+                                }))
+                                .child(status(self.transfer_status.clone().unwrap_or_default()))
+                        }
+                    })
                     .when(has_recorded, |el| {
                         el.child(
                             div()
@@ -331,27 +1617,195 @@ impl Render for PreferencesWindow {
                                 .on_click(cx.listener(|this, _, window, cx| {
                                     this.save(&SavePreferences, window, cx);
                                 }))
-                                .child("Save"),
+                                .child(t("preferences.save")),
                         )
                     }),
             )
     }
 }
 
-impl Focusable for PreferencesWindow {
-    fn focus_handle(&self, _: &App) -> FocusHandle {
-        self.focus_handle.clone()
-    }
+/// Whether two sets of preferences have different further hotkeys, which
+/// are only registered at launch.
+fn further_hotkeys_differ(a: &Preferences, b: &Preferences) -> bool {
+    serde_json::to_value(&a.hotkeys).ok() != serde_json::to_value(&b.hotkeys).ok()
+}
+
+/// A section's title, with a button restoring its defaults.
+fn section_header(title: &'static str, scope: ResetScope, cx: &Context<PreferencesWindow>) -> Div {
+    let theme = cx.global::<Theme>();
+    div()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap(px(8.))
+        .child(
+            div()
+                .flex_1()
+                .text_size(px(11.))
+                .text_color(theme.overlay0)
+                .child(title),
+        )
+        .child(reset_link(scope, cx))
+}
+
+/// Small "Restore Defaults" link, asking for confirmation in the bottom
+/// bar before it does anything.
+fn reset_link(scope: ResetScope, cx: &Context<PreferencesWindow>) -> Stateful<Div> {
+    let theme = cx.global::<Theme>();
+    div()
+        .id(("reset", scope as usize))
+        .text_size(px(11.))
+        .text_color(theme.overlay0)
+        .hover(|s| s.text_color(theme.accent))
+        .cursor(CursorStyle::PointingHand)
+        .on_click(cx.listener(move |this, _, _, cx| this.ask_restore_defaults(scope, cx)))
+        .child(t("preferences.restore_defaults"))
+}
+
+/// A secondary button in the bottom bar.
+fn bar_button(
+    id: &'static str,
+    label: &'static str,
+    cx: &Context<PreferencesWindow>,
+    on_click: impl Fn(&mut PreferencesWindow, &mut Context<PreferencesWindow>) + 'static,
+) -> Stateful<Div> {
+    let theme = cx.global::<Theme>();
+    div()
+        .id(id)
+        .flex()
+        .items_center()
+        .justify_center()
+        .h(px(28.))
+        .px(px(12.))
+        .rounded(px(5.))
+        .bg(theme.surface1)
+        .hover(|s| s.bg(theme.surface2))
+        .cursor(CursorStyle::PointingHand)
+        .text_size(px(12.))
+        .text_color(theme.subtext0)
+        .on_click(cx.listener(move |this, _, _, cx| on_click(this, cx)))
+        .child(label)
 }
 
-#[cfg(target_os = "macos")]
-fn get_hotkey_error() -> Option<String> {
-    hotkey::get_error()
+/// A setting's label, with its control on the right.
+fn setting_row(label: &'static str, control: impl IntoElement) -> Div {
+    div()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap(px(10.))
+        .child(div().flex_1().text_size(px(12.)).child(label))
+        .child(control)
 }
 
-#[cfg(not(target_os = "macos"))]
-fn get_hotkey_error() -> Option<String> {
-    None
+/// Buttons for picking one of `options`, with `current` highlighted.
+fn segmented<T: Copy + PartialEq + 'static>(
+    id: &'static str,
+    options: &[(T, &'static str)],
+    current: T,
+    cx: &Context<PreferencesWindow>,
+    on_pick: impl Fn(&mut PreferencesWindow, T, &mut Context<PreferencesWindow>) + 'static,
+) -> Div {
+    let theme = cx.global::<Theme>();
+    let on_pick = Rc::new(on_pick);
+    div()
+        .flex()
+        .flex_row()
+        .p(px(2.))
+        .rounded(px(6.))
+        .bg(theme.surface0)
+        .children(options.iter().enumerate().map(|(i, &(option, label))| {
+            let on_pick = on_pick.clone();
+            div()
+                .id((id, i))
+                .px(px(10.))
+                .py(px(2.))
+                .rounded(px(4.))
+                .text_size(px(12.))
+                .cursor(CursorStyle::PointingHand)
+                .map(|el| {
+                    if option == current {
+                        el.bg(theme.surface2).text_color(theme.text)
+                    } else {
+                        el.text_color(theme.subtext0).hover(|s| s.text_color(theme.text))
+                    }
+                })
+                .on_click(cx.listener(move |this, _, _, cx| on_pick(this, option, cx)))
+                .child(label)
+        }))
+}
+
+/// An on/off switch.
+fn switch(
+    id: &'static str,
+    on: bool,
+    cx: &Context<PreferencesWindow>,
+    on_toggle: impl Fn(&mut PreferencesWindow, bool, &mut Context<PreferencesWindow>) + 'static,
+) -> Stateful<Div> {
+    let theme = cx.global::<Theme>();
+    div()
+        .id(id)
+        .flex()
+        .flex_row()
+        .items_center()
+        .when(on, |el| el.justify_end())
+        .w(px(32.))
+        .h(px(18.))
+        .p(px(2.))
+        .rounded_full()
+        .bg(if on { theme.accent } else { theme.surface1 })
+        .cursor(CursorStyle::PointingHand)
+        .on_click(cx.listener(move |this, _, _, cx| on_toggle(this, !on, cx)))
+        .child(div().size(px(14.)).rounded_full().bg(gpui::white()))
+}
+
+/// A value between "−" and "+" buttons; `on_step` gets -1 or 1.
+fn stepper(
+    id: &'static str,
+    value: String,
+    cx: &Context<PreferencesWindow>,
+    on_step: impl Fn(&mut PreferencesWindow, i32, &mut Context<PreferencesWindow>) + 'static,
+) -> Div {
+    let theme = cx.global::<Theme>();
+    let on_step = Rc::new(on_step);
+    let button = |i: usize, label: &'static str, delta: i32| {
+        let on_step = on_step.clone();
+        div()
+            .id((id, i))
+            .flex()
+            .items_center()
+            .justify_center()
+            .size(px(22.))
+            .rounded(px(5.))
+            .bg(theme.surface1)
+            .hover(|s| s.bg(theme.surface2))
+            .cursor(CursorStyle::PointingHand)
+            .text_size(px(12.))
+            .text_color(theme.subtext0)
+            .on_click(cx.listener(move |this, _, _, cx| on_step(this, delta, cx)))
+            .child(label)
+    };
+    div()
+        .flex()
+        .flex_row()
+        .items_center()
+        .gap(px(6.))
+        .child(button(0, "−", -1))
+        .child(
+            div()
+                .flex()
+                .justify_center()
+                .min_w(px(110.))
+                .text_size(px(12.))
+                .child(value),
+        )
+        .child(button(1, "+", 1))
+}
+
+impl Focusable for PreferencesWindow {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
 }
 
 /// Convert a GPUI key name to a macOS Carbon virtual key code.
@@ -0,0 +1,81 @@
+//! Crash recovery. A snapshot of each buffer is kept in memory, refreshed
+//! a little after it changes, and a panic writes them to a recovery file
+//! before the process goes down; the next launch offers to bring them
+//! back.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, TryLockError};
+
+use gpui::EntityId;
+use zeditor_core::EditorSnapshot;
+
+use crate::preferences::data_dir;
+
+/// Each buffer as of its last tracked change, by entity id so they come
+/// out in the order they were made
+static LATEST: Mutex<BTreeMap<u64, EditorSnapshot>> = Mutex::new(BTreeMap::new());
+
+fn recovery_path() -> PathBuf {
+    data_dir().join("recovery.json")
+}
+
+/// Remember `snapshot` as what to write out for buffer `id` if the app
+/// panics.
+pub fn track(id: EntityId, snapshot: EditorSnapshot) {
+    let mut latest = LATEST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    latest.insert(id.as_u64(), snapshot);
+}
+
+/// Stop tracking a closed buffer.
+pub fn forget(id: EntityId) {
+    let mut latest = LATEST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    latest.remove(&id.as_u64());
+}
+
+/// Write the tracked buffers to the recovery file on any panic, then carry
+/// on with the default hook.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        dump();
+        default_hook(info);
+    }));
+}
+
+fn dump() {
+    // The panic may have happened while the lock was held; never wait on it
+    let latest = match LATEST.try_lock() {
+        Ok(latest) => latest,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    let snapshots: Vec<&EditorSnapshot> = latest
+        .values()
+        .filter(|snapshot| !snapshot.text.as_deref().unwrap_or_default().trim().is_empty())
+        .collect();
+    if snapshots.is_empty() {
+        return;
+    }
+    let path = recovery_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string(&snapshots) {
+        let _ = std::fs::write(&path, json);
+    }
+}
+
+/// The buffers a crash left behind, if any. They stay on disk until
+/// `discard_recovery`, so they aren't lost if the app goes down again
+/// before they're dealt with.
+pub fn load_recovery() -> Vec<EditorSnapshot> {
+    std::fs::read_to_string(recovery_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+pub fn discard_recovery() {
+    let _ = std::fs::remove_file(recovery_path());
+}
@@ -0,0 +1,97 @@
+//! On-disk copy of the buffer, so a quit or crash never loses a draft.
+//! A snapshot of the editor (text, cursors, scroll position and modes) is
+//! kept next to it so the next launch picks up exactly where the last one
+//! left off.
+
+use std::path::PathBuf;
+
+use zeditor_core::EditorSnapshot;
+
+use crate::preferences::data_dir;
+
+fn scratch_path() -> PathBuf {
+    data_dir().join("scratch.txt")
+}
+
+pub fn load_scratch() -> Option<String> {
+    std::fs::read_to_string(scratch_path()).ok()
+}
+
+pub fn save_scratch(text: &str) {
+    let path = scratch_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(&path, text);
+}
+
+fn session_path() -> PathBuf {
+    data_dir().join("session.json")
+}
+
+/// The last session's snapshot. Sessions saved before snapshots held the
+/// text get it from the scratch file.
+pub fn load_session() -> Option<EditorSnapshot> {
+    let session: Option<EditorSnapshot> = std::fs::read_to_string(session_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok());
+    match session {
+        Some(session) if session.text.is_some() => Some(session),
+        session => Some(EditorSnapshot {
+            text: Some(load_scratch()?),
+            ..session.unwrap_or_default()
+        }),
+    }
+}
+
+pub fn save_session(session: &EditorSnapshot) {
+    let path = session_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(session) {
+        let _ = std::fs::write(&path, json);
+    }
+}
+
+// --- Named scratchpads ---
+
+fn scratchpads_dir() -> PathBuf {
+    data_dir().join("scratchpads")
+}
+
+/// Turn a user-typed name into one that is safe as a file name.
+pub fn sanitize_scratchpad_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .collect()
+}
+
+/// Names of the saved scratchpads, sorted.
+pub fn list_scratchpads() -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(scratchpads_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "txt" {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_string)
+        })
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+pub fn load_scratchpad(name: &str) -> Option<String> {
+    std::fs::read_to_string(scratchpads_dir().join(format!("{}.txt", name))).ok()
+}
+
+pub fn save_scratchpad(name: &str, text: &str) {
+    let dir = scratchpads_dir();
+    let _ = std::fs::create_dir_all(&dir);
+    let _ = std::fs::write(dir.join(format!("{}.txt", name)), text);
+}
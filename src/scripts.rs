@@ -0,0 +1,126 @@
+//! User scripts for custom text commands: Lua files in the `scripts`
+//! folder of the data directory, each defining `transform(text)` that
+//! returns the replacement text (or nil to leave it alone). Scripts are
+//! read fresh on every run, so edits apply without a restart, and run off
+//! the UI thread. They get the base library (less `dofile`, `loadfile`
+//! and binary chunks) and the string, table, math and utf8 libraries
+//! only; no file or OS access.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Value, VmState};
+
+use crate::i18n::t_args;
+use crate::preferences::data_dir;
+
+/// A script that runs longer than this, over all the texts it's given, is
+/// stopped
+const TIME_LIMIT: Duration = Duration::from_secs(2);
+/// Most memory a script's Lua state may allocate
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Run in each state before the script
+const SANDBOX: &str = r#"
+dofile, loadfile = nil, nil
+local load = load
+function _G.load(chunk, name, _, ...)
+    return load(chunk, name, "t", ...)
+end
+"#;
+
+pub fn scripts_dir() -> PathBuf {
+    data_dir().join("scripts")
+}
+
+/// Names of the installed scripts (file names without `.lua`), sorted.
+pub fn list_scripts() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(scripts_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "lua" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    names
+}
+
+/// Run the script called `name` on each of `texts` in one Lua state, all
+/// within the time and memory limits. Gives back what it returned for each
+/// text, None for texts that were None or it failed on, and the first
+/// error.
+pub fn run(name: &str, texts: &[Option<String>]) -> (Vec<Option<String>>, Option<String>) {
+    let mut error = None;
+    let results = match load(name) {
+        Ok((_lua, transform)) => texts
+            .iter()
+            .map(|text| {
+                match transform_with(&transform, name, text.as_deref()?) {
+                    Ok(result) => Some(result),
+                    Err(message) => {
+                        error.get_or_insert(message);
+                        None
+                    }
+                }
+            })
+            .collect(),
+        Err(message) => {
+            error = Some(message);
+            vec![None; texts.len()]
+        }
+    };
+    (results, error)
+}
+
+/// A fresh Lua state with the script called `name` loaded, and its
+/// `transform` function.
+fn load(name: &str) -> Result<(Lua, Function), String> {
+    if name.is_empty() || name.chars().any(|c| c == '/' || c == '\\') {
+        return Err(t_args("scripts.invalid_name", &[("name", &name)]));
+    }
+    let path = scripts_dir().join(format!("{}.lua", name));
+    let source = std::fs::read_to_string(&path)
+        .map_err(|err| t_args("scripts.read_failed", &[("path", &path.display()), ("error", &err)]))?;
+    let fail = |err: mlua::Error| t_args("scripts.failed", &[("name", &name), ("error", &err)]);
+
+    let libs = StdLib::STRING | StdLib::TABLE | StdLib::MATH | StdLib::UTF8;
+    let lua = Lua::new_with(libs, LuaOptions::default()).map_err(fail)?;
+    // The base library comes with every state: take away its file access,
+    // and let `load` take source text only, as Lua doesn't check binary
+    // chunks
+    lua.load(SANDBOX).set_name("=sandbox").exec().map_err(fail)?;
+    lua.set_memory_limit(MEMORY_LIMIT).map_err(fail)?;
+    let started = Instant::now();
+    lua.set_hook(HookTriggers::new().every_nth_instruction(10_000), move |_, _| {
+        if started.elapsed() > TIME_LIMIT {
+            Err(mlua::Error::runtime("took too long"))
+        } else {
+            Ok(VmState::Continue)
+        }
+    });
+
+    lua.load(&source).set_name(format!("@{}", path.display())).exec().map_err(fail)?;
+    let transform: Function = lua
+        .globals()
+        .get("transform")
+        .map_err(|_| t_args("scripts.no_transform", &[("name", &name)]))?;
+    Ok((lua, transform))
+}
+
+fn transform_with(transform: &Function, name: &str, text: &str) -> Result<String, String> {
+    let fail = |err: mlua::Error| t_args("scripts.failed", &[("name", &name), ("error", &err)]);
+    match transform.call::<Value>(text).map_err(fail)? {
+        Value::Nil => Ok(text.to_string()),
+        Value::String(result) => Ok(result.to_str().map_err(fail)?.to_string()),
+        other => Err(t_args(
+            "scripts.not_a_string",
+            &[("name", &name), ("type", &other.type_name())],
+        )),
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use unicode_segmentation::*;
+
+use crate::i18n::{t, t_count};
+
+/// Average silent reading speed used for the reading-time estimate
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Counts shown by the statistics overlay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextStatistics {
+    pub words: usize,
+    pub unique_words: usize,
+    pub characters: usize,
+    pub characters_no_spaces: usize,
+    pub lines: usize,
+    /// Runs of non-blank lines separated by blank lines
+    pub paragraphs: usize,
+    pub reading_minutes: usize,
+}
+
+impl TextStatistics {
+    pub fn for_text(text: &str) -> Self {
+        let words: Vec<&str> = text.unicode_words().collect();
+        let unique_words = words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<HashSet<_>>()
+            .len();
+        let characters = text.graphemes(true).count();
+        let characters_no_spaces = text
+            .graphemes(true)
+            .filter(|g| !g.chars().all(char::is_whitespace))
+            .count();
+
+        let mut paragraphs = 0;
+        let mut in_paragraph = false;
+        for line in text.split('\n') {
+            let blank = line.trim().is_empty();
+            if !blank && !in_paragraph {
+                paragraphs += 1;
+            }
+            in_paragraph = !blank;
+        }
+
+        Self {
+            words: words.len(),
+            unique_words,
+            characters,
+            characters_no_spaces,
+            lines: text.split('\n').count(),
+            paragraphs,
+            reading_minutes: words.len().div_ceil(WORDS_PER_MINUTE),
+        }
+    }
+
+    /// (label, value) rows in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (t("statistics.words"), self.words.to_string()),
+            (t("statistics.unique_words"), self.unique_words.to_string()),
+            (t("statistics.characters"), self.characters.to_string()),
+            (t("statistics.characters_no_spaces"), self.characters_no_spaces.to_string()),
+            (t("statistics.lines"), self.lines.to_string()),
+            (t("statistics.paragraphs"), self.paragraphs.to_string()),
+            (
+                t("statistics.reading_time"),
+                t_count("statistics.minutes", self.reading_minutes),
+            ),
+        ]
+    }
+}
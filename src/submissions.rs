@@ -0,0 +1,70 @@
+//! On-disk log of submitted text, newest first, for the submission history
+//! viewer.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::preferences::data_dir;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Submission {
+    pub text: String,
+    /// Unix timestamp (seconds) of the submit
+    pub submitted_at: i64,
+    /// App the text was pasted into, or "Clipboard" for copy-only submits
+    pub target: Option<String>,
+}
+
+impl Submission {
+    /// First non-blank line, for the viewer.
+    pub fn preview(&self) -> &str {
+        self.text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("")
+    }
+
+    pub fn submitted_at_label(&self) -> String {
+        chrono::DateTime::from_timestamp(self.submitted_at, 0)
+            .map(|t| t.with_timezone(&chrono::Local).format("%b %-d %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+fn submissions_path() -> PathBuf {
+    data_dir().join("submissions.json")
+}
+
+pub fn load_submissions() -> Vec<Submission> {
+    std::fs::read_to_string(submissions_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Add a submit to the front of the log, keeping at most `limit` entries.
+pub fn record_submission(text: &str, target: Option<String>, limit: usize) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let mut submissions = load_submissions();
+    submissions.insert(
+        0,
+        Submission {
+            text: text.to_string(),
+            submitted_at: chrono::Utc::now().timestamp(),
+            target,
+        },
+    );
+    submissions.truncate(limit);
+
+    let path = submissions_path();
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(&submissions) {
+        let _ = std::fs::write(&path, json);
+    }
+}
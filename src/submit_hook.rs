@@ -0,0 +1,54 @@
+//! The submit hook: a shell command that gets each submitted text on stdin,
+//! for workflows like appending to a daily note or posting to a webhook.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::i18n::t_args;
+
+/// Run `command` through the shell with `text` on stdin, waiting for it to
+/// finish. `target` (the app the text was pasted into, if any) is passed
+/// as `ZEDITOR_TARGET`.
+pub fn run(command: &str, text: &str, target: Option<&str>) -> Result<(), String> {
+    let mut process = shell(command);
+    if let Some(target) = target {
+        process.env("ZEDITOR_TARGET", target);
+    }
+    let mut child = process
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| t_args("submit_hook.run_failed", &[("error", &err)]))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early; that's
+        // fine
+        stdin.write_all(text.as_bytes()).ok();
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| t_args("submit_hook.run_failed", &[("error", &err)]))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = stderr.lines().rev().find(|line| !line.trim().is_empty());
+    Err(match detail {
+        Some(line) => t_args("submit_hook.failed", &[("error", &line.trim())]),
+        None => t_args("submit_hook.failed_status", &[("status", &output.status)]),
+    })
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.args(["/C", command]);
+    process
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("sh");
+    process.args(["-c", command]);
+    process
+}
@@ -0,0 +1,84 @@
+//! Named transforms applied to the text as it's submitted: fixed prefix and
+//! suffix, a fenced code block, URL encoding or a sign-off block.
+
+use serde::{Deserialize, Serialize};
+
+/// What a template does to the submitted text.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transform {
+    /// Surround the text with fixed strings
+    Wrap { prefix: String, suffix: String },
+    /// Put the text in a Markdown code fence, tagged with `language` if set
+    CodeFence {
+        #[serde(default)]
+        language: String,
+    },
+    /// Percent-encode everything but unreserved URL characters
+    UrlEncode,
+    /// Add a block after a blank line, such as a signature
+    SignOff { text: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubmitTemplate {
+    pub name: String,
+    #[serde(flatten)]
+    pub transform: Transform,
+}
+
+impl Transform {
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Transform::Wrap { prefix, suffix } => format!("{}{}{}", prefix, text, suffix),
+            Transform::CodeFence { language } => {
+                // A longer fence than any run of backticks inside
+                let longest = text
+                    .split(|c| c != '`')
+                    .map(str::len)
+                    .max()
+                    .unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                format!("{}{}\n{}\n{}", fence, language, text.trim_end_matches('\n'), fence)
+            }
+            Transform::UrlEncode => url_encode(text),
+            Transform::SignOff { text: sign_off } => {
+                format!("{}\n\n{}", text.trim_end_matches('\n'), sign_off)
+            }
+        }
+    }
+}
+
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Templates offered until the user defines their own.
+pub fn default_templates() -> Vec<SubmitTemplate> {
+    vec![
+        SubmitTemplate {
+            name: "Code block".to_string(),
+            transform: Transform::CodeFence { language: String::new() },
+        },
+        SubmitTemplate {
+            name: "Quote".to_string(),
+            transform: Transform::Wrap {
+                prefix: "\u{201c}".to_string(),
+                suffix: "\u{201d}".to_string(),
+            },
+        },
+        SubmitTemplate {
+            name: "URL-encoded".to_string(),
+            transform: Transform::UrlEncode,
+        },
+    ]
+}
@@ -1,4 +1,12 @@
 use gpui::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub use zeditor_core::theme::{SyntaxColors, translucent, with_alpha};
+use zeditor_core::EditorTheme;
+
+use crate::platform;
+use crate::preferences::{Preferences, data_dir};
 
 #[cfg(target_os = "macos")]
 use objc2::rc::Retained;
@@ -7,6 +15,7 @@ use objc2_app_kit::NSColor;
 
 #[allow(dead_code)]
 pub struct Theme {
+    pub name: ThemeName,
     pub text: Rgba,
     pub subtext1: Rgba,
     pub subtext0: Rgba,
@@ -22,13 +31,256 @@ pub struct Theme {
     pub crust: Rgba,
     pub crust_light: Rgba,
     pub accent: Rgba,
+    /// The caret, and the first of several
+    pub cursor: Rgba,
+    /// The other carets, when tinting them is on
+    pub cursor_secondary: Rgba,
+    /// Behind selected text while the editor has focus
+    pub selection: Rgba,
+    /// Behind selected text while it doesn't
+    pub selection_inactive: Rgba,
+    /// Carets are drawn at least this wide, whatever the preference says
+    pub cursor_min_width: f32,
+    /// Errors, warnings and removed diff lines
+    pub error: Rgba,
+    /// Granted permissions and added diff lines
+    pub success: Rgba,
+    pub syntax: SyntaxColors,
 }
 
 impl Global for Theme {}
 
+/// The built-in palettes, picked in the preferences window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    Latte,
+    Frappe,
+    Macchiato,
+    #[default]
+    Mocha,
+    Dark,
+    Light,
+    /// White on pure black
+    ContrastDark,
+    /// Black on pure white
+    ContrastLight,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 8] = [
+        ThemeName::Mocha,
+        ThemeName::Macchiato,
+        ThemeName::Frappe,
+        ThemeName::Latte,
+        ThemeName::Dark,
+        ThemeName::Light,
+        ThemeName::ContrastDark,
+        ThemeName::ContrastLight,
+    ];
+
+    pub fn is_dark(self) -> bool {
+        !matches!(self, ThemeName::Latte | ThemeName::Light | ThemeName::ContrastLight)
+    }
+
+    pub fn is_high_contrast(self) -> bool {
+        matches!(self, ThemeName::ContrastDark | ThemeName::ContrastLight)
+    }
+
+    /// The high-contrast palette for light or dark mode.
+    pub fn high_contrast(dark: bool) -> ThemeName {
+        if dark { ThemeName::ContrastDark } else { ThemeName::ContrastLight }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemeName::Latte => "Latte",
+            ThemeName::Frappe => "Frappé",
+            ThemeName::Macchiato => "Macchiato",
+            ThemeName::Mocha => "Mocha",
+            ThemeName::Dark => "Dark",
+            ThemeName::Light => "Light",
+            ThemeName::ContrastDark => "Contrast",
+            ThemeName::ContrastLight => "Contrast Light",
+        }
+    }
+
+    /// Text, subtext1, subtext0, overlay2, overlay1, overlay0, surface2,
+    /// surface1, surface0, base, mantle and crust, darkest last in the dark
+    /// themes. The Catppuccin ones are its published flavors.
+    fn palette(self) -> [u32; 12] {
+        match self {
+            ThemeName::Latte => [
+                0x4c4f69, 0x5c5f77, 0x6c6f85, 0x7c7f93, 0x8c8fa1, 0x9ca0b0, 0xacb0be, 0xbcc0cc,
+                0xccd0da, 0xeff1f5, 0xe6e9ef, 0xdce0e8,
+            ],
+            ThemeName::Frappe => [
+                0xc6d0f5, 0xb5bfe2, 0xa5adce, 0x949cbb, 0x838ba7, 0x737994, 0x626880, 0x51576d,
+                0x414559, 0x303446, 0x292c3c, 0x232634,
+            ],
+            ThemeName::Macchiato => [
+                0xcad3f5, 0xb8c0e0, 0xa5adcb, 0x939ab7, 0x8087a2, 0x6e738d, 0x5b6078, 0x494d64,
+                0x363a4f, 0x24273a, 0x1e2030, 0x181926,
+            ],
+            ThemeName::Mocha => [
+                0xcdd6f4, 0xbac2de, 0xa6adc8, 0x9399b2, 0x7f849c, 0x6c7086, 0x585b70, 0x45475a,
+                0x313244, 0x1e1e2e, 0x181825, 0x11111b,
+            ],
+            ThemeName::Dark => [
+                0xe6e6e6, 0xcccccc, 0xb3b3b3, 0x999999, 0x808080, 0x6b6b6b, 0x555555, 0x404040,
+                0x2e2e2e, 0x1e1e1e, 0x181818, 0x111111,
+            ],
+            ThemeName::Light => [
+                0x1f1f1f, 0x333333, 0x4d4d4d, 0x666666, 0x808080, 0x999999, 0xb3b3b3, 0xcccccc,
+                0xe0e0e0, 0xffffff, 0xf5f5f5, 0xebebeb,
+            ],
+            ThemeName::ContrastDark => [
+                0xffffff, 0xffffff, 0xf0f0f0, 0xe0e0e0, 0xd0d0d0, 0xc0c0c0, 0xa0a0a0, 0x808080,
+                0x606060, 0x000000, 0x000000, 0x000000,
+            ],
+            ThemeName::ContrastLight => [
+                0x000000, 0x000000, 0x101010, 0x202020, 0x303030, 0x404040, 0x606060, 0x808080,
+                0xa0a0a0, 0xffffff, 0xffffff, 0xffffff,
+            ],
+        }
+    }
+}
+
+/// Red, green and blue of a palette, tuned to read on its base. Blue is
+/// the accent where the system has none, and tints the selection.
+struct SignalColors {
+    red: u32,
+    green: u32,
+    blue: u32,
+}
+
+impl ThemeName {
+    fn signal_colors(self) -> SignalColors {
+        let [red, green, blue] = match self {
+            ThemeName::Latte => [0xd20f39, 0x40a02b, 0x1e66f5],
+            ThemeName::Frappe => [0xe78284, 0xa6d189, 0x8caaee],
+            ThemeName::Macchiato => [0xed8796, 0xa6da95, 0x8aadf4],
+            ThemeName::Mocha => [0xf38ba8, 0xa6e3a1, 0x89b4fa],
+            ThemeName::Dark => [0xf38383, 0xa6e3a1, 0x6ea8fe],
+            ThemeName::Light => [0xc62828, 0x2e7d32, 0x1565c0],
+            ThemeName::ContrastDark => [0xff4d4d, 0x4dff4d, 0x1a8cff],
+            ThemeName::ContrastLight => [0xc00000, 0x006000, 0x0040e0],
+        };
+        SignalColors { red, green, blue }
+    }
+
+    /// Keyword, string, number and function colors; comments take the
+    /// palette's overlay1.
+    fn syntax_palette(self) -> [u32; 4] {
+        match self {
+            ThemeName::Latte => [0x8839ef, 0x40a02b, 0xfe640b, 0x1e66f5],
+            ThemeName::Frappe => [0xca9ee6, 0xa6d189, 0xef9f76, 0x8caaee],
+            ThemeName::Macchiato => [0xc6a0f6, 0xa6da95, 0xf5a97f, 0x8aadf4],
+            ThemeName::Mocha => [0xcba6f7, 0xa6e3a1, 0xfab387, 0x89b4fa],
+            ThemeName::Dark => [0xc586c0, 0xce9178, 0xb5cea8, 0xdcdcaa],
+            ThemeName::Light => [0xaf00db, 0xa31515, 0x098658, 0x795e26],
+            ThemeName::ContrastDark => [0xff80ff, 0x80ff80, 0xffc040, 0x60c0ff],
+            ThemeName::ContrastLight => [0x8000a0, 0x006000, 0xa04000, 0x0040e0],
+        }
+    }
+}
+
+/// A color written as "#rrggbb" or "#rrggbbaa".
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+struct HexColor(Rgba);
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, String> {
+        let digits = hex.trim_start_matches('#');
+        let value = u32::from_str_radix(digits, 16).map_err(|_| format!("{} isn't a hex color", hex))?;
+        match digits.len() {
+            6 => Ok(HexColor(rgb(value))),
+            8 => Ok(HexColor(rgba(value))),
+            _ => Err(format!("{} isn't a hex color", hex)),
+        }
+    }
+}
+
+/// Colors a theme file can set; whatever is left out keeps the palette's.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThemeColors {
+    accent: Option<HexColor>,
+    error: Option<HexColor>,
+    success: Option<HexColor>,
+    cursor: Option<HexColor>,
+    cursor_secondary: Option<HexColor>,
+    selection: Option<HexColor>,
+    selection_inactive: Option<HexColor>,
+    syntax: SyntaxOverrides,
+}
+
+/// The `syntax` table of a theme file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct SyntaxOverrides {
+    keyword: Option<HexColor>,
+    string: Option<HexColor>,
+    comment: Option<HexColor>,
+    number: Option<HexColor>,
+    function: Option<HexColor>,
+}
+
+impl ThemeColors {
+    fn apply(&self, theme: &mut Theme) {
+        // The caret follows a changed accent unless it's set too
+        if let (Some(HexColor(accent)), None) = (&self.accent, &self.cursor) {
+            theme.cursor = *accent;
+        }
+        let slots = [
+            (&self.accent, &mut theme.accent),
+            (&self.error, &mut theme.error),
+            (&self.success, &mut theme.success),
+            (&self.cursor, &mut theme.cursor),
+            (&self.cursor_secondary, &mut theme.cursor_secondary),
+            (&self.selection, &mut theme.selection),
+            (&self.selection_inactive, &mut theme.selection_inactive),
+            (&self.syntax.keyword, &mut theme.syntax.keyword),
+            (&self.syntax.string, &mut theme.syntax.string),
+            (&self.syntax.comment, &mut theme.syntax.comment),
+            (&self.syntax.number, &mut theme.syntax.number),
+            (&self.syntax.function, &mut theme.syntax.function),
+        ];
+        for (color, slot) in slots {
+            if let Some(HexColor(color)) = color {
+                *slot = *color;
+            }
+        }
+    }
+}
+
+/// `theme.json` in the data dir: colors laid over every built-in palette,
+/// then over single ones by name, e.g.
+/// `{"selection": "#3311ff30", "syntax": {"comment": "#7f849c"},
+/// "themes": {"latte": {"selection": "#1e66f540"}}}`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    #[serde(flatten)]
+    colors: ThemeColors,
+    themes: BTreeMap<ThemeName, ThemeColors>,
+}
+
+impl ThemeFile {
+    fn load() -> ThemeFile {
+        std::fs::read_to_string(data_dir().join("theme.json"))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+}
+
 /// Get the system accent color on macOS
 #[cfg(target_os = "macos")]
-fn get_system_accent_color() -> Rgba {
+fn get_system_accent_color() -> Option<Rgba> {
     let accent_color: Retained<NSColor> = NSColor::controlAccentColor();
     // Convert to sRGB color space
     if let Some(rgb_color) = accent_color.colorUsingColorSpace(objc2_app_kit::NSColorSpace::sRGBColorSpace().as_ref()) {
@@ -36,58 +288,148 @@ fn get_system_accent_color() -> Rgba {
         let g = rgb_color.greenComponent() as f32;
         let b = rgb_color.blueComponent() as f32;
         let a = rgb_color.alphaComponent() as f32;
-        return rgba(
+        return Some(rgba(
             ((r * 255.0) as u32) << 24
                 | ((g * 255.0) as u32) << 16
                 | ((b * 255.0) as u32) << 8
                 | (a * 255.0) as u32,
-        );
+        ));
     }
-    // Fallback to default blue
-    gpui::blue().into()
+    None
 }
 
 #[cfg(not(target_os = "macos"))]
-fn get_system_accent_color() -> Rgba {
-    gpui::blue().into()
+fn get_system_accent_color() -> Option<Rgba> {
+    None
+}
+
+/// The system's accessibility text size as a multiple of the normal size.
+/// Editor fonts and the popup's chrome text are scaled by it; display
+/// scaling is applied by GPUI on top.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TextScale(pub f32);
+
+impl Global for TextScale {}
+
+impl TextScale {
+    pub fn init(app: &mut App) {
+        app.set_global(TextScale(platform::current().text_scale()));
+    }
+
+    /// Re-read the system setting and redraw every window if it changed.
+    /// Returns the previous scale when it did.
+    pub fn update(app: &mut App) -> Option<f32> {
+        let old = app.global::<TextScale>().0;
+        let new = platform::current().text_scale();
+        if new == old {
+            return None;
+        }
+        app.set_global(TextScale(new));
+        app.refresh_windows();
+        Some(old)
+    }
+}
+
+/// A chrome text size of `size` pixels at the normal text size.
+pub fn scaled_text(size: f32, cx: &App) -> Pixels {
+    px(size * cx.global::<TextScale>().0)
 }
 
 impl Theme {
     pub fn init(app: &mut App) {
-        let theme = Theme::get_dark();
-        app.set_global(theme);
-    }
-
-    // Catppuccin Mocha
-    // Text	#cdd6f4	rgb(205, 214, 244)	hsl(226, 64%, 88%)
-    // Subtext1	#bac2de	rgb(186, 194, 222)	hsl(227, 35%, 80%)
-    // Subtext0	#a6adc8	rgb(166, 173, 200)	hsl(228, 24%, 72%)
-    // Overlay2	#9399b2	rgb(147, 153, 178)	hsl(228, 17%, 64%)
-    // Overlay1	#7f849c	rgb(127, 132, 156)	hsl(230, 13%, 55%)
-    // Overlay0	#6c7086	rgb(108, 112, 134)	hsl(231, 11%, 47%)
-    // Surface2	#585b70	rgb(88, 91, 112)	hsl(233, 12%, 39%)
-    // Surface1	#45475a	rgb(69, 71, 90)	hsl(234, 13%, 31%)
-    // Surface0	#313244	rgb(49, 50, 68)	hsl(237, 16%, 23%)
-    // Base	#1e1e2e	rgb(30, 30, 46)	hsl(240, 21%, 15%)
-    // Mantle	#181825	rgb(24, 24, 37)	hsl(240, 21%, 12%)
-    // Crust	#11111b	rgb(17, 17, 27)	hsl(240, 23%, 9%)
-    pub fn get_dark() -> Theme {
+        let increase_contrast = platform::current().increase_contrast();
+        let name = app
+            .global::<Preferences>()
+            .window
+            .theme_for(app.window_appearance(), increase_contrast);
+        app.set_global(Theme::customized(name));
+    }
+
+    /// Switch to the palette the preferences pick for `system` mode and
+    /// the system's contrast setting, and redraw every window if that
+    /// changed it.
+    pub fn update(system: WindowAppearance, app: &mut App) {
+        let increase_contrast = platform::current().increase_contrast();
+        let name = app.global::<Preferences>().window.theme_for(system, increase_contrast);
+        if app.global::<Theme>().name != name {
+            app.set_global(Theme::customized(name));
+            app.refresh_windows();
+        }
+    }
+
+    /// The colors the editor paints with.
+    pub fn editor_theme(&self) -> EditorTheme {
+        EditorTheme {
+            surface0: self.surface0,
+            overlay0: self.overlay0,
+            mantle: self.mantle,
+            surface2: self.surface2,
+            cursor: self.cursor,
+            cursor_secondary: self.cursor_secondary,
+            cursor_min_width: self.cursor_min_width,
+            selection: self.selection,
+            selection_inactive: self.selection_inactive,
+            error: self.error,
+            syntax: self.syntax,
+        }
+    }
+
+    /// The built-in palette `name` with the theme file's colors on top.
+    pub fn customized(name: ThemeName) -> Theme {
+        let mut theme = Theme::named(name);
+        let file = ThemeFile::load();
+        file.colors.apply(&mut theme);
+        if let Some(colors) = file.themes.get(&name) {
+            colors.apply(&mut theme);
+        }
+        theme
+    }
+
+    /// The built-in palette `name`, as shown in the theme picker.
+    pub fn named(name: ThemeName) -> Theme {
+        let [text, subtext1, subtext0, overlay2, overlay1, overlay0, surface2, surface1, surface0, base, mantle, crust] =
+            name.palette();
+        let signal = name.signal_colors();
+        let [keyword, string, number, function] = name.syntax_palette();
+        let accent = get_system_accent_color().unwrap_or(rgb(signal.blue));
+        // Light palettes need a stronger tint to show on their pale base,
+        // and high contrast a saturated one
+        let selection_alpha = match (name.is_high_contrast(), name.is_dark()) {
+            (true, _) => 0x99,
+            (false, true) => 0x40,
+            (false, false) => 0x55,
+        };
         Theme {
-            text: rgb(0xcdd6f4),
-            subtext1: rgb(0xbac2de),
-            subtext0: rgb(0xa6adc8),
-            overlay2: rgb(0x9399b2),
-            overlay1: rgb(0x7f849c),
-            overlay0: rgb(0x6c7086),
-            surface2: rgb(0x585b70),
-            surface1: rgb(0x45475a),
-            surface0: rgb(0x313244),
-            base: rgb(0x1e1e2e),
-            base_blur: rgba(0x1e1e2edd),
-            mantle: rgb(0x181825),
-            crust: rgb(0x11111b),
-            crust_light: rgba(0x6c708666),
-            accent: get_system_accent_color(),
+            name,
+            text: rgb(text),
+            subtext1: rgb(subtext1),
+            subtext0: rgb(subtext0),
+            overlay2: rgb(overlay2),
+            overlay1: rgb(overlay1),
+            overlay0: rgb(overlay0),
+            surface2: rgb(surface2),
+            surface1: rgb(surface1),
+            surface0: rgb(surface0),
+            base: rgb(base),
+            base_blur: rgba(base << 8 | 0xdd),
+            mantle: rgb(mantle),
+            crust: rgb(crust),
+            crust_light: rgba(overlay0 << 8 | 0x66),
+            accent,
+            cursor: if name.is_high_contrast() { rgb(text) } else { accent },
+            cursor_secondary: rgb(subtext0),
+            cursor_min_width: if name.is_high_contrast() { 3.0 } else { 0.0 },
+            selection: rgba(signal.blue << 8 | selection_alpha),
+            selection_inactive: rgba(overlay0 << 8 | 0x40),
+            error: rgb(signal.red),
+            success: rgb(signal.green),
+            syntax: SyntaxColors {
+                keyword: rgb(keyword),
+                string: rgb(string),
+                comment: rgb(overlay1),
+                number: rgb(number),
+                function: rgb(function),
+            },
         }
     }
 }